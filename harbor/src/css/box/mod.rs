@@ -8,17 +8,15 @@ use std::{
 
 use crate::{
     css::{
-        colors::Color,
+        colors::UsedColor,
         cssom::{CSSDeclaration, ComputedStyle},
-        properties::{
-            Background, CSSParseable, Display, Font, FontFamily, FontSize, FontStyle, FontWeight,
-            Image, LineHeight, Margin, MarginValue, Origin, Position, PositionValue, RepeatStyle,
-            WidthValue,
-        },
+        parser::ComponentValue,
+        properties::{Display, Float, FontStyle, LengthContext, MarginValue, VerticalAlign},
+        registry::{self, PropertyDefinition},
+        tokenize::CSSToken,
     },
     globals::{DEFAULT_FONT_FAMILY, FONTS},
-    html5::dom::{Document, Element, NodeKind},
-    infra::InputStream,
+    html5::dom::{Document, Element, NodeKind, NodeType},
     render::{RendererIdentifier, TextRenderer},
 };
 
@@ -152,6 +150,12 @@ pub struct Box {
     /// The Y position of the box, set during layout formation
     _position_y: Option<f64>,
 
+    /// Distance from the top of the box to its baseline, set during layout formation
+    _baseline: f64,
+
+    /// For text boxes, the text of each line the box wraps onto, set during layout formation
+    _text_lines: Vec<String>,
+
     pub children: Vec<Rc<RefCell<Box>>>,
 
     pub associated_node: Option<Rc<RefCell<NodeKind>>>,
@@ -272,6 +276,16 @@ impl Box {
         )
     }
 
+    /// Distance from the top of the box to its baseline
+    pub fn baseline(&self) -> f64 {
+        self._baseline
+    }
+
+    /// The lines a text box's text was broken into, as they should be drawn
+    pub fn text_lines(&self) -> &[String] {
+        &self._text_lines
+    }
+
     pub fn get_font_size(&self) -> f64 {
         if let Some(node_rc) = &self.associated_node {
             if let Some(style) = node_rc.borrow().style() {
@@ -292,6 +306,39 @@ impl Box {
         19.2
     }
 
+    /// A compact dump of the laid out box tree, one line per box with its type, tag, position
+    /// and content size, indented by depth. Much easier to read than the derived `Debug` output.
+    pub fn debug_layout_tree(&self) -> String {
+        let mut output = String::new();
+        self.write_layout_tree(0, &mut output);
+
+        output
+    }
+
+    fn write_layout_tree(&self, depth: usize, output: &mut String) {
+        let name = match self.associated_node.as_ref().map(|rc| rc.borrow().clone()) {
+            Some(NodeKind::Element(element)) => format!("<{}>", element.borrow().local_name),
+            Some(NodeKind::Text(_)) => String::from("#text"),
+            _ => String::from("anonymous"),
+        };
+        let (x, y) = self.position();
+
+        output.push_str(&format!(
+            "{}{:?} {} at ({}, {}) size {}x{}\n",
+            "  ".repeat(depth),
+            self._box_type,
+            name,
+            x,
+            y,
+            self._content_width,
+            self._content_height
+        ));
+
+        for child in &self.children {
+            child.borrow().write_layout_tree(depth + 1, output);
+        }
+    }
+
     pub fn build_doc_box_tree(
         doc: &Rc<RefCell<Document>>,
         window_size: (f64, f64),
@@ -308,6 +355,8 @@ impl Box {
             _box_type: BoxType::Block,
             _position_x: Some(0.0),
             _position_y: Some(0.0),
+            _baseline: 0.0,
+            _text_lines: vec![],
             children: vec![],
 
             associated_node: None,
@@ -372,6 +421,26 @@ impl Box {
             NodeKind::Element(element_rc) if element_rc.borrow().local_name.as_str() != "head" => {
                 let element = element_rc.borrow();
 
+                let font_size = element.style().font.resolved_font_size().unwrap_or(16.0);
+                let container_width = parents
+                    .last()
+                    .and_then(|parent| parent.upgrade())
+                    .map_or(0.0, |parent| parent.borrow()._content_width);
+                let root_font_size = parents
+                    .first()
+                    .and_then(|root| root.upgrade())
+                    .and_then(|root| root.borrow().style())
+                    .and_then(|style| style.font.resolved_font_size())
+                    .unwrap_or(16.0);
+                let context = LengthContext {
+                    container_width,
+                    font_size,
+                    root_font_size,
+                };
+                let margin = element.style().margin.to_edges(&context);
+                let padding = element.style().padding.to_edges(&context);
+                let border = element.style().border.to_edges(&context);
+
                 // let display = match element.local_name.as_str() {
                 //     "span" | "em" | "strong" => BoxType::Inline,
                 //     _ => BoxType::Block,
@@ -380,12 +449,18 @@ impl Box {
                 let parent_box = Rc::new(RefCell::new(Box {
                     _content_width: 0.0,
                     _content_height: 0.0,
-                    _padding: Edges::empty(),
-                    _border: Edges::empty(),
-                    _margin: element.style().margin.to_edges(parents),
-                    _box_type: element.style().display.to_box_type(),
+                    _padding: padding,
+                    _border: border,
+                    _margin: margin,
+                    _box_type: match &element.style().display {
+                        // Floats are blockified
+                        Display::Inline if element.style().float != Float::None => BoxType::Block,
+                        display => display.to_box_type(),
+                    },
                     _position_x: None,
                     _position_y: None,
+                    _baseline: 0.0,
+                    _text_lines: vec![],
                     children: vec![],
 
                     associated_node: Some(Rc::clone(tree)),
@@ -403,6 +478,8 @@ impl Box {
                             _box_type: BoxType::Marker,
                             _position_x: None,
                             _position_y: None,
+                            _baseline: 0.0,
+                            _text_lines: vec![],
                             children: vec![],
 
                             associated_node: None,
@@ -412,10 +489,12 @@ impl Box {
                             _content_height: 0.0,
                             _padding: Edges::empty(),
                             _border: Edges::empty(),
-                            _margin: element.style().margin.to_edges(parents),
+                            _margin: margin,
                             _box_type: BoxType::Block,
                             _position_x: None,
                             _position_y: None,
+                            _baseline: 0.0,
+                            _text_lines: vec![],
                             children: vec![],
 
                             associated_node: Some(Rc::clone(tree)),
@@ -455,6 +534,8 @@ impl Box {
                     _box_type: BoxType::Inline,
                     _position_x: None,
                     _position_y: None,
+                    _baseline: 0.0,
+                    _text_lines: vec![],
                     children: vec![],
 
                     associated_node: Some(Rc::clone(tree)),
@@ -505,6 +586,9 @@ impl Box {
                 );
 
                 let mut content_box = content_box_rc.borrow_mut();
+                if let Some(width) = container_width {
+                    content_box.resolve_margin(width, parents);
+                }
 
                 let (content_width, content_height, _) = content_box.layout(
                     container_width,
@@ -540,6 +624,75 @@ impl Box {
         }
     }
 
+    /// Resolves the margins and padding of this box's element now that the width of its
+    /// containing block, which their percentages refer to, is known.
+    fn resolve_margin(&mut self, container_width: f64, parents: &[Rc<RefCell<Element>>]) {
+        if let Some(style) = self.style() {
+            let context = self.length_context(container_width, parents);
+            self._margin = style.margin.to_edges(&context);
+            self._padding = style.padding.to_edges(&context);
+        }
+    }
+
+    /// What this box's lengths are resolved against: the width of its containing block, its
+    /// own font size for `em`, and the font size of the root element, the first of `parents`,
+    /// for `rem`.
+    fn length_context(
+        &self,
+        container_width: f64,
+        parents: &[Rc<RefCell<Element>>],
+    ) -> LengthContext {
+        let font_size = self
+            .style()
+            .and_then(|style| style.font.resolved_font_size())
+            .unwrap_or(16.0);
+        let root_font_size = parents
+            .first()
+            .and_then(|root| root.borrow().style().font.resolved_font_size())
+            .unwrap_or(16.0);
+
+        LengthContext {
+            container_width,
+            font_size,
+            root_font_size,
+        }
+    }
+
+    /// Gives `auto` horizontal margins of a block with a fixed width the space it leaves in
+    /// its containing block, splitting it evenly when both sides are `auto` to center the block.
+    ///
+    /// https://www.w3.org/TR/CSS2/visudet.html#blockwidth
+    fn resolve_auto_margins(&mut self, container_width: f64, parents: &[Rc<RefCell<Element>>]) {
+        let Some(style) = self.style() else {
+            return;
+        };
+
+        if style.width.is_auto() {
+            return;
+        }
+
+        let left_auto = matches!(style.margin.left, MarginValue::Auto);
+        let right_auto = matches!(style.margin.right, MarginValue::Auto);
+
+        let remaining = container_width
+            - style
+                .width
+                .resolve(&self.length_context(container_width, parents))
+            - self._border.horizontal()
+            - self._padding.horizontal();
+
+        match (left_auto, right_auto) {
+            (true, true) => {
+                let side = (remaining / 2.0).max(0.0);
+                self._margin.1 = side;
+                self._margin.3 = side;
+            }
+            (true, false) => self._margin.3 = (remaining - self._margin.right()).max(0.0),
+            (false, true) => self._margin.1 = (remaining - self._margin.left()).max(0.0),
+            (false, false) => {}
+        }
+    }
+
     pub fn style(&self) -> Option<ComputedStyle> {
         if let Some(node_rc) = &self.associated_node {
             if let NodeKind::Element(element_rc) = node_rc.borrow().deref() {
@@ -551,6 +704,158 @@ impl Box {
         None
     }
 
+    /// The border box as (x, y, width, height), in the same space as `position()`.
+    fn border_box_rect(&self) -> (f64, f64, f64, f64) {
+        let height = match self._box_type {
+            // Blocks lay their contents out from the top of their margin, so the top margin,
+            // border and padding are already part of their content height
+            BoxType::Block => {
+                self._content_height - self._margin.top()
+                    + self._padding.bottom()
+                    + self._border.bottom()
+            }
+            _ => self.border_edges().vertical(),
+        };
+
+        (
+            self.position().0 + self._margin.left(),
+            self.position().1 + self._margin.top(),
+            self.border_edges().horizontal(),
+            height,
+        )
+    }
+
+    /// The rectangle (x, y, width, height) painted with the background color, which is the
+    /// border box since `background-clip` is always `border-box`. It's in the same space as
+    /// `position()`, and is `None` when the background is transparent or the box is empty.
+    ///
+    /// https://drafts.csswg.org/css-backgrounds/#background-painting-area
+    pub fn background_rect(&self) -> Option<(f64, f64, f64, f64)> {
+        let style = self.style()?;
+        if style.background.color().used()[3] <= 0.0 {
+            return None;
+        }
+
+        let rect = self.border_box_rect();
+        if rect.2 <= 0.0 || rect.3 <= 0.0 {
+            return None;
+        }
+
+        Some(rect)
+    }
+
+    /// The rectangles (x, y, width, height) covering each side of the border that's drawn,
+    /// along with the color it's drawn in. The top and bottom sides span the whole border box,
+    /// and the left and right sides fit between them. Every style is drawn as if it were
+    /// `solid`.
+    ///
+    /// https://drafts.csswg.org/css-backgrounds/#borders
+    pub fn border_rects(&self) -> Vec<((f64, f64, f64, f64), UsedColor)> {
+        let Some(style) = self.style() else {
+            return Vec::new();
+        };
+
+        let (x, y, width, height) = self.border_box_rect();
+        let (top, right, bottom, left) = (
+            self._border.top(),
+            self._border.right(),
+            self._border.bottom(),
+            self._border.left(),
+        );
+
+        let sides = [
+            (&style.border.top, (x, y, width, top)),
+            (
+                &style.border.bottom,
+                (x, y + height - bottom, width, bottom),
+            ),
+            (
+                &style.border.left,
+                (x, y + top, left, height - top - bottom),
+            ),
+            (
+                &style.border.right,
+                (x + width - right, y + top, right, height - top - bottom),
+            ),
+        ];
+
+        sides
+            .into_iter()
+            .filter(|(_, (_, _, w, h))| *w > 0.0 && *h > 0.0)
+            .map(|(side, rect)| (rect, side.used_color(&style.color).used()))
+            .filter(|(_, color)| color[3] > 0.0)
+            .collect()
+    }
+
+    /// The rectangles (x, y, width, height) making up the outline: one for each side, just
+    /// outside the border box and `outline-offset` away from it. They are in the same space as
+    /// `position()`, and are empty when there is no outline to draw. Outlines don't take up
+    /// space, so nothing here affects layout.
+    ///
+    /// https://drafts.csswg.org/css-ui/#outline-props
+    pub fn outline_rects(&self) -> Vec<(f64, f64, f64, f64)> {
+        let Some(style) = self.style() else {
+            return Vec::new();
+        };
+
+        let context = LengthContext {
+            font_size: style.font.resolved_font_size().unwrap_or(16.0),
+            ..Default::default()
+        };
+        let width = style.outline.resolved_width(&context);
+
+        if width <= 0.0 {
+            return Vec::new();
+        }
+
+        let offset = style.outline.resolved_offset(&context);
+        let border_box = self.border_edges();
+
+        let x = self.position().0 + self._margin.left() - offset - width;
+        let y = self.position().1 + self._margin.top() - offset - width;
+        let outer_width = border_box.horizontal() + 2.0 * (offset + width);
+        let outer_height = border_box.vertical() + 2.0 * (offset + width);
+
+        if outer_width <= 0.0 || outer_height <= 0.0 {
+            return Vec::new();
+        }
+
+        vec![
+            (x, y, outer_width, width),
+            (x, y + outer_height - width, outer_width, width),
+            (x, y + width, width, outer_height - 2.0 * width),
+            (
+                x + outer_width - width,
+                y + width,
+                width,
+                outer_height - 2.0 * width,
+            ),
+        ]
+    }
+
+    /// Whether this box is the root of a new block formatting context, so the margins of its
+    /// children stay inside it instead of collapsing through it.
+    ///
+    /// https://www.w3.org/TR/CSS2/visuren.html#block-formatting
+    pub fn establishes_block_formatting_context(&self) -> bool {
+        let Some(node_rc) = &self.associated_node else {
+            return true;
+        };
+        let NodeKind::Element(element_rc) = node_rc.borrow().deref().clone() else {
+            return false;
+        };
+        let element = element_rc.borrow();
+
+        let is_root = element
+            ._node
+            .borrow()
+            .parent_node()
+            .and_then(|parent| parent.upgrade())
+            .is_some_and(|parent| parent.borrow()._node_type == NodeType::Document);
+
+        is_root || element.style().display == Display::FlowRoot
+    }
+
     pub fn layout_block(
         &mut self,
         container_width: Option<f64>,
@@ -564,12 +869,31 @@ impl Box {
             }
         }
 
+        if let Some(width) = container_width {
+            self.resolve_auto_margins(width, parents);
+        }
+
         let initial_x = self._margin.left() + self._border.3 + self._padding.3;
         let initial_y = self._margin.top() + self._border.0 + self._padding.0;
 
         let mut cursor_x = initial_x;
         let mut cursor_y = initial_y;
 
+        // The width our children are laid out in
+        let available_width = container_width.map(|width| match self.style() {
+            Some(style) if !style.width.is_auto() => {
+                style.width.resolve(&self.length_context(width, parents))
+            }
+            _ => {
+                width
+                    - self._margin.horizontal()
+                    - self._border.horizontal()
+                    - self._padding.horizontal()
+            }
+        });
+
+        let content_right = initial_x + available_width.unwrap_or(0.0);
+
         let mut inline_run: Vec<(Rc<RefCell<Box>>, bool, bool)> = Vec::new();
 
         let flush_inline_run =
@@ -577,50 +901,115 @@ impl Box {
              cursor_x: &mut f64,
              cursor_y: &mut f64,
              content_width: &mut f64,
+             floats: &[PlacedFloat],
              parents: &mut Vec<Rc<RefCell<Element>>>,
              renderers: &HashMap<RendererIdentifier, Option<TextRenderer>>| {
                 if run.is_empty() {
                     return;
                 }
 
-                let mut line_width = 0.0;
-                let mut line_height: f64 = 0.0;
+                // Lines start after any left floats they sit beside, and end before any right
+                // floats
+                let float_inset =
+                    |y: f64| left_float_edge(floats, y).map_or(0.0, |edge| edge - initial_x);
+                let right_float_inset =
+                    |y: f64| right_float_edge(floats, y).map_or(0.0, |edge| content_right - edge);
+
+                let mut line_width = float_inset(*cursor_y);
+                let mut right_inset = right_float_inset(*cursor_y);
+                let mut line: Vec<Rc<RefCell<Box>>> = Vec::new();
 
                 for (child_rc, first, last) in run.drain(..) {
-                    let mut child = child_rc.borrow_mut();
+                    let go_to_next_line = {
+                        let mut child = child_rc.borrow_mut();
 
-                    child._position_x = Some(*cursor_x - initial_x + line_width);
-                    child._position_y = Some(*cursor_y - initial_y);
+                        child._position_x = Some(*cursor_x - initial_x + line_width);
 
-                    let (w, h, go_to_next_line) = child.layout(
-                        container_width,
-                        container_height,
-                        first,
-                        last,
-                        parents,
-                        &renderers,
-                    );
+                        let (w, _, go_to_next_line) = child.layout(
+                            available_width.map(|width| width - line_width - right_inset),
+                            container_height,
+                            first,
+                            last,
+                            parents,
+                            &renderers,
+                        );
+
+                        line_width += w + child._margin.horizontal();
+                        go_to_next_line
+                    };
 
-                    line_width += w + child._margin.horizontal();
-                    line_height = line_height.max(h + child._margin.vertical());
+                    line.push(child_rc);
 
                     if go_to_next_line {
-                        *cursor_y += line_height;
+                        *cursor_y += align_line(&line, *cursor_y - initial_y).0;
                         *cursor_x = initial_x;
                         *content_width = content_width.max(line_width);
 
-                        line_width = 0.0;
+                        line.clear();
+                        line_width = float_inset(*cursor_y);
+                        right_inset = right_float_inset(*cursor_y);
                     }
                 }
 
-                *cursor_y += line_height;
+                *cursor_y += align_line(&line, *cursor_y - initial_y).0;
                 *cursor_x = initial_x;
                 *content_width = content_width.max(line_width);
             };
 
+        // https://www.w3.org/TR/CSS2/box.html#collapsing-margins
+        let collapses_with_children = !self.establishes_block_formatting_context();
+        let mut last_block_end: Option<f64> = None;
+
+        // https://www.w3.org/TR/CSS2/visuren.html#floats
+        let mut floats: Vec<PlacedFloat> = Vec::new();
+
         let mut prev_child: Option<Rc<RefCell<Box>>> = None;
         for (i, child_box_rc) in self.children.iter().enumerate() {
+            if let Some(width) = available_width {
+                child_box_rc.borrow_mut().resolve_margin(width, parents);
+            }
+
             let child_box_type = child_box_rc.borrow()._box_type.clone();
+            let child_float = child_box_rc
+                .borrow()
+                .style()
+                .map_or(Float::None, |style| style.float);
+
+            if child_float != Float::None {
+                // Floats are taken out of flow and placed at the top of the current line, so any
+                // pending inline content is laid out beside them
+                let mut child = child_box_rc.borrow_mut();
+
+                let (w, h, _) = child.layout(
+                    available_width,
+                    container_height,
+                    i == 0,
+                    i == self.children.len() - 1,
+                    parents,
+                    renderers,
+                );
+
+                let outer_width = w + child._margin.horizontal();
+                let left = if child_float == Float::Left {
+                    left_float_edge(&floats, cursor_y).unwrap_or(initial_x)
+                } else {
+                    right_float_edge(&floats, cursor_y).unwrap_or(content_right) - outer_width
+                };
+
+                child._position_x = Some(left);
+                child._position_y = Some(cursor_y);
+
+                floats.push(PlacedFloat {
+                    side: child_float,
+                    left,
+                    right: left + outer_width,
+                    top: cursor_y,
+                    bottom: cursor_y + h + child._margin.bottom(),
+                });
+
+                self._content_width = self._content_width.max(outer_width);
+                continue;
+            }
 
             match child_box_type {
                 BoxType::Inline => {
@@ -633,6 +1022,7 @@ impl Box {
                         &mut cursor_x,
                         &mut cursor_y,
                         &mut self._content_width,
+                        &floats,
                         parents,
                         &renderers,
                     );
@@ -652,13 +1042,23 @@ impl Box {
                                 cursor_y -= prev._margin.bottom();
                             }
                         }
+                    } else if collapses_with_children
+                        && cursor_y == initial_y
+                        && self._padding.top() == 0.0
+                        && self._border.top() == 0.0
+                        && child._padding.is_none()
+                        && child._border.is_none()
+                    {
+                        // The first child's top margin collapses with our own
+                        let (ours, theirs) = (self._margin.top(), child._margin.top());
+                        cursor_y -= ours + theirs - collapse_margins(ours, theirs);
                     }
 
                     child._position_x = Some(cursor_x);
                     child._position_y = Some(cursor_y);
 
                     let (w, h, go_to_next_line) = child.layout(
-                        container_width,
+                        available_width,
                         container_height,
                         i == 0,
                         i == self.children.len() - 1,
@@ -674,6 +1074,7 @@ impl Box {
 
                     self._content_width = self._content_width.max(w + child._margin.horizontal());
                     prev_child = Some(child_box_rc.clone());
+                    last_block_end = Some(cursor_y);
                 }
                 _ => {
                     let mut child = child_box_rc.borrow_mut();
@@ -682,7 +1083,7 @@ impl Box {
                     child._position_y = Some(cursor_y);
 
                     let (w, h, go_to_next_line) = child.layout(
-                        container_width,
+                        available_width,
                         container_height,
                         i == 0,
                         i == self.children.len() - 1,
@@ -707,20 +1108,41 @@ impl Box {
             &mut cursor_x,
             &mut cursor_y,
             &mut self._content_width,
+            &floats,
             parents,
             &renderers,
         );
 
+        if !collapses_with_children {
+            // Block formatting context roots grow to contain their floats
+            for float in &floats {
+                cursor_y = cursor_y.max(float.bottom);
+            }
+        }
+
+        if collapses_with_children
+            && last_block_end == Some(cursor_y)
+            && self._padding.bottom() == 0.0
+            && self._border.bottom() == 0.0
+            && let Some(last_rc) = &prev_child
+        {
+            let last = last_rc.borrow();
+
+            if last._padding.is_none() && last._border.is_none() {
+                // The last child's bottom margin collapses with our own
+                let (ours, theirs) = (self._margin.bottom(), last._margin.bottom());
+                cursor_y -= ours + theirs - collapse_margins(ours, theirs);
+            }
+        }
+
         self._content_height = cursor_y;
 
-        if !matches!(self.style().unwrap().width, WidthValue::Auto) {
+        if !self.style().unwrap().width.is_auto() {
+            let context = self.length_context(container_width.unwrap_or(0.0), parents);
             if let Some(node_rc) = &self.associated_node {
                 if let NodeKind::Element(element_rc) = node_rc.borrow().deref() {
                     let element = element_rc.borrow();
-                    self._content_width = element
-                        .style()
-                        .width
-                        .resolve(container_width.unwrap_or(0.0));
+                    self._content_width = element.style().width.resolve(&context);
                 }
             }
         }
@@ -738,21 +1160,22 @@ impl Box {
     /// Returns (total_width, total_height, go_to_next_line)
     pub fn layout_inline(
         &mut self,
-        _container_width: Option<f64>,
+        container_width: Option<f64>,
         _container_height: Option<f64>,
         first_child: bool,
         last_child: bool,
         parents: &mut Vec<Rc<RefCell<Element>>>,
         renderers: &HashMap<RendererIdentifier, Option<TextRenderer>>,
     ) -> (f64, f64, bool) {
-        let mut pen_x = 0.0;
+        let mut pen_x: f64 = 0.0;
         let mut pen_y = 0.0;
+        let mut go_to_next_line = false;
 
         let node = self.associated_node.as_ref().unwrap().borrow().clone();
 
         match node {
             NodeKind::Text(text_node_rc) => {
-                if text_node_rc.borrow().data().trim().is_empty() {
+                if text_node_rc.borrow().data().chars().all(is_white_space) {
                     // TODO: Handle pre
                     return (0.0, 0.0, false);
                 }
@@ -805,59 +1228,50 @@ impl Box {
                 let scale =
                     style.font.resolved_font_size().unwrap_or(16.0) / font.units_per_em() as f64;
 
-                let mut new_data = String::new();
-
-                let chars = {
-                    let text_node = text_node_rc.borrow();
-                    let data = text_node.data();
-                    if first_child && last_child {
-                        data.trim().chars().collect::<Vec<char>>()
-                    } else if first_child {
-                        data.trim_start().chars().collect::<Vec<char>>()
-                    } else if last_child {
-                        data.trim_end().chars().collect::<Vec<char>>()
-                    } else {
-                        data.chars().collect::<Vec<char>>()
-                    }
-                };
+                let collapsed =
+                    collapse_white_space(text_node_rc.borrow().data(), first_child, last_child);
 
-                let mut last_was_space = false;
+                // Characters without a glyph take up the missing glyph's width
+                let glyph_advance = |glyph_index: u16| {
+                    font.advance_width(glyph_index)
+                        .or_else(|| font.rawdog_advance_width(glyph_index))
+                        .map(|aw| aw as f64 * scale)
+                        .unwrap_or(0.0)
+                };
 
-                for ch in chars {
-                    if ch != '\n' && ch != '\r' && ch != '\t' {
-                        if last_was_space && ch == ' ' {
-                            continue;
-                        }
+                // The whole text is mapped to glyphs at once, rather than a character at a time
+                let advances = collapsed
+                    .chars()
+                    .zip(font.glyph_indices(&collapsed))
+                    .map(|(ch, glyph_index)| (ch, glyph_advance(glyph_index)))
+                    .collect::<HashMap<char, f64>>();
+
+                let advance = |ch: char| {
+                    advances
+                        .get(&ch)
+                        .copied()
+                        .unwrap_or_else(|| glyph_advance(font.resolve_glyph(ch as u32)))
+                };
 
-                        last_was_space = ch == ' ';
-                        new_data.push(ch);
-
-                        let aw = font
-                            .advance_width(
-                                font.glyph_index(ch as u32)
-                                    .unwrap_or_else(|| font.last_glyph_index().unwrap()),
-                            )
-                            // .map(|aw| aw as f64 * self._font_size.unwrap_or(16.0))
-                            .map(|aw| aw as f64 * scale)
-                            .unwrap_or_else(|| {
-                                font.rawdog_advance_width(
-                                    font.glyph_index(ch as u32)
-                                        .unwrap_or_else(|| font.last_glyph_index().unwrap()),
-                                )
-                                .map(|aw| aw as f64 * scale)
-                                .unwrap_or(0.0)
-                            });
-
-                        pen_x += aw;
-                    } else {
-                        // TODO: handle pre
-                    }
+                let lines = break_lines(&collapsed, container_width, advance);
+                for (_, width) in &lines {
+                    pen_x = pen_x.max(*width);
                 }
 
-                text_node_rc.borrow_mut().set_data(&new_data);
-                self._content_height = self
-                    ._content_height
-                    .max(style.font.resolved_line_height().unwrap_or(19.2));
+                text_node_rc.borrow_mut().set_data(&collapsed);
+
+                let line_height = style.font.resolved_line_height().unwrap_or(19.2);
+                self._content_height = self._content_height.max(line_height * lines.len() as f64);
+
+                // Content after text that wrapped starts on a new line
+                go_to_next_line = lines.len() > 1;
+                self._text_lines = lines.into_iter().map(|(line, _)| line).collect();
+
+                // The glyphs sit in the middle of the line, with the leading split above and below
+                // https://drafts.csswg.org/css-inline/#inline-height
+                let ascent = font.ascent().unwrap_or(0) as f64 * scale;
+                let descent = font.descent().unwrap_or(0) as f64 * scale;
+                self._baseline = (line_height - (ascent + descent)) / 2.0 + ascent;
 
                 self._content_width = self._content_width.max(pen_x);
             }
@@ -871,245 +1285,341 @@ impl Box {
                             .resolved_line_height()
                             .unwrap_or(19.2),
                     );
+                    self._baseline = self._content_height;
                     return (pen_x, self._content_height, true);
                 }
 
                 parents.push(e);
 
-                for (i, child_box) in self.children.iter().enumerate() {
-                    let mut child_box = child_box.borrow_mut();
+                // Children sharing a line are aligned on a common baseline, and the first
+                // line's baseline becomes ours
+                let mut line: Vec<Rc<RefCell<Box>>> = Vec::new();
 
-                    child_box._position_x = Some(pen_x);
-                    child_box._position_y = Some(pen_y);
+                for (i, child_rc) in self.children.iter().enumerate() {
+                    let go_to_next_line = {
+                        let mut child_box = child_rc.borrow_mut();
 
-                    let (advance, line_height, go_to_next_line) = child_box.layout(
-                        None,
-                        None,
-                        i == 0,
-                        i == self.children.len() - 1,
-                        parents,
-                        &renderers,
-                    );
+                        if let Some(width) = container_width {
+                            child_box.resolve_margin(width, parents);
+                        }
+                        child_box._position_x = Some(pen_x);
 
-                    pen_x += advance;
-                    self._content_height = self._content_height.max(line_height);
+                        let (advance, _, go_to_next_line) = child_box.layout(
+                            container_width.map(|width| width - pen_x),
+                            None,
+                            i == 0,
+                            i == self.children.len() - 1,
+                            parents,
+                            &renderers,
+                        );
+
+                        pen_x += advance;
+                        go_to_next_line
+                    };
+
+                    line.push(child_rc.clone());
 
                     if go_to_next_line {
+                        let (line_height, baseline) = align_line(&line, pen_y);
+                        if pen_y == 0.0 {
+                            self._baseline = baseline;
+                        }
+
                         self._content_width = self._content_width.max(pen_x);
                         pen_x = 0.0;
                         pen_y += line_height;
+                        line.clear();
                     }
                 }
 
+                let (line_height, baseline) = align_line(&line, pen_y);
+                if pen_y == 0.0 {
+                    self._baseline = baseline;
+                }
+
                 self._content_width = self._content_width.max(pen_x);
+                self._content_height = self._content_height.max(pen_y + line_height);
 
                 parents.pop();
             }
             _ => {}
         }
 
-        (self._content_width, self._content_height, false)
+        (self._content_width, self._content_height, go_to_next_line)
     }
 }
 
-fn compute_doc_styles(doc: &Rc<RefCell<Document>>) {
-    let doc_borrow = doc.borrow();
-    let doc_node = doc_borrow._node.borrow();
-    let children = doc_node.child_nodes();
-
-    for node_rc in children.iter() {
-        let node = node_rc.borrow();
-        if let NodeKind::Element(element_rc) = node.deref() {
-            let mut element = element_rc.borrow_mut();
-            element.compute_element_styles(None);
-        }
-    }
+/// Document white space, which unlike other Unicode spaces (such as U+00A0 NO-BREAK SPACE) is
+/// collapsed and trimmed.
+///
+/// https://drafts.csswg.org/css-text/#white-space
+fn is_white_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r' | '\u{000c}')
 }
 
-fn handle_background(declaration: &CSSDeclaration, style: &mut ComputedStyle) {
-    let mut stream = InputStream::new(&declaration.value);
-
-    let bg = Background::from_cv(&mut stream);
-    if let Some(bg) = bg {
-        style.background = bg;
+/// Collapses each run of white space in `data` to a single space, optionally removing it from
+/// either end. Non-breaking spaces are left alone, so `a&nbsp;&nbsp;b` keeps both.
+///
+/// https://drafts.csswg.org/css-text/#white-space-phase-1
+pub fn collapse_white_space(data: &str, trim_start: bool, trim_end: bool) -> String {
+    let mut data = data;
+    if trim_start {
+        data = data.trim_start_matches(is_white_space);
+    }
+    if trim_end {
+        data = data.trim_end_matches(is_white_space);
     }
-}
 
-fn handle_background_property(declaration: &CSSDeclaration, style: &mut ComputedStyle) {
-    let mut stream = InputStream::new(&declaration.value);
+    let mut collapsed = String::with_capacity(data.len());
+    let mut last_was_space = false;
 
-    match declaration.property_name.as_str() {
-        "background-color" => {
-            let color = Color::from_cv(&mut stream);
-            if let Some(color) = color {
-                style.background.set_color(color);
+    for ch in data.chars() {
+        if is_white_space(ch) {
+            if !last_was_space {
+                collapsed.push(' ');
             }
+            last_was_space = true;
+        } else {
+            collapsed.push(ch);
+            last_was_space = false;
         }
-        "background-image" => {
-            let bg_image = Image::parse_multiple_images(&mut stream);
-            style.background.set_images(bg_image);
-        }
-        "background-repeat" => {
-            let repeat = RepeatStyle::parse_multiple_repeat_styles(&mut stream);
-            style.background.set_repeat_styles(repeat);
-        }
-        "background-position" => {
-            let position = PositionValue::parse_multiple_positions(&mut stream);
-            style.background.set_positions(position);
-        }
-        "background-origin" => {
-            let origin = Origin::parse_multiple_origins(&mut stream);
-            style.background.set_origins(origin);
-        }
-        _ => {}
     }
-}
 
-fn handle_font(
-    declaration: &CSSDeclaration,
-    style: &mut ComputedStyle,
-    parents: Option<&Vec<Rc<RefCell<Element>>>>,
-) {
-    let mut stream = InputStream::new(&declaration.value);
-
-    let font = Font::from_cv(&mut stream);
-    if let Some(font) = font {
-        style.font = font;
-        style.font.resolve_font_size(parents.unwrap_or(&vec![]));
-        style.font.resolve_font_weight(parents.unwrap_or(&vec![]));
-    }
+    collapsed
 }
 
-fn handle_font_property(
-    declaration: &CSSDeclaration,
-    style: &mut ComputedStyle,
-    parents: Option<&Vec<Rc<RefCell<Element>>>>,
-) {
-    let mut stream = InputStream::new(&declaration.value);
+/// Breaks `text` into lines no wider than `max_width` where possible, returning each line with
+/// its width. Lines break after spaces, which hang off the end of the line, and at soft hyphens
+/// (U+00AD), which are invisible unless a line breaks there and then show as a hyphen. A word
+/// with no break opportunity that doesn't fit is left to overflow.
+///
+/// https://drafts.csswg.org/css-text/#line-breaking
+pub fn break_lines(
+    text: &str,
+    max_width: Option<f64>,
+    advance: impl Fn(char) -> f64,
+) -> Vec<(String, f64)> {
+    struct BreakPoint {
+        index: usize,
+        line_width: f64,
+        consumed_width: f64,
+        hyphen: bool,
+    }
 
-    match declaration.property_name.as_str() {
-        "font-family" => {
-            let family = FontFamily::from_cv(&mut stream);
-            if let Some(family) = family {
-                style.font.set_family(family);
-            }
+    let mut lines = vec![];
+    let mut line = String::new();
+    let mut width = 0.0;
+    let mut last_break: Option<BreakPoint> = None;
+
+    for ch in text.chars() {
+        if ch == '\u{00ad}' {
+            last_break = Some(BreakPoint {
+                index: line.len(),
+                line_width: width + advance('-'),
+                consumed_width: width,
+                hyphen: true,
+            });
+            continue;
         }
-        "font-size" => {
-            let size = FontSize::from_cv(&mut stream);
-            if let Some(size) = size {
-                style.font.set_size(size);
-                style.font.resolve_font_size(parents.unwrap_or(&vec![]));
+
+        let ch_width = advance(ch);
+
+        if ch != ' '
+            && max_width.is_some_and(|max_width| width + ch_width > max_width)
+            && let Some(point) = last_break.take()
+        {
+            let rest = line.split_off(point.index);
+            if point.hyphen {
+                line.push('-');
+            } else {
+                line.truncate(line.trim_end_matches(' ').len());
             }
+
+            lines.push((line, point.line_width));
+            line = rest;
+            width -= point.consumed_width;
         }
-        "font-weight" => {
-            let weight = FontWeight::from_cv(&mut stream);
-            if let Some(weight) = weight {
-                style.font.set_weight(weight);
-                style.font.resolve_font_weight(parents.unwrap_or(&vec![]));
-            }
+
+        line.push(ch);
+        width += ch_width;
+
+        if ch == ' ' {
+            last_break = Some(BreakPoint {
+                index: line.len(),
+                line_width: width - ch_width,
+                consumed_width: width,
+                hyphen: false,
+            });
         }
-        "line-height" => {
-            let line_height = LineHeight::from_cv(&mut stream);
-            if let Some(line_height) = line_height {
-                style.font.set_line_height(line_height);
-            }
+    }
+
+    lines.push((line, width));
+    lines
+}
+
+/// Vertically positions the boxes of a line whose top is at `top` according to their
+/// `vertical-align`, and returns the height of the line and where its baseline falls below its
+/// top.
+///
+/// https://drafts.csswg.org/css-inline/#propdef-vertical-align
+fn align_line(line: &[Rc<RefCell<Box>>], top: f64) -> (f64, f64) {
+    let alignment = |child: &Box| {
+        child
+            .associated_node
+            .as_ref()
+            .and_then(|node_rc| node_rc.borrow().style())
+            .map_or(VerticalAlign::Baseline, |style| style.vertical_align)
+    };
+
+    // Boxes on the baseline are stacked so their baselines meet, which sets the line's baseline
+    let mut baseline: f64 = 0.0;
+    for child_rc in line {
+        let child = child_rc.borrow();
+        if alignment(&child) == VerticalAlign::Baseline {
+            baseline = baseline.max(child._margin.top() + child._baseline);
         }
-        "font-style" => {
-            let font_style = FontStyle::from_cv(&mut stream);
-            if let Some(font_style) = font_style {
-                style.font.set_style(font_style);
+    }
+
+    let mut line_height: f64 = 0.0;
+    for child_rc in line {
+        let child = child_rc.borrow();
+        let outer_height = child._content_height + child._margin.vertical();
+
+        line_height = line_height.max(match alignment(&child) {
+            VerticalAlign::Baseline => {
+                baseline - child._margin.top() - child._baseline + outer_height
             }
-        }
-        _ => {}
+            _ => outer_height,
+        });
     }
+
+    for child_rc in line {
+        let mut child = child_rc.borrow_mut();
+        let outer_height = child._content_height + child._margin.vertical();
+
+        let offset = match alignment(&child) {
+            VerticalAlign::Baseline => baseline - child._margin.top() - child._baseline,
+            VerticalAlign::Top => 0.0,
+            VerticalAlign::Middle => (line_height - outer_height) / 2.0,
+            VerticalAlign::Bottom => line_height - outer_height,
+        };
+
+        child._position_y = Some(top + offset);
+    }
+
+    (line_height, baseline)
 }
 
-fn handle_margin(declaration: &CSSDeclaration, style: &mut ComputedStyle) {
-    let mut stream = InputStream::new(&declaration.value);
+/// A float that has been placed in a block formatting context, in its parent's coordinates.
+struct PlacedFloat {
+    side: Float,
+    left: f64,
+    right: f64,
+    top: f64,
+    bottom: f64,
+}
 
-    let margin = Margin::from_cv(&mut stream);
-    if let Some(margin) = margin {
-        style.margin = margin;
+impl PlacedFloat {
+    fn is_beside(&self, y: f64) -> bool {
+        self.top <= y && y < self.bottom
     }
 }
 
-fn handle_margin_property(declaration: &CSSDeclaration, style: &mut ComputedStyle) {
-    let mut stream = InputStream::new(&declaration.value);
+/// The right edge of the rightmost left float beside a line at `y`.
+/// The single margin two adjoining margins collapse into: the largest positive margin plus
+/// the most negative one.
+///
+/// https://www.w3.org/TR/CSS2/box.html#collapsing-margins
+fn collapse_margins(a: f64, b: f64) -> f64 {
+    a.max(b).max(0.0) + a.min(b).min(0.0)
+}
+
+fn left_float_edge(floats: &[PlacedFloat], y: f64) -> Option<f64> {
+    floats
+        .iter()
+        .filter(|float| float.side == Float::Left && float.is_beside(y))
+        .map(|float| float.right)
+        .reduce(f64::max)
+}
 
-    match declaration.property_name.as_str() {
-        "margin-top" => {
-            let top = MarginValue::from_cv(&mut stream);
-            if let Some(top) = top {
-                style.margin.top = top;
-            }
-        }
-        "margin-right" => {
-            let right = MarginValue::from_cv(&mut stream);
-            if let Some(right) = right {
-                style.margin.right = right;
-            }
-        }
-        "margin-bottom" => {
-            let bottom = MarginValue::from_cv(&mut stream);
-            if let Some(bottom) = bottom {
-                style.margin.bottom = bottom;
-            }
-        }
-        "margin-left" => {
-            let left = MarginValue::from_cv(&mut stream);
-            if let Some(left) = left {
-                style.margin.left = left;
-            }
+/// The left edge of the leftmost right float beside a line at `y`.
+fn right_float_edge(floats: &[PlacedFloat], y: f64) -> Option<f64> {
+    floats
+        .iter()
+        .filter(|float| float.side == Float::Right && float.is_beside(y))
+        .map(|float| float.left)
+        .reduce(f64::min)
+}
+
+fn compute_doc_styles(doc: &Rc<RefCell<Document>>) {
+    let doc_borrow = doc.borrow();
+    let doc_node = doc_borrow._node.borrow();
+    let children = doc_node.child_nodes();
+
+    for node_rc in children.iter() {
+        let node = node_rc.borrow();
+        if let NodeKind::Element(element_rc) = node.deref() {
+            let mut element = element_rc.borrow_mut();
+            element.compute_element_styles(None);
         }
-        _ => {}
     }
 }
 
+/// Applies a declaration to `style` through the property registry. Returns `false`, leaving
+/// `style` untouched, if the property isn't supported.
 pub fn handle_declaration(
     declaration: &CSSDeclaration,
     style: &mut ComputedStyle,
     parents: Option<&Vec<Rc<RefCell<Element>>>>,
-) {
-    match declaration.property_name.as_str() {
-        "color" => {
-            let mut stream = InputStream::new(&declaration.value);
-            style.color = Color::from_cv(&mut stream).unwrap_or(Color::default());
-        }
-        "background" => {
-            handle_background(declaration, style);
-        }
-        prop if prop.starts_with("background-") => {
-            handle_background_property(declaration, style);
-        }
-        "font" => {
-            handle_font(declaration, style, parents);
-        }
-        prop if prop.starts_with("font-") || prop == "line-height" => {
-            handle_font_property(declaration, style, parents);
-        }
-        "width" => {
-            let mut stream = InputStream::new(&declaration.value);
-            style.width = WidthValue::from_cv(&mut stream).unwrap_or_default();
-        }
-        "display" => {
-            let mut stream = InputStream::new(&declaration.value);
-            style.display = Display::from_cv(&mut stream).unwrap_or_default();
-        }
-        "margin" => {
-            handle_margin(declaration, style);
-        }
-        prop if prop.starts_with("margin-") => {
-            handle_margin_property(declaration, style);
-        }
-        "position" => {
-            let mut stream = InputStream::new(&declaration.value);
-            style.position = Position::from_cv(&mut stream).unwrap_or_default();
-        }
-        _ => {
-            // todo!(
-            //     "Implement handling for property: {}",
-            //     declaration.property_name
-            // );
+) -> bool {
+    let Some(property) = registry::property(&declaration.property_name) else {
+        return false;
+    };
+
+    if let Some(keyword) = css_wide_keyword(&declaration.value) {
+        handle_css_wide_keyword(property, &keyword, style, parents);
+    } else {
+        property.apply(declaration, style, parents);
+    }
+
+    true
+}
+
+/// The CSS-wide keyword a declaration's value consists of, if any.
+///
+/// https://drafts.csswg.org/css-values/#common-keywords
+fn css_wide_keyword(value: &[ComponentValue]) -> Option<String> {
+    let mut tokens = value
+        .iter()
+        .filter(|cv| !matches!(cv, ComponentValue::Token(CSSToken::Whitespace)));
+
+    match (tokens.next(), tokens.next()) {
+        (Some(ComponentValue::Token(CSSToken::Ident(ident))), None) => {
+            let keyword = ident.to_ascii_lowercase();
+            ["initial", "inherit", "unset"]
+                .contains(&keyword.as_str())
+                .then_some(keyword)
         }
+        _ => None,
     }
 }
+
+/// Sets a property to its initial value, or to the parent's value for `inherit` and for
+/// `unset` on inherited properties.
+///
+/// https://drafts.csswg.org/css-cascade/#defaulting-keywords
+fn handle_css_wide_keyword(
+    property: &PropertyDefinition,
+    keyword: &str,
+    style: &mut ComputedStyle,
+    parents: Option<&Vec<Rc<RefCell<Element>>>>,
+) {
+    let inherits = keyword == "inherit" || (keyword == "unset" && property.inherited);
+
+    let source = match parents.and_then(|p| p.last()) {
+        Some(parent) if inherits => parent.borrow().style().clone(),
+        _ => ComputedStyle::default(),
+    };
+
+    property.copy_from(&source, style, parents);
+}
@@ -106,33 +106,45 @@ pub const SELECTED_ITEM_TEXT_DARK: &str = "#DBEAFE";
 pub const VISITED_TEXT_LIGHT: &str = "#7C3AED";
 pub const VISITED_TEXT_DARK: &str = "#C4B5FD";
 
+const SYSTEM_COLORS: [&str; 19] = [
+    "AccentColor",
+    "AccentColorText",
+    "ActiveText",
+    "ButtonBorder",
+    "ButtonFace",
+    "ButtonText",
+    "Canvas",
+    "CanvasText",
+    "Field",
+    "FieldText",
+    "GrayText",
+    "Highlight",
+    "HighlightText",
+    "LinkText",
+    "Mark",
+    "MarkText",
+    "SelectedItem",
+    "SelectedItemText",
+    "VisitedText",
+];
+
+/// The canonical spelling of a system color keyword, which like all keywords is ASCII
+/// case-insensitive.
+///
+/// https://drafts.csswg.org/css-color/#css-system-colors
+fn canonical_system_color(name: &str) -> Option<&'static str> {
+    SYSTEM_COLORS
+        .iter()
+        .copied()
+        .find(|system_color| system_color.eq_ignore_ascii_case(name))
+}
+
 pub fn is_system_color(name: &str) -> bool {
-    matches!(
-        name,
-        "AccentColor"
-            | "AccentColorText"
-            | "ActiveText"
-            | "ButtonBorder"
-            | "ButtonFace"
-            | "ButtonText"
-            | "Canvas"
-            | "CanvasText"
-            | "Field"
-            | "FieldText"
-            | "GrayText"
-            | "Highlight"
-            | "HighlightText"
-            | "LinkText"
-            | "Mark"
-            | "MarkText"
-            | "SelectedItem"
-            | "SelectedItemText"
-            | "VisitedText"
-    )
+    canonical_system_color(name).is_some()
 }
 
 pub fn get_system_color(name: &str, dark_mode: bool) -> Option<&'static str> {
-    match (name, dark_mode) {
+    match (canonical_system_color(name)?, dark_mode) {
         ("AccentColor", false) => Some(ACCENT_COLOR_LIGHT),
         ("AccentColor", true) => Some(ACCENT_COLOR_DARK),
         ("AccentColorText", false) => Some(ACCENT_COLOR_TEXT_LIGHT),
@@ -338,18 +350,16 @@ pub fn is_color(token: &ComponentValue) -> bool {
     match token {
         ComponentValue::Token(CSSToken::Ident(name))
         // currentColor, transparent, <system-color>
-            if name == "currentColor" || name == "transparent" || is_system_color(name) =>
+            if name.eq_ignore_ascii_case("currentColor")
+                || name.eq_ignore_ascii_case("transparent")
+                || is_system_color(name) =>
         {
             true
         }
         // <named-color>
         ComponentValue::Token(CSSToken::Ident(name)) if get_named_color(name).is_some() => true,
         // <hex-color>
-        ComponentValue::Token(CSSToken::Hash(HashToken{ value: val, .. })) => match val.len() {
-            // #RRGGBB, #RGB, #RRGGBBAA, #RGBA
-            3 | 6 | 4 | 8 => val.chars().all(|c| c.is_ascii_hexdigit()),
-            _ => false,
-        },
+        ComponentValue::Token(CSSToken::Hash(HashToken{ value: val, .. })) => is_hex_color(val),
         // <color-function>
         ComponentValue::Function(Function(name, ..)) if is_color_function(name) =>
         {
@@ -366,41 +376,35 @@ pub fn is_color_function(name: &str) -> bool {
     )
 }
 
+/// Whether `hex`, without its `#`, is a `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa` color.
+///
+/// https://drafts.csswg.org/css-color/#hex-notation
+pub fn is_hex_color(hex: &str) -> bool {
+    matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+}
+
 pub fn hex_to_rgb(hex: &str) -> UsedColor {
     let hex = hex.trim_start_matches('#');
-    let (r, g, b, a) = match hex.len() {
-        3 => (
-            u8::from_str_radix(&hex[0..1].repeat(2), 16).unwrap_or(0),
-            u8::from_str_radix(&hex[1..2].repeat(2), 16).unwrap_or(0),
-            u8::from_str_radix(&hex[2..3].repeat(2), 16).unwrap_or(0),
-            100.0,
-        ),
-        4 => (
-            u8::from_str_radix(&hex[0..1].repeat(2), 16).unwrap_or(0),
-            u8::from_str_radix(&hex[1..2].repeat(2), 16).unwrap_or(0),
-            u8::from_str_radix(&hex[2..3].repeat(2), 16).unwrap_or(0),
-            u8::from_str_radix(&hex[3..4].repeat(2), 16).unwrap_or(0) as f32 * 100.0 / 255.0,
-        ),
-        6 => (
-            u8::from_str_radix(&hex[0..2], 16).unwrap_or(0),
-            u8::from_str_radix(&hex[2..4], 16).unwrap_or(0),
-            u8::from_str_radix(&hex[4..6], 16).unwrap_or(0),
-            100.0,
-        ),
-        8 => (
-            u8::from_str_radix(&hex[0..2], 16).unwrap_or(0),
-            u8::from_str_radix(&hex[2..4], 16).unwrap_or(0),
-            u8::from_str_radix(&hex[4..6], 16).unwrap_or(0),
-            u8::from_str_radix(&hex[6..8], 16).unwrap_or(0) as f32 * 100.0 / 255.0,
-        ),
-        _ => (0, 0, 0, 100.0),
+    if !is_hex_color(hex) {
+        return [0.0, 0.0, 0.0, 1.0];
+    }
+
+    // The shorthand forms repeat each digit, so #f008 is #ff000088
+    let digits = match hex.len() {
+        3 | 4 => hex.chars().flat_map(|c| [c, c]).collect::<String>(),
+        _ => hex.to_string(),
     };
-    [
-        r as f32 / 255.0,
-        g as f32 / 255.0,
-        b as f32 / 255.0,
-        a / 100.0,
-    ]
+
+    // A missing alpha channel is fully opaque
+    let channel = |i: usize| {
+        let byte = digits
+            .get(i * 2..i * 2 + 2)
+            .map_or(255, |byte| u8::from_str_radix(byte, 16).unwrap_or(0));
+
+        byte as f32 / 255.0
+    };
+
+    [channel(0), channel(1), channel(2), channel(3)]
 }
 
 mod functions {
@@ -501,6 +505,11 @@ impl CSSParseable for Color {
     fn from_cv(cvs: &mut InputStream<ComponentValue>) -> Option<Self> {
         if let Some(tok) = cvs.consume() {
             match tok {
+                ComponentValue::Token(CSSToken::Ident(name))
+                    if name.eq_ignore_ascii_case("transparent") =>
+                {
+                    Some(Color::transparent())
+                }
                 ComponentValue::Token(CSSToken::Ident(name))
                     if get_named_color(&name).is_some() =>
                 {
@@ -509,7 +518,9 @@ impl CSSParseable for Color {
                 ComponentValue::Token(CSSToken::Ident(name)) if is_system_color(&name) => {
                     Some(Color::SystemNamed(name.clone()))
                 }
-                ComponentValue::Token(CSSToken::Hash(HashToken { value: val, .. })) => {
+                ComponentValue::Token(CSSToken::Hash(HashToken { value: val, .. }))
+                    if is_hex_color(&val) =>
+                {
                     Some(Color::Hex(val.clone()))
                 }
                 ComponentValue::Function(func) if is_color_function(&func.0) => {
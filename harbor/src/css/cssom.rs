@@ -11,7 +11,10 @@ use crate::{
     css::{
         colors::{Color, is_color},
         parser::{AtRule, ComponentValue, parse_css_declaration_block},
-        properties::{Background, Display, Font, Margin, Position, WidthValue},
+        properties::{
+            Background, Border, Cursor, Display, Float, Font, Margin, Outline, Padding, Position,
+            TextDecoration, VerticalAlign, WidthValue,
+        },
         selectors::SelectorList,
         tokenize::{CSSToken, Dimension},
         values::angles::{is_angle_unit, to_canonical_angle},
@@ -653,6 +656,10 @@ impl CSSStyleSheet {
     pub fn set_location(&mut self, location: String) {
         self._location = Some(location);
     }
+
+    pub fn set_owner_node(&mut self, owner_node: Weak<RefCell<Element>>) {
+        self._owner_node = Some(owner_node);
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -689,7 +696,7 @@ impl PartialEq for DocumentOrShadowRootStyle {
 
 impl Eq for DocumentOrShadowRootStyle {}
 
-#[derive(Default, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct ComputedStyle {
     pub color: Color,
     pub background: Background,
@@ -697,10 +704,57 @@ pub struct ComputedStyle {
 
     pub display: Display,
     pub position: Position,
+    pub float: Float,
+    pub vertical_align: VerticalAlign,
 
     pub margin: Margin,
+    pub padding: Padding,
+    pub border: Border,
+    pub outline: Outline,
+
+    pub text_decoration: TextDecoration,
 
     pub width: WidthValue,
+
+    pub cursor: Cursor,
+}
+
+/// The initial value of every property, which the cascade starts from and `initial` and
+/// `unset` reset to.
+///
+/// https://drafts.csswg.org/css-cascade/#initial-values
+impl Default for ComputedStyle {
+    fn default() -> Self {
+        Self {
+            // black, or rather `CanvasText`
+            color: Color::default(),
+            // transparent with no image
+            background: Background::default(),
+            // medium (16px), normal weight and style, normal line height
+            font: Font::default(),
+
+            display: Display::Inline,
+            position: Position::Static,
+            float: Float::None,
+            vertical_align: VerticalAlign::Baseline,
+
+            // 0 on every side
+            margin: Margin::default(),
+            // 0 on every side
+            padding: Padding::default(),
+            // medium, but drawn 0 wide since the style is none
+            border: Border::default(),
+            // medium, but drawn 0 wide since the style is none
+            outline: Outline::default(),
+
+            // no lines, in the current color
+            text_decoration: TextDecoration::default(),
+
+            width: WidthValue::Auto,
+
+            cursor: Cursor::Auto,
+        }
+    }
 }
 
 impl ComputedStyle {
@@ -708,6 +762,7 @@ impl ComputedStyle {
         Self {
             color: self.color.clone(),
             font: self.font.clone(),
+            cursor: self.cursor,
             ..Default::default()
         }
     }
@@ -8,6 +8,7 @@ use crate::globals::FONTS;
 
 use crate::css::r#box;
 use crate::html5::dom::Document;
+use crate::html5::meta::viewport_config;
 
 #[derive(Clone)]
 pub struct Layout {
@@ -33,16 +34,28 @@ impl Layout {
         this
     }
 
+    /// The size of the initial containing block: the window, unless the document's viewport
+    /// `<meta>` asks for a different layout viewport.
+    ///
+    /// https://drafts.csswg.org/css-viewport/#viewport-meta
+    pub fn viewport_size(&self) -> (f64, f64) {
+        viewport_config(&self.document).map_or(self._window_size, |config| {
+            config.layout_size(self._window_size)
+        })
+    }
+
     pub fn make_tree(&mut self) {
-        let root_box = r#box::Box::build_doc_box_tree(&self.document, self._window_size);
+        let root_box = r#box::Box::build_doc_box_tree(&self.document, self.viewport_size());
         self.root_box = root_box;
     }
 
     pub fn layout(&mut self) {
+        let viewport_size = self.viewport_size();
+
         if let Some(root_box) = &self.root_box {
             root_box.borrow_mut().layout(
-                Some(self._window_size.0),
-                Some(self._window_size.1),
+                Some(viewport_size.0),
+                Some(viewport_size.1),
                 // fuhhh
                 false,
                 false,
@@ -84,12 +97,8 @@ impl Layout {
                     italic: font.is_italic(),
                 };
 
-                let renderer = TextRenderer {
-                    _associated_italic: identifier.italic,
-                    _associated_weight: identifier.font_weight,
-                    font: font.clone(),
-                    glyph_cache: HashMap::new(),
-                };
+                let renderer =
+                    TextRenderer::new(font.clone(), identifier.font_weight, identifier.italic);
 
                 self._renderers.insert(identifier, Some(renderer));
             }
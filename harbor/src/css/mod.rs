@@ -4,6 +4,7 @@ pub mod cssom;
 pub mod layout;
 pub mod parser;
 pub mod properties;
+pub mod registry;
 pub mod selectors;
 pub mod tokenize;
 pub mod values;
@@ -39,6 +39,45 @@ pub struct AtRule {
     block: Option<SimpleBlock>,
 }
 
+impl AtRule {
+    /// The URL of an `@import` rule, given either as a string or as a `url()`.
+    ///
+    /// https://drafts.csswg.org/css-cascade-5/#at-import
+    fn import_url(&self) -> Option<String> {
+        if !self.name.eq_ignore_ascii_case("import") {
+            return None;
+        }
+
+        let first = self
+            .prelude
+            .iter()
+            .find(|cv| !matches!(cv, ComponentValue::Token(CSSToken::Whitespace)))?;
+
+        match first {
+            ComponentValue::Token(CSSToken::String(url) | CSSToken::URL(url)) => Some(url.clone()),
+            ComponentValue::Function(Function(name, args)) if name.eq_ignore_ascii_case("url") => {
+                args.iter().find_map(|arg| match arg {
+                    ComponentValue::Token(CSSToken::String(url)) => Some(url.clone()),
+                    _ => None,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the rule can come before an `@import` without invalidating it: another `@import`,
+    /// `@charset`, or an `@layer` statement.
+    ///
+    /// https://drafts.csswg.org/css-cascade-5/#at-import
+    fn allows_later_imports(&self) -> bool {
+        match self.name.to_ascii_lowercase().as_str() {
+            "import" | "charset" => true,
+            "layer" => self.block.is_none(),
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug)]
 enum Rule {
     QualifiedRule(QualifiedRule),
@@ -349,6 +388,24 @@ pub fn parse_css_declaration_block(input: String) -> Vec<CSSDeclaration> {
     declarations
 }
 
+/// Collects the URL of every top-level `@import` rule in `input`, unresolved. Imports that come
+/// after any other rule besides `@charset` and `@layer` statements are invalid, and left out.
+pub fn import_urls(input: &str) -> Vec<String> {
+    let tokens = tokenize_from_string(preprocess(&input.to_string()));
+
+    consume_list_of_rules(&mut InputStream::new(&tokens), true)
+        .iter()
+        .take_while(|rule| match rule {
+            Rule::AtRule(at_rule) => at_rule.allows_later_imports(),
+            Rule::QualifiedRule(_) => false,
+        })
+        .filter_map(|rule| match rule {
+            Rule::AtRule(at_rule) => at_rule.import_url(),
+            _ => None,
+        })
+        .collect()
+}
+
 pub fn parse_stylesheet(
     stream: &mut InputStream<CSSToken>,
     document: Weak<RefCell<Document>>,
@@ -1,11 +1,8 @@
-use std::{
-    cell::RefCell,
-    rc::{Rc, Weak},
-};
+use std::{cell::RefCell, rc::Rc};
 
 use crate::{
     css::{
-        r#box::{Box, BoxType, Edges},
+        r#box::{BoxType, Edges},
         colors::Color,
         parser::{ComponentValue, Function},
         tokenize::{CSSToken, Dimension, NumberType, Percentage},
@@ -22,8 +19,7 @@ pub trait CSSParseable {
 
 #[derive(Default, Debug, Clone)]
 pub enum WidthValue {
-    Length(Dimension),
-    Percentage(Percentage),
+    Length(Length),
 
     #[default]
     Auto,
@@ -36,48 +32,45 @@ pub enum WidthValue {
 
 impl CSSParseable for WidthValue {
     fn from_cv(cvs: &mut InputStream<ComponentValue>) -> Option<Self> {
-        if let Some(tok) = cvs.consume() {
-            match tok {
-                ComponentValue::Token(CSSToken::Ident(ident)) => match ident.as_str() {
-                    "auto" => Some(WidthValue::Auto),
-                    "max-content" => Some(WidthValue::MaxContent),
-                    "min-content" => Some(WidthValue::MinContent),
-                    "fit-content" => Some(WidthValue::FitContent),
-                    "stretch" => Some(WidthValue::Stretch),
-                    _ => {
-                        cvs.reconsume();
-                        None
-                    }
-                },
-                ComponentValue::Token(CSSToken::Dimension(dim)) => {
-                    Some(WidthValue::Length(dim.clone()))
-                }
-                ComponentValue::Token(CSSToken::Percentage(perc)) => {
-                    Some(WidthValue::Percentage(perc.clone()))
-                }
+        if let Some(length) = Length::from_cv(cvs) {
+            return Some(WidthValue::Length(length));
+        }
+
+        match cvs.consume() {
+            Some(ComponentValue::Token(CSSToken::Ident(ident))) => match ident.as_str() {
+                "auto" => Some(WidthValue::Auto),
+                "max-content" => Some(WidthValue::MaxContent),
+                "min-content" => Some(WidthValue::MinContent),
+                "fit-content" => Some(WidthValue::FitContent),
+                "stretch" => Some(WidthValue::Stretch),
                 _ => {
                     cvs.reconsume();
                     None
                 }
+            },
+            Some(_) => {
+                cvs.reconsume();
+                None
             }
-        } else {
-            None
+            None => None,
         }
     }
 }
 
 impl WidthValue {
-    pub fn resolve(&self, parent_width: f64) -> f64 {
+    /// Resolves to pixels. `auto` takes up the whole containing block.
+    pub fn resolve(&self, context: &LengthContext) -> f64 {
         match self {
-            WidthValue::Length(dim) => match dim.unit.as_str() {
-                "px" => dim.value as f64,
-                _ => todo!("Handle other length units"),
-            },
-            WidthValue::Percentage(perc) => (*perc as f64 / 100.0) * parent_width,
-            WidthValue::Auto => parent_width,
-            _ => todo!("Handle other WidthValue variants"),
+            WidthValue::Length(length) => length.resolve(context),
+            _ => context.container_width,
         }
     }
+
+    /// Whether the width depends on the containing block rather than being given. Layout
+    /// doesn't size boxes to their contents yet, so the content-based keywords act like `auto`.
+    pub fn is_auto(&self) -> bool {
+        !matches!(self, WidthValue::Length(_))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -150,6 +143,7 @@ pub struct BackgroundLayer {
     pub position: PositionValue,
     pub repeat_style: RepeatStyle,
     pub origin: Origin,
+    pub size: BackgroundSize,
 }
 
 impl Default for BackgroundLayer {
@@ -160,6 +154,7 @@ impl Default for BackgroundLayer {
             position: PositionValue::default(),
             repeat_style: RepeatStyle::Repeat,
             origin: Origin::PaddingBox,
+            size: BackgroundSize::default(),
         }
     }
 }
@@ -280,6 +275,20 @@ impl Background {
         }
     }
 
+    pub fn set_sizes(&mut self, sizes: Vec<BackgroundSize>) {
+        for (i, size) in sizes.into_iter().enumerate() {
+            if i < self.layers.len() {
+                self.layers[i].size = size;
+            } else {
+                self.layers.push(BackgroundLayer {
+                    size,
+                    ..Default::default()
+                });
+                self.update_color(self.color());
+            }
+        }
+    }
+
     pub fn set_origins(&mut self, origins: Vec<Origin>) {
         for (i, origin) in origins.into_iter().enumerate() {
             if i < self.layers.len() {
@@ -309,6 +318,13 @@ impl BackgroundLayer {
 
             if let Some(position) = PositionValue::from_cv(cvs) {
                 layer.position = position;
+
+                // <bg-position> [ / <bg-size> ]?
+                if let Some(ComponentValue::Token(CSSToken::Delim('/'))) = cvs.peek() {
+                    cvs.consume();
+                    layer.size = BackgroundSize::from_cv(cvs)?;
+                }
+
                 continue;
             }
 
@@ -353,6 +369,100 @@ pub enum LengthPercentage {
     Percentage(Percentage),
 }
 
+/// A length or percentage in a unit that layout can resolve. Absolute units are converted to
+/// pixels when parsed, while font-relative units and percentages wait for a `LengthContext`.
+///
+/// https://drafts.csswg.org/css-values/#lengths
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Px(f64),
+    Percent(f64),
+    Em(f64),
+    Rem(f64),
+}
+
+/// What relative lengths are resolved against.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LengthContext {
+    /// The width of the containing block, which percentages refer to
+    pub container_width: f64,
+
+    /// The element's own computed font size, for `em`
+    pub font_size: f64,
+
+    /// The root element's computed font size, for `rem`
+    pub root_font_size: f64,
+}
+
+impl Default for LengthContext {
+    fn default() -> Self {
+        Self {
+            container_width: 0.0,
+            font_size: 16.0,
+            root_font_size: 16.0,
+        }
+    }
+}
+
+impl Length {
+    /// The length `dimension` describes, or `None` if its unit isn't supported.
+    ///
+    /// https://drafts.csswg.org/css-values/#absolute-lengths
+    pub fn from_dimension(dimension: &Dimension) -> Option<Length> {
+        let value = dimension.value;
+
+        match dimension.unit.to_ascii_lowercase().as_str() {
+            "px" => Some(Length::Px(value)),
+            "em" => Some(Length::Em(value)),
+            "rem" => Some(Length::Rem(value)),
+            "in" => Some(Length::Px(value * 96.0)),
+            "cm" => Some(Length::Px(value * 96.0 / 2.54)),
+            "mm" => Some(Length::Px(value * 96.0 / 25.4)),
+            "q" => Some(Length::Px(value * 96.0 / 101.6)),
+            "pt" => Some(Length::Px(value * 96.0 / 72.0)),
+            "pc" => Some(Length::Px(value * 16.0)),
+            _ => None,
+        }
+    }
+
+    pub fn from_length_percentage(length_percentage: &LengthPercentage) -> Option<Length> {
+        match length_percentage {
+            LengthPercentage::Length(dimension) => Length::from_dimension(dimension),
+            LengthPercentage::Percentage(percentage) => Some(Length::Percent(*percentage)),
+        }
+    }
+
+    pub fn resolve(&self, context: &LengthContext) -> f64 {
+        match self {
+            Length::Px(px) => *px,
+            Length::Percent(percent) => percent / 100.0 * context.container_width,
+            Length::Em(em) => em * context.font_size,
+            Length::Rem(rem) => rem * context.root_font_size,
+        }
+    }
+}
+
+impl CSSParseable for Length {
+    fn from_cv(cvs: &mut InputStream<ComponentValue>) -> Option<Self> {
+        let length = match cvs.consume()? {
+            ComponentValue::Token(CSSToken::Dimension(dimension)) => {
+                Length::from_dimension(&dimension)
+            }
+            ComponentValue::Token(CSSToken::Percentage(percentage)) => {
+                Some(Length::Percent(percentage))
+            }
+            ComponentValue::Token(CSSToken::Number { value: 0.0, .. }) => Some(Length::Px(0.0)),
+            _ => None,
+        };
+
+        if length.is_none() {
+            cvs.reconsume();
+        }
+
+        length
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum PositionDirection {
     Left,
@@ -647,6 +757,151 @@ impl Origin {
     }
 }
 
+/// One side of an explicit `background-size`.
+#[derive(Debug, Clone)]
+pub enum BackgroundSizeValue {
+    LengthPercentage(LengthPercentage),
+    Auto,
+}
+
+impl CSSParseable for BackgroundSizeValue {
+    fn from_cv(cvs: &mut InputStream<ComponentValue>) -> Option<Self> {
+        match cvs.consume() {
+            Some(ComponentValue::Token(CSSToken::Ident(ident))) if ident == "auto" => {
+                Some(BackgroundSizeValue::Auto)
+            }
+            Some(ComponentValue::Token(CSSToken::Dimension(dim))) if dim.value >= 0.0 => Some(
+                BackgroundSizeValue::LengthPercentage(LengthPercentage::Length(dim)),
+            ),
+            Some(ComponentValue::Token(CSSToken::Percentage(perc))) if perc >= 0.0 => Some(
+                BackgroundSizeValue::LengthPercentage(LengthPercentage::Percentage(perc)),
+            ),
+            Some(ComponentValue::Token(CSSToken::Number { value: 0.0, .. })) => Some(
+                BackgroundSizeValue::LengthPercentage(LengthPercentage::Length(Dimension {
+                    value: 0.0,
+                    number_type: NumberType::Integer,
+                    unit: "px".to_string(),
+                })),
+            ),
+            Some(_) => {
+                cvs.reconsume();
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+impl BackgroundSizeValue {
+    fn resolve(&self, area_length: f64) -> Option<f64> {
+        match self {
+            BackgroundSizeValue::LengthPercentage(LengthPercentage::Length(dim)) => {
+                Some(dim.resolve(&Vec::new()))
+            }
+            BackgroundSizeValue::LengthPercentage(LengthPercentage::Percentage(perc)) => {
+                Some(perc / 100.0 * area_length)
+            }
+            BackgroundSizeValue::Auto => None,
+        }
+    }
+}
+
+/// https://drafts.csswg.org/css-backgrounds/#background-size
+#[derive(Debug, Clone)]
+pub enum BackgroundSize {
+    /// Scale the image to the smallest size that covers the whole area
+    Cover,
+    /// Scale the image to the largest size that fits inside the area
+    Contain,
+    /// A width and a height, where `auto` keeps the image's aspect ratio
+    Explicit(BackgroundSizeValue, BackgroundSizeValue),
+}
+
+impl Default for BackgroundSize {
+    fn default() -> Self {
+        BackgroundSize::Explicit(BackgroundSizeValue::Auto, BackgroundSizeValue::Auto)
+    }
+}
+
+impl CSSParseable for BackgroundSize {
+    fn from_cv(cvs: &mut InputStream<ComponentValue>) -> Option<Self> {
+        match cvs.peek() {
+            Some(ComponentValue::Token(CSSToken::Ident(ident))) if ident == "cover" => {
+                cvs.consume();
+                Some(BackgroundSize::Cover)
+            }
+            Some(ComponentValue::Token(CSSToken::Ident(ident))) if ident == "contain" => {
+                cvs.consume();
+                Some(BackgroundSize::Contain)
+            }
+            _ => {
+                let width = BackgroundSizeValue::from_cv(cvs)?;
+                let height = BackgroundSizeValue::from_cv(cvs).unwrap_or(BackgroundSizeValue::Auto);
+
+                Some(BackgroundSize::Explicit(width, height))
+            }
+        }
+    }
+}
+
+impl BackgroundSize {
+    /// Parses a comma separated list of sizes, one per background layer.
+    pub fn parse_multiple_sizes(cvs: &mut InputStream<ComponentValue>) -> Vec<BackgroundSize> {
+        cvs.finish()
+            .split(|cv| matches!(cv, ComponentValue::Token(CSSToken::Comma)))
+            .map_while(|layer| {
+                let layer: Vec<ComponentValue> = layer
+                    .iter()
+                    .filter(|cv| !matches!(cv, ComponentValue::Token(CSSToken::Whitespace)))
+                    .cloned()
+                    .collect();
+
+                if layer.is_empty() {
+                    return None;
+                }
+
+                let mut stream = InputStream::new(&layer);
+                let size = BackgroundSize::from_cv(&mut stream)?;
+
+                stream.peek().is_none().then_some(size)
+            })
+            .collect()
+    }
+
+    /// The size to draw an image of `image_size` at in a background positioning area of
+    /// `area_size`.
+    ///
+    /// https://drafts.csswg.org/css-backgrounds/#background-size
+    pub fn draw_size(&self, image_size: (f64, f64), area_size: (f64, f64)) -> (f64, f64) {
+        let (image_width, image_height) = image_size;
+        if image_width <= 0.0 || image_height <= 0.0 {
+            return area_size;
+        }
+
+        let scale_x = area_size.0 / image_width;
+        let scale_y = area_size.1 / image_height;
+
+        match self {
+            BackgroundSize::Cover => {
+                let scale = scale_x.max(scale_y);
+                (image_width * scale, image_height * scale)
+            }
+            BackgroundSize::Contain => {
+                let scale = scale_x.min(scale_y);
+                (image_width * scale, image_height * scale)
+            }
+            BackgroundSize::Explicit(width, height) => {
+                match (width.resolve(area_size.0), height.resolve(area_size.1)) {
+                    (Some(width), Some(height)) => (width, height),
+                    (Some(width), None) => (width, width * image_height / image_width),
+                    (None, Some(height)) => (height * image_width / image_height, height),
+                    (None, None) => image_size,
+                }
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Font {
     Constructed(ConstructedFont),
@@ -684,6 +939,13 @@ impl Font {
         }
     }
 
+    pub fn line_height(&self) -> LineHeight {
+        match self {
+            Font::Constructed(cf) => cf.line_height.clone(),
+            Font::SystemFont(_) => LineHeight::default(),
+        }
+    }
+
     pub fn set_size(&mut self, size: FontSize) {
         match self {
             Font::Constructed(cf) => cf.size = size,
@@ -1149,33 +1411,26 @@ pub enum FontSize {
 impl FontSize {
     pub fn resolve(&self, parents: &Vec<Rc<RefCell<Element>>>) -> f64 {
         match self {
-            FontSize::LengthPercentage(lp) => match lp {
-                LengthPercentage::Length(dim) => match dim.unit.as_str() {
-                    "px" => dim.value as f64,
-                    "em" => {
-                        let parent_font_size = parents
-                            .last()
-                            .and_then(|parent| parent.borrow().style().font.resolved_font_size())
-                            .unwrap_or(16.0);
-
-                        dim.value as f64 * parent_font_size
-                    }
-                    "rem" => {
-                        let root_font_size = parents
-                            .first()
-                            .and_then(|root| root.borrow().style().font.resolved_font_size())
-                            .unwrap_or(16.0);
+            FontSize::LengthPercentage(lp) => {
+                let parent_font_size = parents
+                    .last()
+                    .and_then(|parent| parent.borrow().style().font.resolved_font_size())
+                    .unwrap_or(16.0);
+                let root_font_size = parents
+                    .first()
+                    .and_then(|root| root.borrow().style().font.resolved_font_size())
+                    .unwrap_or(16.0);
 
-                        dim.value as f64 * root_font_size
-                    }
-                    _ => todo!("Handle other length units"),
-                },
-                LengthPercentage::Percentage(perc) => {
-                    // For now, assume parent font size is 16px
-                    let parent_font_size = 16.0;
-                    (*perc as f64 / 100.0) * parent_font_size
-                }
-            },
+                // Percentages and ems both refer to the parent's font size here
+                let context = LengthContext {
+                    container_width: parent_font_size,
+                    font_size: parent_font_size,
+                    root_font_size,
+                };
+
+                Length::from_length_percentage(lp)
+                    .map_or(parent_font_size, |length| length.resolve(&context))
+            }
             FontSize::RelativeSize(RelativeSize::Larger) => {
                 let parent_font_size = parents
                     .last()
@@ -1192,7 +1447,7 @@ impl FontSize {
 
                 parent_font_size * 0.833
             }
-            _ => todo!("Handle other FontSize variants"),
+            FontSize::AbsoluteSize(size) => size.resolve(),
         }
 
         // match self {
@@ -1279,6 +1534,25 @@ pub enum AbsoluteSize {
     XXLarge,
 }
 
+impl AbsoluteSize {
+    /// The size in pixels, scaled from a `medium` of 16px.
+    ///
+    /// https://drafts.csswg.org/css-fonts/#absolute-size-mapping
+    pub fn resolve(&self) -> f64 {
+        let scale = match self {
+            AbsoluteSize::XXSmall => 3.0 / 5.0,
+            AbsoluteSize::XSmall => 3.0 / 4.0,
+            AbsoluteSize::Small => 8.0 / 9.0,
+            AbsoluteSize::Medium => 1.0,
+            AbsoluteSize::Large => 6.0 / 5.0,
+            AbsoluteSize::XLarge => 3.0 / 2.0,
+            AbsoluteSize::XXLarge => 2.0,
+        };
+
+        16.0 * scale
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum RelativeSize {
     Larger,
@@ -1429,6 +1703,10 @@ pub enum Display {
     #[default]
     Inline,
     Block,
+    /// A block box that establishes a new block formatting context for its contents.
+    ///
+    /// https://drafts.csswg.org/css-display/#valdef-display-flow-root
+    FlowRoot,
     ListItem,
     None,
 }
@@ -1443,6 +1721,7 @@ impl CSSParseable for Display {
                 ComponentValue::Token(CSSToken::Ident(ident)) => match ident.as_str() {
                     "inline" => return Some(Display::Inline),
                     "block" => return Some(Display::Block),
+                    "flow-root" => return Some(Display::FlowRoot),
                     "list-item" => return Some(Display::ListItem),
                     "none" => return Some(Display::None),
                     _ => {
@@ -1462,7 +1741,7 @@ impl Display {
     pub fn to_box_type(&self) -> BoxType {
         match self {
             Display::Inline => BoxType::Inline,
-            Display::Block => BoxType::Block,
+            Display::Block | Display::FlowRoot => BoxType::Block,
             Display::ListItem => BoxType::ListItem,
             Display::None => BoxType::None,
         }
@@ -1471,37 +1750,25 @@ impl Display {
 
 #[derive(Debug, Clone)]
 pub enum MarginValue {
-    LengthPercentage(LengthPercentage),
+    Length(Length),
     Auto,
 }
 
 impl CSSParseable for MarginValue {
     fn from_cv(stream: &mut InputStream<ComponentValue>) -> Option<Self> {
-        if let Some(next) = stream.consume() {
-            match next {
-                ComponentValue::Token(CSSToken::Number { value: 0.0, .. }) => Some(
-                    MarginValue::LengthPercentage(LengthPercentage::Length(Dimension {
-                        value: 0.0,
-                        number_type: NumberType::Integer,
-                        unit: "px".to_string(),
-                    })),
-                ),
-                ComponentValue::Token(CSSToken::Dimension(dim)) => {
-                    Some(MarginValue::LengthPercentage(LengthPercentage::Length(dim)))
-                }
-                ComponentValue::Token(CSSToken::Percentage(perc)) => Some(
-                    MarginValue::LengthPercentage(LengthPercentage::Percentage(perc)),
-                ),
-                ComponentValue::Token(CSSToken::Ident(ident)) if ident == "auto" => {
-                    Some(MarginValue::Auto)
-                }
-                _ => {
-                    stream.reconsume();
-                    None
-                }
+        if let Some(length) = Length::from_cv(stream) {
+            return Some(MarginValue::Length(length));
+        }
+
+        match stream.consume() {
+            Some(ComponentValue::Token(CSSToken::Ident(ident))) if ident == "auto" => {
+                Some(MarginValue::Auto)
             }
-        } else {
-            None
+            Some(_) => {
+                stream.reconsume();
+                None
+            }
+            None => None,
         }
     }
 }
@@ -1517,69 +1784,52 @@ pub struct Margin {
 impl Default for Margin {
     fn default() -> Self {
         Margin {
-            top: MarginValue::LengthPercentage(LengthPercentage::Length(Dimension {
-                value: 0.0,
-                number_type: NumberType::Integer,
-                unit: "px".to_string(),
-            })),
-            right: MarginValue::LengthPercentage(LengthPercentage::Length(Dimension {
-                value: 0.0,
-                number_type: NumberType::Integer,
-                unit: "px".to_string(),
-            })),
-            bottom: MarginValue::LengthPercentage(LengthPercentage::Length(Dimension {
-                value: 0.0,
-                number_type: NumberType::Integer,
-                unit: "px".to_string(),
-            })),
-            left: MarginValue::LengthPercentage(LengthPercentage::Length(Dimension {
-                value: 0.0,
-                number_type: NumberType::Integer,
-                unit: "px".to_string(),
-            })),
+            top: MarginValue::Length(Length::Px(0.0)),
+            right: MarginValue::Length(Length::Px(0.0)),
+            bottom: MarginValue::Length(Length::Px(0.0)),
+            left: MarginValue::Length(Length::Px(0.0)),
         }
     }
 }
 
-impl Margin {
-    pub fn resolved_top(&self, parents: &Vec<Weak<RefCell<Box>>>) -> f64 {
-        match &self.top {
-            MarginValue::LengthPercentage(LengthPercentage::Length(dim)) => dim.resolve(parents),
-            MarginValue::LengthPercentage(LengthPercentage::Percentage(_)) => 0.0,
+impl MarginValue {
+    /// Resolves to pixels, with percentages relative to the width of the containing block.
+    /// `auto` resolves to 0.
+    ///
+    /// https://www.w3.org/TR/CSS2/box.html#margin-properties
+    pub fn resolve(&self, context: &LengthContext) -> f64 {
+        match self {
+            MarginValue::Length(length) => length.resolve(context),
             MarginValue::Auto => 0.0,
         }
     }
+}
 
-    pub fn resolved_right(&self, parents: &Vec<Weak<RefCell<Box>>>) -> f64 {
-        match &self.right {
-            MarginValue::LengthPercentage(LengthPercentage::Length(dim)) => dim.resolve(parents),
-            MarginValue::LengthPercentage(LengthPercentage::Percentage(_)) => 0.0,
-            MarginValue::Auto => 0.0,
-        }
+impl Margin {
+    pub fn resolved_top(&self, context: &LengthContext) -> f64 {
+        self.top.resolve(context)
     }
 
-    pub fn resolved_bottom(&self, parents: &Vec<Weak<RefCell<Box>>>) -> f64 {
-        match &self.bottom {
-            MarginValue::LengthPercentage(LengthPercentage::Length(dim)) => dim.resolve(parents),
-            MarginValue::LengthPercentage(LengthPercentage::Percentage(_)) => 0.0,
-            MarginValue::Auto => 0.0,
-        }
+    pub fn resolved_right(&self, context: &LengthContext) -> f64 {
+        self.right.resolve(context)
     }
 
-    pub fn resolved_left(&self, parents: &Vec<Weak<RefCell<Box>>>) -> f64 {
-        match &self.left {
-            MarginValue::LengthPercentage(LengthPercentage::Length(dim)) => dim.resolve(parents),
-            MarginValue::LengthPercentage(LengthPercentage::Percentage(_)) => 0.0,
-            MarginValue::Auto => 0.0,
-        }
+    pub fn resolved_bottom(&self, context: &LengthContext) -> f64 {
+        self.bottom.resolve(context)
     }
 
-    pub fn to_edges(&self, parents: &Vec<Weak<RefCell<Box>>>) -> Edges {
+    pub fn resolved_left(&self, context: &LengthContext) -> f64 {
+        self.left.resolve(context)
+    }
+
+    /// Resolves every side to pixels. Percentages refer to the width of the containing
+    /// block, even for the top and bottom margins.
+    pub fn to_edges(&self, context: &LengthContext) -> Edges {
         Edges(
-            self.resolved_top(parents),
-            self.resolved_right(parents),
-            self.resolved_bottom(parents),
-            self.resolved_left(parents),
+            self.resolved_top(context),
+            self.resolved_right(context),
+            self.resolved_bottom(context),
+            self.resolved_left(context),
         )
     }
 }
@@ -1603,33 +1853,92 @@ impl CSSParseable for Margin {
             }
         }
 
-        match values.len() {
-            1 => Some(Margin {
-                top: values[0].clone(),
-                right: values[0].clone(),
-                bottom: values[0].clone(),
-                left: values[0].clone(),
-            }),
-            2 => Some(Margin {
-                top: values[0].clone(),
-                right: values[1].clone(),
-                bottom: values[0].clone(),
-                left: values[1].clone(),
-            }),
-            3 => Some(Margin {
-                top: values[0].clone(),
-                right: values[1].clone(),
-                bottom: values[2].clone(),
-                left: values[1].clone(),
-            }),
-            4 => Some(Margin {
-                top: values[0].clone(),
-                right: values[1].clone(),
-                bottom: values[2].clone(),
-                left: values[3].clone(),
-            }),
-            _ => None,
+        let (top, right, bottom, left) = expand_sides(&values)?;
+        Some(Margin {
+            top,
+            right,
+            bottom,
+            left,
+        })
+    }
+}
+
+/// https://drafts.csswg.org/css-box/#padding-physical
+#[derive(Debug, Clone)]
+pub struct Padding {
+    pub top: Length,
+    pub right: Length,
+    pub bottom: Length,
+    pub left: Length,
+}
+
+impl Default for Padding {
+    fn default() -> Self {
+        Padding {
+            top: Length::Px(0.0),
+            right: Length::Px(0.0),
+            bottom: Length::Px(0.0),
+            left: Length::Px(0.0),
+        }
+    }
+}
+
+impl CSSParseable for Padding {
+    fn from_cv(stream: &mut InputStream<ComponentValue>) -> Option<Self> {
+        let mut values: Vec<Length> = vec![];
+
+        while !stream.lookahead(1).is_empty() {
+            if let [ComponentValue::Token(CSSToken::Whitespace)] = stream.lookahead(1) {
+                stream.consume();
+                continue;
+            }
+
+            values.push(Padding::_parse_length(stream)?);
         }
+
+        let (top, right, bottom, left) = expand_sides(&values)?;
+        Some(Padding {
+            top,
+            right,
+            bottom,
+            left,
+        })
+    }
+}
+
+impl Padding {
+    /// Parses the value of one side, such as `padding-top`. Unlike margins, padding can't be
+    /// negative or `auto`, and anything after the length makes the whole value invalid.
+    pub fn parse_side(stream: &mut InputStream<ComponentValue>) -> Option<Length> {
+        let length = Padding::_parse_length(stream)?;
+
+        while let [ComponentValue::Token(CSSToken::Whitespace)] = stream.lookahead(1) {
+            stream.consume();
+        }
+
+        stream.lookahead(1).is_empty().then_some(length)
+    }
+
+    fn _parse_length(stream: &mut InputStream<ComponentValue>) -> Option<Length> {
+        match Length::from_cv(stream)? {
+            Length::Px(value) | Length::Percent(value) | Length::Em(value) | Length::Rem(value)
+                if value < 0.0 =>
+            {
+                None
+            }
+            length => Some(length),
+        }
+    }
+
+    /// Resolves every side to pixels. Percentages refer to the width of the containing
+    /// block, even for the top and bottom padding.
+    pub fn to_edges(&self, context: &LengthContext) -> Edges {
+        Edges(
+            self.top.resolve(context),
+            self.right.resolve(context),
+            self.bottom.resolve(context),
+            self.left.resolve(context),
+        )
     }
 }
 
@@ -1667,3 +1976,722 @@ impl CSSParseable for Position {
         None
     }
 }
+
+/// https://drafts.csswg.org/css-inline/#propdef-vertical-align
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VerticalAlign {
+    #[default]
+    Baseline,
+    Top,
+    Middle,
+    Bottom,
+}
+
+impl CSSParseable for VerticalAlign {
+    fn from_cv(cvs: &mut InputStream<ComponentValue>) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        if let Some(ComponentValue::Token(CSSToken::Ident(ident))) = cvs.consume() {
+            match ident.as_str() {
+                "baseline" => return Some(VerticalAlign::Baseline),
+                "top" => return Some(VerticalAlign::Top),
+                "middle" => return Some(VerticalAlign::Middle),
+                "bottom" => return Some(VerticalAlign::Bottom),
+                _ => {}
+            }
+        }
+
+        cvs.reconsume();
+        None
+    }
+}
+
+/// https://www.w3.org/TR/CSS2/visuren.html#float-position
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Float {
+    #[default]
+    None,
+    Left,
+    Right,
+}
+
+impl CSSParseable for Float {
+    fn from_cv(cvs: &mut InputStream<ComponentValue>) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        if let Some(ComponentValue::Token(CSSToken::Ident(ident))) = cvs.consume() {
+            match ident.as_str() {
+                "none" => return Some(Float::None),
+                "left" => return Some(Float::Left),
+                "right" => return Some(Float::Right),
+                _ => {}
+            }
+        }
+
+        cvs.reconsume();
+        None
+    }
+}
+
+/// https://drafts.csswg.org/css-backgrounds/#typedef-line-width
+#[derive(Debug, Clone, Default)]
+pub enum LineWidth {
+    Thin,
+    #[default]
+    Medium,
+    Thick,
+    Length(Length),
+}
+
+impl CSSParseable for LineWidth {
+    fn from_cv(cvs: &mut InputStream<ComponentValue>) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        match parse_length_only(cvs) {
+            Some(Length::Px(value) | Length::Em(value) | Length::Rem(value)) if value < 0.0 => {
+                cvs.reconsume();
+                return None;
+            }
+            Some(length) => return Some(LineWidth::Length(length)),
+            None => {}
+        }
+
+        if let Some(ComponentValue::Token(CSSToken::Ident(ident))) = cvs.consume() {
+            match ident.as_str() {
+                "thin" => return Some(LineWidth::Thin),
+                "medium" => return Some(LineWidth::Medium),
+                "thick" => return Some(LineWidth::Thick),
+                _ => {}
+            }
+        }
+
+        cvs.reconsume();
+        None
+    }
+}
+
+impl LineWidth {
+    /// Resolves to pixels. The keywords are 1px, 3px and 5px, which is what browsers use.
+    pub fn resolve(&self, context: &LengthContext) -> f64 {
+        match self {
+            LineWidth::Thin => 1.0,
+            LineWidth::Medium => 3.0,
+            LineWidth::Thick => 5.0,
+            LineWidth::Length(length) => length.resolve(context),
+        }
+    }
+}
+
+/// https://drafts.csswg.org/css-ui/#outline-style
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutlineStyle {
+    #[default]
+    None,
+    Auto,
+    Dotted,
+    Dashed,
+    Solid,
+    Double,
+    Groove,
+    Ridge,
+    Inset,
+    Outset,
+}
+
+impl CSSParseable for OutlineStyle {
+    fn from_cv(cvs: &mut InputStream<ComponentValue>) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        if let Some(ComponentValue::Token(CSSToken::Ident(ident))) = cvs.consume() {
+            match ident.as_str() {
+                "none" => return Some(OutlineStyle::None),
+                "auto" => return Some(OutlineStyle::Auto),
+                "dotted" => return Some(OutlineStyle::Dotted),
+                "dashed" => return Some(OutlineStyle::Dashed),
+                "solid" => return Some(OutlineStyle::Solid),
+                "double" => return Some(OutlineStyle::Double),
+                "groove" => return Some(OutlineStyle::Groove),
+                "ridge" => return Some(OutlineStyle::Ridge),
+                "inset" => return Some(OutlineStyle::Inset),
+                "outset" => return Some(OutlineStyle::Outset),
+                _ => {}
+            }
+        }
+
+        cvs.reconsume();
+        None
+    }
+}
+
+/// The `outline` of a box, drawn around its border box without taking up any space.
+///
+/// https://drafts.csswg.org/css-ui/#outline-props
+#[derive(Debug, Clone)]
+pub struct Outline {
+    pub width: LineWidth,
+    pub style: OutlineStyle,
+
+    /// `None` for `currentColor`
+    pub color: Option<Color>,
+
+    /// Distance between the border box and the outline
+    pub offset: Length,
+}
+
+impl Default for Outline {
+    fn default() -> Self {
+        Outline {
+            width: LineWidth::Medium,
+            style: OutlineStyle::None,
+            color: None,
+            offset: Length::Px(0.0),
+        }
+    }
+}
+
+impl CSSParseable for Outline {
+    /// Parses the `outline` shorthand: a width, style and color in any order, each at most
+    /// once. Anything left out takes its initial value.
+    fn from_cv(cvs: &mut InputStream<ComponentValue>) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let mut width = None;
+        let mut style = None;
+        let mut color = None;
+
+        while !cvs.lookahead(1).is_empty() {
+            if let [ComponentValue::Token(CSSToken::Whitespace)] = cvs.lookahead(1) {
+                cvs.consume();
+                continue;
+            }
+
+            if width.is_none()
+                && let Some(parsed) = LineWidth::from_cv(cvs)
+            {
+                width = Some(parsed);
+                continue;
+            }
+
+            if style.is_none()
+                && let Some(parsed) = OutlineStyle::from_cv(cvs)
+            {
+                style = Some(parsed);
+                continue;
+            }
+
+            if color.is_none()
+                && let Some(parsed) = parse_color_or_current_color(cvs)
+            {
+                color = Some(parsed);
+                continue;
+            }
+
+            return None;
+        }
+
+        if width.is_none() && style.is_none() && color.is_none() {
+            return None;
+        }
+
+        Some(Outline {
+            width: width.unwrap_or_default(),
+            style: style.unwrap_or_default(),
+            color: color.unwrap_or_default(),
+            ..Default::default()
+        })
+    }
+}
+
+impl Outline {
+    /// Parses an `outline-offset`, which unlike a width may be negative.
+    pub fn parse_offset(cvs: &mut InputStream<ComponentValue>) -> Option<Length> {
+        parse_length_only(cvs)
+    }
+
+    /// The width of the outline in pixels, which is 0 when its style is `none`.
+    ///
+    /// https://drafts.csswg.org/css-ui/#outline-width
+    pub fn resolved_width(&self, context: &LengthContext) -> f64 {
+        match self.style {
+            OutlineStyle::None => 0.0,
+            _ => self.width.resolve(context),
+        }
+    }
+
+    pub fn resolved_offset(&self, context: &LengthContext) -> f64 {
+        self.offset.resolve(context)
+    }
+
+    /// The color to draw the outline with, given the element's `color`.
+    pub fn used_color(&self, current_color: &Color) -> Color {
+        self.color.clone().unwrap_or_else(|| current_color.clone())
+    }
+}
+
+/// https://drafts.csswg.org/css-backgrounds/#typedef-line-style
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BorderStyle {
+    #[default]
+    None,
+    Hidden,
+    Dotted,
+    Dashed,
+    Solid,
+    Double,
+    Groove,
+    Ridge,
+    Inset,
+    Outset,
+}
+
+impl CSSParseable for BorderStyle {
+    fn from_cv(cvs: &mut InputStream<ComponentValue>) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        if let Some(ComponentValue::Token(CSSToken::Ident(ident))) = cvs.consume() {
+            match ident.as_str() {
+                "none" => return Some(BorderStyle::None),
+                "hidden" => return Some(BorderStyle::Hidden),
+                "dotted" => return Some(BorderStyle::Dotted),
+                "dashed" => return Some(BorderStyle::Dashed),
+                "solid" => return Some(BorderStyle::Solid),
+                "double" => return Some(BorderStyle::Double),
+                "groove" => return Some(BorderStyle::Groove),
+                "ridge" => return Some(BorderStyle::Ridge),
+                "inset" => return Some(BorderStyle::Inset),
+                "outset" => return Some(BorderStyle::Outset),
+                _ => {}
+            }
+        }
+
+        cvs.reconsume();
+        None
+    }
+}
+
+/// One side of a border, as set by `border-top` and friends.
+///
+/// https://drafts.csswg.org/css-backgrounds/#border-shorthands
+#[derive(Debug, Clone, Default)]
+pub struct BorderSide {
+    pub width: LineWidth,
+    pub style: BorderStyle,
+
+    /// `None` for `currentColor`
+    pub color: Option<Color>,
+}
+
+impl CSSParseable for BorderSide {
+    /// Parses a width, style and color in any order, each at most once. Anything left out
+    /// takes its initial value.
+    fn from_cv(cvs: &mut InputStream<ComponentValue>) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let mut width = None;
+        let mut style = None;
+        let mut color = None;
+
+        while !cvs.lookahead(1).is_empty() {
+            if let [ComponentValue::Token(CSSToken::Whitespace)] = cvs.lookahead(1) {
+                cvs.consume();
+                continue;
+            }
+
+            if width.is_none()
+                && let Some(parsed) = LineWidth::from_cv(cvs)
+            {
+                width = Some(parsed);
+                continue;
+            }
+
+            if style.is_none()
+                && let Some(parsed) = BorderStyle::from_cv(cvs)
+            {
+                style = Some(parsed);
+                continue;
+            }
+
+            if color.is_none()
+                && let Some(parsed) = parse_color_or_current_color(cvs)
+            {
+                color = Some(parsed);
+                continue;
+            }
+
+            return None;
+        }
+
+        if width.is_none() && style.is_none() && color.is_none() {
+            return None;
+        }
+
+        Some(BorderSide {
+            width: width.unwrap_or_default(),
+            style: style.unwrap_or_default(),
+            color: color.unwrap_or_default(),
+        })
+    }
+}
+
+impl BorderSide {
+    /// The width of this side in pixels, which is 0 when its style is `none` or `hidden`.
+    ///
+    /// https://drafts.csswg.org/css-backgrounds/#border-width
+    pub fn resolved_width(&self, context: &LengthContext) -> f64 {
+        match self.style {
+            BorderStyle::None | BorderStyle::Hidden => 0.0,
+            _ => self.width.resolve(context),
+        }
+    }
+
+    /// The color to draw this side with, given the element's `color`.
+    pub fn used_color(&self, current_color: &Color) -> Color {
+        self.color.clone().unwrap_or_else(|| current_color.clone())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Border {
+    pub top: BorderSide,
+    pub right: BorderSide,
+    pub bottom: BorderSide,
+    pub left: BorderSide,
+}
+
+impl CSSParseable for Border {
+    /// Parses the `border` shorthand, which sets all four sides alike.
+    fn from_cv(cvs: &mut InputStream<ComponentValue>) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let side = BorderSide::from_cv(cvs)?;
+
+        Some(Border {
+            top: side.clone(),
+            right: side.clone(),
+            bottom: side.clone(),
+            left: side,
+        })
+    }
+}
+
+impl Border {
+    /// Parses one to four values of `T`, expanded to the four sides the way `margin` is.
+    pub fn parse_sides<T: CSSParseable + Clone>(
+        cvs: &mut InputStream<ComponentValue>,
+    ) -> Option<(T, T, T, T)> {
+        let mut values: Vec<T> = vec![];
+
+        while !cvs.lookahead(1).is_empty() {
+            if let [ComponentValue::Token(CSSToken::Whitespace)] = cvs.lookahead(1) {
+                cvs.consume();
+                continue;
+            }
+
+            values.push(T::from_cv(cvs)?);
+        }
+
+        expand_sides(&values)
+    }
+
+    pub fn set_widths(
+        &mut self,
+        (top, right, bottom, left): (LineWidth, LineWidth, LineWidth, LineWidth),
+    ) {
+        self.top.width = top;
+        self.right.width = right;
+        self.bottom.width = bottom;
+        self.left.width = left;
+    }
+
+    pub fn set_styles(
+        &mut self,
+        (top, right, bottom, left): (BorderStyle, BorderStyle, BorderStyle, BorderStyle),
+    ) {
+        self.top.style = top;
+        self.right.style = right;
+        self.bottom.style = bottom;
+        self.left.style = left;
+    }
+
+    /// The width of every side in pixels.
+    pub fn to_edges(&self, context: &LengthContext) -> Edges {
+        Edges(
+            self.top.resolved_width(context),
+            self.right.resolved_width(context),
+            self.bottom.resolved_width(context),
+            self.left.resolved_width(context),
+        )
+    }
+}
+
+/// The mouse cursor shown over an element. Custom `url()` cursors aren't supported, so only
+/// the keyword they fall back to is kept.
+///
+/// https://drafts.csswg.org/css-ui/#cursor
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Cursor {
+    #[default]
+    Auto,
+    None,
+    Default,
+    ContextMenu,
+    Help,
+    Pointer,
+    Progress,
+    Wait,
+    Cell,
+    Crosshair,
+    Text,
+    VerticalText,
+    Alias,
+    Copy,
+    Move,
+    NoDrop,
+    NotAllowed,
+    Grab,
+    Grabbing,
+    EResize,
+    NResize,
+    NeResize,
+    NwResize,
+    SResize,
+    SeResize,
+    SwResize,
+    WResize,
+    EwResize,
+    NsResize,
+    NeswResize,
+    NwseResize,
+    ColResize,
+    RowResize,
+    AllScroll,
+    ZoomIn,
+    ZoomOut,
+}
+
+impl CSSParseable for Cursor {
+    fn from_cv(cvs: &mut InputStream<ComponentValue>) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        while let Some(tok) = cvs.consume() {
+            let ComponentValue::Token(CSSToken::Ident(ident)) = tok else {
+                // Skip over the custom cursors and their hotspots
+                continue;
+            };
+
+            return Some(match ident.to_ascii_lowercase().as_str() {
+                "auto" => Cursor::Auto,
+                "none" => Cursor::None,
+                "default" => Cursor::Default,
+                "context-menu" => Cursor::ContextMenu,
+                "help" => Cursor::Help,
+                "pointer" => Cursor::Pointer,
+                "progress" => Cursor::Progress,
+                "wait" => Cursor::Wait,
+                "cell" => Cursor::Cell,
+                "crosshair" => Cursor::Crosshair,
+                "text" => Cursor::Text,
+                "vertical-text" => Cursor::VerticalText,
+                "alias" => Cursor::Alias,
+                "copy" => Cursor::Copy,
+                "move" => Cursor::Move,
+                "no-drop" => Cursor::NoDrop,
+                "not-allowed" => Cursor::NotAllowed,
+                "grab" => Cursor::Grab,
+                "grabbing" => Cursor::Grabbing,
+                "e-resize" => Cursor::EResize,
+                "n-resize" => Cursor::NResize,
+                "ne-resize" => Cursor::NeResize,
+                "nw-resize" => Cursor::NwResize,
+                "s-resize" => Cursor::SResize,
+                "se-resize" => Cursor::SeResize,
+                "sw-resize" => Cursor::SwResize,
+                "w-resize" => Cursor::WResize,
+                "ew-resize" => Cursor::EwResize,
+                "ns-resize" => Cursor::NsResize,
+                "nesw-resize" => Cursor::NeswResize,
+                "nwse-resize" => Cursor::NwseResize,
+                "col-resize" => Cursor::ColResize,
+                "row-resize" => Cursor::RowResize,
+                "all-scroll" => Cursor::AllScroll,
+                "zoom-in" => Cursor::ZoomIn,
+                "zoom-out" => Cursor::ZoomOut,
+                _ => return None,
+            });
+        }
+
+        None
+    }
+}
+
+/// https://drafts.csswg.org/css-text-decor/#text-decoration-line-property
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextDecorationLine {
+    pub underline: bool,
+    pub overline: bool,
+    pub line_through: bool,
+}
+
+impl CSSParseable for TextDecorationLine {
+    /// Parses `none`, or any of `underline`, `overline` and `line-through` in any order.
+    fn from_cv(cvs: &mut InputStream<ComponentValue>) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        if let [ComponentValue::Token(CSSToken::Ident(ident))] = cvs.lookahead(1)
+            && ident == "none"
+        {
+            cvs.consume();
+            return Some(TextDecorationLine::default());
+        }
+
+        let mut line = TextDecorationLine::default();
+
+        while let [ComponentValue::Token(token)] = cvs.lookahead(1) {
+            match token {
+                CSSToken::Whitespace => {}
+                CSSToken::Ident(ident) if ident == "underline" && !line.underline => {
+                    line.underline = true
+                }
+                CSSToken::Ident(ident) if ident == "overline" && !line.overline => {
+                    line.overline = true
+                }
+                CSSToken::Ident(ident) if ident == "line-through" && !line.line_through => {
+                    line.line_through = true
+                }
+                _ => break,
+            }
+
+            cvs.consume();
+        }
+
+        (line != TextDecorationLine::default()).then_some(line)
+    }
+}
+
+/// The lines drawn through an element's text, along with the text of its in-flow descendants.
+///
+/// https://drafts.csswg.org/css-text-decor/#text-decoration-property
+#[derive(Debug, Clone, Default)]
+pub struct TextDecoration {
+    pub line: TextDecorationLine,
+
+    /// `None` for `currentColor`
+    pub color: Option<Color>,
+}
+
+impl CSSParseable for TextDecoration {
+    /// Parses the `text-decoration` shorthand. Every line is drawn solid, so the style is
+    /// accepted but not kept.
+    fn from_cv(cvs: &mut InputStream<ComponentValue>) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let mut line = None;
+        let mut style = None;
+        let mut color = None;
+
+        while !cvs.lookahead(1).is_empty() {
+            if let [ComponentValue::Token(CSSToken::Whitespace)] = cvs.lookahead(1) {
+                cvs.consume();
+                continue;
+            }
+
+            if line.is_none()
+                && let Some(parsed) = TextDecorationLine::from_cv(cvs)
+            {
+                line = Some(parsed);
+                continue;
+            }
+
+            if style.is_none()
+                && let [ComponentValue::Token(CSSToken::Ident(ident))] = cvs.lookahead(1)
+                && matches!(
+                    ident.as_str(),
+                    "solid" | "double" | "dotted" | "dashed" | "wavy"
+                )
+            {
+                style = cvs.consume();
+                continue;
+            }
+
+            if color.is_none()
+                && let Some(parsed) = parse_color_or_current_color(cvs)
+            {
+                color = Some(parsed);
+                continue;
+            }
+
+            return None;
+        }
+
+        if line.is_none() && style.is_none() && color.is_none() {
+            return None;
+        }
+
+        Some(TextDecoration {
+            line: line.unwrap_or_default(),
+            color: color.unwrap_or_default(),
+        })
+    }
+}
+
+/// Parses a color for a property whose initial value is `currentColor`, which is `Some(None)`.
+pub fn parse_color_or_current_color(
+    cvs: &mut InputStream<ComponentValue>,
+) -> Option<Option<Color>> {
+    if let [ComponentValue::Token(CSSToken::Ident(ident))] = cvs.lookahead(1)
+        && ident.eq_ignore_ascii_case("currentcolor")
+    {
+        cvs.consume();
+        return Some(None);
+    }
+
+    Color::from_cv(cvs).map(Some)
+}
+
+/// Expands the one to four values of a box shorthand like `margin` to its top, right, bottom
+/// and left sides.
+///
+/// https://drafts.csswg.org/css-box/#margin-shorthand
+fn expand_sides<T: Clone>(values: &[T]) -> Option<(T, T, T, T)> {
+    match values {
+        [all] => Some((all.clone(), all.clone(), all.clone(), all.clone())),
+        [vertical, horizontal] => Some((
+            vertical.clone(),
+            horizontal.clone(),
+            vertical.clone(),
+            horizontal.clone(),
+        )),
+        [top, horizontal, bottom] => Some((
+            top.clone(),
+            horizontal.clone(),
+            bottom.clone(),
+            horizontal.clone(),
+        )),
+        [top, right, bottom, left] => {
+            Some((top.clone(), right.clone(), bottom.clone(), left.clone()))
+        }
+        _ => None,
+    }
+}
+
+/// Parses a `<length>`, for the properties that don't take percentages.
+fn parse_length_only(cvs: &mut InputStream<ComponentValue>) -> Option<Length> {
+    match Length::from_cv(cvs)? {
+        Length::Percent(_) => {
+            cvs.reconsume();
+            None
+        }
+        length => Some(length),
+    }
+}
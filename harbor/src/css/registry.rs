@@ -0,0 +1,641 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::LazyLock};
+
+use crate::{
+    css::tokenize::{Dimension, NumberType},
+    css::{
+        colors::Color,
+        cssom::{CSSDeclaration, ComputedStyle},
+        properties::{
+            Background, BackgroundLayer, BackgroundSize, Border, BorderSide, BorderStyle,
+            CSSParseable, Cursor, Display, Float, Font, FontFamily, FontSize, FontStyle,
+            FontWeight, Image, LengthPercentage, LineHeight, LineWidth, Margin, MarginValue,
+            Origin, Outline, OutlineStyle, Padding, Position, PositionValue, RepeatStyle,
+            TextDecoration, TextDecorationLine, VerticalAlign, WidthValue,
+            parse_color_or_current_color,
+        },
+    },
+    html5::dom::Element,
+    infra::InputStream,
+};
+
+type Applier = fn(&CSSDeclaration, &mut ComputedStyle, Option<&Vec<Rc<RefCell<Element>>>>);
+type Copier = fn(&ComputedStyle, &mut ComputedStyle, Option<&Vec<Rc<RefCell<Element>>>>);
+
+/// A property the cascade knows how to apply to a computed style.
+pub struct PropertyDefinition {
+    pub name: &'static str,
+
+    /// Whether the property takes its parent's value when it isn't set
+    ///
+    /// https://drafts.csswg.org/css-cascade/#inherited-property
+    pub inherited: bool,
+
+    apply: Applier,
+    copy: Copier,
+}
+
+impl PropertyDefinition {
+    /// Parses the declaration's value and sets it on `style`. Values that fail to parse leave
+    /// `style` as it was, except where a property falls back to its initial value.
+    pub fn apply(
+        &self,
+        declaration: &CSSDeclaration,
+        style: &mut ComputedStyle,
+        parents: Option<&Vec<Rc<RefCell<Element>>>>,
+    ) {
+        (self.apply)(declaration, style, parents)
+    }
+
+    /// Sets the property on `style` to its value in `source`, which is how the CSS-wide
+    /// keywords give it the parent's value or its initial value.
+    ///
+    /// https://drafts.csswg.org/css-cascade/#defaulting-keywords
+    pub fn copy_from(
+        &self,
+        source: &ComputedStyle,
+        style: &mut ComputedStyle,
+        parents: Option<&Vec<Rc<RefCell<Element>>>>,
+    ) {
+        (self.copy)(source, style, parents)
+    }
+}
+
+static PROPERTIES: LazyLock<HashMap<&'static str, PropertyDefinition>> = LazyLock::new(|| {
+    let mut properties = HashMap::new();
+    let mut register = |name: &'static str, inherited: bool, apply: Applier, copy: Copier| {
+        properties.insert(
+            name,
+            PropertyDefinition {
+                name,
+                inherited,
+                apply,
+                copy,
+            },
+        );
+    };
+
+    register(
+        "color",
+        true,
+        |declaration, style, _| {
+            style.color = parse::<Color>(declaration).unwrap_or_default();
+        },
+        |source, style, _| style.color = source.color.clone(),
+    );
+
+    register(
+        "background",
+        false,
+        |declaration, style, _| {
+            if let Some(background) = parse::<Background>(declaration) {
+                style.background = background;
+            }
+        },
+        |source, style, _| style.background = source.background.clone(),
+    );
+    register(
+        "background-color",
+        false,
+        |declaration, style, _| {
+            if let Some(color) = parse::<Color>(declaration) {
+                style.background.set_color(color);
+            }
+        },
+        |source, style, _| style.background.set_color(source.background.color()),
+    );
+    register(
+        "background-image",
+        false,
+        |declaration, style, _| {
+            let mut stream = InputStream::new(&declaration.value);
+            style
+                .background
+                .set_images(Image::parse_multiple_images(&mut stream));
+        },
+        |source, style, _| {
+            copy_background_layers(source, style, |from, to| to.image = from.image.clone())
+        },
+    );
+    register(
+        "background-repeat",
+        false,
+        |declaration, style, _| {
+            let mut stream = InputStream::new(&declaration.value);
+            style
+                .background
+                .set_repeat_styles(RepeatStyle::parse_multiple_repeat_styles(&mut stream));
+        },
+        |source, style, _| {
+            copy_background_layers(source, style, |from, to| {
+                to.repeat_style = from.repeat_style.clone()
+            })
+        },
+    );
+    register(
+        "background-position",
+        false,
+        |declaration, style, _| {
+            let mut stream = InputStream::new(&declaration.value);
+            style
+                .background
+                .set_positions(PositionValue::parse_multiple_positions(&mut stream));
+        },
+        |source, style, _| {
+            copy_background_layers(source, style, |from, to| {
+                to.position = from.position.clone()
+            })
+        },
+    );
+    register(
+        "background-size",
+        false,
+        |declaration, style, _| {
+            let mut stream = InputStream::new(&declaration.value);
+            style
+                .background
+                .set_sizes(BackgroundSize::parse_multiple_sizes(&mut stream));
+        },
+        |source, style, _| {
+            copy_background_layers(source, style, |from, to| to.size = from.size.clone())
+        },
+    );
+    register(
+        "background-origin",
+        false,
+        |declaration, style, _| {
+            let mut stream = InputStream::new(&declaration.value);
+            style
+                .background
+                .set_origins(Origin::parse_multiple_origins(&mut stream));
+        },
+        |source, style, _| {
+            copy_background_layers(source, style, |from, to| to.origin = from.origin.clone())
+        },
+    );
+
+    register(
+        "font",
+        true,
+        |declaration, style, parents| {
+            if let Some(font) = parse::<Font>(declaration) {
+                style.font = font;
+                style.font.resolve_font_size(parents.unwrap_or(&vec![]));
+                style.font.resolve_font_weight(parents.unwrap_or(&vec![]));
+            }
+        },
+        |source, style, parents| {
+            style.font = source.font.clone();
+            copy_font_size(source, style, parents);
+            style.font.resolve_font_weight(parents.unwrap_or(&vec![]));
+        },
+    );
+    register(
+        "font-family",
+        true,
+        |declaration, style, _| {
+            if let Some(family) = parse::<FontFamily>(declaration) {
+                style.font.set_family(family);
+            }
+        },
+        |source, style, _| style.font.set_family(source.font.family()),
+    );
+    register(
+        "font-size",
+        true,
+        |declaration, style, parents| {
+            if let Some(size) = parse::<FontSize>(declaration) {
+                style.font.set_size(size);
+                style.font.resolve_font_size(parents.unwrap_or(&vec![]));
+            }
+        },
+        |source, style, parents| {
+            style.font.set_size(source.font.size());
+            copy_font_size(source, style, parents);
+        },
+    );
+    register(
+        "font-weight",
+        true,
+        |declaration, style, parents| {
+            if let Some(weight) = parse::<FontWeight>(declaration) {
+                style.font.set_weight(weight);
+                style.font.resolve_font_weight(parents.unwrap_or(&vec![]));
+            }
+        },
+        |source, style, parents| {
+            style.font.set_weight(source.font.weight());
+            style.font.resolve_font_weight(parents.unwrap_or(&vec![]));
+        },
+    );
+    register(
+        "font-style",
+        true,
+        |declaration, style, _| {
+            if let Some(font_style) = parse::<FontStyle>(declaration) {
+                style.font.set_style(font_style);
+            }
+        },
+        |source, style, _| style.font.set_style(source.font.style()),
+    );
+    register(
+        "line-height",
+        true,
+        |declaration, style, _| {
+            if let Some(line_height) = parse::<LineHeight>(declaration) {
+                style.font.set_line_height(line_height);
+            }
+        },
+        |source, style, _| style.font.set_line_height(source.font.line_height()),
+    );
+
+    register(
+        "width",
+        false,
+        |declaration, style, _| {
+            style.width = parse::<WidthValue>(declaration).unwrap_or_default();
+        },
+        |source, style, _| style.width = source.width.clone(),
+    );
+    register(
+        "display",
+        false,
+        |declaration, style, _| {
+            style.display = parse::<Display>(declaration).unwrap_or_default();
+        },
+        |source, style, _| style.display = source.display.clone(),
+    );
+    register(
+        "position",
+        false,
+        |declaration, style, _| {
+            style.position = parse::<Position>(declaration).unwrap_or_default();
+        },
+        |source, style, _| style.position = source.position.clone(),
+    );
+    register(
+        "float",
+        false,
+        |declaration, style, _| {
+            style.float = parse::<Float>(declaration).unwrap_or_default();
+        },
+        |source, style, _| style.float = source.float,
+    );
+    register(
+        "vertical-align",
+        false,
+        |declaration, style, _| {
+            style.vertical_align = parse::<VerticalAlign>(declaration).unwrap_or_default();
+        },
+        |source, style, _| style.vertical_align = source.vertical_align,
+    );
+
+    register(
+        "margin",
+        false,
+        |declaration, style, _| {
+            if let Some(margin) = parse::<Margin>(declaration) {
+                style.margin = margin;
+            }
+        },
+        |source, style, _| style.margin = source.margin.clone(),
+    );
+    register(
+        "margin-top",
+        false,
+        |declaration, style, _| {
+            if let Some(top) = parse::<MarginValue>(declaration) {
+                style.margin.top = top;
+            }
+        },
+        |source, style, _| style.margin.top = source.margin.top.clone(),
+    );
+    register(
+        "margin-right",
+        false,
+        |declaration, style, _| {
+            if let Some(right) = parse::<MarginValue>(declaration) {
+                style.margin.right = right;
+            }
+        },
+        |source, style, _| style.margin.right = source.margin.right.clone(),
+    );
+    register(
+        "margin-bottom",
+        false,
+        |declaration, style, _| {
+            if let Some(bottom) = parse::<MarginValue>(declaration) {
+                style.margin.bottom = bottom;
+            }
+        },
+        |source, style, _| style.margin.bottom = source.margin.bottom.clone(),
+    );
+    register(
+        "margin-left",
+        false,
+        |declaration, style, _| {
+            if let Some(left) = parse::<MarginValue>(declaration) {
+                style.margin.left = left;
+            }
+        },
+        |source, style, _| style.margin.left = source.margin.left.clone(),
+    );
+
+    register(
+        "padding",
+        false,
+        |declaration, style, _| {
+            if let Some(padding) = parse::<Padding>(declaration) {
+                style.padding = padding;
+            }
+        },
+        |source, style, _| style.padding = source.padding.clone(),
+    );
+    register(
+        "padding-top",
+        false,
+        |declaration, style, _| {
+            let mut stream = InputStream::new(&declaration.value);
+            if let Some(top) = Padding::parse_side(&mut stream) {
+                style.padding.top = top;
+            }
+        },
+        |source, style, _| style.padding.top = source.padding.top,
+    );
+    register(
+        "padding-right",
+        false,
+        |declaration, style, _| {
+            let mut stream = InputStream::new(&declaration.value);
+            if let Some(right) = Padding::parse_side(&mut stream) {
+                style.padding.right = right;
+            }
+        },
+        |source, style, _| style.padding.right = source.padding.right,
+    );
+    register(
+        "padding-bottom",
+        false,
+        |declaration, style, _| {
+            let mut stream = InputStream::new(&declaration.value);
+            if let Some(bottom) = Padding::parse_side(&mut stream) {
+                style.padding.bottom = bottom;
+            }
+        },
+        |source, style, _| style.padding.bottom = source.padding.bottom,
+    );
+    register(
+        "padding-left",
+        false,
+        |declaration, style, _| {
+            let mut stream = InputStream::new(&declaration.value);
+            if let Some(left) = Padding::parse_side(&mut stream) {
+                style.padding.left = left;
+            }
+        },
+        |source, style, _| style.padding.left = source.padding.left,
+    );
+
+    register(
+        "border",
+        false,
+        |declaration, style, _| {
+            if let Some(border) = parse::<Border>(declaration) {
+                style.border = border;
+            }
+        },
+        |source, style, _| style.border = source.border.clone(),
+    );
+    register(
+        "border-top",
+        false,
+        |declaration, style, _| {
+            if let Some(top) = parse::<BorderSide>(declaration) {
+                style.border.top = top;
+            }
+        },
+        |source, style, _| style.border.top = source.border.top.clone(),
+    );
+    register(
+        "border-right",
+        false,
+        |declaration, style, _| {
+            if let Some(right) = parse::<BorderSide>(declaration) {
+                style.border.right = right;
+            }
+        },
+        |source, style, _| style.border.right = source.border.right.clone(),
+    );
+    register(
+        "border-bottom",
+        false,
+        |declaration, style, _| {
+            if let Some(bottom) = parse::<BorderSide>(declaration) {
+                style.border.bottom = bottom;
+            }
+        },
+        |source, style, _| style.border.bottom = source.border.bottom.clone(),
+    );
+    register(
+        "border-left",
+        false,
+        |declaration, style, _| {
+            if let Some(left) = parse::<BorderSide>(declaration) {
+                style.border.left = left;
+            }
+        },
+        |source, style, _| style.border.left = source.border.left.clone(),
+    );
+    register(
+        "border-width",
+        false,
+        |declaration, style, _| {
+            let mut stream = InputStream::new(&declaration.value);
+            if let Some(widths) = Border::parse_sides::<LineWidth>(&mut stream) {
+                style.border.set_widths(widths);
+            }
+        },
+        |source, style, _| {
+            let border = &source.border;
+            style.border.set_widths((
+                border.top.width.clone(),
+                border.right.width.clone(),
+                border.bottom.width.clone(),
+                border.left.width.clone(),
+            ));
+        },
+    );
+    register(
+        "border-style",
+        false,
+        |declaration, style, _| {
+            let mut stream = InputStream::new(&declaration.value);
+            if let Some(styles) = Border::parse_sides::<BorderStyle>(&mut stream) {
+                style.border.set_styles(styles);
+            }
+        },
+        |source, style, _| {
+            let border = &source.border;
+            style.border.set_styles((
+                border.top.style,
+                border.right.style,
+                border.bottom.style,
+                border.left.style,
+            ));
+        },
+    );
+
+    register(
+        "cursor",
+        true,
+        |declaration, style, _| {
+            if let Some(cursor) = parse::<Cursor>(declaration) {
+                style.cursor = cursor;
+            }
+        },
+        |source, style, _| style.cursor = source.cursor,
+    );
+
+    register(
+        "text-decoration",
+        false,
+        |declaration, style, _| {
+            if let Some(text_decoration) = parse::<TextDecoration>(declaration) {
+                style.text_decoration = text_decoration;
+            }
+        },
+        |source, style, _| style.text_decoration = source.text_decoration.clone(),
+    );
+    register(
+        "text-decoration-line",
+        false,
+        |declaration, style, _| {
+            if let Some(line) = parse::<TextDecorationLine>(declaration) {
+                style.text_decoration.line = line;
+            }
+        },
+        |source, style, _| style.text_decoration.line = source.text_decoration.line,
+    );
+    register(
+        "text-decoration-color",
+        false,
+        |declaration, style, _| {
+            let mut stream = InputStream::new(&declaration.value);
+            if let Some(color) = parse_color_or_current_color(&mut stream) {
+                style.text_decoration.color = color;
+            }
+        },
+        |source, style, _| style.text_decoration.color = source.text_decoration.color.clone(),
+    );
+
+    register(
+        "outline",
+        false,
+        |declaration, style, _| {
+            if let Some(outline) = parse::<Outline>(declaration) {
+                style.outline = outline;
+            }
+        },
+        |source, style, _| style.outline = source.outline.clone(),
+    );
+    register(
+        "outline-width",
+        false,
+        |declaration, style, _| {
+            if let Some(width) = parse::<LineWidth>(declaration) {
+                style.outline.width = width;
+            }
+        },
+        |source, style, _| style.outline.width = source.outline.width.clone(),
+    );
+    register(
+        "outline-style",
+        false,
+        |declaration, style, _| {
+            if let Some(outline_style) = parse::<OutlineStyle>(declaration) {
+                style.outline.style = outline_style;
+            }
+        },
+        |source, style, _| style.outline.style = source.outline.style,
+    );
+    register(
+        "outline-color",
+        false,
+        |declaration, style, _| {
+            let mut stream = InputStream::new(&declaration.value);
+            if let Some(color) = parse_color_or_current_color(&mut stream) {
+                style.outline.color = color;
+            }
+        },
+        |source, style, _| style.outline.color = source.outline.color.clone(),
+    );
+    register(
+        "outline-offset",
+        false,
+        |declaration, style, _| {
+            let mut stream = InputStream::new(&declaration.value);
+            if let Some(offset) = Outline::parse_offset(&mut stream) {
+                style.outline.offset = offset;
+            }
+        },
+        |source, style, _| style.outline.offset = source.outline.offset,
+    );
+
+    properties
+});
+
+fn parse<T: CSSParseable>(declaration: &CSSDeclaration) -> Option<T> {
+    T::from_cv(&mut InputStream::new(&declaration.value))
+}
+
+/// Copies one of the background properties that are set for each layer. Layers that `source`
+/// doesn't have take the property's initial value, and any it has beyond `style`'s are added.
+fn copy_background_layers(
+    source: &ComputedStyle,
+    style: &mut ComputedStyle,
+    copy: fn(&BackgroundLayer, &mut BackgroundLayer),
+) {
+    let color = style.background.color();
+    let layers = &mut style.background.layers;
+
+    if layers.len() < source.background.layers.len() {
+        layers.resize_with(source.background.layers.len(), BackgroundLayer::default);
+        style.background.set_color(color);
+    }
+
+    let initial = BackgroundLayer::default();
+    for (i, layer) in style.background.layers.iter_mut().enumerate() {
+        copy(source.background.layers.get(i).unwrap_or(&initial), layer);
+    }
+}
+
+/// Resolves a font size taken from `source`. An inherited size is the parent's computed size,
+/// not one relative to the parent again.
+fn copy_font_size(
+    source: &ComputedStyle,
+    style: &mut ComputedStyle,
+    parents: Option<&Vec<Rc<RefCell<Element>>>>,
+) {
+    if let Some(size) = source.font.resolved_font_size() {
+        style
+            .font
+            .set_size(FontSize::LengthPercentage(LengthPercentage::Length(
+                Dimension {
+                    value: size,
+                    number_type: NumberType::Number,
+                    unit: "px".to_string(),
+                },
+            )));
+    }
+
+    style.font.resolve_font_size(parents.unwrap_or(&vec![]));
+}
+
+/// The definition of the property called `name`, or `None` if it isn't supported.
+pub fn property(name: &str) -> Option<&'static PropertyDefinition> {
+    PROPERTIES.get(name)
+}
+
+/// The names of every supported property, in no particular order.
+pub fn property_names() -> impl Iterator<Item = &'static str> {
+    PROPERTIES.keys().copied()
+}
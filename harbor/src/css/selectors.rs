@@ -1,8 +1,8 @@
 use std::{cell::RefCell, fmt::Debug, ops::Deref, rc::Rc};
 
 use crate::{
-    css::tokenize::{CSSToken, HashToken},
-    html5::dom::Element,
+    css::tokenize::{CSSToken, HashToken, NumberType},
+    html5::dom::{Element, NodeKind},
     infra::InputStream,
 };
 
@@ -83,6 +83,10 @@ pub type ClassSelector = String;
 #[derive(Debug, Clone)]
 pub enum PseudoClassArgs {
     SelectorList(ComplexSelectorList),
+
+    /// https://drafts.csswg.org/css-syntax-3/#anb-microsyntax
+    /// .0 is A and .1 is B
+    AnPlusB(i64, i64),
     Raw(Vec<CSSToken>),
 }
 
@@ -148,7 +152,7 @@ pub trait MatchesElement {
 }
 
 impl MatchesElement for CompoundSelector {
-    fn matches(&self, element: &Element, _parents: Option<&Vec<Rc<RefCell<Element>>>>) -> bool {
+    fn matches(&self, element: &Element, parents: Option<&Vec<Rc<RefCell<Element>>>>) -> bool {
         if let Some(type_selector) = &self.type_selector {
             match type_selector {
                 TypeSelector::WQName(wq_name) => {
@@ -166,58 +170,286 @@ impl MatchesElement for CompoundSelector {
                         }
                     }
 
-                    let hover_modifier = if self.subclass_selectors.iter().any(|subclass| {
-                        matches!(
-                            subclass,
-                            SubclassSelector::PseudoClassSelector(PseudoClassSelector::Raw(
-                                name
-                            )) if name == "hover"
-                        )
-                    }) {
-                        element._element_state.is_hovered
-                    } else {
-                        true
-                    };
-
-                    return (wq_name.local_name == "*" || element.local_name == wq_name.local_name)
-                        && hover_modifier;
+                    if wq_name.local_name != "*" && element.local_name != wq_name.local_name {
+                        return false;
+                    }
                 }
                 TypeSelector::Prefixed(_ns_prefix) => {
                     // Match namespace if specified
                     todo!("Implement matching for Prefixed TypeSelector");
                 }
             }
-        } else {
-            for subclass in &self.subclass_selectors {
-                match subclass {
-                    SubclassSelector::IDSelector(id_selector) => {
-                        if element.get_attribute("id").as_deref() != Some(&id_selector.value) {
-                            return false;
-                        }
+        }
+
+        self.subclass_selectors
+            .iter()
+            .all(|subclass| subclass.matches(element, parents))
+    }
+}
+
+impl MatchesElement for SubclassSelector {
+    fn matches(&self, element: &Element, parents: Option<&Vec<Rc<RefCell<Element>>>>) -> bool {
+        match self {
+            SubclassSelector::IDSelector(id_selector) => {
+                element.get_attribute("id") == Some(id_selector.value.as_str())
+            }
+            SubclassSelector::ClassSelector(class_selector) => element
+                .get_attribute("class")
+                .is_some_and(|classes| classes.split_whitespace().any(|c| c == class_selector)),
+            SubclassSelector::PseudoClassSelector(pseudo_class) => {
+                pseudo_class.matches(element, parents)
+            }
+            SubclassSelector::AttributeSelector(attribute_selector) => {
+                attribute_selector.matches(element, parents)
+            }
+            SubclassSelector::PseudoElementSelector(_) => false,
+        }
+    }
+}
+
+impl MatchesElement for AttributeSelector {
+    /// https://drafts.csswg.org/selectors-4/#attribute-selectors
+    fn matches(&self, element: &Element, _parents: Option<&Vec<Rc<RefCell<Element>>>>) -> bool {
+        match self {
+            AttributeSelector::Exists(wq_name) => {
+                element.get_attribute(&wq_name.local_name).is_some()
+            }
+            AttributeSelector::WithMatcher(wq_name, matcher, expected, modifier) => {
+                let Some(value) = element.get_attribute(&wq_name.local_name) else {
+                    return false;
+                };
+
+                let (value, expected) = match modifier {
+                    Some(AttrModifier::CaseInsensitive) => {
+                        (value.to_ascii_lowercase(), expected.to_ascii_lowercase())
                     }
-                    SubclassSelector::ClassSelector(class_selector) => {
-                        let class_attr = element.get_attribute("class");
-                        if let Some(class_attr) = class_attr {
-                            let classes: Vec<&str> = class_attr.split_whitespace().collect();
-                            if !classes.contains(&class_selector.as_str()) {
-                                return false;
-                            }
-                        } else {
-                            return false;
-                        }
+                    _ => (value.to_string(), expected.clone()),
+                };
+
+                match matcher {
+                    AttrMatcher::Equal => value == expected,
+                    AttrMatcher::Includes => {
+                        !expected.is_empty()
+                            && value.split_ascii_whitespace().any(|word| word == expected)
                     }
-                    _ => {
-                        todo!(
-                            "Implement matching for other SubclassSelectors: {:?}",
-                            subclass
-                        );
+                    AttrMatcher::DashMatch => {
+                        value == expected || value.starts_with(&format!("{expected}-"))
+                    }
+                    AttrMatcher::PrefixMatch => {
+                        !expected.is_empty() && value.starts_with(&expected)
+                    }
+                    AttrMatcher::SuffixMatch => !expected.is_empty() && value.ends_with(&expected),
+                    AttrMatcher::SubstringMatch => {
+                        !expected.is_empty() && value.contains(&expected)
                     }
                 }
             }
+        }
+    }
+}
+
+impl MatchesElement for PseudoClassSelector {
+    /// Pseudo-classes that aren't supported yet never match.
+    fn matches(&self, element: &Element, parents: Option<&Vec<Rc<RefCell<Element>>>>) -> bool {
+        match self {
+            PseudoClassSelector::Raw(name) => match name.to_ascii_lowercase().as_str() {
+                "hover" => element._element_state.is_hovered,
+                "any-link" => element.is_link(),
+                "link" => element.is_link() && !element._element_state.is_visited,
+                "visited" => element._element_state.is_visited,
+                "checked" => is_checked(element),
+                "disabled" => is_disabled(element, parents),
+                "enabled" => {
+                    FORM_CONTROL_NAMES.contains(&element.local_name.as_str())
+                        && !is_disabled(element, parents)
+                }
+                "first-of-type" => position_of_type(element).is_some_and(|(index, _)| index == 1),
+                "last-of-type" => {
+                    position_of_type(element).is_some_and(|(index, count)| index == count)
+                }
+                "only-of-type" => position_of_type(element).is_some_and(|(_, count)| count == 1),
+                _ => false,
+            },
+            PseudoClassSelector::Function(name, PseudoClassArgs::AnPlusB(a, b)) => {
+                let Some((index, count)) = position_of_type(element) else {
+                    return false;
+                };
+
+                match name.to_ascii_lowercase().as_str() {
+                    "nth-of-type" => an_plus_b_matches(*a, *b, index),
+                    "nth-last-of-type" => an_plus_b_matches(*a, *b, count - index + 1),
+                    _ => false,
+                }
+            }
+            PseudoClassSelector::Function(name, PseudoClassArgs::SelectorList(selectors))
+                if name.eq_ignore_ascii_case("not") =>
+            {
+                !selectors
+                    .iter()
+                    .any(|selector| selector.matches(element, parents))
+            }
+            PseudoClassSelector::Function(..) => false,
+        }
+    }
+}
 
-            return true;
+/// The 1-based index of `element` among its siblings with the same local name, and how many of
+/// those siblings there are. `None` if it has no parent.
+///
+/// NOTE: `element` may be mutably borrowed while styles are computed, so it is found by address
+/// rather than by borrowing each sibling.
+fn position_of_type(element: &Element) -> Option<(usize, usize)> {
+    let parent = element._node.borrow().parent_node()?.upgrade()?;
+    let parent = parent.borrow();
+
+    let mut index = None;
+    let mut count = 0;
+
+    for child in parent.child_nodes().iter() {
+        let NodeKind::Element(sibling) = child.borrow().deref().clone() else {
+            continue;
+        };
+
+        if std::ptr::eq(sibling.as_ptr(), element) {
+            count += 1;
+            index = Some(count);
+        } else if sibling.borrow().local_name == element.local_name {
+            count += 1;
+        }
+    }
+
+    Some((index?, count))
+}
+
+/// Whether `index` is `a * n + b` for some `n >= 0`.
+///
+/// https://drafts.csswg.org/selectors-4/#the-nth-child-pseudo
+fn an_plus_b_matches(a: i64, b: i64, index: usize) -> bool {
+    let offset = index as i64 - b;
+
+    if a == 0 {
+        offset == 0
+    } else {
+        offset / a >= 0 && offset % a == 0
+    }
+}
+
+/// Parses the `An+B` notation, such as `odd`, `3`, `-n+2` or `2n - 1`. Whitespace tokens are
+/// expected to have been removed already.
+///
+/// https://drafts.csswg.org/css-syntax-3/#anb-microsyntax
+fn parse_an_plus_b(tokens: &[CSSToken]) -> Option<(i64, i64)> {
+    fn integer(value: f64, number_type: &NumberType) -> Option<i64> {
+        matches!(number_type, NumberType::Integer).then_some(value as i64)
+    }
+
+    /// Splits an ident starting with `n` into the rest after it.
+    fn after_n(ident: &str) -> Option<&str> {
+        ident
+            .get(..1)
+            .filter(|n| n.eq_ignore_ascii_case("n"))
+            .map(|_| &ident[1..])
+    }
+
+    let (a, rest, tokens) = match tokens {
+        [CSSToken::Ident(ident)] if ident.eq_ignore_ascii_case("odd") => return Some((2, 1)),
+        [CSSToken::Ident(ident)] if ident.eq_ignore_ascii_case("even") => return Some((2, 0)),
+        [CSSToken::Number { value, number_type }] => {
+            return Some((0, integer(*value, number_type)?));
+        }
+        [CSSToken::Dimension(dimension), tokens @ ..] => (
+            integer(dimension.value, &dimension.number_type)?,
+            after_n(&dimension.unit)?,
+            tokens,
+        ),
+        [CSSToken::Delim('+'), CSSToken::Ident(ident), tokens @ ..] => (1, after_n(ident)?, tokens),
+        [CSSToken::Ident(ident), tokens @ ..] => match ident.strip_prefix('-') {
+            Some(ident) => (-1, after_n(ident)?, tokens),
+            None => (1, after_n(ident)?, tokens),
+        },
+        _ => return None,
+    };
+
+    let b = match (rest, tokens) {
+        ("", []) => 0,
+        ("", [CSSToken::Number { value, number_type }]) => integer(*value, number_type)?,
+        (
+            "",
+            [
+                CSSToken::Delim(sign @ ('+' | '-')),
+                CSSToken::Number { value, number_type },
+            ],
+        ) if *value >= 0.0 => {
+            let b = integer(*value, number_type)?;
+            if *sign == '-' { -b } else { b }
+        }
+        ("-", [CSSToken::Number { value, number_type }]) if *value >= 0.0 => {
+            -integer(*value, number_type)?
+        }
+        (rest, []) => {
+            let digits = rest.strip_prefix('-')?;
+            if !digits.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+
+            -digits.parse::<i64>().ok()?
+        }
+        _ => return None,
+    };
+
+    Some((a, b))
+}
+
+/// Elements that can be disabled.
+///
+/// https://html.spec.whatwg.org/multipage/semantics-other.html#concept-element-disabled
+const FORM_CONTROL_NAMES: [&str; 7] = [
+    "button", "input", "select", "textarea", "optgroup", "option", "fieldset",
+];
+
+/// https://html.spec.whatwg.org/multipage/semantics-other.html#selector-checked
+///
+/// NOTE: Checkedness can't change after parsing yet, so it is read from the `checked` and
+/// `selected` attributes.
+fn is_checked(element: &Element) -> bool {
+    match element.local_name.as_str() {
+        "input" => {
+            let is_checkable = element.get_attribute("type").is_some_and(|input_type| {
+                input_type.eq_ignore_ascii_case("checkbox")
+                    || input_type.eq_ignore_ascii_case("radio")
+            });
+
+            is_checkable && element.get_attribute("checked").is_some()
         }
+        "option" => element.get_attribute("selected").is_some(),
+        _ => false,
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/semantics-other.html#concept-element-disabled
+///
+/// NOTE: Controls inside the first `legend` of a disabled `fieldset` are treated as disabled too.
+fn is_disabled(element: &Element, parents: Option<&Vec<Rc<RefCell<Element>>>>) -> bool {
+    if !FORM_CONTROL_NAMES.contains(&element.local_name.as_str()) {
+        return false;
+    }
+
+    if element.get_attribute("disabled").is_some() {
+        return true;
     }
+
+    parents.is_some_and(|parents| {
+        parents.iter().any(|parent| {
+            let parent = parent.borrow();
+            let disables_descendants = match parent.local_name.as_str() {
+                "fieldset" => element.local_name != "option" && element.local_name != "optgroup",
+                "optgroup" => element.local_name == "option",
+                _ => false,
+            };
+
+            disables_descendants && parent.get_attribute("disabled").is_some()
+        })
+    })
 }
 
 impl MatchesElement for ComplexSelector {
@@ -393,7 +625,10 @@ fn parse_ns_prefix(tokens: &mut InputStream<CSSToken>) -> Option<NSPrefix> {
         let prefix = ident.clone();
         tokens.consume();
 
-        if let Some(CSSToken::Delim('|')) = tokens.peek() {
+        // NOTE: `|=` is the dash-match attribute matcher, not a namespace separator
+        if let Some(CSSToken::Delim('|')) = tokens.peek()
+            && !matches!(tokens.peek_nth(1), Some(CSSToken::Delim('=')))
+        {
             return matched(tokens, prefix);
         } else {
             *tokens = curr_tokens;
@@ -582,14 +817,22 @@ fn parse_pseudo_class_selector(tokens: &mut InputStream<CSSToken>) -> Option<Pse
                         PseudoClassArgs::SelectorList(parsed_args.unwrap_or_default()),
                     ));
                 }
+                "nth-of-type" | "nth-last-of-type" => {
+                    let (a, b) = parse_an_plus_b(&args)?;
+
+                    return Some(PseudoClassSelector::Function(
+                        name,
+                        PseudoClassArgs::AnPlusB(a, b),
+                    ));
+                }
                 "has" | "defined" | "dir" | "lang" | "any-link" | "link" | "visited"
                 | "local-link" | "target" | "target-within" | "scope" | "hover" | "active"
                 | "focus" | "focus-within" | "focus-visible" | "current" | "past" | "future"
                 | "playing" | "paused" | "empty" | "blank" | "nth-child" | "nth-last-child"
-                | "nth-of-type" | "nth-last-of-type" | "first-child" | "last-child"
-                | "first-of-type" | "last-of-type" | "only-child" | "only-of-type" | "root"
-                | "checked" | "indeterminate" | "default" | "valid" | "invalid" | "in-range"
-                | "out-of-range" | "required" | "optional" | "read-only" | "read-write" => {
+                | "first-child" | "last-child" | "first-of-type" | "last-of-type"
+                | "only-child" | "only-of-type" | "root" | "checked" | "indeterminate"
+                | "default" | "valid" | "invalid" | "in-range" | "out-of-range" | "required"
+                | "optional" | "read-only" | "read-write" => {
                     todo!(
                         "Parsing for pseudo-class function '{}' is not yet implemented",
                         name
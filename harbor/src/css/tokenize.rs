@@ -324,10 +324,7 @@ fn consume_number(stream: &mut InputStream<char>) -> (f64, NumberType) {
 fn consume_numeric(stream: &mut InputStream<char>) -> CSSToken {
     let (number_value, number_type) = consume_number(stream);
 
-    if stream
-        .peek_range(1, 3)
-        .is_some_and(|s| would_start_ident(s))
-    {
+    if would_start_ident(stream.lookahead(3)) {
         let mut token = CSSToken::Dimension(Dimension {
             value: number_value,
             number_type,
@@ -470,10 +467,7 @@ fn consume(stream: &mut InputStream<char>) -> CSSToken {
                             hash_type: HashType::Unrestricted,
                         };
 
-                        if stream
-                            .peek_range(1, 3)
-                            .is_some_and(|s| would_start_ident(s))
-                        {
+                        if would_start_ident(stream.lookahead(3)) {
                             hash.hash_type = HashType::ID;
                         }
 
@@ -502,10 +496,7 @@ fn consume(stream: &mut InputStream<char>) -> CSSToken {
                     if stream.peek().is_some_and(|ch| ch.is_ascii_digit()) {
                         stream.reconsume();
                         return consume_numeric(stream);
-                    } else if stream
-                        .peek_range(1, 2)
-                        .is_some_and(|s| s == &['\u{002D}', '\u{003E}'])
-                    {
+                    } else if stream.lookahead(2) == ['\u{002D}', '\u{003E}'] {
                         stream.consume();
                         stream.consume();
 
@@ -531,10 +522,7 @@ fn consume(stream: &mut InputStream<char>) -> CSSToken {
                 '\u{003A}' => return CSSToken::Colon,
                 '\u{003B}' => return CSSToken::Semicolon,
                 '\u{003C}' => {
-                    if stream
-                        .peek_range(1, 3)
-                        .is_some_and(|s| s == &['\u{0021}', '\u{002D}', '\u{002D}'])
-                    {
+                    if stream.lookahead(3) == ['\u{0021}', '\u{002D}', '\u{002D}'] {
                         stream.consume();
                         stream.consume();
                         stream.consume();
@@ -545,10 +533,7 @@ fn consume(stream: &mut InputStream<char>) -> CSSToken {
                     }
                 }
                 '\u{0040}' => {
-                    if stream
-                        .peek_range(0, 3)
-                        .is_some_and(|s| would_start_ident(s))
-                    {
+                    if would_start_ident(stream.lookahead(3)) {
                         let at_keyword = consume_ident_seq(stream);
                         return CSSToken::AtKeyword(at_keyword);
                     } else {
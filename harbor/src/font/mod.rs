@@ -1,9 +1,11 @@
 pub mod ttc;
 pub mod ttf;
+pub mod woff;
 
 pub mod tables;
 
 pub use ttc::parse_ttc;
+pub use woff::parse_woff;
 
 pub fn parse_ttf(data: &[u8]) -> ttf::TableDirectory {
     ttf::parse_table_directory(data, None)
@@ -1,5 +1,6 @@
 #![allow(non_camel_case_types)]
 
+use std::collections::HashMap;
 use std::fmt::Debug;
 
 use crate::font::otf_dtypes::*;
@@ -388,16 +389,16 @@ impl CMAPSubtableTrait for CMAPSubtable4 {
 
         let char_code_u16 = char_code as uint16;
 
-        let segment_index = self
+        // The segments are sorted by their end codes, so the first one that ends at or after the
+        // character is found by binary search
+        let seg_index = self
             .end_code
-            .iter()
-            .position(|&end_code| char_code_u16 <= end_code);
+            .partition_point(|&end_code| end_code < char_code_u16);
 
-        if segment_index.is_none() {
+        if seg_index == self.end_code.len() {
             return None;
         }
 
-        let seg_index = segment_index.unwrap();
         let start_code = self.start_code[seg_index];
         // let end_code = self.end_code[seg_index];
         let id_delta = self.id_delta[seg_index];
@@ -509,7 +510,7 @@ pub struct SequentialMapGroup {
     /// First character code in the group
     start_char_code: uint32,
 
-    /// Number of character codes in the group
+    /// Last character code in the group
     end_char_code: uint32,
 
     /// Glyph index corresponding to the first character code in the group
@@ -537,22 +538,22 @@ pub struct CMAPSubtable12 {
 }
 
 impl CMAPSubtableTrait for CMAPSubtable12 {
-    fn sub_parse(_data: &[u8], _encoding: &CMAPEncodingRecord) -> Self
+    fn sub_parse(data: &[u8], _encoding: &CMAPEncodingRecord) -> Self
     where
         Self: Sized,
     {
-        let _length = uint32::from_data(&_data[4..]);
+        let _length = uint32::from_data(&data[4..]);
 
-        let _language = uint32::from_data(&_data[8..]);
+        let _language = uint32::from_data(&data[8..]);
 
-        let _n_groups = uint32::from_data(&_data[12..]);
+        let _n_groups = uint32::from_data(&data[12..]);
         let mut groups = Vec::with_capacity(_n_groups as usize);
 
         let mut offset = 16;
         for _ in 0.._n_groups {
-            let start_char_code = uint32::from_data(&_data[offset..]);
-            let end_char_code = uint32::from_data(&_data[offset + 4..]);
-            let start_glyph_id = uint32::from_data(&_data[offset + 8..]);
+            let start_char_code = uint32::from_data(&data[offset..]);
+            let end_char_code = uint32::from_data(&data[offset + 4..]);
+            let start_glyph_id = uint32::from_data(&data[offset + 8..]);
 
             groups.push(SequentialMapGroup {
                 start_char_code,
@@ -571,15 +572,22 @@ impl CMAPSubtableTrait for CMAPSubtable12 {
         }
     }
 
-    fn char_to_glyph_index(&self, _char_code: u32) -> Option<uint16> {
-        for group in &self.groups {
-            if _char_code >= group.start_char_code && _char_code <= group.end_char_code {
-                let glyph_index = group.start_glyph_id + (_char_code - group.start_char_code);
-                return Some(glyph_index as uint16);
-            }
+    fn char_to_glyph_index(&self, char_code: u32) -> Option<uint16> {
+        // The groups are sorted by their character codes and don't overlap
+        let group_index = self
+            .groups
+            .partition_point(|group| group.end_char_code < char_code);
+
+        let group = self.groups.get(group_index)?;
+        if char_code < group.start_char_code {
+            return None;
         }
 
-        None
+        // Glyph IDs past the end of the 32-bit range are as missing as ones past 16 bits
+        let glyph_index = group
+            .start_glyph_id
+            .checked_add(char_code - group.start_char_code)?;
+        uint16::try_from(glyph_index).ok()
     }
 }
 
@@ -689,8 +697,21 @@ impl CMAPTable {
         self.encoding_records.push(record);
     }
 
+    /// Maps a character code to a glyph index. A format 12 subtable is tried first, since it covers
+    /// the characters outside the Basic Multilingual Plane as well as the ones inside it.
+    ///
+    /// https://learn.microsoft.com/en-us/typography/opentype/spec/cmap#format-12-segmented-coverage
     pub fn char_to_glyph_index(&self, char_code: u32) -> Option<GLYPH_ID> {
-        for subtable in &self.subtables {
+        let segmented_coverage = self
+            .subtables
+            .iter()
+            .filter(|subtable| matches!(subtable, CMAPSubtable::Format12(_)));
+        let others = self
+            .subtables
+            .iter()
+            .filter(|subtable| !matches!(subtable, CMAPSubtable::Format12(_)));
+
+        for subtable in segmented_coverage.chain(others) {
             match subtable {
                 CMAPSubtable::Format0(st) => {
                     if let Some(glyph_index) = st.char_to_glyph_index(char_code) {
@@ -707,12 +728,31 @@ impl CMAPTable {
                         return Some(glyph_index);
                     }
                 }
+                CMAPSubtable::Format12(st) => {
+                    if let Some(glyph_index) = st.char_to_glyph_index(char_code) {
+                        return Some(glyph_index);
+                    }
+                }
                 _ => {}
             }
         }
 
         None
     }
+
+    /// Maps every character of `text` to a glyph index, with the ones the font has no glyph for
+    /// mapped to the missing glyph, 0. Each distinct character is only looked up once.
+    pub fn glyph_indices(&self, text: &str) -> Vec<GLYPH_ID> {
+        let mut looked_up = HashMap::<char, GLYPH_ID>::new();
+
+        text.chars()
+            .map(|ch| {
+                *looked_up
+                    .entry(ch)
+                    .or_insert_with(|| self.char_to_glyph_index(ch as u32).unwrap_or(0))
+            })
+            .collect()
+    }
 }
 
 impl Debug for CMAPTable {
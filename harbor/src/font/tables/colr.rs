@@ -0,0 +1,139 @@
+#![allow(non_camel_case_types)]
+
+use std::fmt::Debug;
+
+use crate::font::otf_dtypes::*;
+use crate::font::tables::{ParseContext, TableTrait};
+
+/// The palette index of layers drawn in the text's own color.
+pub const FOREGROUND_PALETTE_INDEX: uint16 = 0xFFFF;
+
+/// The size of the version 0 header. Shorter tables can't be parsed.
+pub const HEADER_SIZE: usize = 14;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BaseGlyphRecord {
+    /// Glyph ID of the base glyph.
+    pub glyph_id: GLYPH_ID,
+
+    /// Index (base 0) into the layerRecords array.
+    pub first_layer_index: uint16,
+
+    /// Number of color layers associated with this glyph.
+    pub num_layers: uint16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LayerRecord {
+    /// Glyph ID of the glyph used for a given layer.
+    pub glyph_id: GLYPH_ID,
+
+    /// Index (base 0) for a palette entry in the CPAL table, or 0xFFFF for the text foreground
+    /// color.
+    pub palette_index: uint16,
+}
+
+/// A layer of a color glyph as it's drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorLayer {
+    pub glyph_id: GLYPH_ID,
+
+    /// RGBA components from 0 to 1, or `None` for the text's own color
+    pub color: Option<[f32; 4]>,
+}
+
+/// The layered color glyphs of a font, drawn by stacking other glyphs, each filled with a color
+/// from the 'CPAL' table. Only the version 0 glyphs are read, since version 1 keeps them as they
+/// are and adds its paint graphs after them.
+///
+/// https://learn.microsoft.com/en-us/typography/opentype/spec/colr
+#[derive(Clone)]
+pub struct COLRTable {
+    /// Table version number (starts at 0).
+    pub version: uint16,
+
+    /// Sorted by glyph ID.
+    pub base_glyph_records: Vec<BaseGlyphRecord>,
+
+    pub layer_records: Vec<LayerRecord>,
+}
+
+impl Debug for COLRTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("COLRTable")
+            .field("version", &self.version)
+            .field("num_base_glyph_records", &self.base_glyph_records.len())
+            .field(
+                "base_glyph_records_preview",
+                &self
+                    .base_glyph_records
+                    .iter()
+                    .take(5)
+                    .collect::<Vec<&BaseGlyphRecord>>(),
+            )
+            .field("num_layer_records", &self.layer_records.len())
+            .finish()
+    }
+}
+
+impl COLRTable {
+    /// The layers of a color glyph from the bottom up, or `None` if it isn't one.
+    pub fn layers(&self, glyph_id: GLYPH_ID) -> Option<&[LayerRecord]> {
+        let index = self
+            .base_glyph_records
+            .binary_search_by_key(&glyph_id, |record| record.glyph_id)
+            .ok()?;
+        let record = &self.base_glyph_records[index];
+
+        let start = record.first_layer_index as usize;
+        self.layer_records
+            .get(start..start + record.num_layers as usize)
+    }
+}
+
+impl TableTrait for COLRTable {
+    fn parse(data: &[u8], _ctx: Option<ParseContext>) -> Self
+    where
+        Self: Sized,
+    {
+        let version = uint16::from_data(&data[0..2]);
+        let num_base_glyph_records = uint16::from_data(&data[2..4]) as usize;
+        let base_glyph_records_offset = uint32::from_data(&data[4..8]) as usize;
+        let layer_records_offset = uint32::from_data(&data[8..12]) as usize;
+        let num_layer_records = uint16::from_data(&data[12..14]) as usize;
+
+        // Records past the end of the table are left out
+        let base_glyph_records = data
+            .get(base_glyph_records_offset..)
+            .unwrap_or_default()
+            .chunks_exact(6)
+            .take(num_base_glyph_records)
+            .map(|record| BaseGlyphRecord {
+                glyph_id: uint16::from_data(&record[0..2]),
+                first_layer_index: uint16::from_data(&record[2..4]),
+                num_layers: uint16::from_data(&record[4..6]),
+            })
+            .collect();
+
+        let layer_records = data
+            .get(layer_records_offset..)
+            .unwrap_or_default()
+            .chunks_exact(4)
+            .take(num_layer_records)
+            .map(|record| LayerRecord {
+                glyph_id: uint16::from_data(&record[0..2]),
+                palette_index: uint16::from_data(&record[2..4]),
+            })
+            .collect();
+
+        COLRTable {
+            version,
+            base_glyph_records,
+            layer_records,
+        }
+    }
+
+    fn construct(&mut self, _data: &[u8]) {
+        panic!("COLRTable does not require construction - simply use COLRTable::parse()");
+    }
+}
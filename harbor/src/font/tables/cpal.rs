@@ -0,0 +1,128 @@
+#![allow(non_camel_case_types)]
+
+use std::fmt::Debug;
+
+use crate::font::otf_dtypes::*;
+use crate::font::tables::{ParseContext, TableTrait};
+
+/// The size of the header before the palettes' color record indices. Shorter tables can't be
+/// parsed.
+pub const HEADER_SIZE: usize = 12;
+
+/// A color as stored in the table, in sRGB.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColorRecord {
+    pub blue: uint8,
+    pub green: uint8,
+    pub red: uint8,
+    pub alpha: uint8,
+}
+
+impl ColorRecord {
+    /// The color as RGBA components from 0 to 1.
+    pub fn used(&self) -> [f32; 4] {
+        [
+            self.red as f32 / 255.0,
+            self.green as f32 / 255.0,
+            self.blue as f32 / 255.0,
+            self.alpha as f32 / 255.0,
+        ]
+    }
+}
+
+/// The palettes of colors that 'COLR' layers are filled with.
+///
+/// https://learn.microsoft.com/en-us/typography/opentype/spec/cpal
+#[derive(Clone)]
+pub struct CPALTable {
+    /// Table version number (=0 or 1).
+    pub version: uint16,
+
+    /// Number of palette entries in each palette.
+    pub num_palette_entries: uint16,
+
+    /// Index of each palette's first color record in the combined color record array.
+    pub color_record_indices: Vec<uint16>,
+
+    pub color_records: Vec<ColorRecord>,
+}
+
+impl Debug for CPALTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CPALTable")
+            .field("version", &self.version)
+            .field("num_palette_entries", &self.num_palette_entries)
+            .field("num_palettes", &self.color_record_indices.len())
+            .field(
+                "color_records_preview",
+                &self
+                    .color_records
+                    .iter()
+                    .take(5)
+                    .collect::<Vec<&ColorRecord>>(),
+            )
+            .finish()
+    }
+}
+
+impl CPALTable {
+    pub fn num_palettes(&self) -> usize {
+        self.color_record_indices.len()
+    }
+
+    /// The color of an entry in a palette, or `None` if either doesn't exist.
+    pub fn color(&self, palette: uint16, palette_index: uint16) -> Option<ColorRecord> {
+        if palette_index >= self.num_palette_entries {
+            return None;
+        }
+
+        let first = *self.color_record_indices.get(palette as usize)?;
+        self.color_records
+            .get(first as usize + palette_index as usize)
+            .copied()
+    }
+}
+
+impl TableTrait for CPALTable {
+    fn parse(data: &[u8], _ctx: Option<ParseContext>) -> Self
+    where
+        Self: Sized,
+    {
+        let version = uint16::from_data(&data[0..2]);
+        let num_palette_entries = uint16::from_data(&data[2..4]);
+        let num_palettes = uint16::from_data(&data[4..6]) as usize;
+        let num_color_records = uint16::from_data(&data[6..8]) as usize;
+        let color_records_array_offset = uint32::from_data(&data[8..12]) as usize;
+
+        let color_record_indices = data[HEADER_SIZE..]
+            .chunks_exact(2)
+            .take(num_palettes)
+            .map(uint16::from_data)
+            .collect();
+
+        // Records past the end of the table are left out
+        let color_records = data
+            .get(color_records_array_offset..)
+            .unwrap_or_default()
+            .chunks_exact(4)
+            .take(num_color_records)
+            .map(|record| ColorRecord {
+                blue: record[0],
+                green: record[1],
+                red: record[2],
+                alpha: record[3],
+            })
+            .collect();
+
+        CPALTable {
+            version,
+            num_palette_entries,
+            color_record_indices,
+            color_records,
+        }
+    }
+
+    fn construct(&mut self, _data: &[u8]) {
+        panic!("CPALTable does not require construction - simply use CPALTable::parse()");
+    }
+}
@@ -255,6 +255,22 @@ pub struct GlyphComponent {
     pub transform: Option<GlyphTransform>,
 }
 
+impl GlyphComponent {
+    /// Whether `arg1` and `arg2` are an x and y offset, rather than the numbers of a point in
+    /// the glyph so far and a point in this component that should line up.
+    pub fn args_are_offsets(&self) -> bool {
+        self.flags & CompositeGlyphFlags::ArgsAreXYValues != 0
+    }
+
+    /// Whether the offset is transformed along with the component's outline.
+    ///
+    /// https://learn.microsoft.com/en-us/typography/opentype/spec/glyf#composite-glyph-description
+    pub fn scales_offset(&self) -> bool {
+        self.flags & CompositeGlyphFlags::ScaledComponentOffset != 0
+            && self.flags & CompositeGlyphFlags::UnscaledComponentOffset == 0
+    }
+}
+
 impl Debug for GlyphComponent {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("GlyphComponent")
@@ -334,11 +350,12 @@ impl TableTrait for GlyfTable {
     }
 
     fn construct(&mut self, data: &[u8]) {
-        for i in 0..(self._loca_offsets.len() - 1) {
+        for i in 0..self._loca_offsets.len().saturating_sub(1) {
             let start = self._loca_offsets[i] as usize;
             let end = self._loca_offsets[i + 1] as usize;
 
-            if start == end {
+            // Offsets past the end of the table are treated like glyphs without an outline
+            if start >= end || end > data.len() {
                 // Empty glyph
                 self.glyphs.push(GlyphData {
                     header: GlyphHeader {
@@ -559,8 +576,19 @@ impl TableTrait for GlyfTable {
 
                         offset += 4;
 
+                        result
+                    } else if flags & CompositeGlyphFlags::ArgsAreXYValues != 0 {
+                        // Offsets are signed bytes
+                        let result = (
+                            glyph_data[offset] as i8 as int16,
+                            glyph_data[offset + 1] as i8 as int16,
+                        );
+
+                        offset += 2;
+
                         result
                     } else {
+                        // Point numbers are unsigned bytes
                         let result = (glyph_data[offset] as int16, glyph_data[offset + 1] as int16);
 
                         offset += 2;
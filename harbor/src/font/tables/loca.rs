@@ -39,31 +39,65 @@ impl Debug for LocaTable {
     }
 }
 
+impl LocaTable {
+    /// Where the glyph's data starts in the `glyf` table, in bytes. Short offsets are stored
+    /// halved.
+    ///
+    /// https://learn.microsoft.com/en-us/typography/opentype/spec/loca
+    pub fn glyph_offset(&self, glyph_index: GLYPH_ID) -> Option<uint32> {
+        let index = glyph_index as usize;
+
+        match self {
+            LocaTable::Short(offsets) => offsets.get(index).map(|&offset| offset as uint32 * 2),
+            LocaTable::Long(offsets) => offsets.get(index).copied(),
+            LocaTable::Interim(_) => None,
+        }
+    }
+
+    /// The start and end of the glyph's data in the `glyf` table, which are the same for glyphs
+    /// without an outline.
+    pub fn glyph_range(&self, glyph_index: GLYPH_ID) -> Option<(uint32, uint32)> {
+        let start = self.glyph_offset(glyph_index)?;
+        let end = self.glyph_offset(glyph_index.checked_add(1)?)?;
+
+        Some((start, end))
+    }
+
+    /// Every offset in bytes, including the one past the end of the last glyph.
+    pub fn offsets(&self) -> Vec<uint32> {
+        match self {
+            LocaTable::Short(offsets) => {
+                offsets.iter().map(|&offset| offset as uint32 * 2).collect()
+            }
+            LocaTable::Long(offsets) => offsets.clone(),
+            LocaTable::Interim(_) => panic!("Loca table not constructed yet."),
+        }
+    }
+}
+
 impl TableTrait for LocaTable {
     fn parse(data: &[u8], ctx: Option<ParseContext>) -> Self
     where
         Self: Sized,
     {
         if let Some(ParseContext::Loca((index_to_loc_format, num_glyphs))) = ctx {
+            // There's an offset for every glyph, and one more for the end of the last glyph. A
+            // table cut short only has the offsets that fit.
+            let count = num_glyphs as usize + 1;
+
             match index_to_loc_format {
-                0 => {
-                    // Short format
-                    let mut offsets = Vec::with_capacity((num_glyphs + 1) as usize);
-                    for i in 0..=(num_glyphs as usize) {
-                        let offset = uint16::from_data(&data[i * 2..i * 2 + 2]);
-                        offsets.push(offset);
-                    }
-                    LocaTable::Short(offsets)
-                }
-                1 => {
-                    // Long format
-                    let mut offsets = Vec::with_capacity((num_glyphs + 1) as usize);
-                    for i in 0..=(num_glyphs as usize) {
-                        let offset = uint32::from_data(&data[i * 4..i * 4 + 4]);
-                        offsets.push(offset);
-                    }
-                    LocaTable::Long(offsets)
-                }
+                0 => LocaTable::Short(
+                    data.chunks_exact(2)
+                        .take(count)
+                        .map(uint16::from_data)
+                        .collect(),
+                ),
+                1 => LocaTable::Long(
+                    data.chunks_exact(4)
+                        .take(count)
+                        .map(uint32::from_data)
+                        .collect(),
+                ),
                 _ => panic!("Invalid indexToLocFormat value"),
             }
         } else {
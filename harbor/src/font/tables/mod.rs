@@ -1,4 +1,6 @@
 pub mod cmap;
+pub mod colr;
+pub mod cpal;
 pub mod gasp;
 pub mod glyf;
 pub mod hdmx;
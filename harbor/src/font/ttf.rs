@@ -3,15 +3,24 @@
 use std::fmt::Debug;
 
 use crate::font::otf_dtypes::*;
-use crate::font::tables::glyf::{CompositeGlyphFlags, FALSE, GlyphDataType, Point, TRUE};
+use crate::font::tables::glyf::{Contour, FALSE, GlyphDataType, Point, TRUE};
 use crate::font::tables::head::MacStyle;
 use crate::font::tables::os2::OS2Table;
 use crate::font::tables::{
-    ParseContext, TableTrait, cmap, cvt, fpgm, gasp, glyf, hdmx, head, hhea, hmtx, loca, maxp,
-    meta, name, os2, post, prep,
+    ParseContext, TableTrait, cmap, colr, cpal, cvt, fpgm, gasp, glyf, hdmx, head, hhea, hmtx,
+    loca, maxp, meta, name, os2, post, prep,
 };
 use crate::render::text::Segment;
 
+/// How deeply composite glyphs may be nested inside each other before the rest of the outline is
+/// given up on.
+const MAX_COMPONENT_DEPTH: usize = 16;
+
+/// How many components, counting those of nested composites, a glyph's outline may be assembled
+/// from. Components that each repeat the same composite many times would otherwise multiply
+/// with every level of nesting.
+const MAX_COMPONENTS: usize = 1024;
+
 #[derive(Clone)]
 pub enum TableRecordData {
     CMAP(cmap::CMAPTable),
@@ -30,6 +39,8 @@ pub enum TableRecordData {
     GASP(gasp::GASPTable),
     Meta(meta::MetaTable),
     HDMX(hdmx::HdmxTable),
+    COLR(colr::COLRTable),
+    CPAL(cpal::CPALTable),
     Raw(Vec<u8>),
 }
 
@@ -52,6 +63,8 @@ impl Debug for TableRecordData {
             TableRecordData::GASP(gasp_table) => gasp_table.fmt(f),
             TableRecordData::Meta(meta_table) => meta_table.fmt(f),
             TableRecordData::HDMX(hdmx_table) => hdmx_table.fmt(f),
+            TableRecordData::COLR(colr_table) => colr_table.fmt(f),
+            TableRecordData::CPAL(cpal_table) => cpal_table.fmt(f),
             TableRecordData::Raw(raw_data) => f
                 .debug_struct("TableRecordData::Raw")
                 .field("data_length", &raw_data.len())
@@ -134,6 +147,13 @@ impl TableRecordData {
                         as uint16,
                 )),
             )),
+            // Truncated color tables are kept raw, so the font is drawn without color
+            b"COLR" if data.len() >= colr::HEADER_SIZE => {
+                TableRecordData::COLR(colr::COLRTable::parse(data, None))
+            }
+            b"CPAL" if data.len() >= cpal::HEADER_SIZE => {
+                TableRecordData::CPAL(cpal::CPALTable::parse(data, None))
+            }
             _ => TableRecordData::Raw(data.to_vec()),
         }
     }
@@ -239,29 +259,28 @@ impl TableRecord {
     }
 
     fn reassign_checksum(mut self, table_data: &[u8]) -> Self {
-        let mut sum: uint32 = 0;
-
-        for chunk in table_data.chunks(4) {
-            let mut word = [0u8; 4];
-            word[..chunk.len()].copy_from_slice(chunk);
-            sum = sum.wrapping_add(uint32::from_be_bytes(word));
-        }
-
-        self.checksum = sum;
+        self.checksum = table_checksum(table_data);
         self
     }
 
     pub fn recalculate_checksum(&self, data: &[u8]) -> uint32 {
-        let mut sum: uint32 = 0;
+        table_checksum(data)
+    }
+}
 
-        for chunk in data.chunks(4) {
-            let mut word = [0u8; 4];
-            word[..chunk.len()].copy_from_slice(chunk);
-            sum = sum.wrapping_add(uint32::from_be_bytes(word));
-        }
+/// The sum of a table's data as big-endian 32-bit words, with the last word padded with zeroes.
+///
+/// https://learn.microsoft.com/en-us/typography/opentype/spec/otff#calculating-checksums
+pub fn table_checksum(data: &[u8]) -> uint32 {
+    let mut sum: uint32 = 0;
 
-        sum
+    for chunk in data.chunks(4) {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum = sum.wrapping_add(uint32::from_be_bytes(word));
     }
+
+    sum
 }
 
 #[derive(Clone)]
@@ -380,6 +399,68 @@ impl TableDirectory {
         None
     }
 
+    /// The layers to draw a color glyph with from the bottom up, colored from `palette`, or
+    /// `None` if the glyph is only an outline. Layers whose palette entry is missing are drawn
+    /// in the text's own color.
+    ///
+    /// https://learn.microsoft.com/en-us/typography/opentype/spec/colr#glyph-layers-and-color-palettes
+    pub fn color_layers(
+        &self,
+        glyph_index: GLYPH_ID,
+        palette: uint16,
+    ) -> Option<Vec<colr::ColorLayer>> {
+        let TableRecordData::COLR(colr_table) = self.get_table_record(b"COLR")?.data() else {
+            return None;
+        };
+        let cpal_table = match self.get_table_record(b"CPAL").map(|record| record.data()) {
+            Some(TableRecordData::CPAL(cpal_table)) => Some(cpal_table),
+            _ => None,
+        };
+
+        let layers = colr_table.layers(glyph_index)?;
+
+        Some(
+            layers
+                .iter()
+                .map(|layer| colr::ColorLayer {
+                    glyph_id: layer.glyph_id,
+                    color: match layer.palette_index {
+                        colr::FOREGROUND_PALETTE_INDEX => None,
+                        palette_index => cpal_table
+                            .and_then(|cpal| cpal.color(palette, palette_index))
+                            .map(|color| color.used()),
+                    },
+                })
+                .collect(),
+        )
+    }
+
+    /// The glyph drawn for characters the font has no glyph for, usually an empty box. It's
+    /// always the first glyph.
+    ///
+    /// https://learn.microsoft.com/en-us/typography/opentype/spec/recom#glyph-0-the-notdef-glyph
+    pub fn notdef_glyph(&self) -> GLYPH_ID {
+        0
+    }
+
+    /// The glyph index of a character, or the missing glyph if the font has none for it.
+    pub fn resolve_glyph(&self, char_code: uint32) -> GLYPH_ID {
+        self.glyph_index(char_code)
+            .unwrap_or_else(|| self.notdef_glyph())
+    }
+
+    /// The glyph index of every character in `text`, with 0, the missing glyph, for characters
+    /// the font has no glyph for.
+    pub fn glyph_indices(&self, text: &str) -> Vec<GLYPH_ID> {
+        if let Some(cmap_record) = self.get_table_record(b"cmap")
+            && let TableRecordData::CMAP(cmap_table) = &cmap_record._data
+        {
+            return cmap_table.glyph_indices(text);
+        }
+
+        vec![0; text.chars().count()]
+    }
+
     pub fn last_glyph_index(&self) -> Option<GLYPH_ID> {
         if let Some(hhea_record) = self.get_table_record(b"hhea") {
             if let TableRecordData::HHea(hhea_table) = &hhea_record._data {
@@ -390,14 +471,72 @@ impl TableDirectory {
         None
     }
 
-    pub fn advance_width(&self, glyph_index: GLYPH_ID) -> Option<uint16> {
-        if let Some(hmtx_record) = self.get_table_record(b"hmtx") {
-            if let TableRecordData::HMtx(hmtx_table) = &hmtx_record._data {
-                return hmtx_table
-                    .h_metrics
-                    .get(glyph_index as usize)
-                    .map(|h_metric| h_metric.advance_width);
+    /// The advance width and left side bearing of a glyph. Glyphs past the last long metric share
+    /// its advance width, and only have a left side bearing of their own.
+    ///
+    /// https://learn.microsoft.com/en-us/typography/opentype/spec/hmtx
+    fn horizontal_metrics(&self, glyph_index: GLYPH_ID) -> Option<(uint16, int16)> {
+        if let Some(hmtx_record) = self.get_table_record(b"hmtx")
+            && let TableRecordData::HMtx(hmtx_table) = &hmtx_record._data
+        {
+            let index = glyph_index as usize;
+            if let Some(h_metric) = hmtx_table.h_metrics.get(index) {
+                return Some((h_metric.advance_width, h_metric.lsb));
             }
+
+            let advance_width = hmtx_table.h_metrics.last()?.advance_width;
+            let lsb = *hmtx_table
+                .left_side_bearings
+                .get(index - hmtx_table.h_metrics.len())?;
+
+            return Some((advance_width, lsb));
+        }
+
+        None
+    }
+
+    pub fn advance_width(&self, glyph_index: GLYPH_ID) -> Option<uint16> {
+        self.horizontal_metrics(glyph_index)
+            .map(|(advance_width, _)| advance_width)
+    }
+
+    /// The space between the glyph's origin and the left edge of its outline, in font units.
+    pub fn left_side_bearing(&self, glyph_index: GLYPH_ID) -> Option<int16> {
+        self.horizontal_metrics(glyph_index).map(|(_, lsb)| lsb)
+    }
+
+    /// The space between the right edge of the glyph's outline and where the next glyph starts,
+    /// in font units. Widened to `i32`, since a wide glyph's bounding box can span more than
+    /// an `int16` holds.
+    ///
+    /// https://learn.microsoft.com/en-us/typography/opentype/spec/hmtx#hmtx-header
+    pub fn right_side_bearing(&self, glyph_index: GLYPH_ID) -> Option<i32> {
+        let (advance_width, lsb) = self.horizontal_metrics(glyph_index)?;
+        let header = self.glyph_header(glyph_index)?;
+
+        Some(advance_width as i32 - (lsb as i32 + header.x_max as i32 - header.x_min as i32))
+    }
+
+    /// Where the left edge of a glyph's outline goes when its origin is at `origin_x`, with
+    /// `scale` pixels to a font unit. Glyphs without horizontal metrics fall back to the left
+    /// edge of their bounding box.
+    pub fn glyph_left_edge(&self, glyph_index: GLYPH_ID, origin_x: f32, scale: f32) -> f32 {
+        let lsb = self
+            .left_side_bearing(glyph_index)
+            .or_else(|| self.glyph_header(glyph_index).map(|header| header.x_min))
+            .unwrap_or(0);
+
+        origin_x + lsb as f32 * scale
+    }
+
+    fn glyph_header(&self, glyph_index: GLYPH_ID) -> Option<&glyf::GlyphHeader> {
+        if let Some(glyf_record) = self.get_table_record(b"glyf")
+            && let TableRecordData::Glyf(glyf_table) = &glyf_record._data
+        {
+            return glyf_table
+                .glyphs
+                .get(glyph_index as usize)
+                .map(|glyph| &glyph.header);
         }
 
         None
@@ -529,6 +668,38 @@ impl TableDirectory {
         None
     }
 
+    /// The distance the font extends below the baseline, as a positive number of font units.
+    /// Widened to `i32`, since `usWinDescent` is unsigned and negating `i16::MIN` overflows.
+    pub fn descent(&self) -> Option<i32> {
+        if let Some(os2_record) = self.get_table_record(b"OS/2")
+            && let TableRecordData::OS2(os2_table) = &os2_record._data
+        {
+            let metrics = match os2_table {
+                OS2Table::V5(v5) => Some((v5.fs_selection, -(v5.s_typo_descender as i32))),
+                OS2Table::V4(t) | OS2Table::V3(t) | OS2Table::V2(t) => {
+                    Some((t.fs_selection, -(t.s_typo_descender as i32)))
+                }
+                OS2Table::V1(t) => Some((t.fs_selection, t.us_win_descent as i32)),
+                // Versions this parser doesn't read fall back to `hhea`
+                OS2Table::Interim(_) => None,
+            };
+
+            if let Some((fs_selection, descent)) = metrics
+                && fs_selection & 0x80 != 0
+            {
+                return Some(descent);
+            }
+        }
+
+        if let Some(hhea_record) = self.get_table_record(b"hhea")
+            && let TableRecordData::HHea(hhea_table) = &hhea_record._data
+        {
+            return Some(-(hhea_table.descender as i32));
+        }
+
+        None
+    }
+
     pub fn advance_width_from_char_code(&self, char_code: uint32) -> Option<uint16> {
         if let Some(glyph_index) = self.glyph_index(char_code) {
             return self.advance_width(glyph_index);
@@ -537,11 +708,30 @@ impl TableDirectory {
         None
     }
 
-    pub fn make_glyph_segments(
+    /// The contours of a glyph's outline, in font units. A composite glyph's components are
+    /// assembled into a single outline, each placed and transformed as its flags say.
+    ///
+    /// https://learn.microsoft.com/en-us/typography/opentype/spec/glyf#composite-glyph-description
+    pub fn glyph_contours(&self, glyph_index: GLYPH_ID) -> Vec<Contour> {
+        let mut contours = Vec::new();
+        let mut components_left = MAX_COMPONENTS;
+        self._assemble_contours(
+            glyph_index,
+            &mut Vec::new(),
+            &mut components_left,
+            &mut contours,
+        );
+
+        contours
+    }
+
+    /// `expanding` holds the composites that `glyph_index` is nested in, from the outermost.
+    fn _assemble_contours(
         &self,
         glyph_index: GLYPH_ID,
-        precision: f32,
-        out: &mut Vec<Segment>,
+        expanding: &mut Vec<GLYPH_ID>,
+        components_left: &mut usize,
+        out: &mut Vec<Contour>,
     ) {
         let glyf = match self.get_table_record(b"glyf").unwrap().data() {
             TableRecordData::Glyf(glyf_table) => glyf_table,
@@ -550,136 +740,170 @@ impl TableDirectory {
             }
         };
 
-        let glyph = glyf.glyphs.get(glyph_index as usize).unwrap();
-
-        let mut segments = Vec::<Segment>::new();
+        let Some(glyph) = glyf.glyphs.get(glyph_index as usize) else {
+            return;
+        };
 
         match &glyph.data {
-            GlyphDataType::Simple(simple) => {
-                for contour in &simple.contours {
-                    let mut segment_part = Vec::<Segment>::new();
+            GlyphDataType::Simple(simple) => out.extend(simple.contours.iter().cloned()),
+            GlyphDataType::Composite(composite) => {
+                // Components that refer back to a glyph they're part of would never end
+                if expanding.len() >= MAX_COMPONENT_DEPTH || expanding.contains(&glyph_index) {
+                    return;
+                }
 
-                    // populate segments
-                    let contour_points = contour.points.clone();
+                expanding.push(glyph_index);
 
-                    let mut prev = if contour_points[0].on_curve == TRUE {
-                        contour_points[0].clone()
-                    } else if contour_points[contour_points.len() - 1].on_curve == TRUE {
-                        contour_points[contour_points.len() - 1].clone()
-                    } else {
-                        Point::midpoint(
-                            &contour_points[0],
-                            &contour_points[contour_points.len() - 1],
-                        )
-                    };
+                for component in &composite.components {
+                    if *components_left == 0 {
+                        break;
+                    }
+                    *components_left -= 1;
+
+                    let mut component_contours = Vec::new();
+                    self._assemble_contours(
+                        component.glyph_index,
+                        expanding,
+                        components_left,
+                        &mut component_contours,
+                    );
+
+                    let transform = &component.transform;
+                    for contour in &mut component_contours {
+                        for point in &mut contour.points {
+                            *point = point.transformed(transform.clone());
+                        }
+                    }
 
-                    let mut i = contour_points.len() - 1;
-                    while segment_part.len() < contour_points.len() {
-                        let mut curr = contour_points[i % contour_points.len()].clone();
-                        let mut next = contour_points[(i + 1) % contour_points.len()].clone();
-
-                        if curr.on_curve == TRUE && next.on_curve == TRUE {
-                            // Line segment
-                            segment_part.push(Segment::Line(curr.clone(), next));
-                            prev = curr;
-                        } else if curr.on_curve == TRUE && next.on_curve == FALSE {
-                            // Quadratic Bezier segment
-                            while next.on_curve == FALSE {
-                                let after_next =
-                                    contour_points[(i + 2) % contour_points.len()].clone();
-
-                                let control_point = next.clone();
-                                let end_point = if after_next.on_curve == TRUE {
-                                    after_next.clone()
-                                } else {
-                                    Point::midpoint(&next, &after_next)
-                                };
-
-                                segment_part.push(Segment::Quadratic(
-                                    curr.clone(),
-                                    control_point,
-                                    end_point.clone(),
-                                ));
-
-                                curr = end_point;
-                                next = after_next;
-                                i += 1;
-                            }
-                            prev = curr;
+                    let (dx, dy) = if component.args_are_offsets() {
+                        let offset = Point {
+                            x: component.arg1 as f32,
+                            y: component.arg2 as f32,
+                            on_curve: TRUE,
+                        };
+                        let offset = if component.scales_offset() {
+                            offset.transformed(transform.clone())
                         } else {
-                            // curr is off-curve
-                            let control_point = curr.clone();
-                            let end_point = if next.on_curve == TRUE {
-                                next.clone()
-                            } else {
-                                Point::midpoint(&curr, &next)
-                            };
-
-                            segment_part.push(Segment::Quadratic(
-                                prev.clone(),
-                                control_point,
-                                end_point.clone(),
-                            ));
-
-                            prev = end_point;
+                            offset
+                        };
+
+                        (offset.x, offset.y)
+                    } else {
+                        // Move the component so that its point lines up with the glyph's
+                        let nth_point = |contours: &[Contour], n: uint16| {
+                            contours
+                                .iter()
+                                .flat_map(|contour| contour.points.iter())
+                                .nth(n as usize)
+                                .cloned()
+                        };
+
+                        match (
+                            nth_point(out, component.arg1 as uint16),
+                            nth_point(&component_contours, component.arg2 as uint16),
+                        ) {
+                            (Some(parent), Some(child)) => (parent.x - child.x, parent.y - child.y),
+                            _ => (0.0, 0.0),
                         }
+                    };
 
-                        i += 1;
+                    for contour in &mut component_contours {
+                        for point in &mut contour.points {
+                            *point = point.translate(dx, dy);
+                        }
                     }
 
-                    segments.extend(segment_part);
+                    out.extend(component_contours);
                 }
-            }
-            GlyphDataType::Composite(composite) => {
-                for component in &composite.components {
-                    let mut segment_part = Vec::<Segment>::new();
-                    let component_glyph_index = component.glyph_index;
-
-                    // if let Some(post_record) = self.get_table_record(b"post") {
-                    //     if let TableRecordData::Post(post_table) = &post_record._data {
-                    //         println!(
-                    //             "Component Glyph Name: {}",
-                    //             post_table.glyph_name(component_glyph_index as u16).unwrap()
-                    //         );
-                    //     }
-                    // }
-
-                    let mut component_segments: Vec<Segment> = Vec::new();
-                    self.make_glyph_segments(
-                        component_glyph_index,
-                        precision,
-                        &mut component_segments,
-                    );
 
-                    if component.flags & CompositeGlyphFlags::ScaledComponentOffset != 0 {
-                        let (x, y) = (component.arg1, component.arg2);
-
-                        for segment in &mut segment_part {
-                            segment.translate(x as f32, y as f32);
-                        }
+                expanding.pop();
+            }
+        }
+    }
 
-                        let transform = &component.transform;
+    pub fn make_glyph_segments(
+        &self,
+        glyph_index: GLYPH_ID,
+        _precision: f32,
+        out: &mut Vec<Segment>,
+    ) {
+        let mut segments = Vec::<Segment>::new();
 
-                        for segment in component_segments {
-                            segment_part.push(segment.transformed(transform.clone()));
-                        }
-                    } else {
-                        let transform = &component.transform;
+        for contour in &self.glyph_contours(glyph_index) {
+            if contour.points.is_empty() {
+                continue;
+            }
 
-                        for segment in component_segments {
-                            segment_part.push(segment.transformed(transform.clone()));
-                        }
+            let mut segment_part = Vec::<Segment>::new();
+
+            // populate segments
+            let contour_points = contour.points.clone();
+
+            let mut prev = if contour_points[0].on_curve == TRUE {
+                contour_points[0].clone()
+            } else if contour_points[contour_points.len() - 1].on_curve == TRUE {
+                contour_points[contour_points.len() - 1].clone()
+            } else {
+                Point::midpoint(
+                    &contour_points[0],
+                    &contour_points[contour_points.len() - 1],
+                )
+            };
+
+            let mut i = contour_points.len() - 1;
+            while segment_part.len() < contour_points.len() {
+                let mut curr = contour_points[i % contour_points.len()].clone();
+                let mut next = contour_points[(i + 1) % contour_points.len()].clone();
+
+                if curr.on_curve == TRUE && next.on_curve == TRUE {
+                    // Line segment
+                    segment_part.push(Segment::Line(curr.clone(), next));
+                    prev = curr;
+                } else if curr.on_curve == TRUE && next.on_curve == FALSE {
+                    // Quadratic Bezier segment
+                    while next.on_curve == FALSE {
+                        let after_next = contour_points[(i + 2) % contour_points.len()].clone();
+
+                        let control_point = next.clone();
+                        let end_point = if after_next.on_curve == TRUE {
+                            after_next.clone()
+                        } else {
+                            Point::midpoint(&next, &after_next)
+                        };
 
-                        let (x, y) = (component.arg1, component.arg2);
+                        segment_part.push(Segment::Quadratic(
+                            curr.clone(),
+                            control_point,
+                            end_point.clone(),
+                        ));
 
-                        for segment in &mut segment_part {
-                            segment.translate(x as f32, y as f32);
-                        }
+                        curr = end_point;
+                        next = after_next;
+                        i += 1;
                     }
+                    prev = curr;
+                } else {
+                    // curr is off-curve
+                    let control_point = curr.clone();
+                    let end_point = if next.on_curve == TRUE {
+                        next.clone()
+                    } else {
+                        Point::midpoint(&curr, &next)
+                    };
+
+                    segment_part.push(Segment::Quadratic(
+                        prev.clone(),
+                        control_point,
+                        end_point.clone(),
+                    ));
 
-                    segments.extend(segment_part);
+                    prev = end_point;
                 }
+
+                i += 1;
             }
+
+            segments.extend(segment_part);
         }
 
         out.extend(segments);
@@ -814,14 +1038,7 @@ fn update_table_directory_with_record(table_directory: &mut TableDirectory) {
         }
         b"loca" => {
             if let TableRecordData::Loca(loca_table) = &table_record._data {
-                table_directory._loca_offsets = Some(match loca_table {
-                    loca::LocaTable::Short(offsets) => offsets
-                        .iter()
-                        .map(|&offset| (offset as uint32) * 2)
-                        .collect(),
-                    loca::LocaTable::Long(offsets) => offsets.clone(),
-                    _ => panic!("Loca table not constructed yet."),
-                });
+                table_directory._loca_offsets = Some(loca_table.offsets());
             }
         }
         _ => {}
@@ -0,0 +1,214 @@
+use std::fmt;
+
+use crate::font::otf_dtypes::*;
+use crate::font::ttf::{TableDirectory, parse_table_directory, table_checksum};
+use crate::http::content_coding::{DecodeError, decode_zlib};
+
+/// 'wOFF'
+pub const WOFF_SIGNATURE: uint32 = 0x774F4646;
+
+/// 'wOF2'
+pub const WOFF2_SIGNATURE: uint32 = 0x774F4632;
+
+const WOFF_HEADER_LENGTH: usize = 44;
+const WOFF_TABLE_ENTRY_LENGTH: usize = 20;
+
+const SFNT_HEADER_LENGTH: usize = 12;
+const SFNT_TABLE_RECORD_LENGTH: usize = 16;
+
+/// The most tables a font can have while its table directory's search fields, which count
+/// table records in bytes, still fit in 16 bits
+const MAX_SFNT_TABLES: uint16 = 0xFFFF / SFNT_TABLE_RECORD_LENGTH as uint16;
+
+/// Why a WOFF file couldn't be turned back into the font it was made from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WoffError {
+    /// The data doesn't start with the WOFF signature
+    InvalidSignature,
+
+    /// The data is WOFF 2.0, whose tables are compressed with Brotli
+    UnsupportedWoff2,
+
+    /// The header or table directory ends early, or a table lies outside the file
+    UnexpectedEnd,
+
+    /// There are more tables than a font's table directory can describe
+    TooManyTables,
+
+    /// A table is stored with a compressed length greater than its original length
+    InvalidTableLength(Tag),
+
+    /// A table's compressed data couldn't be inflated
+    Decompression(Tag, DecodeError),
+
+    /// A table doesn't inflate to the length it's meant to have
+    LengthMismatch(Tag),
+
+    /// A table's data doesn't match the checksum it was stored with
+    ChecksumMismatch(Tag),
+}
+
+impl fmt::Display for WoffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tag = |tag: &Tag| String::from_utf8_lossy(tag).into_owned();
+
+        match self {
+            WoffError::InvalidSignature => write!(f, "not a WOFF file"),
+            WoffError::UnsupportedWoff2 => write!(f, "WOFF 2.0 fonts are not supported"),
+            WoffError::UnexpectedEnd => write!(f, "WOFF file ended unexpectedly"),
+            WoffError::TooManyTables => write!(f, "WOFF file has too many tables"),
+            WoffError::InvalidTableLength(t) => {
+                write!(
+                    f,
+                    "'{}' table is longer compressed than uncompressed",
+                    tag(t)
+                )
+            }
+            WoffError::Decompression(t, error) => {
+                write!(f, "'{}' table could not be decompressed: {}", tag(t), error)
+            }
+            WoffError::LengthMismatch(t) => {
+                write!(
+                    f,
+                    "'{}' table has the wrong length once decompressed",
+                    tag(t)
+                )
+            }
+            WoffError::ChecksumMismatch(t) => write!(f, "'{}' table checksum mismatch", tag(t)),
+        }
+    }
+}
+
+/// Whether `data` starts with the signature of a WOFF file.
+pub fn is_woff(data: &[u8]) -> bool {
+    data.get(..4).map(uint32::from_data) == Some(WOFF_SIGNATURE)
+}
+
+/// Parses a WOFF file into the font it wraps. Only the WOFF container is checked, so like a
+/// font given to `parse_ttf`, tables that are too short for what they claim to hold may still
+/// panic while they're parsed.
+pub fn parse_woff(data: &[u8]) -> Result<TableDirectory, WoffError> {
+    let sfnt = decode_woff(data)?;
+
+    Ok(parse_table_directory(&sfnt, None))
+}
+
+/// Rebuilds the SFNT font data that a WOFF file was made from, inflating each of its tables.
+///
+/// https://www.w3.org/TR/WOFF/#conform-uncompressed-font-data
+pub fn decode_woff(data: &[u8]) -> Result<Vec<u8>, WoffError> {
+    let header = data
+        .get(..WOFF_HEADER_LENGTH)
+        .ok_or(WoffError::UnexpectedEnd)?;
+
+    match uint32::from_data(header) {
+        WOFF_SIGNATURE => {}
+        WOFF2_SIGNATURE => return Err(WoffError::UnsupportedWoff2),
+        _ => return Err(WoffError::InvalidSignature),
+    }
+
+    let flavor = uint32::from_data(&header[4..]);
+    let num_tables = uint16::from_data(&header[12..]);
+
+    if num_tables > MAX_SFNT_TABLES {
+        return Err(WoffError::TooManyTables);
+    }
+
+    let mut tables = Vec::with_capacity(num_tables as usize);
+
+    for i in 0..num_tables as usize {
+        let entry_offset = WOFF_HEADER_LENGTH + i * WOFF_TABLE_ENTRY_LENGTH;
+        let entry = data
+            .get(entry_offset..entry_offset + WOFF_TABLE_ENTRY_LENGTH)
+            .ok_or(WoffError::UnexpectedEnd)?;
+
+        let tag: Tag = entry[..4].try_into().unwrap();
+        let offset = uint32::from_data(&entry[4..]) as usize;
+        let comp_length = uint32::from_data(&entry[8..]) as usize;
+        let orig_length = uint32::from_data(&entry[12..]) as usize;
+        let orig_checksum = uint32::from_data(&entry[16..]);
+
+        let stored = data
+            .get(offset..offset + comp_length)
+            .ok_or(WoffError::UnexpectedEnd)?;
+
+        // Tables that don't get any smaller are stored as they are
+        let table = if comp_length == orig_length {
+            stored.to_vec()
+        } else if comp_length < orig_length {
+            // Inflating stops once the table is any longer than it should be
+            decode_zlib(stored, orig_length)
+                .map_err(|error| WoffError::Decompression(tag, error))?
+        } else {
+            return Err(WoffError::InvalidTableLength(tag));
+        };
+
+        if table.len() != orig_length {
+            return Err(WoffError::LengthMismatch(tag));
+        }
+
+        if checksum(&tag, &table) != orig_checksum {
+            return Err(WoffError::ChecksumMismatch(tag));
+        }
+
+        tables.push((tag, orig_checksum, table));
+    }
+
+    Ok(build_sfnt(flavor, &tables))
+}
+
+/// A table's checksum, which for 'head' is taken with its checksum adjustment set to zero.
+///
+/// https://learn.microsoft.com/en-us/typography/opentype/spec/head
+fn checksum(tag: &Tag, table: &[u8]) -> uint32 {
+    if tag == b"head" && table.len() >= 12 {
+        let mut head = table.to_vec();
+        head[8..12].fill(0);
+
+        return table_checksum(&head);
+    }
+
+    table_checksum(table)
+}
+
+/// Lays tables out one after another behind an SFNT header and table directory, each starting
+/// on a four byte boundary. There may be no more than `MAX_SFNT_TABLES` tables.
+///
+/// https://learn.microsoft.com/en-us/typography/opentype/spec/otff#table-directory
+fn build_sfnt(flavor: uint32, tables: &[(Tag, uint32, Vec<u8>)]) -> Vec<u8> {
+    let num_tables = tables.len() as uint32;
+
+    let entry_selector = if num_tables == 0 {
+        0
+    } else {
+        num_tables.ilog2()
+    };
+    let search_range = (1 << entry_selector) * SFNT_TABLE_RECORD_LENGTH as uint32;
+    let range_shift =
+        (num_tables * SFNT_TABLE_RECORD_LENGTH as uint32).saturating_sub(search_range);
+
+    let mut sfnt = Vec::new();
+    sfnt.extend(flavor.to_be_bytes());
+    sfnt.extend((num_tables as uint16).to_be_bytes());
+    sfnt.extend((search_range as uint16).to_be_bytes());
+    sfnt.extend((entry_selector as uint16).to_be_bytes());
+    sfnt.extend((range_shift as uint16).to_be_bytes());
+
+    let mut offset = SFNT_HEADER_LENGTH + tables.len() * SFNT_TABLE_RECORD_LENGTH;
+
+    for (tag, checksum, table) in tables {
+        sfnt.extend(tag);
+        sfnt.extend(checksum.to_be_bytes());
+        sfnt.extend((offset as uint32).to_be_bytes());
+        sfnt.extend((table.len() as uint32).to_be_bytes());
+
+        offset += table.len().next_multiple_of(4);
+    }
+
+    for (_, _, table) in tables {
+        sfnt.extend(table);
+        sfnt.resize(sfnt.len().next_multiple_of(4), 0);
+    }
+
+    sfnt
+}
@@ -1,6 +1,8 @@
+use std::collections::HashSet;
 use std::fmt::Debug;
 use std::ops::Deref;
 use std::rc::Weak;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::{cell::RefCell, rc::Rc};
 
 use crate::css::r#box::handle_declaration;
@@ -11,7 +13,7 @@ use crate::css::cssom::{
 use crate::css::selectors::MatchesElement;
 use crate::infra::Serializable;
 use crate::{
-    html5::{parse::Token, tag_groups::*},
+    html5::{self, HTML_NAMESPACE, parse::Token, tag_groups::*},
     http::{self},
 };
 
@@ -67,6 +69,7 @@ pub enum NodeKind {
     Comment(Comment),
     DocumentType(DocumentType),
     Document(Document),
+    DocumentFragment(DocumentFragment),
 }
 
 impl NodeKind {
@@ -78,6 +81,7 @@ impl NodeKind {
             NodeKind::Comment(c) => Rc::clone(&c._character_data._node),
             NodeKind::DocumentType(dt) => Rc::clone(&dt._node),
             NodeKind::Document(d) => Rc::clone(&d._node),
+            NodeKind::DocumentFragment(f) => Rc::clone(&f._node),
         }
     }
 
@@ -107,6 +111,55 @@ impl NodeKind {
             _ => None,
         }
     }
+
+    /// https://dom.spec.whatwg.org/#concept-node-equals
+    ///
+    /// Attributes are compared regardless of their order.
+    pub fn is_equal_node(&self, other: &NodeKind) -> bool {
+        let equal = match (self, other) {
+            (NodeKind::Element(a), NodeKind::Element(b)) => {
+                let (a, b) = (a.borrow(), b.borrow());
+
+                a.namespace == b.namespace
+                    && a.namespace_prefix == b.namespace_prefix
+                    && a.local_name == b.local_name
+                    && a.attribute_list.len() == b.attribute_list.len()
+                    && a.attribute_list.iter().all(|attr| {
+                        b.attribute_list.iter().any(|other| {
+                            attr.namespace_uri() == other.namespace_uri()
+                                && attr.local_name() == other.local_name()
+                                && attr.value() == other.value()
+                        })
+                    })
+            }
+            (NodeKind::Text(a), NodeKind::Text(b)) => a.borrow().data() == b.borrow().data(),
+            (NodeKind::Comment(a), NodeKind::Comment(b)) => a.data() == b.data(),
+            (NodeKind::DocumentType(a), NodeKind::DocumentType(b)) => {
+                a.name() == b.name()
+                    && a.public_id() == b.public_id()
+                    && a.system_id() == b.system_id()
+            }
+            (NodeKind::Document(_), NodeKind::Document(_)) => true,
+            (NodeKind::DocumentFragment(_), NodeKind::DocumentFragment(_)) => true,
+            (NodeKind::Node(a), NodeKind::Node(b)) => a._node_type == b._node_type,
+            _ => false,
+        };
+
+        if !equal {
+            return false;
+        }
+
+        let children = |kind: &NodeKind| match kind {
+            NodeKind::Node(node) => node.child_nodes().clone(),
+            _ => kind.node().borrow().child_nodes().clone(),
+        };
+        let (a, b) = (children(self), children(other));
+
+        a.length() == b.length()
+            && a.iter()
+                .zip(b.iter())
+                .all(|(a, b)| a.borrow().is_equal_node(&b.borrow()))
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -125,6 +178,19 @@ pub enum NodeType {
     Notation = 12,
 }
 
+/// The bits of the mask returned by [`Node::compare_document_position`].
+///
+/// https://dom.spec.whatwg.org/#dom-node-document_position_disconnected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentPosition {
+    Disconnected = 0x01,
+    Preceding = 0x02,
+    Following = 0x04,
+    Contains = 0x08,
+    ContainedBy = 0x10,
+    ImplementationSpecific = 0x20,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NodeList {
     _nodes: Vec<Rc<RefCell<NodeKind>>>,
@@ -235,6 +301,7 @@ pub trait INode {
 }
 
 impl Node {
+    /// https://dom.spec.whatwg.org/#dom-node-baseuri
     pub fn base_uri(&self) -> String {
         if let Some(document) = &self.node_document
             && let Some(url) = document.upgrade()
@@ -281,7 +348,46 @@ impl Node {
         self._child_nodes.item(n)
     }
 
+    /// Siblings are not stored on the node; they are looked up through the parent's child list so
+    /// that they can never go out of sync with it.
+    fn sibling(&self, offset: isize) -> Option<Rc<RefCell<NodeKind>>> {
+        let parent = self._parent_node.as_ref()?.upgrade()?;
+        let parent = parent.borrow();
+
+        let index = parent
+            ._child_nodes
+            ._nodes
+            .iter()
+            .position(|n| std::ptr::eq(n.borrow().node().as_ptr(), self))?;
+
+        let sibling_index = index.checked_add_signed(offset)?;
+        parent._child_nodes.item(sibling_index).cloned()
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-node-previoussibling
+    pub fn previous_sibling(&self) -> Option<Rc<RefCell<NodeKind>>> {
+        self.sibling(-1)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-node-nextsibling
+    pub fn next_sibling(&self) -> Option<Rc<RefCell<NodeKind>>> {
+        self.sibling(1)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-node-appendchild
+    ///
+    /// If `child` already has a parent it is moved, so that it never appears in two child lists.
+    /// Appending a document fragment moves its children instead, leaving it empty.
     pub fn append_child(parent: &Rc<RefCell<Node>>, child: Rc<RefCell<NodeKind>>) {
+        if let NodeKind::DocumentFragment(fragment) = child.borrow().deref() {
+            for fragment_child in fragment.take_children() {
+                Node::append_child(parent, fragment_child);
+            }
+            return;
+        }
+
+        Node::detach(&child);
+
         {
             let mut child_borrow = child.borrow_mut();
             child_borrow.set_parent(Some(Rc::clone(parent)));
@@ -298,20 +404,236 @@ impl Node {
             .position(|n| n.borrow().deref() == child)
     }
 
-    pub fn remove_child(&mut self, child: &NodeKind) {
-        if let Some(pos) = self
-            ._child_nodes
+    /// Finds a child by identity rather than by structural equality.
+    fn index_of_child(&self, child: &Rc<RefCell<NodeKind>>) -> Option<usize> {
+        let child_node = child.borrow().node();
+
+        self._child_nodes
             ._nodes
             .iter()
-            .position(|n| n.borrow().deref() == child)
-        {
-            {
-                let mut child = self._child_nodes._nodes[pos].borrow_mut();
-                child.set_parent(None);
+            .position(|n| Rc::ptr_eq(&n.borrow().node(), &child_node))
+    }
+
+    /// Detaches `node` from its current parent, if it has one.
+    fn detach(node: &Rc<RefCell<NodeKind>>) {
+        let parent = node
+            .borrow()
+            .node()
+            .borrow()
+            .parent_node()
+            .and_then(|weak| weak.upgrade());
+
+        if let Some(parent) = parent {
+            let mut parent = parent.borrow_mut();
+            if let Some(index) = parent.index_of_child(node) {
+                parent._child_nodes._nodes.remove(index);
+            }
+        }
+
+        node.borrow_mut().set_parent(None);
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-node-adopt
+    ///
+    /// Moves `node` and its descendants into `document`.
+    pub fn adopt(node: &Rc<RefCell<NodeKind>>, document: &Weak<RefCell<Document>>) {
+        let node = node.borrow().node();
+        node.borrow_mut().node_document = Some(Weak::clone(document));
+
+        for child in node.borrow().child_nodes().iter() {
+            Node::adopt(child, document);
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-node-getrootnode
+    ///
+    /// `composed` only matters for shadow roots, which aren't supported yet, so this is always
+    /// the topmost ancestor: the document, for a connected node.
+    pub fn get_root_node(node: &Rc<RefCell<Node>>, _composed: bool) -> Rc<RefCell<Node>> {
+        Rc::clone(&Node::inclusive_ancestors(node)[0])
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-node-contains
+    ///
+    /// Whether `other` is `node` or one of its descendants.
+    pub fn contains(node: &Rc<RefCell<Node>>, other: &Rc<RefCell<Node>>) -> bool {
+        let mut current = Some(Rc::clone(other));
+
+        while let Some(candidate) = current {
+            if Rc::ptr_eq(&candidate, node) {
+                return true;
+            }
+
+            current = candidate
+                .borrow()
+                .parent_node()
+                .and_then(|weak| weak.upgrade());
+        }
+
+        false
+    }
+
+    /// `node` and its ancestors, root first.
+    fn inclusive_ancestors(node: &Rc<RefCell<Node>>) -> Vec<Rc<RefCell<Node>>> {
+        let mut ancestors = vec![Rc::clone(node)];
+        let mut current = Rc::clone(node);
+
+        loop {
+            let parent = current
+                .borrow()
+                .parent_node()
+                .and_then(|weak| weak.upgrade());
+            match parent {
+                Some(parent) => {
+                    ancestors.push(Rc::clone(&parent));
+                    current = parent;
+                }
+                None => break,
+            }
+        }
+
+        ancestors.reverse();
+        ancestors
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-node-comparedocumentposition
+    ///
+    /// Returns a [`DocumentPosition`] bitmask describing where `other` is relative to `node`.
+    pub fn compare_document_position(node: &Rc<RefCell<Node>>, other: &Rc<RefCell<Node>>) -> u16 {
+        if Rc::ptr_eq(node, other) {
+            return 0;
+        }
+
+        let node_path = Node::inclusive_ancestors(node);
+        let other_path = Node::inclusive_ancestors(other);
+
+        if !Rc::ptr_eq(&node_path[0], &other_path[0]) {
+            let direction = if Rc::as_ptr(other) < Rc::as_ptr(node) {
+                DocumentPosition::Preceding
+            } else {
+                DocumentPosition::Following
+            };
+
+            return DocumentPosition::Disconnected as u16
+                | DocumentPosition::ImplementationSpecific as u16
+                | direction as u16;
+        }
+
+        let common = node_path
+            .iter()
+            .zip(other_path.iter())
+            .take_while(|(a, b)| Rc::ptr_eq(a, b))
+            .count();
+
+        if common == other_path.len() {
+            return DocumentPosition::Contains as u16 | DocumentPosition::Preceding as u16;
+        }
+
+        if common == node_path.len() {
+            return DocumentPosition::ContainedBy as u16 | DocumentPosition::Following as u16;
+        }
+
+        let parent = node_path[common - 1].borrow();
+        let index_of = |target: &Rc<RefCell<Node>>| {
+            parent
+                .child_nodes()
+                .iter()
+                .position(|child| Rc::ptr_eq(&child.borrow().node(), target))
+        };
+
+        if index_of(&other_path[common]) < index_of(&node_path[common]) {
+            DocumentPosition::Preceding as u16
+        } else {
+            DocumentPosition::Following as u16
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-node-insertbefore
+    ///
+    /// Inserts `node` into `parent` before `child`, or at the end if `child` is `None`. Returns
+    /// `None` if `child` is not a child of `parent`.
+    pub fn insert_before(
+        parent: &Rc<RefCell<Node>>,
+        node: Rc<RefCell<NodeKind>>,
+        child: Option<&Rc<RefCell<NodeKind>>>,
+    ) -> Option<Rc<RefCell<NodeKind>>> {
+        if let Some(child) = child {
+            parent.borrow().index_of_child(child)?;
+        }
+
+        // If the reference child is the node being inserted, insert before its next sibling.
+        let next_sibling;
+        let child = match child {
+            Some(child) if Rc::ptr_eq(&child.borrow().node(), &node.borrow().node()) => {
+                next_sibling = node.borrow().node().borrow().next_sibling();
+                next_sibling.as_ref()
+            }
+            _ => child,
+        };
+
+        if let NodeKind::DocumentFragment(fragment) = node.borrow().deref() {
+            for fragment_child in fragment.take_children() {
+                Node::insert_before(parent, fragment_child, child);
             }
+            return Some(Rc::clone(&node));
+        }
+
+        Node::detach(&node);
+
+        let index = match child {
+            Some(child) => parent.borrow().index_of_child(child)?,
+            None => parent.borrow()._child_nodes.length(),
+        };
 
-            self._child_nodes._nodes.remove(pos);
+        node.borrow_mut().set_parent(Some(Rc::clone(parent)));
+        parent
+            .borrow_mut()
+            ._child_nodes
+            ._nodes
+            .insert(index, Rc::clone(&node));
+
+        Some(node)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-node-replacechild
+    ///
+    /// Replaces `child` with `node`, returning the replaced child, or `None` if `child` is not a
+    /// child of `parent`.
+    pub fn replace_child(
+        parent: &Rc<RefCell<Node>>,
+        node: Rc<RefCell<NodeKind>>,
+        child: &Rc<RefCell<NodeKind>>,
+    ) -> Option<Rc<RefCell<NodeKind>>> {
+        parent.borrow().index_of_child(child)?;
+
+        if Rc::ptr_eq(&node.borrow().node(), &child.borrow().node()) {
+            return Some(node);
         }
+
+        Node::detach(&node);
+
+        let index = parent.borrow().index_of_child(child)?;
+
+        node.borrow_mut().set_parent(Some(Rc::clone(parent)));
+        let replaced = std::mem::replace(&mut parent.borrow_mut()._child_nodes._nodes[index], node);
+        replaced.borrow_mut().set_parent(None);
+
+        Some(replaced)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-node-removechild
+    pub fn remove_child(&mut self, child: &NodeKind) -> Option<Rc<RefCell<NodeKind>>> {
+        let child_node = child.node();
+
+        let pos = self
+            ._child_nodes
+            ._nodes
+            .iter()
+            .position(|n| Rc::ptr_eq(&n.borrow().node(), &child_node))?;
+
+        self._child_nodes._nodes[pos].borrow_mut().set_parent(None);
+
+        Some(self._child_nodes._nodes.remove(pos))
     }
 
     pub fn pop_child(&mut self, nth: Option<usize>) -> Option<Rc<RefCell<NodeKind>>> {
@@ -327,7 +649,10 @@ impl Node {
                 None
             }
         } else {
-            self._child_nodes._nodes.pop()
+            let child = self._child_nodes._nodes.pop()?;
+            child.borrow_mut().set_parent(None);
+
+            Some(child)
         }
     }
 
@@ -409,7 +734,7 @@ impl Text {
                 _node: Rc::new(RefCell::new(Node {
                     _node_type: NodeType::Text,
                     _node_name: "#text".to_string(),
-                    _base_uri: document.document_base_url().serialize(),
+                    _base_uri: document.fallback_base_url().serialize(),
                     node_document: Some(Rc::downgrade(&_document)),
                     _parent_node: None,
                     _child_nodes: NodeList::new(),
@@ -481,7 +806,7 @@ impl Comment {
                 _node: Rc::new(RefCell::new(Node {
                     _node_type: NodeType::Comment,
                     _node_name: "#comment".to_string(),
-                    _base_uri: document.document_base_url().serialize(),
+                    _base_uri: document.fallback_base_url().serialize(),
                     node_document: Some(Rc::downgrade(&_document)),
                     _parent_node: None,
                     _child_nodes: NodeList::new(),
@@ -490,6 +815,10 @@ impl Comment {
             },
         }
     }
+
+    pub fn data(&self) -> &str {
+        &self._character_data.data
+    }
 }
 
 impl INode for Comment {
@@ -509,6 +838,69 @@ impl INode for Comment {
     }
 }
 
+/// https://dom.spec.whatwg.org/#interface-documentfragment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentFragment {
+    _node: Rc<RefCell<Node>>,
+}
+
+impl DocumentFragment {
+    pub fn new(_document: Rc<RefCell<Document>>) -> Self {
+        let document = _document.borrow();
+
+        Self {
+            _node: Rc::new(RefCell::new(Node {
+                _node_type: NodeType::DocumentFragment,
+                _node_name: "#document-fragment".to_string(),
+                _base_uri: document.fallback_base_url().serialize(),
+                node_document: Some(Rc::downgrade(&_document)),
+                _parent_node: None,
+                _child_nodes: NodeList::new(),
+            })),
+        }
+    }
+
+    pub fn node(&self) -> &Rc<RefCell<Node>> {
+        &self._node
+    }
+
+    /// Removes and returns every child, in order.
+    fn take_children(&self) -> Vec<Rc<RefCell<NodeKind>>> {
+        let mut node = self._node.borrow_mut();
+        let children = std::mem::take(&mut node._child_nodes._nodes);
+
+        for child in &children {
+            child.borrow_mut().set_parent(None);
+        }
+
+        children
+    }
+}
+
+impl Serializable for DocumentFragment {
+    /// https://html.spec.whatwg.org/multipage/parsing.html#serialising-html-fragments
+    fn serialize(&self) -> String {
+        serialize_children(&self._node.borrow(), None)
+    }
+}
+
+impl INode for DocumentFragment {
+    fn new() -> Self
+    where
+        Self: Sized,
+    {
+        Self::new(Rc::new(RefCell::new(Document::default())))
+    }
+
+    fn node_type(&self) -> u16 {
+        NodeType::DocumentFragment as u16
+    }
+
+    fn node_name(&self) -> DOMString {
+        DOMString::from("#document-fragment")
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DocumentType {
     _node: Rc<RefCell<Node>>,
@@ -556,7 +948,7 @@ impl DocumentType {
                 .as_ref()
                 .unwrap()
                 .borrow()
-                .document_base_url()
+                .fallback_base_url()
                 .serialize();
 
             Self {
@@ -706,7 +1098,7 @@ impl Attr {
             _node: Box::new(Node {
                 _node_type: NodeType::Attribute,
                 _node_name: local_name.clone(),
-                _base_uri: document.document_base_url().serialize(),
+                _base_uri: document.fallback_base_url().serialize(),
                 node_document: Some(Rc::downgrade(&_document)),
                 _parent_node: None,
                 _child_nodes: NodeList::new(),
@@ -752,11 +1144,54 @@ impl Attr {
 
 pub type ElementID = String;
 
+static NEXT_ELEMENT_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// Hands out a process-unique ID for every element that gets created.
+fn next_element_id() -> ElementID {
+    NEXT_ELEMENT_ID.fetch_add(1, Ordering::Relaxed).to_string()
+}
+
 #[derive(Clone, PartialEq, Eq, Default)]
 pub struct ElementState {
     pub is_hovered: bool,
+
+    /// Whether this is a link to a URL in the navigation history, which `:visited` matches
+    pub is_visited: bool,
+}
+
+/// Everything the cascade of an element depends on. If none of it changed since the last
+/// cascade, the element's computed style is still up to date.
+#[derive(Clone, PartialEq, Eq)]
+struct StyleCacheKey {
+    stylesheet_version: u64,
+
+    /// The ID and style generation of the parent element the style was inherited from
+    parent: Option<(ElementID, u64)>,
+
+    attributes: Vec<Attr>,
+    element_state: ElementState,
+
+    /// The IDs of the element children, so that adding or removing a child restyles all
+    /// of them for selectors like `:nth-of-type()`
+    children: Vec<ElementID>,
+}
+
+/// The key of the last cascade of an element, and how many times it has run.
+#[derive(Clone, Default)]
+struct StyleCache {
+    key: Option<StyleCacheKey>,
+    generation: u64,
+}
+
+/// Cached styles are derived from the element, so they never make two elements unequal.
+impl PartialEq for StyleCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
 }
 
+impl Eq for StyleCache {}
+
 #[derive(Clone, PartialEq, Eq)]
 pub struct Element {
     pub _node: Rc<RefCell<Node>>,
@@ -779,6 +1214,14 @@ pub struct Element {
     pub _style: ComputedStyle,
 
     pub _element_state: ElementState,
+
+    _style_cache: StyleCache,
+
+    /// The contents of a `template` element, which are kept out of its children so that they
+    /// aren't rendered or matched by selectors. `None` for every other element.
+    ///
+    /// https://html.spec.whatwg.org/multipage/scripting.html#template-contents
+    template_contents: Option<DocumentFragment>,
 }
 
 impl Debug for Element {
@@ -839,7 +1282,7 @@ impl Element {
         } else if definition.is_some() {
             todo!("handle the case where the definition exists");
         } else {
-            return Element::create_element_internal::<Element>(
+            let mut element = Element::create_element_internal::<Element>(
                 Rc::clone(&_document),
                 namespace,
                 prefix,
@@ -848,6 +1291,14 @@ impl Element {
                 &is,
                 registry.as_ref(),
             );
+
+            if element.local_name == "template"
+                && element.namespace.as_deref() == Some(HTML_NAMESPACE)
+            {
+                element.template_contents = Some(DocumentFragment::new(Rc::clone(&_document)));
+            }
+
+            return element;
         }
     }
 
@@ -855,6 +1306,20 @@ impl Element {
         self._token.as_ref()
     }
 
+    /// https://html.spec.whatwg.org/multipage/scripting.html#dom-template-content
+    pub fn template_contents(&self) -> Option<&DocumentFragment> {
+        self.template_contents.as_ref()
+    }
+
+    /// The node that children parsed or set with `innerHTML` go in: the template contents of a
+    /// `template`, and the element itself otherwise.
+    fn contents_node(&self) -> Rc<RefCell<Node>> {
+        match &self.template_contents {
+            Some(contents) => Rc::clone(contents.node()),
+            None => Rc::clone(&self._node),
+        }
+    }
+
     pub fn with_token(mut self, token: Token) -> Self {
         self._token = Some(token);
         self
@@ -951,14 +1416,62 @@ impl Element {
         self.compute_element_styles(Some(&parents.to_vec()));
     }
 
+    /// How many times the cascade has run for this element. Recomputing the styles of an
+    /// element whose stylesheets, attributes, state, parent and children are unchanged
+    /// reuses the previous result and leaves this as is.
+    pub fn style_generation(&self) -> u64 {
+        self._style_cache.generation
+    }
+
+    fn style_cache_key(
+        &self,
+        document: &Document,
+        parents: Option<&Vec<Rc<RefCell<Element>>>>,
+    ) -> StyleCacheKey {
+        let children = self
+            ._node
+            .borrow()
+            .child_nodes()
+            .iter()
+            .filter_map(|child| match child.borrow().deref() {
+                NodeKind::Element(element) => Some(element.borrow().id.clone()),
+                _ => None,
+            })
+            .collect();
+
+        StyleCacheKey {
+            stylesheet_version: document.stylesheet_version(),
+            parent: parents.and_then(|p| p.last()).map(|parent| {
+                let parent = parent.borrow();
+                (parent.id.clone(), parent.style_generation())
+            }),
+            attributes: self.attribute_list.clone(),
+            element_state: self._element_state.clone(),
+            children,
+        }
+    }
+
     pub fn compute_element_styles(&mut self, parents: Option<&Vec<Rc<RefCell<Element>>>>) {
-        // inherit
-        *self.style_mut() = parents
-            .and_then(|p| p.last())
-            .map_or(ComputedStyle::default(), |parent| {
-                parent.borrow().style().inherit()
-            });
+        // The base URL links resolve against has to be found before the descendants being styled
+        // are borrowed as well
+        let base_url = self
+            ._node
+            .borrow()
+            .node_document
+            .as_ref()
+            .and_then(|document| document.upgrade())
+            .map(|document| document.borrow()._document_base_url(Some(self)));
 
+        if let Some(base_url) = base_url {
+            self._compute_element_styles(parents, &base_url);
+        }
+    }
+
+    fn _compute_element_styles(
+        &mut self,
+        parents: Option<&Vec<Rc<RefCell<Element>>>>,
+        base_url: &http::url::URL,
+    ) {
         let node_doc = &self
             ._node
             .borrow()
@@ -968,6 +1481,43 @@ impl Element {
             .upgrade()
             .unwrap();
         let document = node_doc.borrow();
+
+        self._element_state.is_visited = self._is_visited(&document, base_url);
+
+        let key = self.style_cache_key(&document, parents);
+        if self._style_cache.key.as_ref() != Some(&key) {
+            self.cascade(&document, parents);
+
+            self._style_cache.key = Some(key);
+            self._style_cache.generation += 1;
+        }
+
+        drop(document);
+
+        let mut new_parents = match parents {
+            Some(p) => p.clone(),
+            None => vec![],
+        };
+
+        new_parents.push(Rc::new(RefCell::new(self.clone())));
+
+        for child_rc in self._node.borrow().child_nodes().iter() {
+            let child = child_rc.borrow();
+            if let NodeKind::Element(child_element_rc) = child.deref() {
+                let mut child_element = child_element_rc.borrow_mut();
+                child_element._compute_element_styles(Some(&new_parents), base_url);
+            }
+        }
+    }
+
+    fn cascade(&mut self, document: &Document, parents: Option<&Vec<Rc<RefCell<Element>>>>) {
+        // inherit
+        *self.style_mut() = parents
+            .and_then(|p| p.last())
+            .map_or(ComputedStyle::default(), |parent| {
+                parent.borrow().style().inherit()
+            });
+
         let style_sheets = document.style_sheets();
 
         for stylesheet in style_sheets.style_sheets.iter() {
@@ -982,6 +1532,8 @@ impl Element {
 
                         for selector in style_rule.selectors() {
                             if selector.matches(self, parents) {
+                                // Declarations are applied in source order, so whichever of a
+                                // shorthand and its longhands comes last wins
                                 for declaration in style_rule.declarations() {
                                     handle_declaration(declaration, self.style_mut(), parents);
                                 }
@@ -994,21 +1546,6 @@ impl Element {
                 }
             }
         }
-
-        let mut new_parents = match parents {
-            Some(p) => p.clone(),
-            None => vec![],
-        };
-
-        new_parents.push(Rc::new(RefCell::new(self.clone())));
-
-        for child_rc in self._node.borrow().child_nodes().iter() {
-            let child = child_rc.borrow();
-            if let NodeKind::Element(child_element_rc) = child.deref() {
-                let mut child_element = child_element_rc.borrow_mut();
-                child_element.compute_element_styles(Some(&new_parents));
-            }
-        }
     }
 
     fn create_element_internal<T: IElement>(
@@ -1026,7 +1563,8 @@ impl Element {
             .with_local_name(local_name)
             .with_custom_element_state(&state)
             .with_is_value(is)
-            .with_custom_element_registry(&_registry.cloned());
+            .with_custom_element_registry(&_registry.cloned())
+            .with_id(&next_element_id());
 
         element.node().borrow_mut().node_document = Some(Rc::downgrade(&_document));
 
@@ -1081,6 +1619,30 @@ impl Element {
         self.attribute_list.push(attr);
     }
 
+    /// Whether this is an `a` or `area` element with an `href`, which `:link` and `:visited`
+    /// match.
+    ///
+    /// https://html.spec.whatwg.org/multipage/semantics-other.html#selector-link
+    pub fn is_link(&self) -> bool {
+        matches!(self.local_name.as_str(), "a" | "area") && self.get_attribute("href").is_some()
+    }
+
+    /// Whether this is a link whose URL, resolved against `base_url`, is in the navigation
+    /// history.
+    ///
+    /// https://html.spec.whatwg.org/multipage/semantics-other.html#selector-visited
+    fn _is_visited(&self, document: &Document, base_url: &http::url::URL) -> bool {
+        self.is_link()
+            && self.get_attribute("href").is_some_and(|href| {
+                http::url::URL::parse(
+                    href.to_string(),
+                    Some(base_url.clone()),
+                    Some(document.charset()),
+                )
+                .is_ok_and(|url| document.is_visited(&url))
+            })
+    }
+
     pub fn get_attribute(&self, name: &str) -> Option<&str> {
         for attr in &self.attribute_list {
             if attr.local_name() == name {
@@ -1090,9 +1652,514 @@ impl Element {
         None
     }
 
+    /// The link types in the `rel` attribute, lowercased and without duplicates.
+    ///
+    /// https://html.spec.whatwg.org/multipage/links.html#linkTypes
+    pub fn rel_tokens(&self) -> Vec<String> {
+        let mut tokens: Vec<String> = vec![];
+
+        for token in self
+            .get_attribute("rel")
+            .unwrap_or_default()
+            .split_ascii_whitespace()
+        {
+            let token = token.to_ascii_lowercase();
+            if !tokens.contains(&token) {
+                tokens.push(token);
+            }
+        }
+
+        tokens
+    }
+
+    /// The URL an `a` or `area` element links to, resolved against its document's base URL
+    /// (which honors `<base href>`). `None` if it has no `href` or the `href` fails to parse.
+    ///
+    /// https://html.spec.whatwg.org/multipage/links.html#reinitialise-url
+    pub fn hyperlink_url(&self) -> Option<http::url::URL> {
+        if !matches!(self.local_name.as_str(), "a" | "area") {
+            return None;
+        }
+
+        let href = self.get_attribute("href")?;
+        let document = self._node.borrow().node_document.as_ref()?.upgrade()?;
+        let document = document.borrow();
+
+        http::url::URL::parse(
+            href.to_string(),
+            Some(document.document_base_url()),
+            Some(document.charset()),
+        )
+        .ok()
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-element-setattribute
+    pub fn set_attribute(element: &Rc<RefCell<Element>>, name: &str, value: &str) {
+        let existing = element
+            .borrow_mut()
+            .attribute_list
+            .iter_mut()
+            .find(|attr| attr.local_name() == name)
+            .map(|attr| attr.set_value(value))
+            .is_some();
+
+        if !existing {
+            Element::push_attr_raw_rc(element, name, value);
+        }
+    }
+
     pub fn namespace_uri(&self) -> Option<&str> {
         self.namespace.as_deref()
     }
+
+    /// https://dom.spec.whatwg.org/#dom-element-removeattribute
+    pub fn remove_attribute(&mut self, name: &str) {
+        self.attribute_list.retain(|attr| attr.local_name() != name);
+    }
+
+    /// https://html.spec.whatwg.org/multipage/dom.html#dom-dataset
+    pub fn dataset(element: &Rc<RefCell<Element>>) -> DOMStringMap {
+        DOMStringMap {
+            element: Rc::clone(element),
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-element-classlist
+    pub fn class_list(element: &Rc<RefCell<Element>>) -> ClassList {
+        ClassList {
+            element: Rc::clone(element),
+        }
+    }
+
+    /// The elements enclosing `element`, outermost first, found by walking down from the root of
+    /// its tree.
+    pub fn ancestors(element: &Rc<RefCell<Element>>) -> Vec<Rc<RefCell<Element>>> {
+        fn find_path(
+            parent: &Node,
+            target: &Rc<RefCell<Node>>,
+            path: &mut Vec<Rc<RefCell<Element>>>,
+        ) -> bool {
+            for child in parent.child_nodes().iter() {
+                if let NodeKind::Element(child_element) = child.borrow().deref() {
+                    let child_node = Rc::clone(&child_element.borrow()._node);
+
+                    if Rc::ptr_eq(&child_node, target) {
+                        return true;
+                    }
+
+                    path.push(Rc::clone(child_element));
+                    if find_path(&child_node.borrow(), target, path) {
+                        return true;
+                    }
+                    path.pop();
+                }
+            }
+
+            false
+        }
+
+        let target = Rc::clone(&element.borrow()._node);
+
+        let mut root = Rc::clone(&target);
+        loop {
+            let parent = root.borrow().parent_node().and_then(|p| p.upgrade());
+            match parent {
+                Some(parent) => root = parent,
+                None => break,
+            }
+        }
+
+        let mut path = vec![];
+        if !Rc::ptr_eq(&root, &target) {
+            find_path(&root.borrow(), &target, &mut path);
+        }
+
+        path
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-node-childnodes
+    ///
+    /// Every child node, including text and comments.
+    pub fn child_nodes(&self) -> NodeList {
+        self._node.borrow().child_nodes().clone()
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-parentnode-children
+    ///
+    /// Only the children that are elements.
+    pub fn children(&self) -> Vec<Rc<RefCell<Element>>> {
+        self._node
+            .borrow()
+            .child_nodes()
+            .iter()
+            .filter_map(|child| match child.borrow().deref() {
+                NodeKind::Element(element) => Some(Rc::clone(element)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-parentnode-firstelementchild
+    pub fn first_element_child(&self) -> Option<Rc<RefCell<Element>>> {
+        self.children().first().cloned()
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-parentnode-lastelementchild
+    pub fn last_element_child(&self) -> Option<Rc<RefCell<Element>>> {
+        self.children().last().cloned()
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-nondocumenttypechildnode-previouselementsibling
+    pub fn previous_element_sibling(&self) -> Option<Rc<RefCell<Element>>> {
+        let mut current = self._node.borrow().previous_sibling();
+
+        while let Some(node) = current {
+            if let NodeKind::Element(element) = node.borrow().deref() {
+                return Some(Rc::clone(element));
+            }
+
+            current = node.borrow().node().borrow().previous_sibling();
+        }
+
+        None
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-nondocumenttypechildnode-nextelementsibling
+    pub fn next_element_sibling(&self) -> Option<Rc<RefCell<Element>>> {
+        let mut current = self._node.borrow().next_sibling();
+
+        while let Some(node) = current {
+            if let NodeKind::Element(element) = node.borrow().deref() {
+                return Some(Rc::clone(element));
+            }
+
+            current = node.borrow().node().borrow().next_sibling();
+        }
+
+        None
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-child-text-content
+    pub fn child_text_content(&self) -> String {
+        self._node
+            .borrow()
+            .child_nodes()
+            .iter()
+            .filter_map(|child| match child.borrow().deref() {
+                NodeKind::Text(text) => Some(text.borrow().data().to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-innerhtml
+    pub fn inner_html(&self) -> String {
+        serialize_children(&self.contents_node().borrow(), Some(&self.local_name))
+    }
+
+    /// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-innerhtml
+    ///
+    /// Replaces the children of `element` with `html` parsed as a fragment in its context, then
+    /// recomputes the styles of the new subtree.
+    pub fn set_inner_html(element: &Rc<RefCell<Element>>, html: &str) {
+        let fragment = html5::parse_fragment(html, element);
+        let node = element.borrow().contents_node();
+        let document = node.borrow().node_document.clone();
+
+        while node.borrow_mut().pop_child(None).is_some() {}
+
+        let fragment = Rc::new(RefCell::new(NodeKind::DocumentFragment(fragment)));
+        if let Some(document) = &document {
+            Node::adopt(&fragment, document);
+        }
+        Node::append_child(&node, fragment);
+
+        let ancestors = Element::ancestors(element);
+        element
+            .borrow_mut()
+            .compute_element_styles(Some(&ancestors));
+    }
+
+    /// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-outerhtml
+    pub fn outer_html(&self) -> String {
+        let mut s = String::new();
+        self.serialize_into(&mut s);
+        s
+    }
+
+    fn serialize_into(&self, s: &mut String) {
+        let tag_name = if self.namespace.as_deref() == Some(HTML_NAMESPACE) {
+            self.local_name.clone()
+        } else {
+            self.qualified_name()
+        };
+
+        s.push('<');
+        s.push_str(&tag_name);
+
+        for attr in &self.attribute_list {
+            s.push(' ');
+            s.push_str(attr.local_name());
+            s.push_str("=\"");
+            s.push_str(&escape_string(attr.value(), true));
+            s.push('"');
+        }
+
+        s.push('>');
+
+        if VOID_ELEMENT_NAMES.contains(&self.local_name.as_str()) {
+            return;
+        }
+
+        s.push_str(&self.inner_html());
+
+        s.push_str("</");
+        s.push_str(&tag_name);
+        s.push('>');
+    }
+}
+
+/// A view over an element's `class` attribute.
+///
+/// https://dom.spec.whatwg.org/#interface-domtokenlist
+pub struct ClassList {
+    element: Rc<RefCell<Element>>,
+}
+
+impl ClassList {
+    /// https://dom.spec.whatwg.org/#concept-ordered-set-parser
+    fn tokens(&self) -> Vec<String> {
+        let mut tokens: Vec<String> = vec![];
+
+        if let Some(value) = self.element.borrow().get_attribute("class") {
+            for token in value.split_ascii_whitespace() {
+                if !tokens.iter().any(|t| t == token) {
+                    tokens.push(token.to_string());
+                }
+            }
+        }
+
+        tokens
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-dtl-update
+    fn update(&self, tokens: Vec<String>) {
+        if self.element.borrow().get_attribute("class").is_none() && tokens.is_empty() {
+            return;
+        }
+
+        Element::set_attribute(&self.element, "class", &tokens.join(" "));
+
+        let ancestors = Element::ancestors(&self.element);
+        self.element
+            .borrow_mut()
+            .compute_element_styles(Some(&ancestors));
+    }
+
+    pub fn length(&self) -> usize {
+        self.tokens().len()
+    }
+
+    pub fn item(&self, index: usize) -> Option<String> {
+        self.tokens().get(index).cloned()
+    }
+
+    pub fn value(&self) -> String {
+        self.tokens().join(" ")
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-domtokenlist-contains
+    pub fn contains(&self, token: &str) -> bool {
+        self.tokens().iter().any(|t| t == token)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-domtokenlist-add
+    pub fn add(&self, token: &str) {
+        let mut tokens = self.tokens();
+
+        if !tokens.iter().any(|t| t == token) {
+            tokens.push(token.to_string());
+        }
+
+        self.update(tokens);
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-domtokenlist-remove
+    pub fn remove(&self, token: &str) {
+        let mut tokens = self.tokens();
+        tokens.retain(|t| t != token);
+
+        self.update(tokens);
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-domtokenlist-toggle
+    ///
+    /// Returns whether the token is present afterwards.
+    pub fn toggle(&self, token: &str, force: Option<bool>) -> bool {
+        let mut tokens = self.tokens();
+        let present = tokens.iter().any(|t| t == token);
+
+        if present {
+            if force != Some(true) {
+                tokens.retain(|t| t != token);
+                self.update(tokens);
+                return false;
+            }
+
+            return true;
+        }
+
+        if force != Some(false) {
+            tokens.push(token.to_string());
+            self.update(tokens);
+            return true;
+        }
+
+        false
+    }
+}
+
+/// A view over an element's `data-*` attributes, keyed by camelCased name, so that `data-user-id`
+/// is `userId`.
+///
+/// https://html.spec.whatwg.org/multipage/dom.html#domstringmap
+pub struct DOMStringMap {
+    element: Rc<RefCell<Element>>,
+}
+
+impl DOMStringMap {
+    /// The attribute name for a dataset key, or `None` if the key has a `-` followed by a
+    /// lowercase letter.
+    fn attribute_name(name: &str) -> Option<String> {
+        let mut chars = name.chars().peekable();
+        let mut attribute = String::from("data-");
+
+        while let Some(c) = chars.next() {
+            if c == '-' && chars.peek().is_some_and(|next| next.is_ascii_lowercase()) {
+                return None;
+            }
+
+            if c.is_ascii_uppercase() {
+                attribute.push('-');
+                attribute.push(c.to_ascii_lowercase());
+            } else {
+                attribute.push(c);
+            }
+        }
+
+        Some(attribute)
+    }
+
+    /// The dataset key for an attribute name, or `None` if it isn't a `data-*` attribute.
+    fn key(attribute: &str) -> Option<String> {
+        let name = attribute.strip_prefix("data-")?;
+        if name.chars().any(|c| c.is_ascii_uppercase()) {
+            return None;
+        }
+
+        let mut chars = name.chars().peekable();
+        let mut key = String::new();
+
+        while let Some(c) = chars.next() {
+            match chars.peek() {
+                Some(next) if c == '-' && next.is_ascii_lowercase() => {
+                    key.push(next.to_ascii_uppercase());
+                    chars.next();
+                }
+                _ => key.push(c),
+            }
+        }
+
+        Some(key)
+    }
+
+    /// https://html.spec.whatwg.org/multipage/dom.html#concept-domstringmap-pairs
+    pub fn names(&self) -> Vec<String> {
+        self.element
+            .borrow()
+            .attributes()
+            .iter()
+            .filter_map(|attr| DOMStringMap::key(attr.local_name()))
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        let attribute = DOMStringMap::attribute_name(name)?;
+        self.element
+            .borrow()
+            .get_attribute(&attribute)
+            .map(str::to_string)
+    }
+
+    /// https://html.spec.whatwg.org/multipage/dom.html#dom-domstringmap-setitem
+    pub fn set(&self, name: &str, value: &str) -> Result<(), String> {
+        let attribute = DOMStringMap::attribute_name(name)
+            .ok_or_else(|| format!("Invalid dataset key: {name}"))?;
+
+        Element::set_attribute(&self.element, &attribute, value);
+        Ok(())
+    }
+
+    /// https://html.spec.whatwg.org/multipage/dom.html#dom-domstringmap-removeitem
+    pub fn remove(&self, name: &str) {
+        if let Some(attribute) = DOMStringMap::attribute_name(name) {
+            self.element.borrow_mut().remove_attribute(&attribute);
+        }
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#escapingString
+fn escape_string(input: &str, attribute_mode: bool) -> String {
+    let mut s = String::with_capacity(input.len());
+
+    for ch in input.chars() {
+        match ch {
+            '&' => s.push_str("&amp;"),
+            '\u{00A0}' => s.push_str("&nbsp;"),
+            '"' if attribute_mode => s.push_str("&quot;"),
+            '<' => s.push_str("&lt;"),
+            '>' => s.push_str("&gt;"),
+            _ => s.push(ch),
+        }
+    }
+
+    s
+}
+
+/// Serializes the children of `node`, where `parent_name` is the local name of `node` if it is an
+/// element.
+///
+/// https://html.spec.whatwg.org/multipage/parsing.html#serialising-html-fragments
+fn serialize_children(node: &Node, parent_name: Option<&str>) -> String {
+    let mut s = String::new();
+
+    for child in node.child_nodes().iter() {
+        match child.borrow().deref() {
+            NodeKind::Element(element) => element.borrow().serialize_into(&mut s),
+            NodeKind::Text(text) => {
+                if parent_name.is_some_and(|name| RAW_TEXT_SERIALIZATION_NAMES.contains(&name)) {
+                    s.push_str(text.borrow().data());
+                } else {
+                    s.push_str(&escape_string(text.borrow().data(), false));
+                }
+            }
+            NodeKind::Comment(comment) => {
+                s.push_str("<!--");
+                s.push_str(comment.data());
+                s.push_str("-->");
+            }
+            NodeKind::DocumentType(doctype) => {
+                s.push_str("<!DOCTYPE ");
+                s.push_str(doctype.name());
+                s.push('>');
+            }
+            NodeKind::Document(document) => s.push_str(&document.serialize()),
+            NodeKind::DocumentFragment(fragment) => s.push_str(&fragment.serialize()),
+            NodeKind::Node(node) => s.push_str(&serialize_children(node, None)),
+        }
+    }
+
+    s
 }
 
 impl INode for Element {
@@ -1121,6 +2188,8 @@ impl INode for Element {
             _style: ComputedStyle::default(),
 
             _element_state: ElementState::default(),
+            _style_cache: StyleCache::default(),
+            template_contents: None,
         }
     }
 
@@ -1196,6 +2265,8 @@ impl IElement for Element {
 
             _style: ComputedStyle::default(),
             _element_state: ElementState::default(),
+            _style_cache: StyleCache::default(),
+            template_contents: None,
         }
     }
 
@@ -1302,6 +2373,10 @@ pub struct Document {
     parser_cannot_change_mode: bool,
 
     document_or_shadow_root_style: DocumentOrShadowRootStyle,
+    stylesheet_version: u64,
+
+    /// Serialized URLs from the navigation history, which links match `:visited` against
+    visited_urls: HashSet<String>,
 }
 
 impl Debug for Document {
@@ -1316,6 +2391,13 @@ impl Debug for Document {
     }
 }
 
+impl Serializable for Document {
+    /// https://html.spec.whatwg.org/multipage/parsing.html#serialising-html-fragments
+    fn serialize(&self) -> String {
+        serialize_children(&self._node.borrow(), None)
+    }
+}
+
 impl Default for Document {
     /// Creates a new Document with default values.
     /// According to the HTML5 specification:
@@ -1352,6 +2434,8 @@ impl Default for Document {
                     style_sheets: vec![],
                 },
             },
+            stylesheet_version: 0,
+            visited_urls: HashSet::new(),
         };
 
         document._node.borrow_mut().node_document =
@@ -1420,6 +2504,10 @@ impl Document {
         &self._url
     }
 
+    pub fn set_url(&mut self, url: http::url::URL) {
+        self._url = url;
+    }
+
     pub fn compat_mode(&self) -> &str {
         if self.is_quirks_mode() {
             "BackCompat"
@@ -1444,16 +2532,79 @@ impl Document {
         self._content_type
     }
 
-    /// TODO: Implement according to spec:
-    /// The document base URL of a Document document is the URL record obtained by running these steps:
-    /// 1. If document has no descendant base element that has an href attribute, then return document's fallback base URL.
-    /// 2. Otherwise, return the frozen base URL of the first base element in document that has an href attribute, in tree order.
+    /// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#fallback-base-url
     ///
-    /// NOTE: For simplicity, this implementation always returns the document's URL.
-    pub fn document_base_url(&self) -> &http::url::URL {
+    /// NOTE: iframe srcdoc documents don't exist yet, so this is always the document's URL.
+    pub fn fallback_base_url(&self) -> &http::url::URL {
         &self._url
     }
 
+    /// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#document-base-url
+    ///
+    /// The frozen base URL of the first `base` element with an `href` attribute, in tree order,
+    /// or the fallback base URL if there is none.
+    pub fn document_base_url(&self) -> http::url::URL {
+        self._document_base_url(None)
+    }
+
+    /// The document base URL, found without borrowing `styled` again if it's one of the elements
+    /// in the way.
+    fn _document_base_url(&self, styled: Option<&Element>) -> http::url::URL {
+        fn first_base_href(node: &Node, styled: Option<&Element>) -> Option<String> {
+            for child in node.child_nodes().iter() {
+                if let NodeKind::Element(element) = child.borrow().deref() {
+                    let borrowed;
+                    let element = match styled {
+                        Some(styled) if std::ptr::eq(element.as_ptr(), styled) => styled,
+                        _ => {
+                            borrowed = element.borrow();
+                            &borrowed
+                        }
+                    };
+
+                    if element.local_name == "base"
+                        && let Some(href) = element.get_attribute("href")
+                    {
+                        return Some(href.to_string());
+                    }
+
+                    if let Some(href) = first_base_href(&element._node.borrow(), styled) {
+                        return Some(href);
+                    }
+                }
+            }
+
+            None
+        }
+
+        let fallback = self.fallback_base_url();
+
+        // https://html.spec.whatwg.org/multipage/semantics.html#frozen-base-url
+        first_base_href(&self._node.borrow(), styled)
+            .and_then(|href| {
+                http::url::URL::parse(href, Some(fallback.clone()), Some(self._encoding)).ok()
+            })
+            .unwrap_or_else(|| fallback.clone())
+    }
+
+    /// The text of the first `title` element, with ASCII whitespace stripped and collapsed, or an
+    /// empty string if there is none.
+    ///
+    /// https://html.spec.whatwg.org/multipage/dom.html#document.title
+    pub fn document_title(&self) -> String {
+        let titles = self.get_elements_by_tag_name("title");
+
+        match titles.item(0).map(|title| title.borrow().clone()) {
+            Some(NodeKind::Element(title)) => title
+                .borrow()
+                .child_text_content()
+                .split_ascii_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+            _ => String::new(),
+        }
+    }
+
     pub fn doctype(&self) -> Option<Rc<RefCell<NodeKind>>> {
         for child in self._node.borrow().child_nodes().iter() {
             if let NodeKind::DocumentType(_) = child.borrow().deref() {
@@ -1487,15 +2638,82 @@ impl Document {
         nodes
     }
 
+    /// https://dom.spec.whatwg.org/#dom-document-createelement
+    pub fn create_element(
+        document: &Rc<RefCell<Document>>,
+        local_name: &str,
+    ) -> Rc<RefCell<Element>> {
+        let (local_name, namespace) = {
+            let doc = document.borrow();
+
+            let local_name = if doc.is_html() {
+                local_name.to_ascii_lowercase()
+            } else {
+                local_name.to_string()
+            };
+
+            let namespace = if doc.is_html() || doc.content_type() == "application/xhtml+xml" {
+                Some(HTML_NAMESPACE.to_string())
+            } else {
+                None
+            };
+
+            (local_name, namespace)
+        };
+
+        Rc::new(RefCell::new(Element::new(
+            Rc::clone(document),
+            local_name,
+            namespace,
+            None,
+            None,
+            Some(true),
+            Some(CustomElementRegistryOrDefault::Default),
+        )))
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-document-createtextnode
+    pub fn create_text_node(document: &Rc<RefCell<Document>>, data: &str) -> Rc<RefCell<Text>> {
+        Rc::new(RefCell::new(Text::new(data, Rc::clone(document))))
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-document-createdocumentfragment
+    pub fn create_document_fragment(document: &Rc<RefCell<Document>>) -> DocumentFragment {
+        DocumentFragment::new(Rc::clone(document))
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-document-createcomment
+    pub fn create_comment(document: &Rc<RefCell<Document>>, data: &str) -> Comment {
+        Comment::new(data, Rc::clone(document))
+    }
+
     pub fn style_sheets(&self) -> &StyleSheetList {
         &self.document_or_shadow_root_style.style_sheets
     }
 
+    /// Bumped whenever a stylesheet is added or a URL is visited, so cached element styles know
+    /// to recompute.
+    pub fn stylesheet_version(&self) -> u64 {
+        self.stylesheet_version
+    }
+
+    /// Records `url` as visited, so links to it match `:visited`.
+    pub fn mark_visited(&mut self, url: &http::url::URL) {
+        if self.visited_urls.insert(url.serialize()) {
+            self.stylesheet_version += 1;
+        }
+    }
+
+    pub fn is_visited(&self, url: &http::url::URL) -> bool {
+        self.visited_urls.contains(&url.serialize())
+    }
+
     pub fn push_stylesheet(&mut self, sheet: CSSStyleSheet) {
         self.document_or_shadow_root_style
             .style_sheets
             .style_sheets
             .push(Rc::new(RefCell::new(sheet)));
+        self.stylesheet_version += 1;
     }
 
     pub fn insert_stylesheet(&mut self, index: usize, sheet: CSSStyleSheet) {
@@ -1503,5 +2721,6 @@ impl Document {
             .style_sheets
             .style_sheets
             .insert(index, Rc::new(RefCell::new(sheet)));
+        self.stylesheet_version += 1;
     }
 }
@@ -0,0 +1,261 @@
+use std::{cell::RefCell, ops::Deref, rc::Rc};
+
+use crate::{
+    html5::dom::{Document, Element, Node, NodeKind},
+    http::url::URL,
+};
+
+/// A length given for `width` or `height` in a viewport `<meta>`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ViewportLength {
+    DeviceWidth,
+    DeviceHeight,
+    Px(f64),
+}
+
+impl ViewportLength {
+    fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "device-width" => Some(ViewportLength::DeviceWidth),
+            "device-height" => Some(ViewportLength::DeviceHeight),
+            value => value
+                .parse::<f64>()
+                .ok()
+                .filter(|px| *px > 0.0)
+                .map(ViewportLength::Px),
+        }
+    }
+
+    fn resolve(&self, device_size: (f64, f64)) -> f64 {
+        match self {
+            ViewportLength::DeviceWidth => device_size.0,
+            ViewportLength::DeviceHeight => device_size.1,
+            ViewportLength::Px(px) => *px,
+        }
+    }
+}
+
+/// The properties of a `<meta name=viewport>`. Missing or invalid properties are `None`.
+///
+/// https://drafts.csswg.org/css-viewport/#viewport-meta
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewportConfig {
+    pub width: Option<ViewportLength>,
+    pub height: Option<ViewportLength>,
+
+    pub initial_scale: Option<f64>,
+    pub minimum_scale: Option<f64>,
+    pub maximum_scale: Option<f64>,
+
+    pub user_scalable: bool,
+}
+
+impl Default for ViewportConfig {
+    fn default() -> Self {
+        Self {
+            width: None,
+            height: None,
+            initial_scale: None,
+            minimum_scale: None,
+            maximum_scale: None,
+            user_scalable: true,
+        }
+    }
+}
+
+impl ViewportConfig {
+    /// Parses the `content` attribute of a viewport `<meta>`, a list of `key=value` pairs
+    /// separated by commas, semicolons or whitespace. Unknown keys are ignored.
+    ///
+    /// https://drafts.csswg.org/css-viewport/#parsing-algorithm
+    pub fn parse(content: &str) -> Self {
+        let mut config = ViewportConfig::default();
+
+        let is_separator = |c: char| c == ',' || c == ';' || c.is_ascii_whitespace();
+        let mut rest = content.trim_matches(is_separator);
+
+        while !rest.is_empty() {
+            let key_end = rest
+                .find(|c: char| c == '=' || is_separator(c))
+                .unwrap_or(rest.len());
+            let key = &rest[..key_end];
+            rest = rest[key_end..].trim_start_matches(|c: char| c.is_ascii_whitespace());
+
+            let mut value = "";
+            if let Some(after_equals) = rest.strip_prefix('=') {
+                let after_equals =
+                    after_equals.trim_start_matches(|c: char| c.is_ascii_whitespace());
+                let value_end = after_equals
+                    .find(is_separator)
+                    .unwrap_or(after_equals.len());
+
+                value = &after_equals[..value_end];
+                rest = &after_equals[value_end..];
+            }
+
+            config.set_property(&key.to_ascii_lowercase(), value);
+            rest = rest.trim_start_matches(is_separator);
+        }
+
+        config
+    }
+
+    fn set_property(&mut self, key: &str, value: &str) {
+        let scale = || value.parse::<f64>().ok().filter(|scale| *scale > 0.0);
+
+        match key {
+            "width" => self.width = ViewportLength::parse(value),
+            "height" => self.height = ViewportLength::parse(value),
+            "initial-scale" => self.initial_scale = scale(),
+            "minimum-scale" => self.minimum_scale = scale(),
+            "maximum-scale" => self.maximum_scale = scale(),
+            "user-scalable" => {
+                self.user_scalable = match value.to_ascii_lowercase().as_str() {
+                    "yes" => true,
+                    "no" => false,
+                    value => value.parse::<f64>().is_ok_and(|number| number.abs() >= 1.0),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The size of the layout viewport on a device of `device_size`. A missing width is
+    /// derived from the initial scale, and a missing height keeps the device's aspect ratio.
+    ///
+    /// https://drafts.csswg.org/css-viewport/#translate-meta-to-at-viewport
+    pub fn layout_size(&self, device_size: (f64, f64)) -> (f64, f64) {
+        let width = match (&self.width, self.initial_scale) {
+            (Some(width), _) => width.resolve(device_size),
+            (None, Some(scale)) => device_size.0 / scale,
+            (None, None) => device_size.0,
+        };
+
+        let height = match &self.height {
+            Some(height) => height.resolve(device_size),
+            None => width * device_size.1 / device_size.0,
+        };
+
+        (width, height)
+    }
+}
+
+/// The viewport configuration of the first `<meta name=viewport>` in the document, if any.
+pub fn viewport_config(document: &Rc<RefCell<Document>>) -> Option<ViewportConfig> {
+    let meta = find_meta(&document.borrow()._node.borrow(), &|element| {
+        element
+            .get_attribute("name")
+            .is_some_and(|name| name.eq_ignore_ascii_case("viewport"))
+    })?;
+
+    let content = meta.borrow().get_attribute("content")?.to_string();
+    Some(ViewportConfig::parse(&content))
+}
+
+/// A navigation asked for by `<meta http-equiv=refresh>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefreshDirective {
+    /// Seconds to wait before navigating
+    pub delay: u64,
+
+    /// Where to navigate to, or `None` to reload the document
+    pub url: Option<URL>,
+}
+
+impl RefreshDirective {
+    /// Parses a `content` attribute like `5; url=/next`, resolving the URL against `base`.
+    /// Returns `None` if there is no delay or the URL fails to parse.
+    ///
+    /// https://html.spec.whatwg.org/multipage/semantics.html#shared-declarative-refresh-steps
+    pub fn parse(content: &str, base: &URL) -> Option<Self> {
+        let input = content.trim_start_matches(|c: char| c.is_ascii_whitespace());
+
+        let digits_end = input
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(input.len());
+        let (digits, rest) = input.split_at(digits_end);
+
+        if digits.is_empty() && !rest.starts_with('.') {
+            return None;
+        }
+
+        // `.5` waits no time at all, while a delay too long to count waits forever
+        let delay = match digits {
+            "" => 0,
+            digits => digits.parse::<u64>().unwrap_or(u64::MAX),
+        };
+        let rest = rest.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+        if rest.is_empty() {
+            return Some(Self { delay, url: None });
+        }
+
+        if !rest.starts_with([';', ',']) && !rest.starts_with(|c: char| c.is_ascii_whitespace()) {
+            return None;
+        }
+
+        let rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace());
+        let rest = rest.strip_prefix([';', ',']).unwrap_or(rest);
+        let mut url = rest.trim_start_matches(|c: char| c.is_ascii_whitespace());
+
+        if url.is_empty() {
+            return Some(Self { delay, url: None });
+        }
+
+        if url
+            .get(..3)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("url"))
+        {
+            let after_url = url[3..].trim_start_matches(|c: char| c.is_ascii_whitespace());
+
+            if let Some(after_equals) = after_url.strip_prefix('=') {
+                url = after_equals.trim_start_matches(|c: char| c.is_ascii_whitespace());
+            }
+        }
+
+        if let Some(quote) = url.chars().next().filter(|c| *c == '"' || *c == '\'') {
+            url = &url[1..];
+            if let Some(end) = url.find(quote) {
+                url = &url[..end];
+            }
+        }
+
+        let url = URL::parse(url.to_string(), Some(base.clone()), None).ok()?;
+
+        Some(Self {
+            delay,
+            url: Some(url),
+        })
+    }
+}
+
+/// The refresh directive of the first `<meta http-equiv=refresh>` in the document, if any.
+pub fn refresh_directive(document: &Rc<RefCell<Document>>) -> Option<RefreshDirective> {
+    let meta = find_meta(&document.borrow()._node.borrow(), &|element| {
+        element
+            .get_attribute("http-equiv")
+            .is_some_and(|value| value.eq_ignore_ascii_case("refresh"))
+    })?;
+
+    let content = meta.borrow().get_attribute("content")?.to_string();
+    RefreshDirective::parse(&content, &document.borrow().document_base_url())
+}
+
+/// The first `<meta>` element under `node`, in tree order, that `matches` accepts.
+fn find_meta(node: &Node, matches: &dyn Fn(&Element) -> bool) -> Option<Rc<RefCell<Element>>> {
+    for child in node.child_nodes().iter() {
+        let NodeKind::Element(element) = child.borrow().deref().clone() else {
+            continue;
+        };
+
+        if element.borrow().local_name == "meta" && matches(&element.borrow()) {
+            return Some(element);
+        }
+
+        if let Some(found) = find_meta(&element.borrow()._node.borrow(), matches) {
+            return Some(found);
+        }
+    }
+
+    None
+}
@@ -1,7 +1,13 @@
 pub mod dom;
+pub mod meta;
 /// Custom implementation of the HTML5 spec:
 /// https://html.spec.whatwg.org/
 pub mod parse;
+pub mod preload;
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::infra::InputStream;
 
 macro_rules! concat_arrays {
     ( $ty:ty, $default:expr => $($arr:expr),* $(,)? ) => {{
@@ -26,6 +32,31 @@ macro_rules! concat_arrays {
 
 pub const HTML_NAMESPACE: &str = "http://www.w3.org/1999/xhtml";
 
+/// Parses `input` as a complete HTML document, running both the tokenizer and the tree builder.
+///
+/// https://html.spec.whatwg.org/multipage/parsing.html#overview-of-the-parsing-model
+pub fn parse_html(input: &str) -> Rc<RefCell<dom::Document>> {
+    let chars = input.chars().collect::<Vec<char>>();
+    let mut stream = InputStream::new(&chars);
+    let mut parser = parse::Parser::new(&mut stream);
+
+    parser.parse();
+
+    Rc::clone(parser.document.document())
+}
+
+/// Parses `input` as the contents of `context`, the way `innerHTML` does, and returns the
+/// resulting nodes in a fragment. They aren't attached to `context`.
+///
+/// https://html.spec.whatwg.org/multipage/parsing.html#parsing-html-fragments
+pub fn parse_fragment(input: &str, context: &Rc<RefCell<dom::Element>>) -> dom::DocumentFragment {
+    let chars = input.chars().collect::<Vec<char>>();
+    let mut stream = InputStream::new(&chars);
+    let mut parser = parse::Parser::new(&mut stream);
+
+    parser.parse_fragment(context)
+}
+
 pub mod tag_groups {
     pub const DEFAULT_SCOPE_NAMES: [&str; 14] = [
         "applet", "caption", "html", "table", "td", "th", "marquee", "object", "template", "mi",
@@ -35,6 +66,8 @@ pub mod tag_groups {
     pub const BUTTON_SCOPE_NAMES: [&str; 15] =
         concat_arrays!(&str, "" => &DEFAULT_SCOPE_NAMES, &["button"]);
 
+    pub const TABLE_SCOPE_NAMES: [&str; 3] = ["html", "table", "template"];
+
     pub const LIST_ITEM_SCOPE_NAMES: [&str; 16] =
         concat_arrays!(&str, "" => &DEFAULT_SCOPE_NAMES, &["ol", "ul"]);
 
@@ -42,6 +75,12 @@ pub mod tag_groups {
         "dd", "dt", "li", "option", "optgroup", "p", "rb", "rp", "rt", "rtc",
     ];
 
+    /// https://html.spec.whatwg.org/multipage/parsing.html#generate-all-implied-end-tags-thoroughly
+    pub const THOROUGHLY_IMPLIED_END_TAGS: [&str; 18] = concat_arrays!(
+        &str, "" => &IMPLIED_END_TAGS,
+        &["caption", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr"]
+    );
+
     pub const FORMATTING_ELEMENT_NAMES: [&str; 12] = [
         "b", "big", "code", "em", "font", "i", "s", "small", "strike", "strong", "tt", "u",
     ];
@@ -190,4 +229,22 @@ pub mod tag_groups {
         "summary",
         "ul",
     ];
+
+    /// https://html.spec.whatwg.org/multipage/syntax.html#void-elements
+    pub const VOID_ELEMENT_NAMES: [&str; 18] = [
+        "area", "base", "basefont", "bgsound", "br", "col", "embed", "frame", "hr", "img", "input",
+        "keygen", "link", "meta", "param", "source", "track", "wbr",
+    ];
+
+    /// Elements whose text children are serialized without escaping.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#serialising-html-fragments
+    pub const RAW_TEXT_SERIALIZATION_NAMES: [&str; 7] = [
+        "style",
+        "script",
+        "xmp",
+        "iframe",
+        "noembed",
+        "noframes",
+        "plaintext",
+    ];
 }
@@ -85,6 +85,21 @@ impl ActiveFormattingElements {
         })
     }
 
+    pub fn position(&self, element: &Rc<RefCell<Element>>) -> Option<usize> {
+        self.elements.iter().position(|el| match el {
+            ElementOrMarker::Element(e) => Rc::ptr_eq(e, element),
+            ElementOrMarker::Marker => false,
+        })
+    }
+
+    /// Removes `element` from the list, returning where it was.
+    pub fn remove(&mut self, element: &Rc<RefCell<Element>>) -> Option<usize> {
+        let index = self.position(element)?;
+        self.elements.remove(index);
+
+        Some(index)
+    }
+
     pub fn reconstruct(&mut self, parser: &mut Parser) {
         parser._reconstruct_active_formatting_elements();
     }
@@ -3,19 +3,26 @@ use std::{cell::RefCell, rc::Rc};
 
 mod afe;
 mod open_elems;
+mod script;
 mod tokenize;
 mod tree;
 
 pub use afe::{ActiveFormattingElements, ElementOrMarker};
 pub use open_elems::OpenElementsStack;
-pub use tokenize::{ParseError, ParserState};
+pub use script::{NoopScriptRunner, PendingScript, ScriptMode, ScriptRunner};
+pub use tokenize::{ParseError, ParserState, StepResult};
 pub use tree::{DOCTYPE, InsertMode, Tag, TagToken, Token};
 
 pub use crate::html5::dom::*;
 use crate::infra::InputStream;
 
+/// Every named character reference and the characters it stands for, sorted by name so it can be
+/// binary searched.
+///
+/// https://html.spec.whatwg.org/multipage/named-characters.html
+///
 /// TODO: Possibly convert this into a trie structure
-pub const NAMED_CHARACTER_REFERENCES: [(&str, &str); 2231] = [
+pub static NAMED_CHARACTER_REFERENCES: [(&str, &str); 2231] = [
     ("AElig", "Æ"),
     ("AElig;", "Æ"),
     ("AMP", "&"),
@@ -2249,6 +2256,31 @@ pub const NAMED_CHARACTER_REFERENCES: [(&str, &str); 2231] = [
     ("zwnj;", "\u{200c}"),
 ];
 
+/// The longest name in [`NAMED_CHARACTER_REFERENCES`], in characters.
+const LONGEST_NAMED_CHARACTER_REFERENCE: usize = 32;
+
+/// The named character reference with the longest name that `input` starts with, if any, as a
+/// `(name, characters)` pair.
+///
+/// https://html.spec.whatwg.org/multipage/parsing.html#named-character-reference-state
+pub fn longest_named_character_reference(input: &[char]) -> Option<(&'static str, &'static str)> {
+    let candidate = input
+        .iter()
+        .take(LONGEST_NAMED_CHARACTER_REFERENCE)
+        .collect::<String>();
+
+    candidate
+        .char_indices()
+        .map(|(index, ch)| &candidate[..index + ch.len_utf8()])
+        .rev()
+        .find_map(|name| {
+            NAMED_CHARACTER_REFERENCES
+                .binary_search_by(|(entry, _)| entry.cmp(&name))
+                .ok()
+                .map(|index| NAMED_CHARACTER_REFERENCES[index])
+        })
+}
+
 #[derive(Debug)]
 pub struct _Document {
     pub document: Rc<RefCell<Document>>,
@@ -2260,48 +2292,6 @@ impl _Document {
     }
 }
 
-fn is_noncharacter(code: u32) -> bool {
-    matches!(
-        code,
-        0xFDD0
-            ..=0xFDEF
-                | 0xFFFE
-                | 0xFFFF
-                | 0x1FFFE
-                | 0x1FFFF
-                | 0x2FFFE
-                | 0x2FFFF
-                | 0x3FFFE
-                | 0x3FFFF
-                | 0x4FFFE
-                | 0x4FFFF
-                | 0x5FFFE
-                | 0x5FFFF
-                | 0x6FFFE
-                | 0x6FFFF
-                | 0x7FFFE
-                | 0x7FFFF
-                | 0x8FFFE
-                | 0x8FFFF
-                | 0x9FFFE
-                | 0x9FFFF
-                | 0xAFFFE
-                | 0xAFFFF
-                | 0xBFFFE
-                | 0xBFFFF
-                | 0xCFFFE
-                | 0xCFFFF
-                | 0xDFFFE
-                | 0xDFFFF
-                | 0xEFFFE
-                | 0xEFFFF
-                | 0xFFFFE
-                | 0xFFFFF
-                | 0x10FFFE
-                | 0x10FFFF
-    )
-}
-
 fn is_c0_control(code: u32) -> bool {
     (0x0000..=0x001F).contains(&code)
 }
@@ -2367,6 +2357,15 @@ pub struct Parser<'a> {
     temporary_buffer: String,
     character_reference_code: u32,
 
+    /// Characters collected in the in table text insertion mode, inserted all at once so that
+    /// only non-whitespace runs get foster parented.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#concept-pending-table-char-tokens
+    pending_table_character_tokens: Vec<char>,
+
+    /// Whether the last input passed to `feed` ended in a CR, whose LF might start the next.
+    fed_carriage_return: bool,
+
     pub document: _Document,
 
     active_formatting_elements: ActiveFormattingElements,
@@ -2374,10 +2373,31 @@ pub struct Parser<'a> {
 
     head_element_id: Option<ElementID>,
 
+    /// The element whose contents are being parsed, when parsing a fragment.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#concept-frag-parse-context
+    context_element: Option<Rc<RefCell<Element>>>,
+
+    /// The insertion modes to go back to inside each open `template`, innermost last.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#stack-of-template-insertion-modes
+    template_insertion_modes: Vec<InsertMode>,
+
     pub emitted_tokens: Vec<Token>,
 
+    /// Every parse error reported so far, in the order they were found.
+    errors: Vec<ParseError>,
+
+    /// Every script element the parser has finished, in document order.
+    scripts: Vec<PendingScript>,
+
+    script_runner: Box<dyn ScriptRunner>,
+
     flag_scripting: bool,
     flag_frameset_ok: bool,
+
+    /// Set once parsing has stopped, so that finishing twice doesn't run scripts twice.
+    flag_stopped: bool,
 }
 
 impl _Document {
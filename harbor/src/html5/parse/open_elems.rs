@@ -7,11 +7,19 @@ use std::rc::Rc;
 
 pub struct OpenElementsStack {
     pub elements: Vec<Rc<RefCell<Element>>>,
+
+    /// Whether content that would land directly inside a table goes in front of it instead.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#foster-parent
+    pub foster_parenting: bool,
 }
 
 impl OpenElementsStack {
     pub fn new() -> OpenElementsStack {
-        OpenElementsStack { elements: vec![] }
+        OpenElementsStack {
+            elements: vec![],
+            foster_parenting: false,
+        }
     }
 
     pub fn push(&mut self, element: Rc<RefCell<Element>>) {
@@ -30,6 +38,14 @@ impl OpenElementsStack {
         }
     }
 
+    /// Pops elements up to and including `element` itself, leaving the stack alone if it isn't
+    /// on it.
+    pub fn pop_through(&mut self, element: &Rc<RefCell<Element>>) {
+        if let Some(index) = self.position(element) {
+            self.elements.truncate(index);
+        }
+    }
+
     pub fn nth(&self, index: usize) -> Option<Rc<RefCell<Element>>> {
         self.elements.get(index).map(Rc::clone)
     }
@@ -42,13 +58,16 @@ impl OpenElementsStack {
             .any(|el| el.borrow().qualified_name() == element_name)
     }
 
+    /// Whether `element` itself, rather than any element with its name, is on the stack.
     pub fn contains_rc(&self, element: &Rc<RefCell<Element>>) -> bool {
-        let element_name = element.borrow().qualified_name();
+        self.position(element).is_some()
+    }
 
-        self.elements
-            .iter()
-            .any(|el| el.borrow().qualified_name() == element_name)
+    /// The index of `element` in the stack, counting from the bottom (the `html` element).
+    pub fn position(&self, element: &Rc<RefCell<Element>>) -> Option<usize> {
+        self.elements.iter().position(|el| Rc::ptr_eq(el, element))
     }
+
     pub fn contains_tag(&self, tag_name: &str) -> bool {
         self.elements
             .iter()
@@ -78,11 +97,88 @@ impl OpenElementsStack {
                 .expect("No current node for appropriate insertion place")
         });
 
-        let adjusted_insertion_position = target.borrow().node().borrow().child_nodes().length();
+        if self.foster_parenting
+            && matches!(
+                target.borrow().qualified_name().as_str(),
+                "table" | "tbody" | "tfoot" | "thead" | "tr"
+            )
+        {
+            return self.foster_parent_insertion_place();
+        }
+
+        Self::end_of(&target)
+    }
+
+    /// Where foster parented content goes: just before the last table on the stack.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#appropriate-place-for-inserting-a-node
+    fn foster_parent_insertion_place(&self) -> InsertLocation {
+        let last_table = self
+            .elements
+            .iter()
+            .rposition(|el| el.borrow().qualified_name() == "table");
+        let last_template = self
+            .elements
+            .iter()
+            .rposition(|el| el.borrow().qualified_name() == "template");
+
+        let table_index = match (last_table, last_template) {
+            (Some(table), Some(template)) if template > table => {
+                return Self::end_of(&self.elements[template]);
+            }
+            (Some(table), _) => table,
+            (None, Some(template)) => return Self::end_of(&self.elements[template]),
+            (None, None) => return Self::end_of(&self.elements[0]),
+        };
+
+        let table = &self.elements[table_index];
+        let table_node = Rc::clone(table.borrow().node());
+
+        // The table's parent is normally somewhere on the stack, since only the parser moves
+        // elements around while parsing.
+        let parent = table_node
+            .borrow()
+            .parent_node()
+            .and_then(|parent| parent.upgrade())
+            .and_then(|parent| {
+                self.elements[..table_index]
+                    .iter()
+                    .rev()
+                    .find(|el| Rc::ptr_eq(el.borrow().node(), &parent))
+                    .map(|el| (Rc::clone(el), parent))
+            });
+
+        match parent {
+            Some((parent, parent_node)) => {
+                let position = parent_node
+                    .borrow()
+                    .child_nodes()
+                    .iter()
+                    .position(|child| Rc::ptr_eq(&child.borrow().node(), &table_node))
+                    .expect("The table is one of its parent's children");
+
+                InsertLocation::new(Rc::new(RefCell::new(NodeKind::Element(parent))), position)
+            }
+            None => Self::end_of(&self.elements[table_index - 1]),
+        }
+    }
 
+    /// The place after the last child of `element`, or of its template contents for a
+    /// `template` element.
+    pub fn end_of(element: &Rc<RefCell<Element>>) -> InsertLocation {
+        if let Some(contents) = element.borrow().template_contents() {
+            let length = contents.node().borrow().child_nodes().length();
+
+            return InsertLocation::new(
+                Rc::new(RefCell::new(NodeKind::DocumentFragment(contents.clone()))),
+                length,
+            );
+        }
+
+        let length = element.borrow().node().borrow().child_nodes().length();
         InsertLocation::new(
-            Rc::new(RefCell::new(NodeKind::Element(target))),
-            adjusted_insertion_position,
+            Rc::new(RefCell::new(NodeKind::Element(Rc::clone(element)))),
+            length,
         )
     }
 
@@ -157,6 +253,36 @@ impl OpenElementsStack {
         self.has_element_in_specific_scope(target_name, &BUTTON_SCOPE_NAMES)
     }
 
+    pub fn has_element_in_table_scope(&self, target_name: &str) -> bool {
+        self.has_element_in_specific_scope(target_name, &TABLE_SCOPE_NAMES)
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#clear-the-stack-back-to-a-table-context
+    pub fn clear_back_to_table_context(&mut self) {
+        self.clear_back_to(&["table", "template", "html"]);
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#clear-the-stack-back-to-a-table-body-context
+    pub fn clear_back_to_table_body_context(&mut self) {
+        self.clear_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#clear-the-stack-back-to-a-table-row-context
+    pub fn clear_back_to_table_row_context(&mut self) {
+        self.clear_back_to(&["tr", "template", "html"]);
+    }
+
+    /// Pops elements until the current node is one of `names`.
+    fn clear_back_to(&mut self, names: &[&str]) {
+        while let Some(current_node) = self.current_node() {
+            if names.contains(&current_node.borrow().qualified_name().as_str()) {
+                break;
+            }
+
+            self.pop();
+        }
+    }
+
     pub fn generate_implied_end_tags(&mut self, exclude: Option<&str>) {
         loop {
             let _current_node = match self.adjusted_current_node() {
@@ -176,6 +302,15 @@ impl OpenElementsStack {
         }
     }
 
+    /// https://html.spec.whatwg.org/multipage/parsing.html#generate-all-implied-end-tags-thoroughly
+    pub fn generate_all_implied_end_tags_thoroughly(&mut self) {
+        while self.current_node().is_some_and(|node| {
+            THOROUGHLY_IMPLIED_END_TAGS.contains(&node.borrow().qualified_name().as_str())
+        }) {
+            self.pop();
+        }
+    }
+
     pub fn close_p_tag(&mut self) {
         self.generate_implied_end_tags(Some("p"));
         self.pop_until("p")
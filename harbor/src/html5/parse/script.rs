@@ -0,0 +1,98 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::html5::dom::Element;
+
+/// Integration point for a script engine. The parser hands every finished script element to its
+/// runner.
+///
+/// https://html.spec.whatwg.org/multipage/scripting.html#execute-the-script-element
+pub trait ScriptRunner {
+    fn run(&mut self, src: &str, element: &Element);
+}
+
+/// The default runner, which ignores every script since Harbor has no script engine.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopScriptRunner;
+
+impl ScriptRunner for NoopScriptRunner {
+    fn run(&mut self, _src: &str, _element: &Element) {}
+}
+
+/// When a script element would run relative to parsing.
+///
+/// https://html.spec.whatwg.org/multipage/scripting.html#script-processing-model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptMode {
+    /// An inline classic script, run as soon as its end tag is parsed.
+    Inline,
+
+    /// An external classic script without `async` or `defer`, which blocks the parser.
+    ParserBlocking,
+
+    /// Run in document order once parsing has finished.
+    Defer,
+
+    /// Run as soon as it is available, in no particular order.
+    Async,
+}
+
+impl ScriptMode {
+    /// Works out the mode of a script element from its attributes, or `None` if the element is a
+    /// data block rather than a script.
+    pub fn for_element(element: &Element) -> Option<ScriptMode> {
+        let is_module = match element.get_attribute("type") {
+            None => false,
+            Some(ty) if ty.is_empty() || JAVASCRIPT_MIME_TYPES.contains(&ty.trim()) => false,
+            Some(ty) if ty.trim().eq_ignore_ascii_case("module") => true,
+            Some(_) => return None,
+        };
+
+        let has_src = element.get_attribute("src").is_some();
+        let is_async = element.get_attribute("async").is_some();
+        let is_defer = element.get_attribute("defer").is_some();
+
+        Some(match (is_module, has_src) {
+            (true, _) if is_async => ScriptMode::Async,
+            (true, _) => ScriptMode::Defer,
+            (false, true) if is_async => ScriptMode::Async,
+            (false, true) if is_defer => ScriptMode::Defer,
+            (false, true) => ScriptMode::ParserBlocking,
+            (false, false) => ScriptMode::Inline,
+        })
+    }
+}
+
+/// https://mimesniff.spec.whatwg.org/#javascript-mime-type
+const JAVASCRIPT_MIME_TYPES: [&str; 16] = [
+    "application/ecmascript",
+    "application/javascript",
+    "application/x-ecmascript",
+    "application/x-javascript",
+    "text/ecmascript",
+    "text/javascript",
+    "text/javascript1.0",
+    "text/javascript1.1",
+    "text/javascript1.2",
+    "text/javascript1.3",
+    "text/javascript1.4",
+    "text/javascript1.5",
+    "text/jscript",
+    "text/livescript",
+    "text/x-ecmascript",
+    "text/x-javascript",
+];
+
+/// A script element seen by the parser, along with when it would run.
+#[derive(Debug, Clone)]
+pub struct PendingScript {
+    pub mode: ScriptMode,
+
+    /// The value of the `src` attribute, if the script is external.
+    pub src: Option<String>,
+
+    /// The inline source text of the element.
+    pub source: String,
+
+    pub element: Rc<RefCell<Element>>,
+}
@@ -1,12 +1,14 @@
 use crate::html5::{
+    self,
     dom::*,
     parse::{
-        _Document, ActiveFormattingElements, DOCTYPE, ElementOrMarker, InputStream, InsertMode,
-        NAMED_CHARACTER_REFERENCES, OpenElementsStack, Parser, Tag, TagToken, Token,
-        is_ascii_whitespace, is_control, is_noncharacter, map_character_reference,
+        _Document, ActiveFormattingElements, DOCTYPE, ElementOrMarker, InsertMode,
+        LONGEST_NAMED_CHARACTER_REFERENCE, NoopScriptRunner, OpenElementsStack, Parser,
+        PendingScript, ScriptMode, ScriptRunner, Tag, TagToken, Token, is_ascii_whitespace,
+        is_control, longest_named_character_reference, map_character_reference,
     },
 };
-use crate::infra::is_surrogate;
+use crate::infra::{InputStream, is_noncharacter, is_surrogate};
 
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -142,8 +144,25 @@ pub enum ParserState {
     NumericCharacterReferenceEnd = 80,
 }
 
+/// What happened when the parser tried to take a step.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepResult {
+    /// The step ran and the parser can carry on.
+    Continue,
+
+    /// The stream is still open but doesn't hold enough input for the next step, so nothing was
+    /// consumed. Append more input, or close the stream, and step again.
+    NeedsMoreInput,
+}
+
 impl<'a> Parser<'a> {
+    /// Creates a parser for `stream`, normalizing its newlines first as part of preprocessing
+    /// the input stream.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#preprocessing-the-input-stream
     pub fn new(stream: &mut InputStream<char>) -> Parser {
+        stream.normalize_newlines();
+
         Parser {
             stream,
 
@@ -164,6 +183,10 @@ impl<'a> Parser<'a> {
             temporary_buffer: String::new(),
             character_reference_code: 0,
 
+            pending_table_character_tokens: vec![],
+
+            fed_carriage_return: false,
+
             // Initialize an empty document
             document: _Document {
                 document: Document::new(Origin::Opaque),
@@ -174,10 +197,83 @@ impl<'a> Parser<'a> {
 
             head_element_id: None,
 
+            context_element: None,
+            template_insertion_modes: vec![],
+
             emitted_tokens: vec![],
+            errors: vec![],
+
+            scripts: vec![],
+            script_runner: Box::new(NoopScriptRunner),
 
             flag_scripting: false,
             flag_frameset_ok: true,
+            flag_stopped: false,
+        }
+    }
+
+    /// Sets the scripting flag. Harbor has no script engine, so it is disabled by default.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#scripting-flag
+    pub fn with_scripting(mut self, enabled: bool) -> Self {
+        self.flag_scripting = enabled;
+        self
+    }
+
+    pub fn scripting(&self) -> bool {
+        self.flag_scripting
+    }
+
+    /// Replaces the runner that finished script elements are handed to.
+    pub fn with_script_runner(mut self, runner: Box<dyn ScriptRunner>) -> Self {
+        self.script_runner = runner;
+        self
+    }
+
+    pub fn scripts(&self) -> &Vec<PendingScript> {
+        &self.scripts
+    }
+
+    pub fn scripts_with_mode(&self, mode: ScriptMode) -> Vec<&PendingScript> {
+        self.scripts
+            .iter()
+            .filter(|script| script.mode == mode)
+            .collect()
+    }
+
+    /// https://html.spec.whatwg.org/multipage/scripting.html#prepare-the-script-element
+    ///
+    /// Nothing is fetched. Inline scripts are handed to the script runner straight away, while
+    /// deferred and async inline module scripts wait until parsing has finished.
+    pub fn _prepare_script(&mut self, element: &Rc<RefCell<Element>>) {
+        let Some(mode) = ScriptMode::for_element(&element.borrow()) else {
+            return;
+        };
+
+        let script = PendingScript {
+            mode,
+            src: element.borrow().get_attribute("src").map(str::to_string),
+            source: element.borrow().child_text_content(),
+            element: Rc::clone(element),
+        };
+
+        if script.mode == ScriptMode::Inline {
+            self.script_runner
+                .run(&script.source, &script.element.borrow());
+        }
+
+        self.scripts.push(script);
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#the-end
+    fn _run_scripts_after_parsing(&mut self) {
+        for mode in [ScriptMode::Defer, ScriptMode::Async] {
+            for script in self.scripts.iter().filter(|script| script.mode == mode) {
+                if script.src.is_none() {
+                    self.script_runner
+                        .run(&script.source, &script.element.borrow());
+                }
+            }
         }
     }
 
@@ -188,6 +284,223 @@ impl<'a> Parser<'a> {
             .any(|el| el.borrow().qualified_name() == name)
     }
 
+    /// Runs the adoption agency algorithm for an end tag named `subject`, which untangles
+    /// misnested formatting elements such as `<b>1<i>2</b>3</i>`. Returns `false` when there's no
+    /// formatting element to adopt, in which case the token should be handled as "any other end
+    /// tag" instead.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#adoption-agency-algorithm
+    ///
+    /// Let subject be token's tag name.
+    /// If the current node is an HTML element whose tag name is subject, and the current node is
+    /// not in the list of active formatting elements, then pop the current node off the stack of
+    /// open elements and return.
+    /// Let outerLoopCounter be 0.
+    /// While true:
+    ///     If outerLoopCounter is greater than or equal to 8, then return.
+    ///     Increment outerLoopCounter by 1.
+    ///     Let formattingElement be the last element in the list of active formatting elements that:
+    ///         is between the end of the list and the last marker in the list, if any, or the start of the list otherwise, and
+    ///         has the tag name subject.
+    ///     If there is no such element, then return and instead act as described in the "any other end tag" entry above.
+    ///     If formattingElement is not in the stack of open elements, then this is a parse error; remove the element from the list, and return.
+    ///     If formattingElement is in the stack of open elements, but the element is not in scope, then this is a parse error; return.
+    ///     If formattingElement is not the current node, this is a parse error. (But do not return.)
+    ///     Let furthestBlock be the topmost node in the stack of open elements that is lower in the stack than formattingElement, and is an element in the special category. There might not be one.
+    ///     If there is no furthestBlock, then the UA must first pop all the nodes from the bottom of the stack of open elements, from the current node up to and including formattingElement, then remove formattingElement from the list of active formatting elements, and finally return.
+    ///     Let commonAncestor be the element immediately above formattingElement in the stack of open elements.
+    ///     Let a bookmark note the position of formattingElement in the list of active formatting elements relative to the elements on either side of it in the list.
+    ///     Let node and lastNode be furthestBlock.
+    ///     Let innerLoopCounter be 0.
+    ///     While true:
+    ///         Increment innerLoopCounter by 1.
+    ///         Let node be the element immediately above node in the stack of open elements, or if node is no longer in the stack of open elements (e.g. because it got removed by this algorithm), the element that was immediately above node in the stack of open elements before node was removed.
+    ///         Append lastNode to node.
+    ///         Set lastNode to node.
+    ///     Insert whatever lastNode ended up being in the previous step at the appropriate place for inserting a node, but using commonAncestor as the override target.
+    ///     Create an element for the token for which formattingElement was created, in the HTML namespace, with furthestBlock as the intended parent.
+    ///     Take all of the child nodes of furthestBlock and append them to the element created in the last step.
+    ///     Append that new element to furthestBlock.
+    ///     Remove formattingElement from the list of active formatting elements, and insert the new element into the list of active formatting elements at the position of the aforementioned bookmark.
+    ///     Remove formattingElement from the stack of open elements, and insert the new element into the stack of open elements immediately below the position of furthestBlock in that stack.
+    pub fn adoption_agency(&mut self, subject: &str) -> bool {
+        if let Some(current) = self.open_elements_stack.current_node()
+            && current.borrow().qualified_name() == subject
+            && !self.active_formatting_elements.contains(&current)
+        {
+            self.open_elements_stack.pop();
+            return true;
+        }
+
+        for _ in 0..8 {
+            let after_marker = self
+                .active_formatting_elements
+                .last_marker()
+                .map_or(0, |marker| marker + 1);
+
+            let Some(formatting_element) = self.active_formatting_elements.elements[after_marker..]
+                .iter()
+                .rev()
+                .find_map(|entry| match entry {
+                    ElementOrMarker::Element(e) if e.borrow().qualified_name() == subject => {
+                        Some(Rc::clone(e))
+                    }
+                    _ => None,
+                })
+            else {
+                return false;
+            };
+
+            let Some(formatting_index) = self.open_elements_stack.position(&formatting_element)
+            else {
+                self.error(ParseError::Custom(
+                    "Formatting element is not in the stack of open elements",
+                ));
+                self.active_formatting_elements.remove(&formatting_element);
+                return true;
+            };
+
+            if !self
+                .open_elements_stack
+                .has_element_in_default_scope(subject)
+            {
+                self.error(ParseError::Custom("Formatting element is not in scope"));
+                return true;
+            }
+
+            if formatting_index != self.open_elements_stack.elements.len() - 1 {
+                self.error(ParseError::Custom(
+                    "Formatting element is not the current node",
+                ));
+            }
+
+            let Some(furthest_block_index) = self.open_elements_stack.elements
+                [formatting_index + 1..]
+                .iter()
+                .position(|el| el.borrow().is_special())
+                .map(|offset| formatting_index + 1 + offset)
+            else {
+                self.open_elements_stack.elements.truncate(formatting_index);
+                self.active_formatting_elements.remove(&formatting_element);
+                return true;
+            };
+
+            let furthest_block =
+                Rc::clone(&self.open_elements_stack.elements[furthest_block_index]);
+            let common_ancestor =
+                Rc::clone(&self.open_elements_stack.elements[formatting_index - 1]);
+
+            let mut bookmark = self
+                .active_formatting_elements
+                .position(&formatting_element)
+                .expect("Formatting element was just found in the list");
+
+            let mut last_node = Rc::clone(&furthest_block);
+            let mut node_index = furthest_block_index;
+            let mut inner_loop_counter = 0;
+
+            loop {
+                inner_loop_counter += 1;
+                node_index -= 1;
+
+                let node = Rc::clone(&self.open_elements_stack.elements[node_index]);
+                if Rc::ptr_eq(&node, &formatting_element) {
+                    break;
+                }
+
+                if inner_loop_counter > 3
+                    && let Some(index) = self.active_formatting_elements.remove(&node)
+                    && index < bookmark
+                {
+                    bookmark -= 1;
+                }
+
+                let Some(afe_index) = self.active_formatting_elements.position(&node) else {
+                    self.open_elements_stack.elements.remove(node_index);
+                    continue;
+                };
+
+                let token = node
+                    .borrow()
+                    .token()
+                    .cloned()
+                    .expect("Formatting elements are created from tokens");
+                let new_node = Element::from_token(
+                    &token,
+                    html5::HTML_NAMESPACE,
+                    &NodeKind::Element(Rc::clone(&common_ancestor)),
+                );
+
+                self.active_formatting_elements.elements[afe_index] =
+                    ElementOrMarker::Element(Rc::clone(&new_node));
+                self.open_elements_stack.elements[node_index] = Rc::clone(&new_node);
+
+                if Rc::ptr_eq(&last_node, &furthest_block) {
+                    bookmark = afe_index + 1;
+                }
+
+                Node::append_child(
+                    new_node.borrow().node(),
+                    Rc::new(RefCell::new(NodeKind::Element(last_node))),
+                );
+                last_node = new_node;
+            }
+
+            // TODO: Foster parent last_node when common_ancestor is a table element
+            Node::append_child(
+                common_ancestor.borrow().node(),
+                Rc::new(RefCell::new(NodeKind::Element(last_node))),
+            );
+
+            let token = formatting_element
+                .borrow()
+                .token()
+                .cloned()
+                .expect("Formatting elements are created from tokens");
+            let new_element = Element::from_token(
+                &token,
+                html5::HTML_NAMESPACE,
+                &NodeKind::Element(Rc::clone(&furthest_block)),
+            );
+
+            let children = furthest_block
+                .borrow()
+                .node()
+                .borrow()
+                .child_nodes()
+                .map(Rc::clone);
+            for child in children {
+                Node::append_child(new_element.borrow().node(), child);
+            }
+            Node::append_child(
+                furthest_block.borrow().node(),
+                Rc::new(RefCell::new(NodeKind::Element(Rc::clone(&new_element)))),
+            );
+
+            if let Some(index) = self.active_formatting_elements.remove(&formatting_element)
+                && index < bookmark
+            {
+                bookmark -= 1;
+            }
+            self.active_formatting_elements
+                .elements
+                .insert(bookmark, ElementOrMarker::Element(Rc::clone(&new_element)));
+
+            self.open_elements_stack
+                .elements
+                .retain(|el| !Rc::ptr_eq(el, &formatting_element));
+            let furthest_block_index = self
+                .open_elements_stack
+                .position(&furthest_block)
+                .expect("Furthest block is still on the stack");
+            self.open_elements_stack
+                .elements
+                .insert(furthest_block_index + 1, new_element);
+        }
+
+        true
+    }
+
     pub fn _reconstruct_active_formatting_elements(&mut self) {
         if self.active_formatting_elements.elements.is_empty() {
             return;
@@ -316,6 +629,16 @@ impl<'a> Parser<'a> {
         location.insert(&mut NodeKind::Comment(comment));
     }
 
+    /// Inserts a comment as the last child of the document itself, outside the `html` element.
+    pub fn _insert_comment_in_document(&mut self, data: &str) {
+        let comment = Comment::new(data, Rc::clone(self.document.document()));
+
+        Node::append_child(
+            &Rc::clone(&self.document.document().borrow()._node),
+            Rc::new(RefCell::new(NodeKind::Comment(comment))),
+        );
+    }
+
     pub fn _generic_text_parsing_algorithm(&mut self, token: &Token) {
         self.open_elements_stack.insert_html_element(token);
         self.state = ParserState::RAWTEXT;
@@ -386,9 +709,26 @@ impl<'a> Parser<'a> {
         }
     }
 
-    pub fn error(&self, err: ParseError) {
-        // For now, just print the error to the console.
+    pub fn error(&mut self, err: ParseError) {
         eprintln!("Parse error: {:?}", err);
+        self.errors.push(err);
+    }
+
+    /// The parse errors reported so far, in the order they were found.
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// How many parse errors have been reported so far.
+    pub fn parse_errors(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Whether the input parsed so far has no parse errors at all.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-errors
+    pub fn is_well_formed(&self) -> bool {
+        self.errors.is_empty()
     }
 
     pub fn reconsume(&mut self, state: ParserState) {
@@ -469,6 +809,15 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Adds a digit to the numeric character reference being read. The code saturates rather
+    /// than overflowing, since anything past U+10FFFF ends up as U+FFFD anyway.
+    fn push_character_reference_digit(&mut self, base: u32, digit: u32) {
+        self.character_reference_code = self
+            .character_reference_code
+            .saturating_mul(base)
+            .saturating_add(digit);
+    }
+
     pub fn flush_consumed_as_char_ref(&mut self) {
         let part_of_attr = self.char_ref_as_part_of_attr();
 
@@ -482,12 +831,197 @@ impl<'a> Parser<'a> {
     }
 
     pub fn parse(&mut self) {
+        while !self.stream.is_eof {
+            if self.step() == StepResult::NeedsMoreInput {
+                return;
+            }
+        }
+
+        self.finish();
+    }
+
+    /// Ends parsing once the input has run out: parses whatever's left of a closed stream, makes
+    /// sure the tree builder has seen the end-of-file token, then stops parsing. Calling it again
+    /// does nothing.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#the-end
+    pub fn finish(&mut self) {
+        if self.flag_stopped {
+            return;
+        }
+
+        self.stream.close();
         while !self.stream.is_eof {
             self.step();
         }
+
+        // Some states reach the end of the input without emitting the end-of-file token
+        if !matches!(self.emitted_tokens.last(), Some(Token::EOF)) {
+            self.emit(Token::EOF);
+        }
+
+        self._stop_parsing();
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#stop-parsing
+    fn _stop_parsing(&mut self) {
+        self.flag_stopped = true;
+
+        // Pop all the nodes off the stack of open elements
+        while self.open_elements_stack.pop().is_some() {}
+
+        self._run_scripts_after_parsing();
+    }
+
+    /// The elements that are still open, from the outermost to the current node.
+    pub fn open_elements(&self) -> &[Rc<RefCell<Element>>] {
+        &self.open_elements_stack.elements
+    }
+
+    /// Appends `input` to an open stream and parses as far as it allows. Anything cut off at the
+    /// end, such as half of a tag, is kept until the next call.
+    pub fn feed(&mut self, input: &str) {
+        let mut input = input.chars().collect::<Vec<char>>();
+
+        // A CRLF pair split between two calls has already become a LF
+        if self.fed_carriage_return && input.first() == Some(&'\u{000A}') {
+            input.remove(0);
+        }
+
+        if !input.is_empty() {
+            self.fed_carriage_return = input.last() == Some(&'\u{000D}');
+        }
+
+        self.stream.append(&input);
+        self.stream.normalize_newlines();
+
+        self.parse();
+    }
+
+    /// Closes the stream after the last call to `feed` and parses whatever is left.
+    pub fn close_input(&mut self) {
+        self.stream.close();
+        self.parse();
+    }
+
+    /// How many characters the current state might look at. The longest lookaheads are the
+    /// keywords after `<!` and in a DOCTYPE, and the longest named character reference.
+    fn input_needed(&self) -> usize {
+        match self.state {
+            ParserState::MarkupDeclarationOpen => "[CDATA[".len(),
+            ParserState::AfterDOCTYPEName => "PUBLIC".len(),
+            ParserState::NamedCharacterReference => LONGEST_NAMED_CHARACTER_REFERENCE,
+            ParserState::NumericCharacterReferenceEnd => 0,
+            _ => 1,
+        }
+    }
+
+    /// Parses the input as the contents of `context`, returning the nodes it produced in a
+    /// fragment owned by `context`'s document.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-html-fragments
+    pub fn parse_fragment(&mut self, context: &Rc<RefCell<Element>>) -> DocumentFragment {
+        self.state = match context.borrow().local_name.as_str() {
+            "title" | "textarea" => ParserState::RCDATA,
+            "style" | "xmp" | "iframe" | "noembed" | "noframes" => ParserState::RAWTEXT,
+            "noscript" if self.flag_scripting => ParserState::RAWTEXT,
+            "script" => ParserState::ScriptData,
+            "plaintext" => ParserState::PLAINTEXT,
+            _ => ParserState::Data,
+        };
+        self.prev_state = self.state.clone();
+
+        let root = Element::from_token(
+            &Token::StartTag(Tag {
+                name: String::from("html"),
+                is_self_closing: false,
+                attributes: vec![],
+            }),
+            html5::HTML_NAMESPACE,
+            &NodeKind::Document(self.document.document().borrow().clone()),
+        );
+        Node::append_child(
+            &self.document.document().borrow()._node,
+            Rc::new(RefCell::new(NodeKind::Element(Rc::clone(&root)))),
+        );
+        self.open_elements_stack.push(Rc::clone(&root));
+
+        self.context_element = Some(Rc::clone(context));
+        if context.borrow().local_name == "template" {
+            self.template_insertion_modes.push(InsertMode::InTemplate);
+        }
+        self._reset_insertion_mode_appropriately();
+
+        self.parse();
+
+        let document = context
+            .borrow()
+            .node()
+            .borrow()
+            .node_document
+            .as_ref()
+            .and_then(|document| document.upgrade())
+            .unwrap_or_else(|| Rc::clone(self.document.document()));
+        let fragment = DocumentFragment::new(document);
+
+        let children = root
+            .borrow()
+            .node()
+            .borrow()
+            .child_nodes()
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        for child in children {
+            Node::append_child(fragment.node(), child);
+        }
+
+        fragment
     }
 
-    pub fn step(&mut self) {
+    /// https://html.spec.whatwg.org/multipage/parsing.html#reset-the-insertion-mode-appropriately
+    pub fn _reset_insertion_mode_appropriately(&mut self) {
+        for (index, element) in self.open_elements_stack.elements.iter().enumerate().rev() {
+            let last = index == 0;
+            let node = match &self.context_element {
+                Some(context) if last => Rc::clone(context),
+                _ => Rc::clone(element),
+            };
+
+            let mode = match node.borrow().local_name.as_str() {
+                "td" | "th" if !last => InsertMode::InCell,
+                "tr" => InsertMode::InRow,
+                "tbody" | "thead" | "tfoot" => InsertMode::InTableBody,
+                "caption" => InsertMode::InCaption,
+                "colgroup" => InsertMode::InColumnGroup,
+                "table" => InsertMode::InTable,
+                "template" => self
+                    .template_insertion_modes
+                    .last()
+                    .cloned()
+                    .unwrap_or(InsertMode::InTemplate),
+                "head" if !last => InsertMode::InHead,
+                "body" => InsertMode::InBody,
+                "frameset" => InsertMode::InFrameset,
+                "html" if self.head_element_id.is_none() => InsertMode::BeforeHead,
+                "html" => InsertMode::AfterHead,
+                _ if last => InsertMode::InBody,
+                _ => continue,
+            };
+
+            self.insertion_mode = mode;
+            return;
+        }
+    }
+
+    /// Runs the current tokenizer state once. While the stream is open, a step that could run out
+    /// of input isn't taken, so running dry isn't mistaken for the end of the file.
+    pub fn step(&mut self) -> StepResult {
+        let needed = self.input_needed();
+        if !self.stream.is_closed() && self.stream.lookahead(needed).len() < needed {
+            return StepResult::NeedsMoreInput;
+        }
+
         if self.prev_state != self.state {
             if let Some(callback) = self.leave_callback.take() {
                 callback(self);
@@ -2317,69 +2851,35 @@ impl<'a> Parser<'a> {
             }
             ParserState::NamedCharacterReference => {
                 // https://html.spec.whatwg.org/multipage/parsing.html#named-character-reference-state
-                let mut last_match: Option<(&'static str, &'static str)> = None;
-                let mut consumed = String::new();
-
-                loop {
-                    let c = match self.stream.peek() {
-                        Some(ch) => ch,
-                        None => break,
-                    };
-
-                    self.temporary_buffer.push(c);
+                let lookahead = self.stream.lookahead(LONGEST_NAMED_CHARACTER_REFERENCE);
 
-                    let mut lo = 0;
-                    let mut hi = NAMED_CHARACTER_REFERENCES.len();
-
-                    while lo < hi {
-                        let mid = (lo + hi) / 2;
-                        if NAMED_CHARACTER_REFERENCES[mid].0 < &self.temporary_buffer[1..] {
-                            lo = mid + 1;
-                        } else {
-                            hi = mid;
+                if let Some((name, value)) = longest_named_character_reference(lookahead) {
+                    for _ in name.chars() {
+                        if let Some(ch) = self.stream.consume() {
+                            self.temporary_buffer.push(ch);
                         }
                     }
 
-                    if lo >= NAMED_CHARACTER_REFERENCES.len()
-                        || !NAMED_CHARACTER_REFERENCES[lo]
-                            .0
-                            .starts_with(&self.temporary_buffer[1..])
-                    {
-                        self.temporary_buffer.pop();
-                        break;
-                    }
-
-                    self.stream.consume();
-                    consumed.push(c);
+                    let ends_with_semicolon = name.ends_with('\u{003B}');
+                    let next_is_alphanumeric_or_equals = self
+                        .stream
+                        .lookahead(1)
+                        .first()
+                        .is_some_and(|ch| ch.is_ascii_alphanumeric() || *ch == '\u{003D}');
 
-                    if NAMED_CHARACTER_REFERENCES[lo].0 == &self.temporary_buffer[1..] {
-                        last_match = Some(NAMED_CHARACTER_REFERENCES[lo]);
-                    }
-                }
-
-                println!("Exited with: {:?}", last_match);
-
-                if let Some((name, value)) = last_match {
-                    let ends_with_semicolon = name.ends_with(';');
-
-                    if self.char_ref_as_part_of_attr()
+                    if !(self.char_ref_as_part_of_attr()
                         && !ends_with_semicolon
-                        && self
-                            .stream
-                            .peek()
-                            .is_some_and(|ch| ch.is_ascii_alphanumeric() || ch == '=')
+                        && next_is_alphanumeric_or_equals)
                     {
-                        self.flush_consumed_as_char_ref();
-                        self.state = self.return_state.clone().unwrap();
-                    } else {
                         if !ends_with_semicolon {
                             self.error(ParseError::MissingSemicolonAfterCharacterReference);
                         }
 
                         self.temporary_buffer = String::from(value);
-                        self.flush_consumed_as_char_ref();
-                        self.state = self.return_state.clone().unwrap();
                     }
+
+                    self.flush_consumed_as_char_ref();
+                    self.state = self.return_state.clone().unwrap();
                 } else {
                     self.flush_consumed_as_char_ref();
                     self.state = ParserState::AmbiguousAmpersand;
@@ -2387,7 +2887,6 @@ impl<'a> Parser<'a> {
             }
             ParserState::AmbiguousAmpersand => {
                 // https://html.spec.whatwg.org/multipage/parsing.html#ambiguous-ampersand-state
-                // TODO: This is only reachable through NamedCharacterReference
                 match self.stream.consume() {
                     Some(ch) => match ch {
                         _ if ch.is_ascii_alphanumeric() => {
@@ -2457,16 +2956,13 @@ impl<'a> Parser<'a> {
                 if let Some(ch) = self.stream.consume() {
                     match ch {
                         _ if ch.is_ascii_digit() => {
-                            self.character_reference_code *= 16;
-                            self.character_reference_code += ch as u32 - 0x30;
+                            self.push_character_reference_digit(16, ch as u32 - 0x30);
                         }
                         '\u{0041}'..='\u{0046}' => {
-                            self.character_reference_code *= 16;
-                            self.character_reference_code += ch as u32 - 0x37;
+                            self.push_character_reference_digit(16, ch as u32 - 0x37);
                         }
                         '\u{0061}'..='\u{0066}' => {
-                            self.character_reference_code *= 16;
-                            self.character_reference_code += ch as u32 - 0x57;
+                            self.push_character_reference_digit(16, ch as u32 - 0x57);
                         }
                         '\u{003B}' => {
                             self.state = ParserState::NumericCharacterReferenceEnd;
@@ -2489,8 +2985,7 @@ impl<'a> Parser<'a> {
                 if let Some(ch) = self.stream.consume() {
                     match ch {
                         _ if ch.is_ascii_digit() => {
-                            self.character_reference_code *= 10;
-                            self.character_reference_code += ch as u32 - 0x30;
+                            self.push_character_reference_digit(10, ch as u32 - 0x30);
                         }
                         '\u{003B}' => {
                             self.state = ParserState::NumericCharacterReferenceEnd;
@@ -2510,7 +3005,7 @@ impl<'a> Parser<'a> {
                         self.error(ParseError::NullCharacterReference);
                         self.character_reference_code = 0xFFFD;
                     }
-                    0x10FFFF.. => {
+                    0x110000.. => {
                         self.error(ParseError::CharacterReferenceOutsideUnicodeRange);
                         self.character_reference_code = 0xFFFD;
                     }
@@ -2545,5 +3040,7 @@ impl<'a> Parser<'a> {
                 self.state = return_state;
             }
         }
+
+        StepResult::Continue
     }
 }
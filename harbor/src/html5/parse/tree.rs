@@ -6,7 +6,10 @@ use crate::{
     html5::{
         self,
         dom::*,
-        parse::{ElementOrMarker, ParseError, Parser, ParserState},
+        parse::{
+            ElementOrMarker, OpenElementsStack, ParseError, Parser, ParserState,
+            is_ascii_whitespace,
+        },
         tag_groups::*,
     },
     infra::InputStream,
@@ -154,16 +157,7 @@ impl InsertMode {
         match token {
             Token::Character('\u{0009}' | '\u{000A}' | '\u{000C}' | '\u{000D}' | '\u{0020}') => {}
             Token::Comment(data) => {
-                let comment = Comment::new(data.as_str(), Rc::clone(parser.document.document()));
-
-                parser
-                    .document
-                    .document()
-                    .borrow_mut()
-                    ._node
-                    .borrow_mut()
-                    .child_nodes_mut()
-                    .push(&Rc::new(RefCell::new(NodeKind::Comment(comment))));
+                parser._insert_comment_in_document(data.as_str());
             }
             Token::DOCTYPE(doctype) => {
                 if doctype.name.as_ref().unwrap().to_ascii_lowercase() != "html"
@@ -196,6 +190,8 @@ impl InsertMode {
                 parser.insertion_mode = InsertMode::BeforeHTML;
             }
             _ => {
+                parser.error(ParseError::Custom("Missing DOCTYPE"));
+
                 // TODO: Set quirks mode:
 
                 parser.insertion_mode = InsertMode::BeforeHTML;
@@ -214,7 +210,8 @@ impl InsertMode {
                 ));
             }
             Token::Comment(data) => {
-                parser._insert_comment(data.as_str(), None);
+                // There's no html element yet, so the comment goes in the document
+                parser._insert_comment_in_document(data.as_str());
             }
             Token::Character('\u{0009}' | '\u{000A}' | '\u{000C}' | '\u{000D}' | '\u{0020}') => {}
             Token::StartTag(ref tag) if tag.name.as_str() == "html" => {
@@ -381,7 +378,42 @@ impl InsertMode {
                 parser.open_elements_stack.pop();
                 parser.insertion_mode = InsertMode::AfterHead;
             }
-            // TODO: Start Tag "template", End Tag "template"
+            Token::StartTag(ref tag) if tag.name.as_str() == "template" => {
+                parser.open_elements_stack.insert_html_element(&token);
+                parser.active_formatting_elements.push_marker();
+                parser.flag_frameset_ok = false;
+
+                parser.insertion_mode = InsertMode::InTemplate;
+                parser.template_insertion_modes.push(InsertMode::InTemplate);
+            }
+            Token::EndTag(ref tag) if tag.name.as_str() == "template" => {
+                if !parser._is_element_on_open_elements("template") {
+                    parser.error(ParseError::Custom(
+                        "Unexpected template end tag token in in head insertion mode",
+                    ));
+                    return true;
+                }
+
+                parser
+                    .open_elements_stack
+                    .generate_all_implied_end_tags_thoroughly();
+
+                if parser
+                    .open_elements_stack
+                    .current_node()
+                    .is_some_and(|node| node.borrow().qualified_name() != "template")
+                {
+                    parser.error(ParseError::Custom(
+                        "Unexpected template end tag token in in head insertion mode",
+                    ));
+                }
+
+                parser.open_elements_stack.pop_until("template");
+                parser.active_formatting_elements.pop_until_marker();
+                parser.template_insertion_modes.pop();
+
+                parser._reset_insertion_mode_appropriately();
+            }
             Token::StartTag(ref start) if start.name.as_str() == "head" => {
                 parser.error(ParseError::Custom(
                     "Unexpected start tag token in in head insertion mode",
@@ -637,29 +669,20 @@ impl InsertMode {
                 {
                     return true;
                 } else {
-                    let second = &parser.open_elements_stack.elements[1];
+                    let second = Rc::clone(&parser.open_elements_stack.elements[1]);
 
-                    let position = second
+                    let parent = second
                         .borrow()
                         .node()
                         .borrow()
                         .parent_node()
-                        .unwrap()
-                        .upgrade()
-                        .unwrap()
-                        .borrow()
-                        .position_of_child(&NodeKind::Element(second.clone()));
+                        .and_then(|parent| parent.upgrade());
 
-                    second
-                        .borrow()
-                        .node()
-                        .borrow_mut()
-                        .parent_node_mut()
-                        .unwrap()
-                        .upgrade()
-                        .unwrap()
-                        .borrow_mut()
-                        .pop_child(position);
+                    if let Some(parent) = parent {
+                        parent
+                            .borrow_mut()
+                            .remove_child(&NodeKind::Element(Rc::clone(&second)));
+                    }
 
                     parser.open_elements_stack.elements.drain(1..);
                     parser.open_elements_stack.insert_html_element(&token);
@@ -668,8 +691,13 @@ impl InsertMode {
                 }
             }
             Token::EOF => {
-                if !parser.open_elements_stack.elements.is_empty() {
-                    InsertMode::handle_in_template(parser, token);
+                if parser
+                    .open_elements_stack
+                    .elements
+                    .iter()
+                    .any(|el| el.borrow().qualified_name() == "template")
+                {
+                    return InsertMode::handle_in_template(parser, token);
                 } else {
                     if parser
                         .open_elements_stack
@@ -763,42 +791,7 @@ impl InsertMode {
             }
             Token::StartTag(ref tag) if tag.name.as_str() == "li" => {
                 parser.flag_frameset_ok = false;
-
-                let mut node_index = parser.open_elements_stack.elements.len();
-                let mut node = parser.open_elements_stack.nth(node_index);
-
-                loop {
-                    if node
-                        .as_ref()
-                        .is_some_and(|el| el.borrow().qualified_name() == "li")
-                    {
-                        parser
-                            .open_elements_stack
-                            .generate_implied_end_tags(Some("li"));
-
-                        if parser
-                            .open_elements_stack
-                            .adjusted_current_node()
-                            .is_some_and(|el| el.borrow().qualified_name() != "li")
-                        {
-                            parser.error(ParseError::Custom(
-                                "Unexpected current node after generating implied end tags for li",
-                            ));
-                        }
-
-                        parser.open_elements_stack.pop_until("li");
-                        break;
-                    }
-
-                    if node.as_ref().is_some_and(|el| {
-                        el.borrow().is_special_excluding(&["address", "div", "p"])
-                    }) {
-                        break;
-                    } else {
-                        node_index -= 1;
-                        node = parser.open_elements_stack.nth(node_index);
-                    }
-                }
+                InsertMode::_close_open_list_item(parser, &["li"]);
 
                 if parser.open_elements_stack.has_element_in_button_scope("p") {
                     parser.open_elements_stack.close_p_tag();
@@ -808,64 +801,7 @@ impl InsertMode {
             }
             Token::StartTag(ref tag) if matches!(tag.name.as_str(), "dd" | "dt") => {
                 parser.flag_frameset_ok = false;
-
-                let mut node_index = parser.open_elements_stack.elements.len();
-                let mut node = parser.open_elements_stack.nth(node_index);
-
-                loop {
-                    if node
-                        .as_ref()
-                        .is_some_and(|el| el.borrow().qualified_name() == "dd")
-                    {
-                        parser
-                            .open_elements_stack
-                            .generate_implied_end_tags(Some("dd"));
-
-                        if parser
-                            .open_elements_stack
-                            .adjusted_current_node()
-                            .is_some_and(|el| el.borrow().qualified_name() != "dd")
-                        {
-                            parser.error(ParseError::Custom(
-                                "Unexpected current node after generating implied end tags for dd",
-                            ));
-                        }
-
-                        parser.open_elements_stack.pop_until("dd");
-                        break;
-                    }
-
-                    if node
-                        .as_ref()
-                        .is_some_and(|el| el.borrow().qualified_name() == "dt")
-                    {
-                        parser
-                            .open_elements_stack
-                            .generate_implied_end_tags(Some("dt"));
-
-                        if parser
-                            .open_elements_stack
-                            .adjusted_current_node()
-                            .is_some_and(|el| el.borrow().qualified_name() != "dt")
-                        {
-                            parser.error(ParseError::Custom(
-                                "Unexpected current node after generating implied end tags for dt",
-                            ));
-                        }
-
-                        parser.open_elements_stack.pop_until("dt");
-                        break;
-                    }
-
-                    if node.as_ref().is_some_and(|el| {
-                        el.borrow().is_special_excluding(&["address", "div", "p"])
-                    }) {
-                        break;
-                    } else {
-                        node_index -= 1;
-                        node = parser.open_elements_stack.nth(node_index);
-                    }
-                }
+                InsertMode::_close_open_list_item(parser, &["dd", "dt"]);
 
                 if parser.open_elements_stack.has_element_in_button_scope("p") {
                     parser.open_elements_stack.close_p_tag();
@@ -1036,15 +972,25 @@ impl InsertMode {
                     [last_marker_pos.unwrap_or(0)..]
                     .to_vec();
 
-                if relevant_slice.iter().any(|el| match el {
-                    ElementOrMarker::Element(e) => e.borrow().qualified_name() == "a",
-                    ElementOrMarker::Marker => false,
-                }) {
+                let open_a = relevant_slice.iter().find_map(|el| match el {
+                    ElementOrMarker::Element(e) if e.borrow().qualified_name() == "a" => {
+                        Some(Rc::clone(e))
+                    }
+                    _ => None,
+                });
+
+                if let Some(open_a) = open_a {
                     parser.error(ParseError::Custom(
                         "Unexpected a start tag token in in body insertion mode",
                     ));
 
-                    InsertMode::_adoption_agency(parser, tag);
+                    parser.adoption_agency("a");
+
+                    parser.active_formatting_elements.remove(&open_a);
+                    parser
+                        .open_elements_stack
+                        .elements
+                        .retain(|el| !Rc::ptr_eq(el, &open_a));
                 }
 
                 parser._reconstruct_active_formatting_elements();
@@ -1056,11 +1002,31 @@ impl InsertMode {
                 let element = parser.open_elements_stack.insert_html_element(&token);
                 parser.active_formatting_elements.push(element);
             }
+            Token::StartTag(ref tag) if tag.name.as_str() == "nobr" => {
+                parser._reconstruct_active_formatting_elements();
+
+                if parser
+                    .open_elements_stack
+                    .has_element_in_default_scope("nobr")
+                {
+                    parser.error(ParseError::Custom(
+                        "Unexpected nobr start tag token in in body insertion mode",
+                    ));
+
+                    parser.adoption_agency("nobr");
+                    parser._reconstruct_active_formatting_elements();
+                }
+
+                let element = parser.open_elements_stack.insert_html_element(&token);
+                parser.active_formatting_elements.push(element);
+            }
             Token::EndTag(ref tag)
                 if FORMATTING_ELEMENT_NAMES.contains(&tag.name.as_str())
-                    || tag.name.as_str() == "a" =>
+                    || matches!(tag.name.as_str(), "a" | "nobr") =>
             {
-                InsertMode::_adoption_agency(parser, tag);
+                if !parser.adoption_agency(&tag.name) {
+                    InsertMode::handle_any_other_end_tag_in_body(parser, tag);
+                }
             }
             Token::StartTag(ref tag)
                 if matches!(tag.name.as_str(), "applet" | "marquee" | "object") =>
@@ -1113,6 +1079,35 @@ impl InsertMode {
 
                 parser.flag_frameset_ok = false;
             }
+            Token::EndTag(ref tag) if tag.name.as_str() == "br" => {
+                parser.error(ParseError::Custom(
+                    "Unexpected br end tag token in in body insertion mode",
+                ));
+
+                return InsertMode::handle_in_body(
+                    parser,
+                    Token::StartTag(Tag::new(&String::from("br"))),
+                );
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "input" => {
+                parser._reconstruct_active_formatting_elements();
+                parser.open_elements_stack.insert_html_element(&token);
+                parser.open_elements_stack.pop();
+
+                if !tag
+                    .attributes
+                    .iter()
+                    .any(|(name, value)| name == "type" && value.eq_ignore_ascii_case("hidden"))
+                {
+                    parser.flag_frameset_ok = false;
+                }
+            }
+            Token::StartTag(ref tag)
+                if matches!(tag.name.as_str(), "param" | "source" | "track") =>
+            {
+                parser.open_elements_stack.insert_html_element(&token);
+                parser.open_elements_stack.pop();
+            }
             Token::StartTag(ref tag) if tag.name.as_str() == "hr" => {
                 if parser.open_elements_stack.has_element_in_button_scope("p") {
                     parser.open_elements_stack.close_p_tag();
@@ -1141,46 +1136,79 @@ impl InsertMode {
 
                 parser.flag_frameset_ok = false;
             }
+            Token::StartTag(ref tag) if tag.name.as_str() == "image" => {
+                parser.error(ParseError::Custom(
+                    "Unexpected image start tag token in in body insertion mode",
+                ));
+
+                let mut img = tag.clone();
+                img.name = String::from("img");
+                return InsertMode::handle_in_body(parser, Token::StartTag(img));
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "xmp" => {
+                if parser.open_elements_stack.has_element_in_button_scope("p") {
+                    parser.open_elements_stack.close_p_tag();
+                }
+
+                parser._reconstruct_active_formatting_elements();
+                parser.flag_frameset_ok = false;
+                parser._generic_text_parsing_algorithm(&token);
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "iframe" => {
+                parser.flag_frameset_ok = false;
+                parser._generic_text_parsing_algorithm(&token);
+            }
+            Token::StartTag(ref tag) if matches!(tag.name.as_str(), "optgroup" | "option") => {
+                if InsertMode::_current_node_is(parser, "option") {
+                    parser.open_elements_stack.pop();
+                }
+
+                parser._reconstruct_active_formatting_elements();
+                parser.open_elements_stack.insert_html_element(&token);
+            }
+            Token::StartTag(ref tag)
+                if tag.name.as_str() == "noembed"
+                    || (tag.name.as_str() == "noscript" && parser.flag_scripting) =>
+            {
+                parser._generic_text_parsing_algorithm(&token);
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "table" => {
+                if !parser.document.document().borrow().is_quirks_mode()
+                    && parser.open_elements_stack.has_element_in_button_scope("p")
+                {
+                    parser.open_elements_stack.close_p_tag();
+                }
+
+                parser.open_elements_stack.insert_html_element(&token);
+                parser.flag_frameset_ok = false;
+                parser.insertion_mode = InsertMode::InTable;
+            }
+            Token::StartTag(ref tag)
+                if matches!(
+                    tag.name.as_str(),
+                    "caption"
+                        | "col"
+                        | "colgroup"
+                        | "frame"
+                        | "head"
+                        | "tbody"
+                        | "td"
+                        | "tfoot"
+                        | "th"
+                        | "thead"
+                        | "tr"
+                ) =>
+            {
+                parser.error(ParseError::Custom(
+                    "Unexpected table start tag token in in body insertion mode",
+                ));
+            }
             Token::StartTag(_) => {
                 parser._reconstruct_active_formatting_elements();
                 parser.open_elements_stack.insert_html_element(&token);
             }
             Token::EndTag(ref tag) => {
-                let mut node = parser.open_elements_stack.elements.last().unwrap().clone();
-
-                loop {
-                    if node.borrow().qualified_name() == tag.name {
-                        parser
-                            .open_elements_stack
-                            .generate_implied_end_tags(Some(&tag.name));
-
-                        if node
-                            != *parser
-                                .open_elements_stack
-                                .adjusted_current_node()
-                                .as_ref()
-                                .unwrap()
-                        {
-                            parser.error(ParseError::Custom(
-                                "Unexpected current node after generating implied end tags for any other end tag",
-                            ));
-                        }
-
-                        parser.open_elements_stack.pop_until(&tag.name);
-                        return true;
-                    } else if SPECIAL_CATEGORY_NAMES
-                        .contains(&node.borrow().qualified_name().as_str())
-                    {
-                        parser.error(ParseError::Custom(
-                            "Unexpected any other end tag token in in body insertion mode",
-                        ));
-                        return true;
-                    } else {
-                        node = parser.open_elements_stack.elements
-                            [parser.open_elements_stack.elements.len() - 2]
-                            .clone();
-                    }
-                }
+                InsertMode::handle_any_other_end_tag_in_body(parser, tag);
             }
             _ => {
                 todo!("Handle other tokens in in body insertion mode: {:?}", token);
@@ -1190,6 +1218,82 @@ impl InsertMode {
         return true;
     }
 
+    /// Closes the nearest open element named one of `names`, unless a special element other
+    /// than `address`, `div` or `p` comes first. Shared by the `li`, `dd` and `dt` start tags.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
+    fn _close_open_list_item(parser: &mut Parser, names: &[&str]) {
+        let open_item = parser
+            .open_elements_stack
+            .elements
+            .iter()
+            .rev()
+            .find(|el| {
+                let el = el.borrow();
+                names.contains(&el.qualified_name().as_str())
+                    || el.is_special_excluding(&["address", "div", "p"])
+            })
+            .map(|el| el.borrow().qualified_name())
+            .filter(|name| names.contains(&name.as_str()));
+
+        let Some(name) = open_item else {
+            return;
+        };
+
+        parser
+            .open_elements_stack
+            .generate_implied_end_tags(Some(&name));
+
+        if parser
+            .open_elements_stack
+            .adjusted_current_node()
+            .is_some_and(|el| el.borrow().qualified_name() != name)
+        {
+            parser.error(ParseError::Custom(
+                "Unexpected current node after generating implied end tags for a list item",
+            ));
+        }
+
+        parser.open_elements_stack.pop_until(&name);
+    }
+
+    /// The "any other end tag" entry of the in body insertion mode, which the adoption agency
+    /// algorithm also falls back to.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
+    fn handle_any_other_end_tag_in_body(parser: &mut Parser, tag: &Tag) {
+        // Walk down the stack from the current node
+        for index in (0..parser.open_elements_stack.elements.len()).rev() {
+            let node = parser.open_elements_stack.elements[index].clone();
+
+            if node.borrow().qualified_name() == tag.name {
+                parser
+                    .open_elements_stack
+                    .generate_implied_end_tags(Some(&tag.name));
+
+                if node
+                    != *parser
+                        .open_elements_stack
+                        .adjusted_current_node()
+                        .as_ref()
+                        .unwrap()
+                {
+                    parser.error(ParseError::Custom(
+                        "Unexpected current node after generating implied end tags for any other end tag",
+                    ));
+                }
+
+                parser.open_elements_stack.pop_through(&node);
+                return;
+            } else if SPECIAL_CATEGORY_NAMES.contains(&node.borrow().qualified_name().as_str()) {
+                parser.error(ParseError::Custom(
+                    "Unexpected any other end tag token in in body insertion mode",
+                ));
+                return;
+            }
+        }
+    }
+
     fn handle_text(parser: &mut Parser, token: Token) -> bool {
         match token {
             Token::Character(ch) => {
@@ -1205,10 +1309,10 @@ impl InsertMode {
                 return false;
             }
             Token::EndTag(ref tag) if tag.name.as_str() == "script" => {
-                parser.open_elements_stack.pop();
+                let script = parser.open_elements_stack.pop().unwrap();
                 parser.insertion_mode = parser.original_insertion_mode.clone().unwrap();
 
-                // todo!("Handle script end tag correctly");
+                parser._prepare_script(&script);
             }
             Token::EndTag(ref tag) => {
                 let popped_elem = parser.open_elements_stack.pop().unwrap();
@@ -1228,11 +1332,12 @@ impl InsertMode {
                         let tokens = tokenize(&mut stream);
 
                         let mut tok_stream = InputStream::new(&tokens[..]);
-                        let parsed = parse_stylesheet(
+                        let mut parsed = parse_stylesheet(
                             &mut tok_stream,
                             Rc::downgrade(&parser.document.document),
                             None,
                         );
+                        parsed.set_owner_node(Rc::downgrade(&popped_elem));
 
                         parser
                             .document
@@ -1252,282 +1357,899 @@ impl InsertMode {
         return true;
     }
 
-    fn handle_in_template(_parser: &mut Parser, _token: Token) -> bool {
-        todo!("Implement in template insertion mode");
-
-        // match token {
-        //     _ => {}
-        // }
-
-        // return true;
-    }
-
-    /// Let subject be token's tag name.
-    /// If the current node is an HTML element whose tag name is subject, and the current node is
-    /// not in the list of active formatting elements, then pop the current node off the stack of
-    /// open elements and return.
-    /// Let outerLoopCounter be 0.
-    /// While true:
-    ///     If outerLoopCounter is greater than or equal to 8, then return.
-    ///     Increment outerLoopCounter by 1.
-    ///     Let formattingElement be the last element in the list of active formatting elements that:
-    ///         is between the end of the list and the last marker in the list, if any, or the start of the list otherwise, and
-    ///         has the tag name subject.
-    ///     If there is no such element, then return and instead act as described in the "any other end tag" entry above.
-    ///     If formattingElement is not in the stack of open elements, then this is a parse error; remove the element from the list, and return.
-    ///     If formattingElement is in the stack of open elements, but the element is not in scope, then this is a parse error; return.
-    ///     If formattingElement is not the current node, this is a parse error. (But do not return.)
-    ///     Let furthestBlock be the topmost node in the stack of open elements that is lower in the stack than formattingElement, and is an element in the special category. There might not be one.
-    ///     If there is no furthestBlock, then the UA must first pop all the nodes from the bottom of the stack of open elements, from the current node up to and including formattingElement, then remove formattingElement from the list of active formatting elements, and finally return.
-    ///     Let commonAncestor be the element immediately above formattingElement in the stack of open elements.
-    ///     Let a bookmark note the position of formattingElement in the list of active formatting elements relative to the elements on either side of it in the list.
-    ///     Let node and lastNode be furthestBlock.
-    ///     Let innerLoopCounter be 0.
-    ///     While true:
-    ///         Increment innerLoopCounter by 1.
-    ///         Let node be the element immediately above node in the stack of open elements, or if node is no longer in the stack of open elements (e.g. because it got removed by this algorithm), the element that was immediately above node in the stack of open elements before node was removed.
-    ///         Append lastNode to node.
-    ///         Set lastNode to node.
-    ///     Insert whatever lastNode ended up being in the previous step at the appropriate place for inserting a node, but using commonAncestor as the override target.
-    ///     Create an element for the token for which formattingElement was created, in the HTML namespace, with furthestBlock as the intended parent.
-    ///     Take all of the child nodes of furthestBlock and append them to the element created in the last step.
-    ///     Append that new element to furthestBlock.
-    ///     Remove formattingElement from the list of active formatting elements, and insert the new element into the list of active formatting elements at the position of the aforementioned bookmark.
-    ///     Remove formattingElement from the stack of open elements, and insert the new element into the stack of open elements immediately below the position of furthestBlock in that stack.
-    fn _adoption_agency(parser: &mut Parser, tag: &Tag) {
-        let subject = tag.name.as_str();
-
-        if parser.open_elements_stack.current_node().is_some_and(|el| {
-            el.borrow().qualified_name() == subject
-                && !parser.active_formatting_elements.contains(&el)
-        }) {
-            parser.open_elements_stack.pop();
-            return;
-        }
-
-        let mut outer_loop_counter = 0;
-
-        loop {
-            if outer_loop_counter >= 8 {
-                return;
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intable
+    fn handle_in_table(parser: &mut Parser, token: Token) -> bool {
+        match token {
+            Token::Character(_)
+                if parser.open_elements_stack.current_node().is_some_and(|el| {
+                    matches!(
+                        el.borrow().qualified_name().as_str(),
+                        "table" | "tbody" | "template" | "tfoot" | "thead" | "tr"
+                    )
+                }) =>
+            {
+                parser.pending_table_character_tokens.clear();
+                parser.original_insertion_mode = Some(parser.insertion_mode.clone());
+                parser.insertion_mode = InsertMode::InTableText;
+                return false;
             }
-
-            outer_loop_counter += 1;
-
-            let last_marker_pos = parser.active_formatting_elements.last_marker();
-            let relevant_slice =
-                parser.active_formatting_elements.elements[last_marker_pos.unwrap_or(0)..].to_vec();
-
-            let formatting_element_pos = relevant_slice.iter().rposition(|el| match el {
-                ElementOrMarker::Element(e) => e.borrow().qualified_name() == subject,
-                ElementOrMarker::Marker => false,
-            });
-            let adjusted_formatting_element_pos =
-                formatting_element_pos.map(|pos| pos + last_marker_pos.unwrap_or(0));
-
-            let _formatting_element = formatting_element_pos.map(|pos| relevant_slice[pos].clone());
-
-            if _formatting_element.is_none() {
-                // TODO: Come up with a way to singal "any other end tag" handling
-                return;
+            Token::Comment(data) => {
+                parser._insert_comment(data.as_str(), None);
             }
-
-            let formatting_element = match _formatting_element.unwrap() {
-                ElementOrMarker::Element(e) => e,
-                ElementOrMarker::Marker => {
-                    panic!("Formatting element cannot be a marker");
-                }
-            };
-
-            if !parser.open_elements_stack.contains_rc(&formatting_element) {
+            Token::DOCTYPE(_) => {
                 parser.error(ParseError::Custom(
-                    "Formatting element not in open elements stack during adoption agency algorithm",
+                    "Unexpected DOCTYPE token in in table insertion mode",
                 ));
-
-                // Remove the element from the list
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "caption" => {
+                parser.open_elements_stack.clear_back_to_table_context();
+                parser.active_formatting_elements.push_marker();
+                parser.open_elements_stack.insert_html_element(&token);
+                parser.insertion_mode = InsertMode::InCaption;
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "colgroup" => {
+                parser.open_elements_stack.clear_back_to_table_context();
+                parser.open_elements_stack.insert_html_element(&token);
+                parser.insertion_mode = InsertMode::InColumnGroup;
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "col" => {
+                parser.open_elements_stack.clear_back_to_table_context();
                 parser
-                    .active_formatting_elements
-                    .elements
-                    .retain(|el| match el {
-                        ElementOrMarker::Element(e) => !Rc::ptr_eq(&e, &formatting_element),
-                        ElementOrMarker::Marker => true,
-                    });
-
-                return;
+                    .open_elements_stack
+                    .insert_html_element(&Token::StartTag(Tag::new(&String::from("colgroup"))));
+                parser.insertion_mode = InsertMode::InColumnGroup;
+                return false;
             }
-
-            if !parser
-                .open_elements_stack
-                .has_element_in_default_scope(&subject)
+            Token::StartTag(ref tag)
+                if matches!(tag.name.as_str(), "tbody" | "tfoot" | "thead") =>
             {
-                parser.error(ParseError::Custom(
-                    "Formatting element not in scope during adoption agency algorithm",
-                ));
-
-                return;
+                parser.open_elements_stack.clear_back_to_table_context();
+                parser.open_elements_stack.insert_html_element(&token);
+                parser.insertion_mode = InsertMode::InTableBody;
             }
-
-            if !parser
-                .open_elements_stack
-                .current_node()
-                .is_some_and(|el| Rc::ptr_eq(&el, &formatting_element))
-            {
+            Token::StartTag(ref tag) if matches!(tag.name.as_str(), "td" | "th" | "tr") => {
+                parser.open_elements_stack.clear_back_to_table_context();
+                parser
+                    .open_elements_stack
+                    .insert_html_element(&Token::StartTag(Tag::new(&String::from("tbody"))));
+                parser.insertion_mode = InsertMode::InTableBody;
+                return false;
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "table" => {
                 parser.error(ParseError::Custom(
-                    "Formatting element not current node during adoption agency algorithm",
+                    "Unexpected table start tag token in in table insertion mode",
                 ));
-            }
 
-            let furthest_block_pos = relevant_slice[formatting_element_pos.unwrap() + 1..]
-                .iter()
-                .position(|el| match el {
-                    ElementOrMarker::Element(e) => e.borrow().is_special(),
-                    ElementOrMarker::Marker => false,
-                });
-            let adjusted_furthest_block_pos =
-                furthest_block_pos.map(|pos| pos + adjusted_formatting_element_pos.unwrap() + 1);
-
-            let _furthest_block = furthest_block_pos.map(|el_idx| {
-                match &relevant_slice[el_idx + formatting_element_pos.unwrap() + 1] {
-                    ElementOrMarker::Element(e) => Rc::clone(&e),
-                    ElementOrMarker::Marker => {
-                        panic!("Furthest block cannot be a marker");
-                    }
-                }
-            });
+                if parser
+                    .open_elements_stack
+                    .has_element_in_table_scope("table")
+                {
+                    parser.open_elements_stack.pop_until("table");
+                    parser._reset_insertion_mode_appropriately();
+                    return false;
+                }
+            }
+            Token::EndTag(ref tag) if tag.name.as_str() == "table" => {
+                if !parser
+                    .open_elements_stack
+                    .has_element_in_table_scope("table")
+                {
+                    parser.error(ParseError::Custom(
+                        "Unexpected table end tag token in in table insertion mode",
+                    ));
+                    return true;
+                }
+
+                parser.open_elements_stack.pop_until("table");
+                parser._reset_insertion_mode_appropriately();
+            }
+            Token::EndTag(ref tag)
+                if matches!(
+                    tag.name.as_str(),
+                    "body"
+                        | "caption"
+                        | "col"
+                        | "colgroup"
+                        | "html"
+                        | "tbody"
+                        | "td"
+                        | "tfoot"
+                        | "th"
+                        | "thead"
+                        | "tr"
+                ) =>
+            {
+                parser.error(ParseError::Custom(
+                    "Unexpected end tag token in in table insertion mode",
+                ));
+            }
+            Token::StartTag(ref tag)
+                if matches!(tag.name.as_str(), "style" | "script" | "template") =>
+            {
+                return InsertMode::handle_in_head(parser, token);
+            }
+            Token::EndTag(ref tag) if tag.name.as_str() == "template" => {
+                return InsertMode::handle_in_head(parser, token);
+            }
+            Token::StartTag(ref tag)
+                if tag.name.as_str() == "input"
+                    && tag.attributes.iter().any(|(name, value)| {
+                        name == "type" && value.eq_ignore_ascii_case("hidden")
+                    }) =>
+            {
+                parser.error(ParseError::Custom(
+                    "Unexpected hidden input start tag token in in table insertion mode",
+                ));
 
-            if _furthest_block.is_none() {
-                parser.open_elements_stack.pop_until(&subject);
+                parser.open_elements_stack.insert_html_element(&token);
+                parser.open_elements_stack.pop();
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "form" => {
+                parser.error(ParseError::Custom(
+                    "Unexpected form start tag token in in table insertion mode",
+                ));
 
-                parser
-                    .active_formatting_elements
-                    .elements
-                    .retain(|el| match el {
-                        ElementOrMarker::Element(e) => !Rc::ptr_eq(&e, &formatting_element),
-                        ElementOrMarker::Marker => true,
-                    });
+                // TODO: Also ignore the token when the form element pointer is set
+                if !parser._is_element_on_open_elements("template") {
+                    parser.open_elements_stack.insert_html_element(&token);
+                    parser.open_elements_stack.pop();
+                }
+            }
+            Token::EOF => {
+                return InsertMode::handle_in_body(parser, token);
+            }
+            _ => {
+                parser.error(ParseError::Custom(
+                    "Unexpected token in in table insertion mode",
+                ));
 
-                return;
+                return InsertMode::_foster_parent(parser, token);
+            }
+        }
+
+        true
+    }
+
+    /// Processes `token` using the rules for the in body insertion mode, with foster parenting
+    /// enabled so that anything inserted ends up in front of the table.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intable
+    fn _foster_parent(parser: &mut Parser, token: Token) -> bool {
+        parser.open_elements_stack.foster_parenting = true;
+        let handled = InsertMode::handle_in_body(parser, token);
+        parser.open_elements_stack.foster_parenting = false;
+
+        handled
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intabletext
+    fn handle_in_table_text(parser: &mut Parser, token: Token) -> bool {
+        match token {
+            Token::Character('\u{0000}') => {
+                parser.error(ParseError::UnexpectedNullCharacter);
+            }
+            Token::Character(ch) => {
+                parser.pending_table_character_tokens.push(ch);
+            }
+            _ => {
+                let pending = std::mem::take(&mut parser.pending_table_character_tokens);
+
+                if pending.iter().any(|ch| !is_ascii_whitespace(*ch)) {
+                    parser.error(ParseError::Custom(
+                        "Unexpected non-whitespace characters in table text",
+                    ));
+
+                    for ch in pending {
+                        InsertMode::_foster_parent(parser, Token::Character(ch));
+                    }
+                } else {
+                    for ch in pending {
+                        parser._insert_character(ch);
+                    }
+                }
+
+                parser.insertion_mode = parser.original_insertion_mode.clone().unwrap();
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-incaption
+    fn handle_in_caption(parser: &mut Parser, token: Token) -> bool {
+        match token {
+            Token::EndTag(ref tag) if tag.name.as_str() == "caption" => {
+                if !parser
+                    .open_elements_stack
+                    .has_element_in_table_scope("caption")
+                {
+                    parser.error(ParseError::Custom(
+                        "Unexpected caption end tag token in in caption insertion mode",
+                    ));
+                    return true;
+                }
+
+                InsertMode::_close_the_caption(parser);
+            }
+            Token::StartTag(ref tag)
+                if matches!(
+                    tag.name.as_str(),
+                    "caption"
+                        | "col"
+                        | "colgroup"
+                        | "tbody"
+                        | "td"
+                        | "tfoot"
+                        | "th"
+                        | "thead"
+                        | "tr"
+                ) =>
+            {
+                return InsertMode::_close_the_caption_and_reprocess(parser);
             }
+            Token::EndTag(ref tag) if tag.name.as_str() == "table" => {
+                return InsertMode::_close_the_caption_and_reprocess(parser);
+            }
+            Token::EndTag(ref tag)
+                if matches!(
+                    tag.name.as_str(),
+                    "body"
+                        | "col"
+                        | "colgroup"
+                        | "html"
+                        | "tbody"
+                        | "td"
+                        | "tfoot"
+                        | "th"
+                        | "thead"
+                        | "tr"
+                ) =>
+            {
+                parser.error(ParseError::Custom(
+                    "Unexpected end tag token in in caption insertion mode",
+                ));
+            }
+            _ => {
+                return InsertMode::handle_in_body(parser, token);
+            }
+        }
+
+        true
+    }
 
-            let furthest_block = _furthest_block.unwrap();
+    fn _close_the_caption_and_reprocess(parser: &mut Parser) -> bool {
+        if !parser
+            .open_elements_stack
+            .has_element_in_table_scope("caption")
+        {
+            parser.error(ParseError::Custom(
+                "Unexpected table token in in caption insertion mode",
+            ));
+            return true;
+        }
 
-            let common_ancestor = parser
-                .open_elements_stack
-                .nth(adjusted_formatting_element_pos.unwrap() - 1)
-                .unwrap();
+        InsertMode::_close_the_caption(parser);
+        false
+    }
 
-            let mut bookmark = adjusted_formatting_element_pos.unwrap();
+    fn _close_the_caption(parser: &mut Parser) {
+        parser.open_elements_stack.generate_implied_end_tags(None);
+
+        if parser
+            .open_elements_stack
+            .current_node()
+            .is_some_and(|el| el.borrow().qualified_name() != "caption")
+        {
+            parser.error(ParseError::Custom(
+                "Unexpected current node when closing the caption",
+            ));
+        }
 
-            let mut node_index = adjusted_furthest_block_pos.unwrap();
-            let last_node_index = adjusted_furthest_block_pos.unwrap();
+        parser.open_elements_stack.pop_until("caption");
+        parser.active_formatting_elements.pop_until_marker();
+        parser.insertion_mode = InsertMode::InTable;
+    }
 
-            // let mut node = Rc::clone(parser.open_elements_stack.nth(node_index).as_ref().unwrap());
-            let mut last_node = Rc::clone(
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-incolgroup
+    fn handle_in_column_group(parser: &mut Parser, token: Token) -> bool {
+        match token {
+            Token::Character(ch) if is_ascii_whitespace(ch) => {
+                parser._insert_character(ch);
+            }
+            Token::Comment(data) => {
+                parser._insert_comment(data.as_str(), None);
+            }
+            Token::DOCTYPE(_) => {
+                parser.error(ParseError::Custom(
+                    "Unexpected DOCTYPE token in in column group insertion mode",
+                ));
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "html" => {
+                return InsertMode::handle_in_body(parser, token);
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "col" => {
+                parser.open_elements_stack.insert_html_element(&token);
+                parser.open_elements_stack.pop();
+            }
+            Token::EndTag(ref tag) if tag.name.as_str() == "colgroup" => {
+                if !InsertMode::_current_node_is(parser, "colgroup") {
+                    parser.error(ParseError::Custom(
+                        "Unexpected colgroup end tag token in in column group insertion mode",
+                    ));
+                    return true;
+                }
+
+                parser.open_elements_stack.pop();
+                parser.insertion_mode = InsertMode::InTable;
+            }
+            Token::EndTag(ref tag) if tag.name.as_str() == "col" => {
+                parser.error(ParseError::Custom(
+                    "Unexpected col end tag token in in column group insertion mode",
+                ));
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "template" => {
+                return InsertMode::handle_in_head(parser, token);
+            }
+            Token::EndTag(ref tag) if tag.name.as_str() == "template" => {
+                return InsertMode::handle_in_head(parser, token);
+            }
+            Token::EOF => {
+                return InsertMode::handle_in_body(parser, token);
+            }
+            _ => {
+                if !InsertMode::_current_node_is(parser, "colgroup") {
+                    parser.error(ParseError::Custom(
+                        "Unexpected token in in column group insertion mode",
+                    ));
+                    return true;
+                }
+
+                parser.open_elements_stack.pop();
+                parser.insertion_mode = InsertMode::InTable;
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn _current_node_is(parser: &Parser, name: &str) -> bool {
+        parser
+            .open_elements_stack
+            .current_node()
+            .is_some_and(|el| el.borrow().qualified_name() == name)
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intbody
+    fn handle_in_table_body(parser: &mut Parser, token: Token) -> bool {
+        match token {
+            Token::StartTag(ref tag) if tag.name.as_str() == "tr" => {
                 parser
                     .open_elements_stack
-                    .nth(last_node_index)
-                    .as_ref()
-                    .unwrap(),
-            );
+                    .clear_back_to_table_body_context();
+                parser.open_elements_stack.insert_html_element(&token);
+                parser.insertion_mode = InsertMode::InRow;
+            }
+            Token::StartTag(ref tag) if matches!(tag.name.as_str(), "td" | "th") => {
+                parser.error(ParseError::Custom(
+                    "Unexpected cell start tag token in in table body insertion mode",
+                ));
 
-            let mut inner_loop_counter = 0;
+                parser
+                    .open_elements_stack
+                    .clear_back_to_table_body_context();
+                parser
+                    .open_elements_stack
+                    .insert_html_element(&Token::StartTag(Tag::new(&String::from("tr"))));
+                parser.insertion_mode = InsertMode::InRow;
+                return false;
+            }
+            Token::EndTag(ref tag) if matches!(tag.name.as_str(), "tbody" | "tfoot" | "thead") => {
+                if !parser
+                    .open_elements_stack
+                    .has_element_in_table_scope(&tag.name)
+                {
+                    parser.error(ParseError::Custom(
+                        "Unexpected end tag token in in table body insertion mode",
+                    ));
+                    return true;
+                }
 
-            loop {
-                inner_loop_counter += 1;
+                parser
+                    .open_elements_stack
+                    .clear_back_to_table_body_context();
+                parser.open_elements_stack.pop();
+                parser.insertion_mode = InsertMode::InTable;
+            }
+            Token::StartTag(ref tag)
+                if matches!(
+                    tag.name.as_str(),
+                    "caption" | "col" | "colgroup" | "tbody" | "tfoot" | "thead"
+                ) =>
+            {
+                return InsertMode::_close_the_table_body_and_reprocess(parser);
+            }
+            Token::EndTag(ref tag) if tag.name.as_str() == "table" => {
+                return InsertMode::_close_the_table_body_and_reprocess(parser);
+            }
+            Token::EndTag(ref tag)
+                if matches!(
+                    tag.name.as_str(),
+                    "body" | "caption" | "col" | "colgroup" | "html" | "td" | "th" | "tr"
+                ) =>
+            {
+                parser.error(ParseError::Custom(
+                    "Unexpected end tag token in in table body insertion mode",
+                ));
+            }
+            _ => {
+                return InsertMode::handle_in_table(parser, token);
+            }
+        }
+
+        true
+    }
 
-                node_index -= 1;
-                let mut node =
-                    Rc::clone(parser.open_elements_stack.nth(node_index).as_ref().unwrap());
+    fn _close_the_table_body_and_reprocess(parser: &mut Parser) -> bool {
+        if !["tbody", "thead", "tfoot"]
+            .iter()
+            .any(|name| parser.open_elements_stack.has_element_in_table_scope(name))
+        {
+            parser.error(ParseError::Custom(
+                "Unexpected table token in in table body insertion mode",
+            ));
+            return true;
+        }
+
+        parser
+            .open_elements_stack
+            .clear_back_to_table_body_context();
+        parser.open_elements_stack.pop();
+        parser.insertion_mode = InsertMode::InTable;
+        false
+    }
 
-                if Rc::ptr_eq(&node, &formatting_element) {
-                    break;
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intr
+    fn handle_in_row(parser: &mut Parser, token: Token) -> bool {
+        match token {
+            Token::StartTag(ref tag) if matches!(tag.name.as_str(), "td" | "th") => {
+                parser.open_elements_stack.clear_back_to_table_row_context();
+                parser.open_elements_stack.insert_html_element(&token);
+                parser.insertion_mode = InsertMode::InCell;
+                parser.active_formatting_elements.push_marker();
+            }
+            Token::EndTag(ref tag) if tag.name.as_str() == "tr" => {
+                if !parser.open_elements_stack.has_element_in_table_scope("tr") {
+                    parser.error(ParseError::Custom(
+                        "Unexpected tr end tag token in in row insertion mode",
+                    ));
+                    return true;
                 }
 
-                if inner_loop_counter > 3 && !parser.open_elements_stack.contains_rc(&node) {
-                    parser
-                        .active_formatting_elements
-                        .elements
-                        .retain(|el| match el {
-                            ElementOrMarker::Element(e) => !Rc::ptr_eq(&e, &node),
-                            ElementOrMarker::Marker => true,
-                        });
+                InsertMode::_close_the_row(parser);
+            }
+            Token::StartTag(ref tag)
+                if matches!(
+                    tag.name.as_str(),
+                    "caption" | "col" | "colgroup" | "tbody" | "tfoot" | "thead" | "tr"
+                ) =>
+            {
+                return InsertMode::_close_the_row_and_reprocess(parser);
+            }
+            Token::EndTag(ref tag) if tag.name.as_str() == "table" => {
+                return InsertMode::_close_the_row_and_reprocess(parser);
+            }
+            Token::EndTag(ref tag) if matches!(tag.name.as_str(), "tbody" | "tfoot" | "thead") => {
+                if !parser
+                    .open_elements_stack
+                    .has_element_in_table_scope(&tag.name)
+                {
+                    parser.error(ParseError::Custom(
+                        "Unexpected end tag token in in row insertion mode",
+                    ));
+                    return true;
+                }
+
+                return InsertMode::_close_the_row_and_reprocess(parser);
+            }
+            Token::EndTag(ref tag)
+                if matches!(
+                    tag.name.as_str(),
+                    "body" | "caption" | "col" | "colgroup" | "html" | "td" | "th"
+                ) =>
+            {
+                parser.error(ParseError::Custom(
+                    "Unexpected end tag token in in row insertion mode",
+                ));
+            }
+            _ => {
+                return InsertMode::handle_in_table(parser, token);
+            }
+        }
+
+        true
+    }
+
+    fn _close_the_row_and_reprocess(parser: &mut Parser) -> bool {
+        if !parser.open_elements_stack.has_element_in_table_scope("tr") {
+            parser.error(ParseError::Custom(
+                "Unexpected table token in in row insertion mode",
+            ));
+            return true;
+        }
+
+        InsertMode::_close_the_row(parser);
+        false
+    }
+
+    fn _close_the_row(parser: &mut Parser) {
+        parser.open_elements_stack.clear_back_to_table_row_context();
+        parser.open_elements_stack.pop();
+        parser.insertion_mode = InsertMode::InTableBody;
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intd
+    fn handle_in_cell(parser: &mut Parser, token: Token) -> bool {
+        match token {
+            Token::EndTag(ref tag) if matches!(tag.name.as_str(), "td" | "th") => {
+                if !parser
+                    .open_elements_stack
+                    .has_element_in_table_scope(&tag.name)
+                {
+                    parser.error(ParseError::Custom(
+                        "Unexpected td/th end tag token in in cell insertion mode",
+                    ));
+                    return true;
                 }
 
-                let common_ancestor_node_kind = NodeKind::Element(Rc::clone(&common_ancestor));
+                InsertMode::_close_the_cell(parser);
+            }
+            Token::StartTag(ref tag)
+                if matches!(
+                    tag.name.as_str(),
+                    "caption"
+                        | "col"
+                        | "colgroup"
+                        | "tbody"
+                        | "td"
+                        | "tfoot"
+                        | "th"
+                        | "thead"
+                        | "tr"
+                ) =>
+            {
+                if !parser.open_elements_stack.has_element_in_table_scope("td")
+                    && !parser.open_elements_stack.has_element_in_table_scope("th")
+                {
+                    parser.error(ParseError::Custom(
+                        "Unexpected table start tag token in in cell insertion mode",
+                    ));
+                    return true;
+                }
 
-                let element = Element::from_token(
-                    node.borrow().token().unwrap(),
-                    html5::HTML_NAMESPACE,
-                    &common_ancestor_node_kind,
-                );
+                InsertMode::_close_the_cell(parser);
+                return false;
+            }
+            Token::EndTag(ref tag)
+                if matches!(
+                    tag.name.as_str(),
+                    "body" | "caption" | "col" | "colgroup" | "html"
+                ) =>
+            {
+                parser.error(ParseError::Custom(
+                    "Unexpected end tag token in in cell insertion mode",
+                ));
+            }
+            Token::EndTag(ref tag)
+                if matches!(
+                    tag.name.as_str(),
+                    "table" | "tbody" | "tfoot" | "thead" | "tr"
+                ) =>
+            {
+                if !parser
+                    .open_elements_stack
+                    .has_element_in_table_scope(&tag.name)
+                {
+                    parser.error(ParseError::Custom(
+                        "Unexpected table end tag token in in cell insertion mode",
+                    ));
+                    return true;
+                }
+
+                InsertMode::_close_the_cell(parser);
+                return false;
+            }
+            _ => {
+                return InsertMode::handle_in_body(parser, token);
+            }
+        }
+
+        true
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#close-the-cell
+    fn _close_the_cell(parser: &mut Parser) {
+        parser.open_elements_stack.generate_implied_end_tags(None);
+
+        if parser
+            .open_elements_stack
+            .current_node()
+            .is_some_and(|el| !matches!(el.borrow().qualified_name().as_str(), "td" | "th"))
+        {
+            parser.error(ParseError::Custom(
+                "Unexpected current node when closing the cell",
+            ));
+        }
+
+        while let Some(element) = parser.open_elements_stack.pop() {
+            if matches!(element.borrow().qualified_name().as_str(), "td" | "th") {
+                break;
+            }
+        }
+
+        parser.active_formatting_elements.pop_until_marker();
+        parser.insertion_mode = InsertMode::InRow;
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-afterbody
+    fn handle_after_body(parser: &mut Parser, token: Token) -> bool {
+        match token {
+            Token::Character('\u{0009}' | '\u{000A}' | '\u{000C}' | '\u{000D}' | '\u{0020}') => {
+                return InsertMode::handle_in_body(parser, token);
+            }
+            Token::Comment(data) => {
+                // The comment goes at the end of the html element, after the body
+                let html = Rc::clone(&parser.open_elements_stack.elements[0]);
+                parser._insert_comment(data.as_str(), Some(OpenElementsStack::end_of(&html)));
+            }
+            Token::DOCTYPE(_) => {
+                parser.error(ParseError::Custom(
+                    "Unexpected DOCTYPE token in after body insertion mode",
+                ));
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "html" => {
+                return InsertMode::handle_in_body(parser, token);
+            }
+            Token::EndTag(ref tag) if tag.name.as_str() == "html" => {
+                // TODO: Ignore the token in the HTML fragment parsing algorithm
+                parser.insertion_mode = InsertMode::AfterAfterBody;
+            }
+            Token::EOF => {
+                return true;
+            }
+            _ => {
+                parser.error(ParseError::Custom(
+                    "Unexpected token in after body insertion mode",
+                ));
 
-                parser.active_formatting_elements.elements[bookmark] =
-                    ElementOrMarker::Element(Rc::clone(&element));
-                parser.open_elements_stack.elements[adjusted_formatting_element_pos.unwrap() - 1] =
-                    Rc::clone(&element);
+                parser.insertion_mode = InsertMode::InBody;
+                return false;
+            }
+        }
 
-                node = Rc::clone(&element);
+        true
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#the-after-after-body-insertion-mode
+    fn handle_after_after_body(parser: &mut Parser, token: Token) -> bool {
+        match token {
+            Token::Comment(data) => {
+                parser._insert_comment_in_document(data.as_str());
+            }
+            Token::DOCTYPE(_)
+            | Token::Character('\u{0009}' | '\u{000A}' | '\u{000C}' | '\u{000D}' | '\u{0020}') => {
+                return InsertMode::handle_in_body(parser, token);
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "html" => {
+                return InsertMode::handle_in_body(parser, token);
+            }
+            Token::EOF => {
+                return true;
+            }
+            _ => {
+                parser.error(ParseError::Custom(
+                    "Unexpected token in after after body insertion mode",
+                ));
+
+                parser.insertion_mode = InsertMode::InBody;
+                return false;
+            }
+        }
 
-                if Rc::ptr_eq(&last_node, &furthest_block) {
-                    bookmark = node_index + 1;
+        true
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inframeset
+    fn handle_in_frameset(parser: &mut Parser, token: Token) -> bool {
+        match token {
+            Token::Character(
+                ch @ ('\u{0009}' | '\u{000A}' | '\u{000C}' | '\u{000D}' | '\u{0020}'),
+            ) => {
+                parser._insert_character(ch);
+            }
+            Token::Comment(data) => {
+                parser._insert_comment(data.as_str(), None);
+            }
+            Token::DOCTYPE(_) => {
+                parser.error(ParseError::Custom(
+                    "Unexpected DOCTYPE token in in frameset insertion mode",
+                ));
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "html" => {
+                return InsertMode::handle_in_body(parser, token);
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "frameset" => {
+                parser.open_elements_stack.insert_html_element(&token);
+            }
+            Token::EndTag(ref tag) if tag.name.as_str() == "frameset" => {
+                if InsertMode::_current_node_is(parser, "html") {
+                    parser.error(ParseError::Custom(
+                        "Unexpected frameset end tag token for the root html element",
+                    ));
+                    return true;
                 }
 
-                Node::append_child(
-                    &node.borrow().node(),
-                    Rc::new(RefCell::new(NodeKind::Element(Rc::clone(&last_node)))),
-                );
+                parser.open_elements_stack.pop();
 
-                last_node = Rc::clone(&node);
+                // TODO: Stay in frameset in the HTML fragment parsing algorithm
+                if !InsertMode::_current_node_is(parser, "frameset") {
+                    parser.insertion_mode = InsertMode::AfterFrameset;
+                }
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "frame" => {
+                parser.open_elements_stack.insert_html_element(&token);
+                parser.open_elements_stack.pop();
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "noframes" => {
+                return InsertMode::handle_in_head(parser, token);
+            }
+            Token::EOF => {
+                if !InsertMode::_current_node_is(parser, "html") {
+                    parser.error(ParseError::Custom(
+                        "Unexpected EOF token in in frameset insertion mode",
+                    ));
+                }
+            }
+            _ => {
+                parser.error(ParseError::Custom(
+                    "Unexpected token in in frameset insertion mode",
+                ));
             }
+        }
 
-            parser
-                .open_elements_stack
-                .appropriate_insertion_place(Some(Rc::clone(&common_ancestor)))
-                .insert(&mut NodeKind::Element(last_node.clone()));
+        true
+    }
 
-            let new_element = Element::from_token(
-                formatting_element.borrow().token().unwrap(),
-                html5::HTML_NAMESPACE,
-                &NodeKind::Element(Rc::clone(&furthest_block)),
-            );
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-afterframeset
+    fn handle_after_frameset(parser: &mut Parser, token: Token) -> bool {
+        match token {
+            Token::Character(
+                ch @ ('\u{0009}' | '\u{000A}' | '\u{000C}' | '\u{000D}' | '\u{0020}'),
+            ) => {
+                parser._insert_character(ch);
+            }
+            Token::Comment(data) => {
+                parser._insert_comment(data.as_str(), None);
+            }
+            Token::DOCTYPE(_) => {
+                parser.error(ParseError::Custom(
+                    "Unexpected DOCTYPE token in after frameset insertion mode",
+                ));
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "html" => {
+                return InsertMode::handle_in_body(parser, token);
+            }
+            Token::EndTag(ref tag) if tag.name.as_str() == "html" => {
+                parser.insertion_mode = InsertMode::AfterAfterFrameset;
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "noframes" => {
+                return InsertMode::handle_in_head(parser, token);
+            }
+            Token::EOF => {}
+            _ => {
+                parser.error(ParseError::Custom(
+                    "Unexpected token in after frameset insertion mode",
+                ));
+            }
+        }
 
-            furthest_block
-                .borrow()
-                .node()
-                .borrow()
-                .child_nodes()
-                .map(|child| {
-                    Node::append_child(&new_element.borrow().node(), Rc::clone(&child));
-                });
+        true
+    }
 
-            Node::append_child(
-                &furthest_block.borrow().node(),
-                Rc::new(RefCell::new(NodeKind::Element(Rc::clone(&new_element)))),
-            );
-
-            parser
-                .active_formatting_elements
-                .elements
-                .retain(|el| match el {
-                    ElementOrMarker::Element(e) => !Rc::ptr_eq(&e, &formatting_element),
-                    ElementOrMarker::Marker => true,
-                });
+    /// https://html.spec.whatwg.org/multipage/parsing.html#the-after-after-frameset-insertion-mode
+    fn handle_after_after_frameset(parser: &mut Parser, token: Token) -> bool {
+        match token {
+            Token::Comment(data) => {
+                parser._insert_comment_in_document(data.as_str());
+            }
+            Token::DOCTYPE(_)
+            | Token::Character('\u{0009}' | '\u{000A}' | '\u{000C}' | '\u{000D}' | '\u{0020}') => {
+                return InsertMode::handle_in_body(parser, token);
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "html" => {
+                return InsertMode::handle_in_body(parser, token);
+            }
+            Token::StartTag(ref tag) if tag.name.as_str() == "noframes" => {
+                return InsertMode::handle_in_head(parser, token);
+            }
+            Token::EOF => {}
+            _ => {
+                parser.error(ParseError::Custom(
+                    "Unexpected token in after after frameset insertion mode",
+                ));
+            }
+        }
 
-            parser
-                .active_formatting_elements
-                .elements
-                .insert(bookmark, ElementOrMarker::Element(Rc::clone(&new_element)));
+        true
+    }
 
-            parser
-                .open_elements_stack
-                .elements
-                .retain(|el| !Rc::ptr_eq(el, &formatting_element));
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intemplate
+    fn handle_in_template(parser: &mut Parser, token: Token) -> bool {
+        match token {
+            Token::Character(_) | Token::Comment(_) | Token::DOCTYPE(_) => {
+                return InsertMode::handle_in_body(parser, token);
+            }
+            Token::StartTag(ref tag)
+                if matches!(
+                    tag.name.as_str(),
+                    "base"
+                        | "basefont"
+                        | "bgsound"
+                        | "link"
+                        | "meta"
+                        | "noframes"
+                        | "script"
+                        | "style"
+                        | "template"
+                        | "title"
+                ) =>
+            {
+                return InsertMode::handle_in_head(parser, token);
+            }
+            Token::EndTag(ref tag) if tag.name.as_str() == "template" => {
+                return InsertMode::handle_in_head(parser, token);
+            }
+            Token::StartTag(ref tag) => {
+                let mode = match tag.name.as_str() {
+                    "caption" | "colgroup" | "tbody" | "tfoot" | "thead" => InsertMode::InTable,
+                    "col" => InsertMode::InColumnGroup,
+                    "tr" => InsertMode::InTableBody,
+                    "td" | "th" => InsertMode::InRow,
+                    _ => InsertMode::InBody,
+                };
+
+                parser.template_insertion_modes.pop();
+                parser.template_insertion_modes.push(mode.clone());
+                parser.insertion_mode = mode;
+
+                return false;
+            }
+            Token::EndTag(_) => {
+                parser.error(ParseError::Custom(
+                    "Unexpected end tag token in in template insertion mode",
+                ));
+            }
+            Token::EOF => {
+                if !parser._is_element_on_open_elements("template") {
+                    return true;
+                }
+
+                parser.error(ParseError::Custom(
+                    "Unexpected EOF token in in template insertion mode",
+                ));
 
-            parser.open_elements_stack.elements.insert(
-                adjusted_furthest_block_pos.unwrap() + 1,
-                Rc::clone(&new_element),
-            );
+                parser.open_elements_stack.pop_until("template");
+                parser.active_formatting_elements.pop_until_marker();
+                parser.template_insertion_modes.pop();
+
+                parser._reset_insertion_mode_appropriately();
+
+                return false;
+            }
         }
+
+        true
     }
 
     pub fn handle(&self, parser: &mut Parser, token: &Token) -> bool {
@@ -1542,9 +2264,20 @@ impl InsertMode {
             InsertMode::AfterHead => InsertMode::handle_after_head(parser, token),
             InsertMode::InBody => InsertMode::handle_in_body(parser, token),
             InsertMode::Text => InsertMode::handle_text(parser, token),
-            _ => {
-                true
-                // todo!("Handle insertion mode {:?}", self);
+            InsertMode::InTable => InsertMode::handle_in_table(parser, token),
+            InsertMode::InTableText => InsertMode::handle_in_table_text(parser, token),
+            InsertMode::InCaption => InsertMode::handle_in_caption(parser, token),
+            InsertMode::InColumnGroup => InsertMode::handle_in_column_group(parser, token),
+            InsertMode::InTableBody => InsertMode::handle_in_table_body(parser, token),
+            InsertMode::InRow => InsertMode::handle_in_row(parser, token),
+            InsertMode::InCell => InsertMode::handle_in_cell(parser, token),
+            InsertMode::InTemplate => InsertMode::handle_in_template(parser, token),
+            InsertMode::AfterBody => InsertMode::handle_after_body(parser, token),
+            InsertMode::AfterAfterBody => InsertMode::handle_after_after_body(parser, token),
+            InsertMode::InFrameset => InsertMode::handle_in_frameset(parser, token),
+            InsertMode::AfterFrameset => InsertMode::handle_after_frameset(parser, token),
+            InsertMode::AfterAfterFrameset => {
+                InsertMode::handle_after_after_frameset(parser, token)
             }
         }
     }
@@ -0,0 +1,322 @@
+use std::{cell::RefCell, ops::Deref, rc::Rc};
+
+use crate::{
+    css::{
+        cssom::{CSSRuleExt, CSSStyleSheet, CSSStyleSheetExt, StyleSheet},
+        parser::{import_urls, parse_stylesheet, preprocess},
+        tokenize::tokenize_from_string,
+    },
+    html5::dom::{Document, Element, Node, NodeKind},
+    http::{Client, url::URL},
+    infra::{InputStream, Serializable},
+};
+
+/// What a subresource is needed for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubresourceKind {
+    /// `<link rel=stylesheet href>`
+    Stylesheet,
+
+    /// `@import` inside a `<style>` element
+    Import,
+
+    /// `<img src>`
+    Image,
+
+    /// `<script src>`
+    Script,
+}
+
+#[derive(Debug, Clone)]
+pub struct Subresource {
+    pub kind: SubresourceKind,
+    pub url: URL,
+
+    /// The element that refers to the resource
+    pub element: Rc<RefCell<Element>>,
+}
+
+/// Scans a parsed document for the resources it refers to, so that they can be fetched ahead of
+/// time. URLs are resolved against the document's base URL, and ones that fail to parse are
+/// skipped.
+///
+/// https://html.spec.whatwg.org/multipage/links.html#link-type-preload
+pub fn collect_subresources(document: &Rc<RefCell<Document>>) -> Vec<Subresource> {
+    let document = document.borrow();
+    let base = document.document_base_url();
+    let encoding = document.charset();
+
+    let mut found = Vec::new();
+    collect_from_node(&document._node.borrow(), &mut found);
+
+    found
+        .into_iter()
+        .filter_map(|(kind, href, element)| {
+            URL::parse(href, Some(base.clone()), Some(encoding))
+                .ok()
+                .map(|url| Subresource { kind, url, element })
+        })
+        .collect()
+}
+
+/// The URL of the icon to show for the document: the `<link rel=icon>` with the largest `sizes`,
+/// where `any` beats every fixed size and links without `sizes` come last. Ties go to the first
+/// link in tree order. Without an icon link this falls back to `/favicon.ico`.
+///
+/// https://html.spec.whatwg.org/multipage/links.html#rel-icon
+pub fn favicon_url(document: &Rc<RefCell<Document>>) -> Option<URL> {
+    let document = document.borrow();
+    let base = document.document_base_url();
+    let encoding = document.charset();
+
+    let mut icons = Vec::new();
+    collect_icons(&document._node.borrow(), &mut icons);
+
+    let mut best: Option<(u32, URL)> = None;
+
+    for (href, sizes) in icons {
+        let Ok(url) = URL::parse(href, Some(base.clone()), Some(encoding)) else {
+            continue;
+        };
+
+        let size = icon_size(sizes.as_deref());
+        if best.as_ref().is_none_or(|(best_size, _)| size > *best_size) {
+            best = Some((size, url));
+        }
+    }
+
+    match best {
+        Some((_, url)) => Some(url),
+        None => URL::parse(
+            String::from("/favicon.ico"),
+            Some(document.url().clone()),
+            Some(encoding),
+        )
+        .ok(),
+    }
+}
+
+/// The largest size in a `sizes` attribute, with `any` as the largest possible.
+///
+/// https://html.spec.whatwg.org/multipage/semantics.html#attr-link-sizes
+fn icon_size(sizes: Option<&str>) -> u32 {
+    sizes
+        .unwrap_or_default()
+        .split_ascii_whitespace()
+        .filter_map(|size| {
+            if size.eq_ignore_ascii_case("any") {
+                return Some(u32::MAX);
+            }
+
+            let (width, height) = size.split_once(['x', 'X'])?;
+            Some(width.parse::<u32>().ok()?.max(height.parse::<u32>().ok()?))
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// The `href` and `sizes` of every `<link rel=icon>` under `node`, in tree order.
+fn collect_icons(node: &Node, found: &mut Vec<(String, Option<String>)>) {
+    for child in node.child_nodes().iter() {
+        let NodeKind::Element(element) = child.borrow().deref().clone() else {
+            continue;
+        };
+
+        let element = element.borrow();
+
+        if element.local_name == "link"
+            && element.rel_tokens().iter().any(|rel| rel == "icon")
+            && let Some(href) = element.get_attribute("href")
+        {
+            found.push((
+                href.to_string(),
+                element.get_attribute("sizes").map(str::to_string),
+            ));
+        }
+
+        collect_icons(&element._node.borrow(), found);
+    }
+}
+
+/// Fetches every `<link rel=stylesheet>` of the document and adds the parsed sheets to its style
+/// sheets, then fetches the `@import`s of sheets from `<style>` elements. Sheets that fail to load
+/// are skipped.
+///
+/// External sheets cascade after the user agent sheet but before sheets from `<style>` elements,
+/// so they're inserted ahead of the first of those, keeping their own tree order.
+///
+/// https://html.spec.whatwg.org/multipage/links.html#link-type-stylesheet
+pub fn load_stylesheets(document: &Rc<RefCell<Document>>, client: &mut Client) {
+    let mut index = first_style_element_sheet(&document.borrow());
+
+    for subresource in collect_subresources(document) {
+        if subresource.kind != SubresourceKind::Stylesheet {
+            continue;
+        }
+
+        let Some(mut stylesheet) =
+            fetch_stylesheet(document, client, &subresource.url, &mut vec![])
+        else {
+            continue;
+        };
+        stylesheet.set_owner_node(Rc::downgrade(&subresource.element));
+
+        document.borrow_mut().insert_stylesheet(index, stylesheet);
+        index += 1;
+    }
+
+    let style_element_sheets = document
+        .borrow()
+        .style_sheets()
+        .style_sheets
+        .iter()
+        .filter_map(|sheet| {
+            let owner = sheet.borrow().owner_node().as_ref()?.upgrade()?;
+            let is_style_element = owner.borrow().local_name == "style";
+
+            is_style_element.then(|| (Rc::clone(sheet), owner))
+        })
+        .collect::<Vec<_>>();
+
+    let base = document.borrow().document_base_url();
+
+    for (sheet, owner) in style_element_sheets {
+        let css = owner.borrow().child_text_content();
+        let imported = imported_rules(document, client, &css, &base, &mut vec![]);
+
+        sheet.borrow_mut().css_rules_mut().splice(0..0, imported);
+    }
+}
+
+/// Fetches and parses the stylesheet at `url`, with the rules of the sheets it `@import`s inlined
+/// ahead of its own. `chain` holds the URLs of the sheets that led to this one being imported, so
+/// that an import cycle is cut off instead of being followed forever.
+///
+/// https://drafts.csswg.org/css-cascade-5/#fetch-an-import
+fn fetch_stylesheet(
+    document: &Rc<RefCell<Document>>,
+    client: &mut Client,
+    url: &URL,
+    chain: &mut Vec<String>,
+) -> Option<CSSStyleSheet> {
+    let location = url.serialize();
+    if chain.contains(&location) {
+        return None;
+    }
+
+    let response = client.fetch(url)?;
+    if !response.status().is_some_and(|status| status.is_success()) {
+        return None;
+    }
+
+    let css = response.body.unwrap_or_default();
+
+    chain.push(location.clone());
+    let imported = imported_rules(document, client, &css, url, chain);
+    chain.pop();
+
+    let tokens = tokenize_from_string(preprocess(&css));
+    let mut stylesheet = parse_stylesheet(
+        &mut InputStream::new(&tokens),
+        Rc::downgrade(document),
+        Some(location),
+    );
+    stylesheet.css_rules_mut().splice(0..0, imported);
+
+    Some(stylesheet)
+}
+
+/// The rules of every sheet `@import`ed by `css`, in import order. Relative import URLs are
+/// resolved against `base`, the URL of the importing sheet.
+fn imported_rules(
+    document: &Rc<RefCell<Document>>,
+    client: &mut Client,
+    css: &str,
+    base: &URL,
+    chain: &mut Vec<String>,
+) -> Vec<Box<dyn CSSRuleExt>> {
+    let encoding = document.borrow().charset();
+    let mut rules = Vec::new();
+
+    for href in import_urls(css) {
+        let Ok(url) = URL::parse(href, Some(base.clone()), Some(encoding)) else {
+            continue;
+        };
+
+        if let Some(mut imported) = fetch_stylesheet(document, client, &url, chain) {
+            rules.append(imported.css_rules_mut());
+        }
+    }
+
+    rules
+}
+
+/// The index of the first sheet owned by a `<style>` element, or the end of the list.
+fn first_style_element_sheet(document: &Document) -> usize {
+    let style_sheets = &document.style_sheets().style_sheets;
+
+    style_sheets
+        .iter()
+        .position(|sheet| {
+            sheet
+                .borrow()
+                .owner_node()
+                .as_ref()
+                .and_then(|owner| owner.upgrade())
+                .is_some_and(|owner| owner.borrow().local_name == "style")
+        })
+        .unwrap_or(style_sheets.len())
+}
+
+fn collect_from_node(
+    node: &Node,
+    found: &mut Vec<(SubresourceKind, String, Rc<RefCell<Element>>)>,
+) {
+    for child in node.child_nodes().iter() {
+        let NodeKind::Element(element_rc) = child.borrow().deref().clone() else {
+            continue;
+        };
+
+        let element = element_rc.borrow();
+
+        match element.local_name.as_str() {
+            "link" => {
+                let is_stylesheet = element.rel_tokens().iter().any(|rel| rel == "stylesheet");
+
+                if is_stylesheet && let Some(href) = element.get_attribute("href") {
+                    found.push((
+                        SubresourceKind::Stylesheet,
+                        href.to_string(),
+                        Rc::clone(&element_rc),
+                    ));
+                }
+            }
+            "style" => {
+                for url in import_urls(&element.child_text_content()) {
+                    found.push((SubresourceKind::Import, url, Rc::clone(&element_rc)));
+                }
+            }
+            "img" => {
+                if let Some(src) = element.get_attribute("src") {
+                    found.push((
+                        SubresourceKind::Image,
+                        src.to_string(),
+                        Rc::clone(&element_rc),
+                    ));
+                }
+            }
+            "script" => {
+                if let Some(src) = element.get_attribute("src") {
+                    found.push((
+                        SubresourceKind::Script,
+                        src.to_string(),
+                        Rc::clone(&element_rc),
+                    ));
+                }
+            }
+            _ => {}
+        }
+
+        collect_from_node(&element._node.borrow(), found);
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    collections::HashMap,
+    time::{Instant, SystemTime},
+};
+
+use crate::{
+    http::{self, Header, Response, StatusCode, date::parse_http_date},
+    infra::Serializable,
+};
+
+/// The directives of a `Cache-Control` response header that the cache acts on. Unknown directives
+/// are ignored.
+///
+/// https://httpwg.org/specs/rfc9111.html#cache-response-directive
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CacheControl {
+    /// Seconds the response stays fresh for
+    pub max_age: Option<u64>,
+
+    /// The response may be stored, but must be revalidated before every use
+    pub no_cache: bool,
+
+    /// The response must not be stored at all
+    pub no_store: bool,
+
+    /// Only a private (single user) cache may store the response
+    pub private: bool,
+
+    /// Any cache may store the response
+    pub public: bool,
+
+    /// A stale response must not be used without revalidating it
+    pub must_revalidate: bool,
+}
+
+impl CacheControl {
+    pub fn parse(value: &str) -> Self {
+        let mut cache_control = CacheControl::default();
+
+        for directive in value.split(',') {
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name.trim(), Some(argument.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+
+            match name.to_ascii_lowercase().as_str() {
+                "max-age" => {
+                    cache_control.max_age = argument.and_then(|seconds| seconds.parse().ok())
+                }
+                "no-cache" => cache_control.no_cache = true,
+                "no-store" => cache_control.no_store = true,
+                "private" => cache_control.private = true,
+                "public" => cache_control.public = true,
+                "must-revalidate" => cache_control.must_revalidate = true,
+                _ => {}
+            }
+        }
+
+        cache_control
+    }
+
+    pub fn from_response(response: &Response) -> Self {
+        response
+            .get_header_value(String::from("Cache-Control"))
+            .map(|value| Self::parse(&value))
+            .unwrap_or_default()
+    }
+}
+
+/// A stored response along with what's needed to decide whether it can still be used.
+///
+/// https://httpwg.org/specs/rfc9111.html#storing.fields
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub response: Response,
+    stored_at: Instant,
+
+    /// How long the response stays fresh for, in seconds
+    max_age: u64,
+
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl CacheEntry {
+    /// https://httpwg.org/specs/rfc9111.html#expiration.model
+    pub fn is_fresh(&self) -> bool {
+        self.stored_at.elapsed().as_secs() < self.max_age
+    }
+
+    /// The conditional request headers to revalidate a stale entry with.
+    ///
+    /// https://httpwg.org/specs/rfc9111.html#validation.sent
+    pub fn validators(&self) -> Vec<Header> {
+        let mut headers = vec![];
+
+        if let Some(etag) = &self.etag {
+            headers.push(Header::new(String::from("If-None-Match"), etag.clone()));
+        }
+
+        if let Some(last_modified) = &self.last_modified {
+            headers.push(Header::new(
+                String::from("If-Modified-Since"),
+                last_modified.clone(),
+            ));
+        }
+
+        headers
+    }
+}
+
+/// A private HTTP cache, keyed by the URL of the request without its fragment.
+///
+/// https://httpwg.org/specs/rfc9111.html
+#[derive(Default)]
+pub struct HttpCache {
+    entries: HashMap<String, CacheEntry>,
+}
+
+impl HttpCache {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+
+    fn key(url: &http::url::URL) -> String {
+        let mut url = url.clone();
+        url.fragment = None;
+        url.serialize()
+    }
+
+    pub fn lookup(&self, url: &http::url::URL) -> Option<&CacheEntry> {
+        self.entries.get(&Self::key(url))
+    }
+
+    /// Stores `response` if it's a successful response that's either fresh for a while or can be
+    /// revalidated. Responses marked `no-store` are never kept.
+    ///
+    /// https://httpwg.org/specs/rfc9111.html#response.cacheability
+    pub fn store(&mut self, url: &http::url::URL, response: &Response) {
+        if response.status() != Some(StatusCode::OK) {
+            return;
+        }
+
+        let cache_control = CacheControl::from_response(response);
+
+        if cache_control.no_store {
+            return;
+        }
+
+        let max_age = freshness_lifetime(response, &cache_control);
+
+        let etag = response.get_header_value(String::from("ETag"));
+        let last_modified = response.get_header_value(String::from("Last-Modified"));
+
+        if max_age == 0 && etag.is_none() && last_modified.is_none() {
+            return;
+        }
+
+        self.entries.insert(
+            Self::key(url),
+            CacheEntry {
+                response: response.clone(),
+                stored_at: Instant::now(),
+                max_age,
+                etag,
+                last_modified,
+            },
+        );
+    }
+
+    /// Handles a `304 Not Modified` for `url` by renewing the stored entry and handing back its
+    /// response. The headers of the `304` replace the stored ones, so freshness is worked out again
+    /// from the new `Cache-Control`, `Expires` and `Date`.
+    ///
+    /// https://httpwg.org/specs/rfc9111.html#freshening.responses
+    pub fn revalidate(
+        &mut self,
+        url: &http::url::URL,
+        not_modified: &Response,
+    ) -> Option<Response> {
+        let entry = self.entries.get_mut(&Self::key(url))?;
+
+        // https://httpwg.org/specs/rfc9111.html#update
+        for header in &not_modified.headers {
+            if header.name.eq_ignore_ascii_case("Content-Length") {
+                continue;
+            }
+
+            entry
+                .response
+                .headers
+                .retain(|stored| !stored.name.eq_ignore_ascii_case(&header.name));
+            entry.response.headers.push(header.clone());
+        }
+
+        let cache_control = CacheControl::from_response(&entry.response);
+        entry.max_age = freshness_lifetime(&entry.response, &cache_control);
+        entry.etag = entry.response.get_header_value(String::from("ETag"));
+        entry.last_modified = entry
+            .response
+            .get_header_value(String::from("Last-Modified"));
+        entry.stored_at = Instant::now();
+
+        Some(entry.response.clone())
+    }
+}
+
+/// How many seconds `response` stays fresh for after being received. An explicit `max-age` wins
+/// over `Expires`, and without either a tenth of the time since `Last-Modified` is used.
+///
+/// https://httpwg.org/specs/rfc9111.html#calculating.freshness.lifetime
+fn freshness_lifetime(response: &Response, cache_control: &CacheControl) -> u64 {
+    if cache_control.no_cache {
+        return 0;
+    }
+
+    if let Some(max_age) = cache_control.max_age {
+        return max_age;
+    }
+
+    let header_date = |name: &str| {
+        response
+            .get_header_value(String::from(name))
+            .and_then(|value| parse_http_date(&value))
+    };
+    let date = header_date("Date").unwrap_or_else(SystemTime::now);
+
+    // An Expires that can't be parsed means the response is already stale
+    if let Some(expires) = response.get_header_value(String::from("Expires")) {
+        return parse_http_date(&expires)
+            .and_then(|expires| expires.duration_since(date).ok())
+            .map_or(0, |lifetime| lifetime.as_secs());
+    }
+
+    // https://httpwg.org/specs/rfc9111.html#heuristic.freshness
+    header_date("Last-Modified")
+        .and_then(|last_modified| date.duration_since(last_modified).ok())
+        .map_or(0, |age| age.as_secs() / 10)
+}
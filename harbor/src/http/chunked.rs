@@ -0,0 +1,156 @@
+use std::fmt;
+
+/// Why a chunked body couldn't be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkedError {
+    /// The body ended before the last chunk and trailer section did
+    Incomplete,
+
+    /// A chunk size isn't a hexadecimal number, or is too big
+    InvalidChunkSize,
+
+    /// A chunk's data isn't followed by a line break
+    MissingLineBreak,
+}
+
+impl fmt::Display for ChunkedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            ChunkedError::Incomplete => "chunked body ended before its last chunk",
+            ChunkedError::InvalidChunkSize => "invalid chunk size",
+            ChunkedError::MissingLineBreak => "chunk data not followed by a line break",
+        };
+
+        write!(f, "{}", message)
+    }
+}
+
+#[derive(Debug, Default)]
+enum ChunkedState {
+    /// Reading a chunk size line
+    #[default]
+    Size,
+
+    /// Reading a chunk's data, this many bytes of which are left
+    Data(usize),
+
+    /// Reading the line break after a chunk's data
+    DataEnd,
+
+    /// Reading the trailer fields after the last chunk, which are discarded
+    Trailer,
+
+    Done,
+    Failed(ChunkedError),
+}
+
+/// Undoes chunked transfer coding as the body comes in, a piece at a time.
+///
+/// https://httpwg.org/specs/rfc9112.html#chunked.encoding
+#[derive(Debug, Default)]
+pub struct ChunkedDecoder {
+    state: ChunkedState,
+
+    /// The part of the current line that's been read so far
+    line: Vec<u8>,
+
+    body: Vec<u8>,
+}
+
+impl ChunkedDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feed(&mut self, data: &[u8]) {
+        let mut data = data;
+
+        while !data.is_empty() {
+            match self.state {
+                ChunkedState::Data(remaining) => {
+                    let length = remaining.min(data.len());
+                    self.body.extend_from_slice(&data[..length]);
+                    data = &data[length..];
+
+                    self.state = match remaining - length {
+                        0 => ChunkedState::DataEnd,
+                        remaining => ChunkedState::Data(remaining),
+                    };
+                }
+                ChunkedState::Size | ChunkedState::DataEnd | ChunkedState::Trailer => {
+                    let Some(end) = data.iter().position(|&byte| byte == b'\n') else {
+                        self.line.extend_from_slice(data);
+                        return;
+                    };
+
+                    self.line.extend_from_slice(&data[..end]);
+                    data = &data[end + 1..];
+
+                    let mut line = std::mem::take(&mut self.line);
+                    if line.last() == Some(&b'\r') {
+                        line.pop();
+                    }
+
+                    self.state = self.after_line(&line);
+                }
+                ChunkedState::Done | ChunkedState::Failed(_) => return,
+            }
+        }
+    }
+
+    /// The state to move to once `line` has been read, without its line break.
+    fn after_line(&self, line: &[u8]) -> ChunkedState {
+        match self.state {
+            ChunkedState::Size => match parse_chunk_size(line) {
+                Some(0) => ChunkedState::Trailer,
+                Some(size) => ChunkedState::Data(size),
+                None => ChunkedState::Failed(ChunkedError::InvalidChunkSize),
+            },
+            ChunkedState::DataEnd if line.is_empty() => ChunkedState::Size,
+            ChunkedState::DataEnd => ChunkedState::Failed(ChunkedError::MissingLineBreak),
+            // The trailer section ends with an empty line
+            ChunkedState::Trailer if line.is_empty() => ChunkedState::Done,
+            ChunkedState::Trailer => ChunkedState::Trailer,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Whether the last chunk and trailer section have been read, or the body turned out to be
+    /// malformed. Either way, nothing more will be decoded.
+    pub fn is_complete(&self) -> bool {
+        matches!(self.state, ChunkedState::Done | ChunkedState::Failed(_))
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>, ChunkedError> {
+        match self.state {
+            ChunkedState::Done => Ok(self.body),
+            ChunkedState::Failed(error) => Err(error),
+            _ => Err(ChunkedError::Incomplete),
+        }
+    }
+}
+
+/// Decodes a whole chunked body, concatenating the data of its chunks.
+pub fn decode_chunked(data: &[u8]) -> Result<Vec<u8>, ChunkedError> {
+    let mut decoder = ChunkedDecoder::new();
+    decoder.feed(data);
+    decoder.finish()
+}
+
+/// Parses the hexadecimal size at the start of a chunk size line, ignoring any chunk extensions
+/// after it.
+///
+/// https://httpwg.org/specs/rfc9112.html#chunked.extension
+fn parse_chunk_size(line: &[u8]) -> Option<usize> {
+    let size = match line.iter().position(|&byte| byte == b';') {
+        Some(extensions) => &line[..extensions],
+        None => line,
+    };
+
+    let size = std::str::from_utf8(size).ok()?.trim_matches([' ', '\t']);
+    if size.is_empty() || !size.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    usize::from_str_radix(size, 16).ok()
+}
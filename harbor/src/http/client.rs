@@ -2,6 +2,8 @@ use std::fmt;
 use std::io::{Read, Write};
 use std::net::{SocketAddr, TcpStream};
 use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 use crate::http;
 use crate::infra::Serializable;
@@ -9,6 +11,10 @@ use crate::infra::Serializable;
 pub const MINIMUM_CHUNK_LENGTH: usize = 8;
 pub const CHUNK_LENGTH: usize = 512;
 
+/// The most a response body may decompress to, so that a small compressed body can't take up
+/// all of the memory there is
+pub const MAX_DECODED_BODY_LENGTH: usize = 64 * 1024 * 1024;
+
 pub const BLUE: &str = "\x1b[1;34m";
 pub const GREEN: &str = "\x1b[1;32m";
 pub const YELLOW: &str = "\x1b[1;33m";
@@ -18,6 +24,22 @@ pub const BOLD: &str = "\x1b[1m";
 
 pub const RESET: &str = "\x1b[0m";
 
+pub const DEFAULT_USER_AGENT: &str = "Harbor Browser";
+
+/// https://fetch.spec.whatwg.org/#fetching (step 12.2, for navigations)
+pub const DEFAULT_NAVIGATION_ACCEPT: &str =
+    "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+
+pub const DEFAULT_ACCEPT_LANGUAGE: &str = "en-US,en;q=0.9";
+
+/// The content codings `http::content_coding` can decode
+pub const DEFAULT_ACCEPT_ENCODING: &str = "gzip, deflate";
+
+/// How many redirects are followed before giving up on a request.
+///
+/// https://fetch.spec.whatwg.org/#http-redirect-fetch
+pub const DEFAULT_MAX_REDIRECTS: u32 = 20;
+
 trait ReqEncodable {
     fn encode(&self) -> String;
 }
@@ -28,6 +50,15 @@ pub enum RequestIntegrityErrorKind {
     InvalidHeaders,
     InvalidBody,
     NoConnection,
+
+    /// The response body couldn't be decoded according to its `Transfer-Encoding`
+    InvalidTransferEncoding,
+
+    /// The response body couldn't be decoded according to its `Content-Encoding`
+    InvalidContentEncoding,
+
+    /// Reading from or writing to the connection failed
+    Connection(std::io::ErrorKind),
 }
 
 #[derive(Debug)]
@@ -36,6 +67,33 @@ pub struct RequestIntegrityError {
     pub message: String,
 }
 
+impl RequestIntegrityError {
+    fn connection(error: std::io::Error) -> Self {
+        Self {
+            kind: RequestIntegrityErrorKind::Connection(error.kind()),
+            message: error.to_string(),
+        }
+    }
+
+    /// Whether the request might succeed if it were sent again.
+    pub fn is_transient(&self) -> bool {
+        use std::io::ErrorKind;
+
+        matches!(
+            self.kind,
+            RequestIntegrityErrorKind::Connection(
+                ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::TimedOut
+                    | ErrorKind::WouldBlock
+                    | ErrorKind::Interrupted
+                    | ErrorKind::UnexpectedEof
+            )
+        )
+    }
+}
+
 impl fmt::Display for RequestIntegrityError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -64,12 +122,10 @@ pub enum Protocol {
 }
 
 impl Protocol {
-    pub fn connect(&self, addr: String) -> Option<TcpStream> {
+    pub fn connect(&self, addr: String) -> std::io::Result<TcpStream> {
         match self {
-            Protocol::HTTP0_9 | Protocol::HTTP1_0 | Protocol::HTTP1_1 => {
-                TcpStream::connect(addr).ok()
-            }
-            _ => None,
+            Protocol::HTTP0_9 | Protocol::HTTP1_0 | Protocol::HTTP1_1 => TcpStream::connect(addr),
+            _ => Err(std::io::ErrorKind::Unsupported.into()),
         }
     }
 }
@@ -222,15 +278,17 @@ impl Request {
                 if let Some(stream) = client.connection.as_mut() {
                     // let mut stream = client.connection;
 
-                    if let Err(e) = stream.cs_write(self.encode().as_bytes()) {
-                        eprintln!("Error in sending request: {}", e);
-                    }
+                    stream
+                        .cs_write(self.encode().as_bytes())
+                        .map_err(RequestIntegrityError::connection)?;
 
                     let mut response = Response::new();
 
                     loop {
                         let mut chunk = [0; CHUNK_LENGTH];
-                        let bytes_read = stream.cs_read(&mut chunk);
+                        let bytes_read = stream
+                            .cs_read(&mut chunk)
+                            .map_err(RequestIntegrityError::connection)?;
 
                         if bytes_read == 0 {
                             break;
@@ -251,9 +309,9 @@ impl Request {
             }
             Protocol::HTTP1_0 | Protocol::HTTP1_1 => {
                 if let Some(stream) = client.connection.as_mut() {
-                    if let Err(e) = stream.cs_write(self.encode().as_bytes()) {
-                        eprintln!("Error in writing: {}", e);
-                    }
+                    stream
+                        .cs_write(self.encode().as_bytes())
+                        .map_err(RequestIntegrityError::connection)?;
 
                     let mut response_decoder = ResponseDecoder::new();
 
@@ -261,24 +319,23 @@ impl Request {
 
                     loop {
                         let mut resp: [u8; 512] = [0; 512];
-                        let bytes_read = stream.cs_read(&mut resp);
+                        let bytes_read = stream
+                            .cs_read(&mut resp)
+                            .map_err(RequestIntegrityError::connection)?;
                         if bytes_read == 0 {
                             break;
                         }
 
-                        response_decoder.decode(&resp[..bytes_read]);
+                        response_decoder.decode(&resp[..bytes_read])?;
 
-                        if response_decoder.is_complete {
+                        if response_decoder.is_complete
+                            || response_decoder.ends_with_head(&self.method)
+                        {
                             break;
                         }
 
                         if let Some(len) = content_length {
-                            if response_decoder
-                                .response
-                                .body
-                                .as_ref()
-                                .is_some_and(|body| body.len() >= len)
-                            {
+                            if response_decoder.body_length() >= len {
                                 break;
                             }
                         } else if let Some(len) = response_decoder
@@ -287,15 +344,13 @@ impl Request {
                         {
                             content_length = Some(len.parse::<usize>().unwrap());
 
-                            if let Some(body) = response_decoder.response.body.as_ref() {
-                                if body.len() >= content_length.unwrap() {
-                                    break;
-                                }
+                            if response_decoder.body_length() >= content_length.unwrap() {
+                                break;
                             }
                         }
                     }
 
-                    Ok(response_decoder.response)
+                    response_decoder.finish()
                 } else {
                     Err(RequestIntegrityError {
                         kind: RequestIntegrityErrorKind::NoConnection,
@@ -336,6 +391,16 @@ pub struct ResponseDecoder {
     state: ResponseDecoderState,
     response: Response,
 
+    /// The head as it's been received so far, which is only read once the blank line that
+    /// ends it arrives
+    head: Vec<u8>,
+
+    /// The body as it was received, which is only decoded once all of it has been
+    body: Option<Vec<u8>>,
+
+    /// Takes the place of `body` when the body is sent in chunks
+    chunked: Option<http::chunked::ChunkedDecoder>,
+
     is_complete: bool,
 }
 
@@ -344,8 +409,109 @@ impl ResponseDecoder {
         Self::default()
     }
 
-    pub fn decode(&mut self, data: &[u8]) {
-        let mut string_data = String::from_utf8(data.to_vec()).unwrap();
+    /// Whether the whole response has arrived once its head has, as happens for responses to
+    /// `HEAD` and for statuses that never have a body. Waiting for more would otherwise hang on a
+    /// connection that's kept alive.
+    ///
+    /// https://httpwg.org/specs/rfc9112.html#message.body.length
+    pub fn ends_with_head(&self, method: &str) -> bool {
+        matches!(self.state, ResponseDecoderState::Body)
+            && (method == "HEAD" || self.response.status().is_some_and(|s| s.has_no_body()))
+    }
+
+    /// How many bytes of the body have been received, before any content coding is undone.
+    /// This is what `Content-Length` counts.
+    pub fn body_length(&self) -> usize {
+        self.body.as_ref().map_or(0, |body| body.len())
+    }
+
+    pub fn decode(&mut self, data: &[u8]) -> Result<(), RequestIntegrityError> {
+        if matches!(self.state, ResponseDecoderState::Body) {
+            if data.is_empty() {
+                return Ok(());
+            }
+
+            if self.response.is_chunked() {
+                // The last chunk marks the end of the body, rather than a Content-Length
+                let chunked = self.chunked.get_or_insert_default();
+                chunked.feed(data);
+                self.is_complete = chunked.is_complete();
+            } else {
+                self.body.get_or_insert_default().extend_from_slice(data);
+            }
+
+            return Ok(());
+        }
+
+        // Only the head is text, so the body (which may well be compressed) is split off
+        // before anything is read as UTF-8. The blank line that ends the head may be split
+        // across reads, so the head is gathered until all of it is here.
+        self.head.extend_from_slice(data);
+
+        let Some(head_length) = self
+            .head
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .map(|position| position + 4)
+        else {
+            return Ok(());
+        };
+
+        let mut head = std::mem::take(&mut self.head);
+        let body = head.split_off(head_length);
+
+        let head = String::from_utf8(head).map_err(|_| RequestIntegrityError {
+            kind: RequestIntegrityErrorKind::InvalidHeaders,
+            message: String::from("Response head is not valid UTF-8"),
+        })?;
+
+        self.decode_head(&head);
+
+        // Everything after the blank line is body, whatever the head left off reading
+        self.state = ResponseDecoderState::Body;
+
+        self.decode(&body)
+    }
+
+    /// Undoes the transfer and content codings of the body and finishes the response.
+    ///
+    /// https://httpwg.org/specs/rfc9110.html#field.content-encoding
+    pub fn finish(mut self) -> Result<Response, RequestIntegrityError> {
+        if let Some(chunked) = self.chunked.take() {
+            let body = chunked.finish().map_err(|e| RequestIntegrityError {
+                kind: RequestIntegrityErrorKind::InvalidTransferEncoding,
+                message: format!("Couldn't decode chunked response body: {}", e),
+            })?;
+
+            self.body = Some(body);
+        }
+
+        let Some(body) = self.body.take() else {
+            return Ok(self.response);
+        };
+
+        let body = match self
+            .response
+            .get_header_value("Content-Encoding".to_string())
+        {
+            Some(content_encoding) => http::content_coding::decode_content(
+                &content_encoding,
+                &body,
+                MAX_DECODED_BODY_LENGTH,
+            )
+            .map_err(|e| RequestIntegrityError {
+                kind: RequestIntegrityErrorKind::InvalidContentEncoding,
+                message: format!("Couldn't decode {} response body: {}", content_encoding, e),
+            })?,
+            None => body,
+        };
+
+        self.response.body = Some(String::from_utf8_lossy(&body).into_owned());
+        Ok(self.response)
+    }
+
+    fn decode_head(&mut self, data: &str) {
+        let mut string_data = data.to_string();
 
         if !matches!(self.state, ResponseDecoderState::Body) {
             string_data = string_data.trim_start_matches(' ').to_string();
@@ -366,7 +532,7 @@ impl ResponseDecoder {
 
                 self.state = ResponseDecoderState::Status;
 
-                self.decode(remaining.as_bytes())
+                self.decode_head(remaining)
             }
             // All following states may be false alerts, brought about by a recursion when the
             // string data is insufficient to properly construct the required field
@@ -381,7 +547,7 @@ impl ResponseDecoder {
 
                 self.state = ResponseDecoderState::Reason;
 
-                self.decode(remaining.as_bytes())
+                self.decode_head(remaining)
             }
 
             ResponseDecoderState::Reason => {
@@ -395,7 +561,7 @@ impl ResponseDecoder {
 
                     let remaining = string_data.strip_prefix("\r\n").unwrap();
 
-                    return self.decode(remaining.as_bytes());
+                    return self.decode_head(remaining);
                 }
 
                 if string_data.contains("\r\n") {
@@ -415,7 +581,7 @@ impl ResponseDecoder {
 
                     self.state = ResponseDecoderState::HeaderName;
 
-                    return self.decode(remaining.as_bytes());
+                    return self.decode_head(remaining);
                 } else {
                     // Data contains only partial reason
                     match &self.response.reason {
@@ -443,7 +609,7 @@ impl ResponseDecoder {
 
                     self.state = ResponseDecoderState::HeaderValue;
 
-                    return self.decode(remaining.as_bytes());
+                    return self.decode_head(remaining);
                 }
 
                 if string_data.starts_with("\r\n") {
@@ -452,7 +618,7 @@ impl ResponseDecoder {
 
                     self.state = ResponseDecoderState::Body;
 
-                    return self.decode(remaining.as_bytes());
+                    return self.decode_head(remaining);
                 }
 
                 if string_data.contains(":") {
@@ -480,7 +646,7 @@ impl ResponseDecoder {
 
                     self.state = ResponseDecoderState::HeaderValue;
 
-                    return self.decode(remaining.as_bytes());
+                    return self.decode_head(remaining);
                 } else {
                     // First or middle part of name in string data
                     match self.response.headers.last_mut() {
@@ -521,7 +687,7 @@ impl ResponseDecoder {
 
                     self.state = ResponseDecoderState::HeaderName;
 
-                    return self.decode(remaining.as_bytes());
+                    return self.decode_head(remaining);
                 }
 
                 if string_data.contains("\r\n") {
@@ -550,7 +716,7 @@ impl ResponseDecoder {
                         // }
                     }
 
-                    return self.decode(remaining.as_bytes());
+                    return self.decode_head(remaining);
                 } else {
                     // First or middle part of value in string data
                     let previous = self.response.headers.last_mut().unwrap();
@@ -558,18 +724,112 @@ impl ResponseDecoder {
                 }
             }
 
-            ResponseDecoderState::Body => {
-                if string_data.len() == 0 {
-                    return;
-                }
+            ResponseDecoderState::Body => {}
+        }
+    }
+}
 
-                match &self.response.body {
-                    Some(body) => self.response.body = Some(body.to_owned() + string_data.as_str()),
-                    None => self.response.body = Some(string_data),
-                }
-            }
+/// https://httpwg.org/specs/rfc9110.html#status.codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StatusCode(pub u32);
+
+impl StatusCode {
+    pub const OK: StatusCode = StatusCode(200);
+    pub const NOT_MODIFIED: StatusCode = StatusCode(304);
+
+    pub fn is_success(&self) -> bool {
+        (200..=299).contains(&self.0)
+    }
+
+    /// Only the statuses that come with a `Location` to follow, so not `300 Multiple Choices` or
+    /// `304 Not Modified`.
+    ///
+    /// https://fetch.spec.whatwg.org/#redirect-status
+    pub fn is_redirect(&self) -> bool {
+        matches!(self.0, 301 | 302 | 303 | 307 | 308)
+    }
+
+    /// The method to request the `Location` of this redirect with, when `method` was used for the
+    /// request that got it. `303 See Other` always asks for a `GET`, and `301` and `302` change a
+    /// `POST` into one as browsers always have, while `307` and `308` keep the method.
+    ///
+    /// https://fetch.spec.whatwg.org/#http-redirect-fetch
+    pub fn method_after_redirect<'a>(&self, method: &'a str) -> &'a str {
+        match self.0 {
+            301 | 302 if method == "POST" => "GET",
+            303 if method != "GET" && method != "HEAD" => "GET",
+            _ => method,
         }
     }
+
+    /// Informational, `204 No Content` and `304 Not Modified` responses never have a body, so
+    /// they end with their head whatever their headers say.
+    ///
+    /// https://httpwg.org/specs/rfc9112.html#message.body.length
+    pub fn has_no_body(&self) -> bool {
+        matches!(self.0, 100..=199 | 204 | 304)
+    }
+
+    pub fn is_client_error(&self) -> bool {
+        (400..=499).contains(&self.0)
+    }
+
+    pub fn is_server_error(&self) -> bool {
+        (500..=599).contains(&self.0)
+    }
+
+    /// The reason phrase registered for the status, which servers aren't required to send.
+    ///
+    /// https://www.iana.org/assignments/http-status-codes/http-status-codes.xhtml
+    pub fn reason_phrase(&self) -> Option<&'static str> {
+        Some(match self.0 {
+            100 => "Continue",
+            101 => "Switching Protocols",
+            200 => "OK",
+            201 => "Created",
+            202 => "Accepted",
+            203 => "Non-Authoritative Information",
+            204 => "No Content",
+            205 => "Reset Content",
+            206 => "Partial Content",
+            300 => "Multiple Choices",
+            301 => "Moved Permanently",
+            302 => "Found",
+            303 => "See Other",
+            304 => "Not Modified",
+            307 => "Temporary Redirect",
+            308 => "Permanent Redirect",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            406 => "Not Acceptable",
+            408 => "Request Timeout",
+            409 => "Conflict",
+            410 => "Gone",
+            411 => "Length Required",
+            412 => "Precondition Failed",
+            413 => "Content Too Large",
+            414 => "URI Too Long",
+            415 => "Unsupported Media Type",
+            416 => "Range Not Satisfiable",
+            417 => "Expectation Failed",
+            421 => "Misdirected Request",
+            422 => "Unprocessable Content",
+            426 => "Upgrade Required",
+            428 => "Precondition Required",
+            429 => "Too Many Requests",
+            431 => "Request Header Fields Too Large",
+            500 => "Internal Server Error",
+            501 => "Not Implemented",
+            502 => "Bad Gateway",
+            503 => "Service Unavailable",
+            504 => "Gateway Timeout",
+            505 => "HTTP Version Not Supported",
+            _ => return None,
+        })
+    }
 }
 
 /// https://developer.mozilla.org/en-US/docs/Web/HTTP/Guides/Messages#http_responses
@@ -593,6 +853,10 @@ impl Response {
         Default::default()
     }
 
+    pub fn status(&self) -> Option<StatusCode> {
+        self.status_code.map(StatusCode)
+    }
+
     fn decode_body_chunk(&mut self, chunk: &[u8]) {
         let mut body = self.body.clone().unwrap_or(String::new());
         body.push_str(str::from_utf8(chunk).unwrap());
@@ -613,9 +877,25 @@ impl Response {
         }
     }
 
-    fn get_header_value(&self, name: String) -> Option<String> {
+    /// Whether the body is sent in chunks. Chunked is always the last transfer coding applied,
+    /// if it's applied at all.
+    ///
+    /// https://httpwg.org/specs/rfc9112.html#field.transfer-encoding
+    pub fn is_chunked(&self) -> bool {
+        self.get_header_value("Transfer-Encoding".to_string())
+            .and_then(|codings| {
+                codings
+                    .rsplit(',')
+                    .next()
+                    .map(|last| last.trim().eq_ignore_ascii_case("chunked"))
+            })
+            .unwrap_or(false)
+    }
+
+    /// Header names are matched case-insensitively.
+    pub fn get_header_value(&self, name: String) -> Option<String> {
         for header in &self.headers {
-            if header.is_complete && header.name == name {
+            if header.is_complete && header.name.eq_ignore_ascii_case(&name) {
                 return Some(header.value.clone());
             }
         }
@@ -659,16 +939,85 @@ impl fmt::Display for Response {
     }
 }
 
+/// How a client retries requests that failed because of the connection rather than the server.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt, so 0 disables retrying
+    pub max_retries: u32,
+
+    /// The wait before the first retry, doubled for every retry after it
+    pub initial_backoff: Duration,
+
+    /// Also retry methods that aren't idempotent, which may then take effect more than once
+    pub retry_all_methods: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            initial_backoff: Duration::from_millis(100),
+            retry_all_methods: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// https://httpwg.org/specs/rfc9110.html#idempotent.methods
+    fn allows(&self, method: &str) -> bool {
+        self.retry_all_methods || matches!(method, "GET" | "HEAD")
+    }
+}
+
 #[derive(Default)]
 pub struct Client {
     addr: Option<String>,
 
+    /// The server name of the TLS session over the connection, if any
+    tls_host: Option<String>,
+
+    /// An HTTP proxy to send requests through
+    proxy: Option<http::url::URL>,
+
+    /// The `host:port` the connection tunnels to through the proxy, if it's tunnelled
+    tunnel: Option<String>,
+
     dns_resolver: Option<http::dns::DnsResolver>,
 
+    cache: http::cache::HttpCache,
+
     connection: Option<Box<dyn ConnectionStream>>,
     preferred_protocol: Option<Protocol>,
 
     permissive: bool,
+
+    retry_policy: RetryPolicy,
+
+    /// Sent as the `User-Agent` of every request that doesn't set its own
+    user_agent: String,
+
+    /// Sent with every request, unless the request sets a header of the same name
+    default_headers: Vec<Header>,
+
+    /// Sent as the `Accept` of navigations
+    accept: String,
+
+    /// Sent as the `Accept-Language` of every request
+    accept_language: String,
+
+    /// Sent as the `Accept-Encoding` of every request, so it should only list content codings
+    /// that can be decoded
+    accept_encoding: String,
+
+    /// The URLs of every successful navigation, oldest first
+    history: Vec<http::url::URL>,
+
+    /// How many redirects a request follows before its last redirect is returned as is
+    max_redirects: u32,
+
+    /// Accept any certificate a TLS server presents, for testing against local servers with
+    /// self-signed ones
+    skip_certificate_verification: bool,
 }
 
 impl Client {
@@ -676,6 +1025,11 @@ impl Client {
         Self {
             preferred_protocol: Some(prefers),
             permissive,
+            user_agent: String::from(DEFAULT_USER_AGENT),
+            accept: String::from(DEFAULT_NAVIGATION_ACCEPT),
+            accept_language: String::from(DEFAULT_ACCEPT_LANGUAGE),
+            accept_encoding: String::from(DEFAULT_ACCEPT_ENCODING),
+            max_redirects: DEFAULT_MAX_REDIRECTS,
             ..Default::default()
         }
     }
@@ -685,52 +1039,136 @@ impl Client {
         self
     }
 
-    pub fn connect_to_tls(&mut self, addr: String, host: String) {
-        self.addr = Some(addr.clone());
+    pub fn with_retry_policy(&mut self, retry_policy: RetryPolicy) -> &mut Self {
+        self.retry_policy = retry_policy;
+        self
+    }
 
-        let root_store = rustls::RootCertStore {
-            roots: webpki_roots::TLS_SERVER_ROOTS.into(),
-        };
+    /// Sends requests over `connection` instead of a socket the client opens itself. Such a
+    /// connection is reused as is when retrying.
+    pub fn with_connection(&mut self, connection: Box<dyn ConnectionStream>) -> &mut Self {
+        self.addr = None;
+        self.tls_host = None;
+        self.connection = Some(connection);
+        self
+    }
 
-        let mut config = rustls::ClientConfig::builder()
-            .with_root_certificates(root_store)
-            .with_no_client_auth();
-        config.key_log = Arc::new(rustls::KeyLogFile::new());
+    /// Sends requests through the HTTP proxy at `proxy`. Plain HTTP requests are forwarded by the
+    /// proxy, while HTTPS ones go through a tunnel opened with `CONNECT`.
+    pub fn with_proxy(&mut self, proxy: http::url::URL) -> &mut Self {
+        self.proxy = Some(proxy);
+        self
+    }
 
-        let server_name = host.try_into().unwrap();
+    pub fn with_user_agent(&mut self, user_agent: String) -> &mut Self {
+        self.user_agent = user_agent;
+        self
+    }
 
-        let conn = rustls::ClientConnection::new(Arc::new(config), server_name).unwrap();
+    /// Adds a header to send with every request, replacing any default header of the same name.
+    pub fn with_default_header(&mut self, name: String, value: String) -> &mut Self {
+        self.default_headers
+            .retain(|header| !header.name.eq_ignore_ascii_case(&name));
+        self.default_headers.push(Header::new(name, value));
+        self
+    }
 
-        let tcp_stream = TcpStream::connect(addr.clone()).unwrap();
+    pub fn with_accept(&mut self, accept: String) -> &mut Self {
+        self.accept = accept;
+        self
+    }
 
-        let tls_stream = TlsStream {
-            conn,
-            sock: tcp_stream,
-        };
+    pub fn with_accept_language(&mut self, accept_language: String) -> &mut Self {
+        self.accept_language = accept_language;
+        self
+    }
 
-        self.connection = Some(Box::new(tls_stream));
+    /// An empty `accept_encoding` leaves the header out, asking for the body as is.
+    pub fn with_accept_encoding(&mut self, accept_encoding: String) -> &mut Self {
+        self.accept_encoding = accept_encoding;
+        self
     }
 
-    pub fn connect_to(&mut self, addr: String) {
-        self.addr = Some(addr.clone());
-        match &self.preferred_protocol {
-            Some(proto) => {
-                self.connection = Some(Box::new(proto.connect(addr).unwrap()));
+    /// A `max_redirects` of 0 returns redirects without following them.
+    pub fn with_max_redirects(&mut self, max_redirects: u32) -> &mut Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Whether TLS servers must present a certificate that's valid for their host and signed by a
+    /// trusted root. Only turn this off for testing against local servers.
+    pub fn with_certificate_verification(&mut self, verify: bool) -> &mut Self {
+        self.skip_certificate_verification = !verify;
+        self
+    }
+
+    pub fn user_agent(&self) -> &str {
+        &self.user_agent
+    }
+
+    pub fn default_headers(&self) -> &Vec<Header> {
+        &self.default_headers
+    }
+
+    /// Adds the default headers, then the user agent, accepted languages and accepted encodings,
+    /// to `request`, leaving alone any header that's already set.
+    fn apply_default_headers(&self, request: &mut Request) {
+        let negotiated = [
+            Header::new(String::from("User-Agent"), self.user_agent.clone()),
+            Header::new(
+                String::from("Accept-Language"),
+                self.accept_language.clone(),
+            ),
+            Header::new(
+                String::from("Accept-Encoding"),
+                self.accept_encoding.clone(),
+            ),
+        ];
+
+        for header in self.default_headers.iter().chain(negotiated.iter()) {
+            if header.value.is_empty() {
+                continue;
             }
-            None => {
-                self.preferred_protocol = Some(Protocol::HTTP1_1);
-                self.connection = Some(Box::new(
-                    self.preferred_protocol
-                        .as_ref()
-                        .unwrap()
-                        .connect(addr)
-                        .unwrap(),
-                ));
+
+            let is_set = request
+                .headers
+                .iter()
+                .any(|existing| existing.name.eq_ignore_ascii_case(&header.name));
+
+            if !is_set {
+                request.headers.push(header.clone());
             }
         }
     }
 
-    pub fn get_addr_url(&mut self, url: String) -> SocketAddr {
+    pub fn connect_to_tls(&mut self, addr: String, host: String) -> std::io::Result<()> {
+        self.addr = Some(addr.clone());
+        self.tls_host = Some(host.clone());
+        self.tunnel = None;
+        self.connection = None;
+
+        let tls_stream = TlsStream::connect(&addr, host, !self.skip_certificate_verification)?;
+
+        self.connection = Some(Box::new(tls_stream));
+        Ok(())
+    }
+
+    pub fn connect_to(&mut self, addr: String) -> std::io::Result<()> {
+        self.addr = Some(addr.clone());
+        self.tls_host = None;
+        self.tunnel = None;
+        self.connection = None;
+
+        let protocol = self
+            .preferred_protocol
+            .get_or_insert(Protocol::HTTP1_1)
+            .clone();
+        self.connection = Some(Box::new(protocol.connect(addr)?));
+
+        Ok(())
+    }
+
+    pub fn get_addr_url(&mut self, url: &http::url::URL) -> Option<SocketAddr> {
         match &mut self.dns_resolver {
             Some(resolver) => resolver.resolve_url(url),
             None => {
@@ -741,7 +1179,11 @@ impl Client {
         }
     }
 
-    pub fn get_addr_host(&mut self, host: http::url::Host, port: u16) -> SocketAddr {
+    pub fn get_addr_host(
+        &mut self,
+        host: http::url::Host,
+        port: u16,
+    ) -> std::io::Result<SocketAddr> {
         match &mut self.dns_resolver {
             Some(resolver) => resolver.resolve(host, port),
             None => {
@@ -752,73 +1194,330 @@ impl Client {
         }
     }
 
-    pub fn connect_to_host_tls(&mut self, host: http::url::Host, port: u16) {
-        let target = self.get_addr_host(host.clone(), port);
+    pub fn connect_to_host_tls(&mut self, host: http::url::Host, port: u16) -> std::io::Result<()> {
+        let target = self.get_addr_host(host.clone(), port)?;
         self.connect_to_tls(target.to_string(), host.serialize())
     }
 
-    pub fn connect_to_host(&mut self, host: http::url::Host, port: u16) {
-        let target = self.get_addr_host(host, port);
+    pub fn connect_to_host(&mut self, host: http::url::Host, port: u16) -> std::io::Result<()> {
+        let target = self.get_addr_host(host, port)?;
         self.connect_to(target.to_string())
     }
 
-    pub fn connect_to_url(&mut self, url: String) -> http::url::URL {
-        let url_obj = http::url::URL::pure_parse(url.clone()).unwrap();
-
-        let addr = self.get_addr_url(url).to_string();
+    pub fn connect_to_url(
+        &mut self,
+        url: String,
+    ) -> Result<http::url::URL, http::url::ParseURLError> {
+        let url_obj = http::url::URL::pure_parse(url)?;
+
+        let host = url_obj
+            .host
+            .clone()
+            .ok_or(http::url::ParseURLError::HostMissing)?;
+
+        let connected = match url_obj.scheme.as_str() {
+            "http" => self.connect_to_host(host, url_obj.effective_port().unwrap_or(80)),
+            "https" => self.connect_to_host_tls(host, url_obj.effective_port().unwrap_or(443)),
+            scheme => {
+                return Err(http::url::ParseURLError::SchemeUnsupported(
+                    scheme.to_string(),
+                ));
+            }
+        };
 
-        match url_obj.scheme.as_str() {
-            "http" => self.connect_to(addr),
-            "https" => self.connect_to_tls(addr, url_obj.host.as_ref().unwrap().serialize()),
-            _ => unimplemented!(),
+        // The URL itself is fine, so a host that can't be reached only leaves the client without
+        // a connection, which sending a request on reports
+        if let Err(e) = connected {
+            eprintln!("Couldn't connect to {}: {}", url_obj.serialize(), e);
         }
 
-        url_obj
+        Ok(url_obj)
+    }
+
+    /// Requests `url` as a document to be displayed, negotiating for HTML in the configured
+    /// languages. Returns the response along with the URL it came from after any redirects, which
+    /// is the one that goes in the history.
+    pub fn navigate(&mut self, url: &http::url::URL) -> Option<(Response, http::url::URL)> {
+        let accept = self.accept.clone();
+        let (response, final_url) = self.get(url, accept)?;
+
+        self.history.push(final_url.clone());
+        Some((response, final_url))
+    }
+
+    /// The URLs navigated to so far, oldest first. Links to any of them are `:visited`.
+    pub fn history(&self) -> &[http::url::URL] {
+        &self.history
+    }
+
+    /// Requests `url` as a subresource of a document, accepting any content type.
+    pub fn fetch(&mut self, url: &http::url::URL) -> Option<Response> {
+        self.get(url, String::from("*/*"))
+            .map(|(response, _)| response)
+    }
+
+    /// Connects to the host of `url` and sends a `GET` request for it, following redirects.
+    fn get(&mut self, url: &http::url::URL, accept: String) -> Option<(Response, http::url::URL)> {
+        self.follow_redirects(String::from("GET"), url, accept, None)
     }
 
-    pub fn send_request(&mut self, request: Request) -> Option<Response> {
-        let maybe_resp = match request.send(self) {
-            Ok(resp) => Some(resp),
-            Err(e) => {
-                eprintln!("{}", e);
-                None
+    /// Sends a `method` request for `url`, then for the `Location` of every redirect it gets, up
+    /// to `max_redirects` of them. Returns the last response along with the URL it came from.
+    ///
+    /// A redirect that can't be followed is returned as is: one past the limit, one back to a URL
+    /// already visited, and one from `https` to plain `http` or to a scheme that isn't HTTP.
+    ///
+    /// https://fetch.spec.whatwg.org/#http-redirect-fetch
+    fn follow_redirects(
+        &mut self,
+        mut method: String,
+        url: &http::url::URL,
+        accept: String,
+        mut body: Option<String>,
+    ) -> Option<(Response, http::url::URL)> {
+        let mut url = url.clone();
+        let mut visited = vec![];
+
+        loop {
+            let response = self.request_url(&method, &url, accept.clone(), body.clone())?;
+
+            let Some(status) = response.status().filter(|status| status.is_redirect()) else {
+                return Some((response, url));
+            };
+            let Some(location) = response.get_header_value("Location".to_string()) else {
+                return Some((response, url));
+            };
+            let Ok(mut next) = http::url::URL::parse(location, Some(url.clone()), None) else {
+                return Some((response, url));
+            };
+
+            // A location without a fragment keeps the one of the URL it redirects from
+            if next.fragment.is_none() {
+                next.fragment = url.fragment.clone();
+            }
+
+            visited.push(url.clone());
+
+            if visited.len() > self.max_redirects as usize {
+                eprintln!("Too many redirects, stopping at {}", next.serialize());
+                return Some((response, url));
+            }
+
+            if visited.contains(&next) {
+                eprintln!("Redirect loop back to {}", next.serialize());
+                return Some((response, url));
+            }
+
+            let from_https = visited.last().is_some_and(|url| url.scheme == "https");
+            match next.scheme.as_str() {
+                "https" => {}
+                "http" if !from_https => {}
+                scheme => {
+                    eprintln!("Refusing to follow a redirect to {}", scheme);
+                    return Some((response, url));
+                }
+            }
+
+            let next_method = status.method_after_redirect(&method);
+            if next_method != method {
+                method = next_method.to_string();
+                body = None;
             }
+
+            url = next;
+        }
+    }
+
+    /// Connects to the host of `url` and sends a single `method` request for it.
+    ///
+    /// For `GET` requests, fresh responses in the cache are returned without a request being made,
+    /// and stale ones are revalidated with a conditional request.
+    fn request_url(
+        &mut self,
+        method: &str,
+        url: &http::url::URL,
+        accept: String,
+        body: Option<String>,
+    ) -> Option<Response> {
+        let cached = match method {
+            "GET" => self.cache.lookup(url),
+            _ => None,
+        };
+
+        let validators = match cached {
+            Some(entry) if entry.is_fresh() => return Some(entry.response.clone()),
+            Some(entry) => entry.validators(),
+            None => vec![],
         };
 
-        if let Some(resp) = maybe_resp.as_ref() {
-            let status_code = resp.status_code.unwrap();
-            if status_code >= 300 && status_code <= 399 {
-                return self.handle_redirect(request, resp.clone());
+        let host = url.host.clone()?;
+        let port = url.effective_port()?;
+
+        let mut request_target = url.path.serialize();
+        if let Some(query) = &url.query {
+            request_target.push('?');
+            request_target.push_str(query);
+        }
+
+        let connected = match (self.proxy.clone(), url.scheme.as_str()) {
+            (None, "http") => self.connect_to_host(host.clone(), port),
+            (None, "https") => self.connect_to_host_tls(host.clone(), port),
+            (Some(proxy), "http") => {
+                let proxy_port = proxy.effective_port()?;
+
+                // https://httpwg.org/specs/rfc9112.html#absolute-form
+                let mut absolute = url.clone();
+                absolute.fragment = None;
+                request_target = absolute.serialize();
+
+                self.connect_to_host(proxy.host?, proxy_port)
             }
+            (Some(proxy), "https") => {
+                let authority = format!("{}:{}", host.serialize(), port);
+                self.connect_through_tunnel(&proxy, authority, host.serialize())
+            }
+            _ => return None,
+        };
+
+        // Pages pick the hosts of their subresources, so one that can't be reached is only a
+        // failed load
+        if let Err(e) = connected {
+            eprintln!("Couldn't connect to {}: {}", url.serialize(), e);
+            return None;
+        }
+
+        // The port is only part of the Host header when it isn't the scheme's default
+        let host_header = match url.port {
+            Some(port) => format!("{}:{}", host.serialize(), port),
+            None => host.serialize(),
+        };
+
+        let mut headers = vec![
+            Header::new(String::from("Host"), host_header),
+            Header::new(String::from("Accept"), accept),
+        ];
+        headers.extend(validators);
+
+        if let Some(body) = &body {
+            headers.push(Header::new(
+                String::from("Content-Length"),
+                body.len().to_string(),
+            ));
+        }
+
+        let response = self.send_request(Request {
+            method: method.to_string(),
+            request_target,
+            protocol: self.preferred_protocol.clone().unwrap_or(Protocol::HTTP1_1),
+            headers,
+            body,
+        })?;
+
+        if method != "GET" {
+            return Some(response);
         }
 
-        maybe_resp
+        if response.status() == Some(StatusCode::NOT_MODIFIED) {
+            return self.cache.revalidate(url, &response).or(Some(response));
+        }
+
+        self.cache.store(url, &response);
+
+        Some(response)
     }
 
-    pub fn handle_redirect(&mut self, initial: Request, response: Response) -> Option<Response> {
-        if let Some(redirect_url) = response.get_header_value("Location".to_string()) {
-            let url = http::url::URL::pure_parse(redirect_url.clone()).unwrap();
+    /// Opens a fresh connection to the address the client last connected to, as the old one may
+    /// have been dropped by the server. Connections handed over with `with_connection` are kept.
+    fn reconnect(&mut self) -> std::io::Result<()> {
+        let Some(addr) = self.addr.clone() else {
+            return Ok(());
+        };
 
-            self.connect_to_url(redirect_url);
-            return self.send_request(Request {
-                method: initial.method,
-                request_target: url.path.serialize(),
-                protocol: initial.protocol,
-                headers: initial.headers,
-                body: initial.body,
-            });
+        let sock = match &self.tunnel {
+            Some(authority) => open_tunnel(&addr, authority)?,
+            None => TcpStream::connect(addr)?,
         };
 
-        // Couldn't find a location to redirect to, so just take the original response
-        // Do what u want w ts lol
-        Some(response)
+        self.connection = Some(match self.tls_host.clone() {
+            Some(host) => Box::new(TlsStream::over(
+                sock,
+                host,
+                !self.skip_certificate_verification,
+            )?),
+            None => Box::new(sock),
+        });
+
+        Ok(())
+    }
+
+    /// Asks the proxy to open a tunnel to `authority`, then starts a TLS session with `tls_host`
+    /// through it.
+    fn connect_through_tunnel(
+        &mut self,
+        proxy: &http::url::URL,
+        authority: String,
+        tls_host: String,
+    ) -> std::io::Result<()> {
+        let proxy_host = proxy.host.clone().ok_or(std::io::ErrorKind::InvalidInput)?;
+        let proxy_port = proxy
+            .effective_port()
+            .ok_or(std::io::ErrorKind::InvalidInput)?;
+
+        let addr = self.get_addr_host(proxy_host, proxy_port)?.to_string();
+        let sock = open_tunnel(&addr, &authority)?;
+
+        self.connection = Some(Box::new(TlsStream::over(
+            sock,
+            tls_host.clone(),
+            !self.skip_certificate_verification,
+        )?));
+        self.addr = Some(addr);
+        self.tls_host = Some(tls_host);
+        self.tunnel = Some(authority);
+
+        Ok(())
+    }
+
+    /// Sends `request` over the current connection, retrying it as the retry policy allows.
+    /// Redirects are returned rather than followed, as the request doesn't say which URL its
+    /// target is relative to; `navigate` and `fetch` follow them.
+    pub fn send_request(&mut self, mut request: Request) -> Option<Response> {
+        self.apply_default_headers(&mut request);
+
+        let mut retries = 0;
+        let mut backoff = self.retry_policy.initial_backoff;
+
+        loop {
+            match request.send(self) {
+                Ok(resp) => break Some(resp),
+                Err(e)
+                    if e.is_transient()
+                        && retries < self.retry_policy.max_retries
+                        && self.retry_policy.allows(&request.method) =>
+                {
+                    eprintln!("{}\nRetrying in {:?}", e, backoff);
+
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                    retries += 1;
+
+                    if let Err(e) = self.reconnect() {
+                        eprintln!("Error in reconnecting: {}", e);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    break None;
+                }
+            }
+        }
     }
 }
 
 /// A trait to abstract over different connection stream types
 /// This allows us to use both plain TCP streams and TLS streams interchangeably
-trait ConnectionStream {
-    fn cs_read(&mut self, buffer: &mut [u8]) -> usize;
+pub trait ConnectionStream {
+    fn cs_read(&mut self, buffer: &mut [u8]) -> Result<usize, std::io::Error>;
     fn cs_write(&mut self, data: &[u8]) -> Result<usize, std::io::Error>;
 }
 
@@ -827,9 +1526,154 @@ struct TlsStream {
     sock: TcpStream,
 }
 
+impl TlsStream {
+    fn connect(addr: &str, host: String, verify: bool) -> Result<Self, std::io::Error> {
+        Self::over(TcpStream::connect(addr)?, host, verify)
+    }
+
+    /// Starts a TLS session with `host` over `sock`, which is also the name sent to the server to
+    /// pick a certificate by. The handshake itself happens on the first read or write.
+    ///
+    /// https://www.rfc-editor.org/rfc/rfc6066#section-3
+    fn over(sock: TcpStream, host: String, verify: bool) -> Result<Self, std::io::Error> {
+        let builder = rustls::ClientConfig::builder();
+        let mut config = if verify {
+            let root_store = rustls::RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.into(),
+            };
+
+            builder
+                .with_root_certificates(root_store)
+                .with_no_client_auth()
+        } else {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(AnyCertificate::new()))
+                .with_no_client_auth()
+        };
+        config.key_log = Arc::new(rustls::KeyLogFile::new());
+
+        // IPv6 hosts are serialized in brackets, which aren't part of the address
+        let server_name = host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string()
+            .try_into()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
+        let conn = rustls::ClientConnection::new(Arc::new(config), server_name)
+            .map_err(std::io::Error::other)?;
+
+        Ok(Self { conn, sock })
+    }
+}
+
+/// Accepts whatever certificate the server presents, so that a session can be set up with servers
+/// whose certificates can't be verified. Handshake signatures are still checked, so the server
+/// must hold the key of the certificate it sent.
+#[derive(Debug)]
+struct AnyCertificate {
+    provider: rustls::crypto::CryptoProvider,
+}
+
+impl AnyCertificate {
+    fn new() -> Self {
+        Self {
+            provider: rustls::crypto::aws_lc_rs::default_provider(),
+        }
+    }
+}
+
+impl rustls::client::danger::ServerCertVerifier for AnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &rustls::pki_types::CertificateDer<'_>,
+        _intermediates: &[rustls::pki_types::CertificateDer<'_>],
+        _server_name: &rustls::pki_types::ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: rustls::pki_types::UnixTime,
+    ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
+        Ok(rustls::client::danger::ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &rustls::pki_types::CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &rustls::pki_types::CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// Connects to the proxy at `proxy_addr` and has it open a tunnel to `authority`, returning the
+/// socket once the proxy is ready to relay over it.
+///
+/// https://httpwg.org/specs/rfc9110.html#CONNECT
+fn open_tunnel(proxy_addr: &str, authority: &str) -> Result<TcpStream, std::io::Error> {
+    let mut sock = TcpStream::connect(proxy_addr)?;
+    write!(
+        sock,
+        "CONNECT {} HTTP/1.1\r\nHost: {}\r\n\r\n",
+        authority, authority
+    )?;
+
+    // Read a byte at a time, so nothing past the proxy's response is taken from the tunnel
+    let mut head = Vec::new();
+    let mut byte = [0; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        if sock.read(&mut byte)? == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        head.push(byte[0]);
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let status = head
+        .split(' ')
+        .nth(1)
+        .and_then(|status| status.parse::<u32>().ok())
+        .map(StatusCode);
+
+    if !status.is_some_and(|status| status.is_success()) {
+        return Err(std::io::Error::other(format!(
+            "Proxy refused to connect to {}: {}",
+            authority,
+            head.lines().next().unwrap_or_default()
+        )));
+    }
+
+    Ok(sock)
+}
+
 impl ConnectionStream for TcpStream {
-    fn cs_read(&mut self, buffer: &mut [u8]) -> usize {
-        self.read(buffer).unwrap()
+    fn cs_read(&mut self, buffer: &mut [u8]) -> Result<usize, std::io::Error> {
+        self.read(buffer)
     }
 
     fn cs_write(&mut self, data: &[u8]) -> Result<usize, std::io::Error> {
@@ -838,9 +1682,9 @@ impl ConnectionStream for TcpStream {
 }
 
 impl<'a> ConnectionStream for TlsStream {
-    fn cs_read(&mut self, buffer: &mut [u8]) -> usize {
+    fn cs_read(&mut self, buffer: &mut [u8]) -> Result<usize, std::io::Error> {
         let mut stream = rustls::Stream::new(&mut self.conn, &mut self.sock);
-        stream.read(buffer).unwrap()
+        stream.read(buffer)
     }
 
     fn cs_write(&mut self, data: &[u8]) -> Result<usize, std::io::Error> {
@@ -0,0 +1,507 @@
+use std::fmt;
+
+/// Why a compressed body couldn't be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The data ended before the compressed stream did
+    UnexpectedEnd,
+
+    /// The gzip or zlib header is malformed, or uses a compression method other than DEFLATE
+    InvalidHeader,
+
+    /// A block has a reserved type, or codes that don't describe valid lengths and distances
+    InvalidData,
+
+    /// The decompressed data doesn't match the checksum or length stored after it
+    ChecksumMismatch,
+
+    /// The data decompresses to more than the most it was allowed to
+    TooLarge,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            DecodeError::UnexpectedEnd => "compressed data ended unexpectedly",
+            DecodeError::InvalidHeader => "invalid compressed data header",
+            DecodeError::InvalidData => "invalid compressed data",
+            DecodeError::ChecksumMismatch => "compressed data checksum mismatch",
+            DecodeError::TooLarge => "decompressed data is too large",
+        };
+
+        write!(f, "{}", message)
+    }
+}
+
+/// Undoes the content codings listed in a `Content-Encoding` header, in the reverse of the
+/// order they were applied. The body is returned untouched if any of them isn't supported.
+/// Decoding fails once any of the codings undoes to more than `max_length` bytes.
+///
+/// https://httpwg.org/specs/rfc9110.html#field.content-encoding
+pub fn decode_content(
+    content_encoding: &str,
+    body: &[u8],
+    max_length: usize,
+) -> Result<Vec<u8>, DecodeError> {
+    let mut decoded = body.to_vec();
+
+    for coding in content_encoding.split(',').rev() {
+        decoded = match coding.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => decode_gzip(&decoded, max_length)?,
+            "deflate" => decode_deflate(&decoded, max_length)?,
+            "identity" | "" => decoded,
+            _ => return Ok(body.to_vec()),
+        };
+    }
+
+    Ok(decoded)
+}
+
+/// Decodes the `gzip` content coding. Only the first member of the file is read.
+///
+/// https://www.rfc-editor.org/rfc/rfc1952#section-2.3
+pub fn decode_gzip(data: &[u8], max_length: usize) -> Result<Vec<u8>, DecodeError> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    let header = data.get(..10).ok_or(DecodeError::UnexpectedEnd)?;
+    if header[..3] != [0x1f, 0x8b, 0x08] || header[3] & 0xe0 != 0 {
+        return Err(DecodeError::InvalidHeader);
+    }
+
+    let flags = header[3];
+    let mut position = 10;
+
+    if flags & FEXTRA != 0 {
+        let length = data
+            .get(position..position + 2)
+            .ok_or(DecodeError::UnexpectedEnd)?;
+        position += 2 + u16::from_le_bytes([length[0], length[1]]) as usize;
+    }
+
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            // A zero-terminated string
+            let length = data
+                .get(position..)
+                .and_then(|rest| rest.iter().position(|&byte| byte == 0))
+                .ok_or(DecodeError::UnexpectedEnd)?;
+            position += length + 1;
+        }
+    }
+
+    if flags & FHCRC != 0 {
+        position += 2;
+    }
+
+    let stream = data.get(position..).ok_or(DecodeError::UnexpectedEnd)?;
+    let (output, length) = inflate_stream(stream, max_length)?;
+    position += length;
+
+    let trailer = data
+        .get(position..position + 8)
+        .ok_or(DecodeError::UnexpectedEnd)?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+
+    if crc != crc32(&output) || size != output.len() as u32 {
+        return Err(DecodeError::ChecksumMismatch);
+    }
+
+    Ok(output)
+}
+
+/// Decodes the `deflate` content coding, which is a zlib stream. Some servers send raw DEFLATE
+/// data without the zlib wrapper instead, so that's accepted too.
+///
+/// https://httpwg.org/specs/rfc9110.html#deflate.coding
+pub fn decode_deflate(data: &[u8], max_length: usize) -> Result<Vec<u8>, DecodeError> {
+    match decode_zlib(data, max_length) {
+        Err(DecodeError::InvalidHeader) => inflate(data, max_length),
+        result => result,
+    }
+}
+
+/// https://www.rfc-editor.org/rfc/rfc1950#section-2.2
+pub fn decode_zlib(data: &[u8], max_length: usize) -> Result<Vec<u8>, DecodeError> {
+    const FDICT: u8 = 0x20;
+
+    let header = data.get(..2).ok_or(DecodeError::UnexpectedEnd)?;
+    let (cmf, flg) = (header[0], header[1]);
+
+    if cmf & 0x0f != 8 || cmf >> 4 > 7 || u16::from_be_bytes([cmf, flg]) % 31 != 0 {
+        return Err(DecodeError::InvalidHeader);
+    }
+
+    // Preset dictionaries aren't used over HTTP
+    if flg & FDICT != 0 {
+        return Err(DecodeError::InvalidHeader);
+    }
+
+    let (output, length) = inflate_stream(&data[2..], max_length)?;
+
+    let trailer = data
+        .get(2 + length..6 + length)
+        .ok_or(DecodeError::UnexpectedEnd)?;
+    let checksum = u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+
+    if checksum != adler32(&output) {
+        return Err(DecodeError::ChecksumMismatch);
+    }
+
+    Ok(output)
+}
+
+/// Decompresses raw DEFLATE data, failing as soon as there's more than `max_length` bytes of it.
+///
+/// https://www.rfc-editor.org/rfc/rfc1951
+pub fn inflate(data: &[u8], max_length: usize) -> Result<Vec<u8>, DecodeError> {
+    inflate_stream(data, max_length).map(|(output, _)| output)
+}
+
+/// Decompresses the DEFLATE stream at the start of `data`, returning the output and how many
+/// bytes of `data` the stream took up.
+///
+/// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.3
+fn inflate_stream(data: &[u8], max_length: usize) -> Result<(Vec<u8>, usize), DecodeError> {
+    let mut reader = BitReader::new(data);
+    let mut output = Vec::new();
+
+    loop {
+        let is_final = reader.bits(1)? == 1;
+
+        match reader.bits(2)? {
+            0 => inflate_stored_block(&mut reader, &mut output, max_length)?,
+            1 => {
+                let (literals, distances) = fixed_codes()?;
+                inflate_block(&mut reader, &mut output, &literals, &distances, max_length)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut output, &literals, &distances, max_length)?;
+            }
+            _ => return Err(DecodeError::InvalidData),
+        }
+
+        if is_final {
+            break;
+        }
+    }
+
+    reader.align_to_byte();
+    Ok((output, reader.position))
+}
+
+/// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.4
+fn inflate_stored_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    max_length: usize,
+) -> Result<(), DecodeError> {
+    reader.align_to_byte();
+
+    let header = reader.bytes(4)?;
+    let length = u16::from_le_bytes([header[0], header[1]]);
+    let complement = u16::from_le_bytes([header[2], header[3]]);
+
+    if length != !complement {
+        return Err(DecodeError::InvalidData);
+    }
+
+    if output.len() + length as usize > max_length {
+        return Err(DecodeError::TooLarge);
+    }
+
+    output.extend_from_slice(reader.bytes(length as usize)?);
+    Ok(())
+}
+
+/// The base length of each length code from 257, and how many extra bits follow it
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// The base distance of each distance code, and how many extra bits follow it
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u32; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Decodes a compressed block's literals and back references until its end-of-block code.
+///
+/// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.5
+fn inflate_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+    max_length: usize,
+) -> Result<(), DecodeError> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+
+        match symbol {
+            0..=255 if output.len() >= max_length => return Err(DecodeError::TooLarge),
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                if index >= LENGTH_BASES.len() {
+                    return Err(DecodeError::InvalidData);
+                }
+                let length =
+                    LENGTH_BASES[index] as usize + reader.bits(LENGTH_EXTRA_BITS[index])? as usize;
+
+                let index = distances.decode(reader)? as usize;
+                if index >= DISTANCE_BASES.len() {
+                    return Err(DecodeError::InvalidData);
+                }
+                let distance = DISTANCE_BASES[index] as usize
+                    + reader.bits(DISTANCE_EXTRA_BITS[index])? as usize;
+
+                if distance > output.len() {
+                    return Err(DecodeError::InvalidData);
+                }
+
+                if output.len() + length > max_length {
+                    return Err(DecodeError::TooLarge);
+                }
+
+                // The copy may overlap what it's copying, so it goes a byte at a time
+                let start = output.len() - distance;
+                for i in 0..length {
+                    output.push(output[start + i]);
+                }
+            }
+        }
+    }
+}
+
+/// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.6
+fn fixed_codes() -> Result<(Huffman, Huffman), DecodeError> {
+    let mut lengths = [0; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+/// Reads the code lengths at the start of a block compressed with dynamic Huffman codes.
+///
+/// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.7
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), DecodeError> {
+    const CODE_LENGTH_ORDER: [usize; 19] = [
+        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+    ];
+
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    if literal_count > 286 || distance_count > 30 {
+        return Err(DecodeError::InvalidData);
+    }
+
+    let mut code_lengths = [0; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (length, repeat) = match code_lengths.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.last().ok_or(DecodeError::InvalidData)?;
+                (previous, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            18 => (0, 11 + reader.bits(7)?),
+            _ => return Err(DecodeError::InvalidData),
+        };
+
+        if lengths.len() + repeat as usize > literal_count + distance_count {
+            return Err(DecodeError::InvalidData);
+        }
+        lengths.extend(std::iter::repeat_n(length, repeat as usize));
+    }
+
+    // Without an end-of-block code the block could never end
+    if lengths[256] == 0 {
+        return Err(DecodeError::InvalidData);
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count])?,
+        Huffman::new(&lengths[literal_count..])?,
+    ))
+}
+
+/// Reads bits from the least significant end of each byte first, the order DEFLATE packs them in.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+
+    buffer: u32,
+    buffered: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            position: 0,
+            buffer: 0,
+            buffered: 0,
+        }
+    }
+
+    /// Reads `count` bits, up to 16, as a number.
+    fn bits(&mut self, count: u32) -> Result<u32, DecodeError> {
+        while self.buffered < count {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or(DecodeError::UnexpectedEnd)?;
+            self.position += 1;
+
+            self.buffer |= (byte as u32) << self.buffered;
+            self.buffered += 8;
+        }
+
+        let value = self.buffer & ((1 << count) - 1);
+        self.buffer >>= count;
+        self.buffered -= count;
+
+        Ok(value)
+    }
+
+    /// Skips the rest of the current byte. Fewer than 8 bits are ever buffered between reads,
+    /// so they all belong to it.
+    fn align_to_byte(&mut self) {
+        self.buffer = 0;
+        self.buffered = 0;
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], DecodeError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + count)
+            .ok_or(DecodeError::UnexpectedEnd)?;
+        self.position += count;
+
+        Ok(bytes)
+    }
+}
+
+/// A canonical Huffman code, described by the length of each symbol's code.
+///
+/// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.2
+struct Huffman {
+    /// How many codes there are of each length
+    counts: [u16; 16],
+
+    /// The symbols ordered by their codes
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, DecodeError> {
+        let mut counts = [0; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        // More codes of a length than there is room for
+        let mut available: i32 = 1;
+        for &count in &counts[1..] {
+            available = (available << 1) - count as i32;
+            if available < 0 {
+                return Err(DecodeError::InvalidData);
+            }
+        }
+
+        let mut offsets = [0; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Ok(Self { counts, symbols })
+    }
+
+    /// Reads a code a bit at a time until it's one of this code's, returning its symbol.
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, DecodeError> {
+        // The first code of the current length, and the index of its symbol
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+
+        for &count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(DecodeError::InvalidData)
+    }
+}
+
+/// https://www.rfc-editor.org/rfc/rfc1952#section-8
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffff;
+
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+/// https://www.rfc-editor.org/rfc/rfc1950#section-9
+fn adler32(data: &[u8]) -> u32 {
+    const MODULUS: u32 = 65521;
+
+    let (mut a, mut b) = (1, 0);
+    for &byte in data {
+        a = (a + byte as u32) % MODULUS;
+        b = (b + a) % MODULUS;
+    }
+
+    (b << 16) | a
+}
@@ -0,0 +1,141 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Parses an HTTP-date in any of the three formats senders have used:
+///
+/// - `Sun, 06 Nov 1994 08:49:37 GMT` (IMF-fixdate, the preferred format)
+/// - `Sunday, 06-Nov-94 08:49:37 GMT` (obsolete RFC 850 format)
+/// - `Sun Nov  6 08:49:37 1994` (obsolete ANSI C asctime() format)
+///
+/// The day name isn't checked against the date, and dates before 1970 aren't representable.
+///
+/// https://httpwg.org/specs/rfc9110.html#http.date
+pub fn parse_http_date(input: &str) -> Option<SystemTime> {
+    let parts = input.split_ascii_whitespace().collect::<Vec<_>>();
+
+    let (year, month, day, time) = match parts.as_slice() {
+        [day_name, day, month, year, time, "GMT"] if day_name.ends_with(',') => (
+            parse_year(year)?,
+            parse_month(month)?,
+            day.parse::<u64>().ok()?,
+            *time,
+        ),
+        [day_name, date, time, "GMT"] if day_name.ends_with(',') => {
+            let mut date = date.split('-');
+            let day = date.next()?.parse::<u64>().ok()?;
+            let month = parse_month(date.next()?)?;
+            let year = date.next()?;
+
+            if year.len() != 2 {
+                return None;
+            }
+
+            (expand_two_digit_year(year.parse().ok()?), month, day, *time)
+        }
+        [_, month, day, time, year] => (
+            parse_year(year)?,
+            parse_month(month)?,
+            day.parse::<u64>().ok()?,
+            *time,
+        ),
+        _ => return None,
+    };
+
+    let mut time = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+
+    if time.next().is_some()
+        || year < 1970
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    let seconds = days_since_epoch(year, month, day)
+        .checked_mul(SECONDS_PER_DAY)?
+        .checked_add(hour * 3600 + minute * 60 + second)?;
+
+    UNIX_EPOCH.checked_add(Duration::from_secs(seconds))
+}
+
+/// Years have four digits at most, which keeps working out how far they are from 1970 cheap.
+fn parse_year(year: &str) -> Option<u64> {
+    if year.len() > 4 {
+        return None;
+    }
+
+    year.parse().ok()
+}
+
+/// The month number, from 1, of a three letter month name. Month names are case-sensitive.
+fn parse_month(name: &str) -> Option<u64> {
+    MONTHS
+        .iter()
+        .position(|month| *month == name)
+        .map(|index| index as u64 + 1)
+}
+
+/// A two digit year that appears to be more than 50 years in the future is taken to be the most
+/// recent year in the past with the same last two digits.
+///
+/// https://httpwg.org/specs/rfc9110.html#rfc.section.5.6.7.p.8
+fn expand_two_digit_year(year: u64) -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs() / SECONDS_PER_DAY)
+        .unwrap_or_default();
+    let current_year = year_of_day(now);
+
+    let mut expanded = current_year - current_year % 100 + year;
+    if expanded > current_year + 50 {
+        expanded -= 100;
+    }
+
+    expanded
+}
+
+fn is_leap_year(year: u64) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn days_since_epoch(year: u64, month: u64, day: u64) -> u64 {
+    let years = (1970..year)
+        .map(|year| if is_leap_year(year) { 366 } else { 365 })
+        .sum::<u64>();
+    let months = (1..month)
+        .map(|month| days_in_month(year, month))
+        .sum::<u64>();
+
+    years + months + day - 1
+}
+
+fn year_of_day(mut days: u64) -> u64 {
+    let mut year = 1970;
+
+    loop {
+        let length = if is_leap_year(year) { 366 } else { 365 };
+        if days < length {
+            return year;
+        }
+
+        days -= length;
+        year += 1;
+    }
+}
@@ -1,5 +1,6 @@
 use std::{
     collections::HashMap,
+    io,
     net::{SocketAddr, ToSocketAddrs},
     time::Instant,
 };
@@ -22,34 +23,34 @@ impl DnsResolver {
         }
     }
 
-    pub fn resolve_url(&mut self, url: String) -> SocketAddr {
-        let url_obj = http::url::URL::parse(url, None, None).unwrap();
-
-        self.resolve(
-            url_obj.host.unwrap(),
-            url_obj
-                .port
-                .unwrap_or(http::url::special_scheme_default_port(&url_obj.scheme).unwrap()),
-        )
+    /// Resolves the host and effective port of an already parsed URL, or `None` if the URL
+    /// has no host or no port to connect to, or its host doesn't resolve.
+    pub fn resolve_url(&mut self, url: &http::url::URL) -> Option<SocketAddr> {
+        self.resolve(url.host.clone()?, url.effective_port()?).ok()
     }
 
-    pub fn resolve(&mut self, host: http::url::Host, port: u16) -> SocketAddr {
+    pub fn resolve(&mut self, host: http::url::Host, port: u16) -> io::Result<SocketAddr> {
         let pair = (host.clone(), port);
 
         if let Some((addr, created_at)) = self.resolved_urls.get(&pair) {
             if created_at.elapsed().as_secs() >= DEFAULT_TTL_SECS {
                 self.resolved_urls.remove(&pair);
             } else {
-                return *addr;
+                return Ok(*addr);
             }
         }
 
-        let mut addrs = (host.serialize(), port).to_socket_addrs().unwrap();
-        let sock_addr = addrs.next().unwrap();
+        let mut addrs = (host.serialize(), port).to_socket_addrs()?;
+        let sock_addr = addrs.next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} has no addresses", host.serialize()),
+            )
+        })?;
 
         self.resolved_urls
             .insert((host, port), (sock_addr, Instant::now()));
 
-        sock_addr
+        Ok(sock_addr)
     }
 }
@@ -1,4 +1,8 @@
+pub mod cache;
+pub mod chunked;
 pub mod client;
+pub mod content_coding;
+pub mod date;
 pub mod dns;
 pub mod url;
 
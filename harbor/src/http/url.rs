@@ -1,3 +1,4 @@
+use std::fmt;
 use std::num::{IntErrorKind, ParseIntError};
 
 use encoding_rs;
@@ -11,6 +12,11 @@ pub const FORBIDDEN_HOST_CODE_POINTS: [char; 17] = [
     '\u{007c}',
 ];
 
+/// https://url.spec.whatwg.org/#forbidden-domain-code-point
+pub fn is_forbidden_domain_code_point(c: char) -> bool {
+    FORBIDDEN_HOST_CODE_POINTS.contains(&c) || c.is_ascii_control() || c == '%'
+}
+
 pub fn is_url_codepoint(c: char) -> bool {
     matches!(c,
         'a'..='z' |
@@ -54,7 +60,10 @@ pub fn special_scheme_default_port(scheme: &String) -> Option<u16> {
 fn _is_windows_drive_letter(codepoint: &String, second: &[char]) -> bool {
     let mut iter = codepoint.chars();
 
-    iter.next().unwrap().is_ascii_alphabetic() && second.contains(&iter.next().unwrap())
+    matches!(
+        (iter.next(), iter.next(), iter.next()),
+        (Some(first), Some(c), None) if first.is_ascii_alphabetic() && second.contains(&c)
+    )
 }
 
 fn is_windows_drive_letter(codepoint: &String) -> bool {
@@ -65,9 +74,9 @@ fn is_normalized_windows_drive_letter(codepoint: &String) -> bool {
     _is_windows_drive_letter(codepoint, &[':'])
 }
 
-fn starts_with_windows_drive_letter(string: &String) -> bool {
+fn starts_with_windows_drive_letter(string: &str) -> bool {
     string.chars().count() >= 2
-        && is_windows_drive_letter(string)
+        && is_windows_drive_letter(&string.chars().take(2).collect())
         && (string.chars().count() == 2
             || matches!(string.chars().nth(2).unwrap(), '/' | '\\' | '?' | '#'))
 }
@@ -113,11 +122,18 @@ impl StringPointer {
     }
 
     pub fn update_c(&mut self) {
-        if self.pointer + 1 > (self.chars.len() as isize) {
+        // The spec lets the pointer sit just before the input so that the next increment lands on
+        // the first code point.
+        if self.pointer < 0 {
+            self.is_eof = false;
+            self.c = '\0';
+            self.remaining = self.chars.clone();
+        } else if self.pointer + 1 > (self.chars.len() as isize) {
             self.is_eof = true;
             self.c = '\0';
             self.remaining = vec![];
         } else {
+            self.is_eof = false;
             self.c = self.chars[self.pointer as usize];
             self.remaining = self.chars[self.pointer as usize + 1..].to_vec();
         }
@@ -133,9 +149,9 @@ fn char_as_hex(c: char) -> u8 {
     if c >= '0' && c <= '9' {
         return (c as u8) - b'0';
     } else if c >= 'a' && c <= 'f' {
-        return (c as u8) - b'a';
+        return (c as u8) - b'a' + 10;
     } else if c >= 'A' && c <= 'F' {
-        return (c as u8) - b'A';
+        return (c as u8) - b'A' + 10;
     }
 
     return 0;
@@ -304,7 +320,8 @@ impl IPv6 {
         Ok(address)
     }
 
-    fn compressed_piece_index(&self) -> usize {
+    /// The start of the first longest run of two or more zero pieces, if any.
+    fn compressed_piece_index(&self) -> Option<usize> {
         let mut longest_index = None;
         let mut longest_size = 1;
         let mut found_index: Option<usize> = None;
@@ -312,26 +329,23 @@ impl IPv6 {
 
         for piece_index in 0..8 {
             if self.0[piece_index] != 0 {
-                if found_size > longest_size {
-                    longest_index = found_index;
-                    longest_size = found_size;
-                }
-
                 found_index = None;
                 found_size = 0;
-            } else {
-                if found_index.is_none() {
-                    found_index = Some(piece_index);
-                    found_size += 1;
-                }
+                continue;
             }
-        }
 
-        if found_size > longest_size {
-            return found_index.unwrap();
+            if found_index.is_none() {
+                found_index = Some(piece_index);
+            }
+            found_size += 1;
+
+            if found_size > longest_size {
+                longest_index = found_index;
+                longest_size = found_size;
+            }
         }
 
-        longest_index.unwrap()
+        longest_index
     }
 }
 
@@ -348,7 +362,7 @@ impl Serializable for IPv6 {
                 ignore0 = false;
             }
 
-            if compress == piece_index {
+            if compress == Some(piece_index) {
                 let separator = if piece_index == 0 { "::" } else { ":" };
                 output.push_str(separator);
 
@@ -685,11 +699,14 @@ pub fn percent_decode(bytes: &[u8]) -> Vec<u8> {
 
         if *byte != b'%' {
             output.push(*byte);
-        } else if !bytes[i + 1].is_ascii_hexdigit() || !bytes[i + 2].is_ascii_hexdigit() {
+        } else if i + 2 >= bytes.len()
+            || !bytes[i + 1].is_ascii_hexdigit()
+            || !bytes[i + 2].is_ascii_hexdigit()
+        {
             output.push(*byte);
         } else {
             let hi = bytes[i + 1];
-            let lo = bytes[i + 1];
+            let lo = bytes[i + 2];
 
             let hex = |b| match b {
                 b'0'..=b'9' => b - b'0',
@@ -829,6 +846,8 @@ pub enum HostParseError {
     IPv4ParsingError(IPv4ParseError),
     OpaqueParseError(OpaqueParseError),
     DomainToAsciiError(idna::Errors),
+    DomainEmpty,
+    DomainInvalidCodePoint,
 }
 
 impl Host {
@@ -838,7 +857,7 @@ impl Host {
                 return Err(HostParseError::IPv6UnclosedValidation);
             }
 
-            return match IPv6::parse(input[1..input.len() - 2].to_string()) {
+            return match IPv6::parse(input[1..input.len() - 1].to_string()) {
                 Ok(ip) => Ok(Host::IPAddress(IPAddress::IPv6(ip))),
                 Err(e) => Err(HostParseError::IPv6ParsingError(e)),
             };
@@ -858,7 +877,15 @@ impl Host {
         return match domain_ascii {
             Err(e) => Err(HostParseError::DomainToAsciiError(e)),
             Ok(dom_ascii) => {
-                if dom_ascii.chars().last().unwrap().is_ascii_digit() {
+                if dom_ascii.is_empty() {
+                    return Err(HostParseError::DomainEmpty);
+                }
+
+                if dom_ascii.chars().any(is_forbidden_domain_code_point) {
+                    return Err(HostParseError::DomainInvalidCodePoint);
+                }
+
+                if dom_ascii.ends_with(|c: char| c.is_ascii_digit()) {
                     return match IPv4::parse(dom_ascii) {
                         Ok(ipv4) => Ok(Host::IPAddress(IPAddress::IPv4(ipv4))),
                         Err(e) => Err(HostParseError::IPv4ParsingError(e)),
@@ -869,6 +896,17 @@ impl Host {
             }
         };
     }
+
+    /// The host as it should be shown to a user, with Punycode labels decoded back to Unicode.
+    /// Hosts are always stored in their ASCII form, which is what goes to DNS and over the wire.
+    ///
+    /// https://url.spec.whatwg.org/#url-rendering-i18n
+    pub fn to_unicode(&self) -> String {
+        match self {
+            Host::Domain(domain) => idna::domain_to_unicode(domain).0,
+            _ => self.serialize(),
+        }
+    }
 }
 
 impl Serializable for Host {
@@ -895,6 +933,27 @@ pub enum ParseURLError {
     PortOutOfRange,
     ParseIntError(ParseIntError),
     PortInvalid,
+    /// The URL is valid, but its scheme isn't one we know how to connect to.
+    SchemeUnsupported(String),
+}
+
+impl fmt::Display for ParseURLError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseURLError::Failure => write!(f, "invalid URL"),
+            ParseURLError::MissingSchemeNonRelativeURL => {
+                write!(f, "URL has no scheme and no base URL to resolve it against")
+            }
+            ParseURLError::HostMissing => write!(f, "URL has no host"),
+            ParseURLError::HostParseError(e) => write!(f, "invalid host: {:?}", e),
+            ParseURLError::PortOutOfRange => write!(f, "port is out of range"),
+            ParseURLError::ParseIntError(e) => write!(f, "invalid number: {}", e),
+            ParseURLError::PortInvalid => write!(f, "port is not a number"),
+            ParseURLError::SchemeUnsupported(scheme) => {
+                write!(f, "unsupported scheme '{}'", scheme)
+            }
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -954,6 +1013,38 @@ impl URL {
         }
     }
 
+    /// The URL's port, falling back to its scheme's default port when none was given.
+    pub fn effective_port(&self) -> Option<u16> {
+        self.port
+            .or_else(|| special_scheme_default_port(&self.scheme))
+    }
+
+    /// The scheme, host and effective port of the URL, or `None` if its origin is opaque. Only
+    /// URLs with a scheme in the web's security model (http, https, ws, wss and ftp) have a
+    /// tuple origin; the origin of a `file` URL is left opaque.
+    ///
+    /// https://url.spec.whatwg.org/#concept-url-origin
+    pub fn origin(&self) -> Option<(String, String, u16)> {
+        match self.scheme.as_str() {
+            "ftp" | "http" | "https" | "ws" | "wss" => Some((
+                self.scheme.clone(),
+                self.host.as_ref()?.serialize(),
+                self.effective_port()?,
+            )),
+            _ => None,
+        }
+    }
+
+    /// Opaque origins are only the same as themselves, so URLs with one are never same origin.
+    ///
+    /// https://html.spec.whatwg.org/multipage/browsers.html#same-origin
+    pub fn is_same_origin(&self, other: &URL) -> bool {
+        match (self.origin(), other.origin()) {
+            (Some(origin), Some(other)) => origin == other,
+            _ => false,
+        }
+    }
+
     pub fn pure_parse(input: String) -> Result<URL, ParseURLError> {
         URL::parse(input, None, None)
     }
@@ -1079,7 +1170,7 @@ impl URL {
                     } else if state_override.is_none() {
                         buffer = String::new();
                         state = ParseURLState::NoScheme;
-                        pointer.advance_by(-pointer.pointer);
+                        pointer.advance_by(-pointer.pointer - 1);
                     } else {
                         return Err(ParseURLError::Failure);
                     }
@@ -1367,7 +1458,9 @@ impl URL {
                         } else if !pointer.is_eof {
                             url.query = None;
                             if !starts_with_windows_drive_letter(
-                                &pointer.chars[pointer.pointer as usize..].iter().collect(),
+                                &pointer.chars[pointer.pointer as usize..]
+                                    .iter()
+                                    .collect::<String>(),
                             ) {
                                 url.shorten_path();
                             } else {
@@ -1397,7 +1490,9 @@ impl URL {
                             url.host = burl.host.clone();
 
                             if !starts_with_windows_drive_letter(
-                                &pointer.chars[pointer.pointer as usize..].iter().collect(),
+                                &pointer.chars[pointer.pointer as usize..]
+                                    .iter()
+                                    .collect::<String>(),
                             ) && is_normalized_windows_drive_letter(&path[0])
                             {
                                 url.path.push(path[0].clone());
@@ -1561,7 +1656,7 @@ impl URL {
                         encoding = encoding_rs::Encoding::for_label(b"utf-8");
                     }
 
-                    if (state_override.is_none() && pointer.c == '#') && pointer.is_eof {
+                    if (state_override.is_none() && pointer.c == '#') || pointer.is_eof {
                         let new_query_percent_encode_set = if is_special_scheme(&url.scheme) {
                             special_query_percent_encode_set
                         } else {
@@ -1569,7 +1664,7 @@ impl URL {
                         };
 
                         let result = percent_encoding_after_encoding(
-                            encoding.unwrap(),
+                            encoding.unwrap_or(encoding_rs::UTF_8),
                             &buffer,
                             &new_query_percent_encode_set,
                             None,
@@ -1593,8 +1688,8 @@ impl URL {
                 ParseURLState::Fragment => {
                     if !pointer.is_eof {
                         let result = percent_encoding_after_encoding(
-                            encoding.unwrap(),
-                            &buffer,
+                            encoding_rs::UTF_8,
+                            &pointer.c.to_string(),
                             &fragment_percent_encode_set,
                             None,
                         );
@@ -12,6 +12,50 @@ pub fn is_surrogate(code: u32) -> bool {
     is_leading_surrogate(code) || is_trailing_surrogate(code)
 }
 
+/// https://infra.spec.whatwg.org/#noncharacter
+///
+/// The range U+FDD0 to U+FDEF, plus the last two code points of every plane.
+pub fn is_noncharacter(code: u32) -> bool {
+    (0xFDD0..=0xFDEF).contains(&code)
+        || matches!(
+            code,
+            0xFFFE
+                | 0xFFFF
+                | 0x1FFFE
+                | 0x1FFFF
+                | 0x2FFFE
+                | 0x2FFFF
+                | 0x3FFFE
+                | 0x3FFFF
+                | 0x4FFFE
+                | 0x4FFFF
+                | 0x5FFFE
+                | 0x5FFFF
+                | 0x6FFFE
+                | 0x6FFFF
+                | 0x7FFFE
+                | 0x7FFFF
+                | 0x8FFFE
+                | 0x8FFFF
+                | 0x9FFFE
+                | 0x9FFFF
+                | 0xAFFFE
+                | 0xAFFFF
+                | 0xBFFFE
+                | 0xBFFFF
+                | 0xCFFFE
+                | 0xCFFFF
+                | 0xDFFFE
+                | 0xDFFFF
+                | 0xEFFFE
+                | 0xEFFFF
+                | 0xFFFFE
+                | 0xFFFFF
+                | 0x10FFFE
+                | 0x10FFFF
+        )
+}
+
 pub fn char_is_ident_start(ch: char) -> bool {
     ch.is_ascii_alphabetic() || !ch.is_ascii() || ch == '\u{005F}'
 }
@@ -89,6 +133,8 @@ pub fn char_is_non_printable(ch: char) -> bool {
         || (code & 0xFFFE) == 0xFFFE && code >= 0xFFFE && code <= 0x10FFFF
 }
 
+/// A cursor over a sequence of items, shared by every parser: the HTML tokenizer reads `char`s,
+/// and the CSS tokenizer and parser read `char`s, tokens and component values.
 #[derive(Clone)]
 pub struct InputStream<T> {
     input: Vec<T>,
@@ -98,6 +144,10 @@ pub struct InputStream<T> {
     pub is_eof: bool,
 
     is_started: bool,
+
+    /// Whether every item has been supplied. Streams made with `streaming` stay open until
+    /// `close` is called, so running out of items doesn't mean the input has ended.
+    is_closed: bool,
 }
 
 impl<T> InputStream<T>
@@ -111,9 +161,33 @@ where
             is_reconsume: false,
             is_eof: false,
             is_started: false,
+            is_closed: true,
         }
     }
 
+    /// An empty stream that items are appended to as they arrive, for example from the network.
+    pub fn streaming() -> InputStream<T> {
+        InputStream {
+            is_closed: false,
+            ..InputStream::new(&[])
+        }
+    }
+
+    /// Adds `data` to the end of an open stream.
+    pub fn append(&mut self, data: &[T]) {
+        debug_assert!(!self.is_closed, "Cannot append to a closed input stream");
+        self.input.extend_from_slice(data);
+    }
+
+    /// Marks the stream as complete, so running out of items is the end of the input.
+    pub fn close(&mut self) {
+        self.is_closed = true;
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.is_closed
+    }
+
     pub fn current(&self) -> T {
         self.input[self.pos].clone()
     }
@@ -152,6 +226,25 @@ where
         Some(self.input[self.pos + diff + n].clone())
     }
 
+    /// The next `n` items that `consume` would return, without consuming them. Shorter than
+    /// `n` when the stream ends sooner.
+    pub fn lookahead(&self, n: usize) -> &[T] {
+        if self.is_eof {
+            return &[];
+        }
+
+        let next = if self.is_started && !self.is_reconsume {
+            self.pos + 1
+        } else {
+            self.pos
+        };
+
+        let start = next.min(self.input.len());
+        let end = next.saturating_add(n).min(self.input.len());
+
+        &self.input[start..end]
+    }
+
     pub fn peek_range(&self, start: usize, n: usize) -> Option<&[T]> {
         if self.pos + start + n >= self.input.len() {
             return None;
@@ -203,6 +296,38 @@ where
 }
 
 impl InputStream<char> {
+    /// Replaces every CRLF pair, and then every remaining CR, with a LF in the characters that
+    /// haven't been consumed yet.
+    ///
+    /// https://infra.spec.whatwg.org/#normalize-newlines
+    pub fn normalize_newlines(&mut self) {
+        let next = if self.is_started && !self.is_reconsume {
+            self.pos + 1
+        } else {
+            self.pos
+        };
+
+        if next >= self.input.len() {
+            return;
+        }
+
+        let rest = self.input.split_off(next);
+        let mut normalized = Vec::with_capacity(rest.len());
+        let mut after_carriage_return = false;
+
+        for ch in rest {
+            match ch {
+                '\u{000D}' => normalized.push('\u{000A}'),
+                '\u{000A}' if after_carriage_return => {}
+                _ => normalized.push(ch),
+            }
+
+            after_carriage_return = ch == '\u{000D}';
+        }
+
+        self.input.extend(normalized);
+    }
+
     pub fn matches(
         &self,
         text: &str,
@@ -5,3 +5,5 @@ pub mod html5;
 pub mod http;
 pub mod infra;
 pub mod render;
+
+pub use html5::{parse_fragment, parse_html};
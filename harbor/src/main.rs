@@ -23,48 +23,45 @@ fn main() {
     println!("Parsing target: {}", url_target);
 
     let mut client = http::Client::new(http::Protocol::HTTP1_1, true);
-    let url = client.connect_to_url(url_target);
+    let url = match http::url::URL::pure_parse(url_target.clone()) {
+        Ok(url) => url,
+        Err(e) => {
+            eprintln!("Couldn't open {}: {}", url_target, e);
+            return;
+        }
+    };
 
     println!("Sending request to: {}", url.serialize());
 
-    let resp = client.send_request(http::Request {
-        method: String::from("GET"),
-        request_target: url.path.serialize(),
-        protocol: http::Protocol::HTTP1_1,
-        headers: vec![
-            http::Header::new(String::from("User-Agent"), String::from("Harbor Browser")),
-            http::Header::new(String::from("Host"), url.host.unwrap().serialize()),
-        ],
-        body: None,
-    });
-
-    let response = resp.unwrap();
-    println!("Received response: \n\n{}", response.body.clone().unwrap());
-
-    let html_content = response.body.unwrap();
+    let Some((response, url)) = client.navigate(&url) else {
+        eprintln!("Couldn't load {}", url.serialize());
+        return;
+    };
+
+    let html_content = response.body.clone().unwrap_or_default();
+    println!("Received response: \n\n{}", html_content);
     // let html_content = include_str!("../../assets/html/custom004.html");
 
-    let mut stream = InputStream::new(&html_content.chars().collect::<Vec<char>>()[..]);
-    let mut parser = html5::parse::Parser::new(&mut stream);
+    let document = html5::parse_html(&html_content);
 
-    parser.parse();
+    document.borrow_mut().set_url(url);
+    for visited in client.history() {
+        document.borrow_mut().mark_visited(visited);
+    }
+    html5::preload::load_stylesheets(&document, &mut client);
 
     let stylesheet = include_str!("../../res/css/ua.css").to_string();
     let css_content = parse_stylesheet(
         &mut InputStream::new(&tokenize(&mut InputStream::new(
             &stylesheet.chars().collect::<Vec<char>>()[..],
         ))),
-        Rc::downgrade(parser.document.document()),
+        Rc::downgrade(&document),
         None,
     );
 
-    parser
-        .document
-        .document()
-        .borrow_mut()
-        .insert_stylesheet(0, css_content);
+    document.borrow_mut().insert_stylesheet(0, css_content);
 
-    let mut layout = Layout::new(Rc::clone(&parser.document.document()), (800.0, 600.0));
+    let mut layout = Layout::new(Rc::clone(&document), (800.0, 600.0));
     layout.make_tree();
     layout.layout();
 
@@ -82,7 +79,7 @@ fn main() {
             },
         },
         state: None,
-        document: parser.document.document.borrow().clone(),
+        document: document.borrow().clone(),
         layout,
     };
 
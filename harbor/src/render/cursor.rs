@@ -0,0 +1,109 @@
+use std::{cell::RefCell, rc::Rc};
+
+use winit::window::{CursorIcon, Window};
+
+use crate::{css::properties::Cursor, html5::dom::Element};
+
+/// The mouse cursor shown over the page, kept so the window is only told when it changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseCursor {
+    /// The icon to show, or `None` when the cursor is hidden
+    pub icon: Option<CursorIcon>,
+}
+
+impl Default for MouseCursor {
+    fn default() -> Self {
+        Self {
+            icon: Some(CursorIcon::Default),
+        }
+    }
+}
+
+impl MouseCursor {
+    /// The cursor to show over `hovered`, the elements under the mouse from the outermost to the
+    /// innermost.
+    ///
+    /// The innermost element's `cursor` is used, since the property is inherited. Where it's
+    /// `auto`, the cursor is a pointer over links and the default arrow anywhere else.
+    ///
+    /// https://drafts.csswg.org/css-ui/#valdef-cursor-auto
+    pub fn for_hovered(hovered: &[Rc<RefCell<Element>>]) -> Self {
+        let cursor = hovered
+            .last()
+            .map(|element| element.borrow().style().cursor)
+            .unwrap_or_default();
+
+        let over_link = || hovered.iter().any(|element| element.borrow().is_link());
+
+        match cursor {
+            Cursor::Auto if over_link() => Self {
+                icon: Some(CursorIcon::Pointer),
+            },
+            cursor => Self {
+                icon: Self::icon_for(cursor),
+            },
+        }
+    }
+
+    /// The OS cursor to show for `cursor`, or `None` to hide it. `auto` is left to the
+    /// platform's default.
+    fn icon_for(cursor: Cursor) -> Option<CursorIcon> {
+        Some(match cursor {
+            Cursor::Auto => CursorIcon::Default,
+            Cursor::None => return None,
+            Cursor::Default => CursorIcon::Default,
+            Cursor::ContextMenu => CursorIcon::ContextMenu,
+            Cursor::Help => CursorIcon::Help,
+            Cursor::Pointer => CursorIcon::Pointer,
+            Cursor::Progress => CursorIcon::Progress,
+            Cursor::Wait => CursorIcon::Wait,
+            Cursor::Cell => CursorIcon::Cell,
+            Cursor::Crosshair => CursorIcon::Crosshair,
+            Cursor::Text => CursorIcon::Text,
+            Cursor::VerticalText => CursorIcon::VerticalText,
+            Cursor::Alias => CursorIcon::Alias,
+            Cursor::Copy => CursorIcon::Copy,
+            Cursor::Move => CursorIcon::Move,
+            Cursor::NoDrop => CursorIcon::NoDrop,
+            Cursor::NotAllowed => CursorIcon::NotAllowed,
+            Cursor::Grab => CursorIcon::Grab,
+            Cursor::Grabbing => CursorIcon::Grabbing,
+            Cursor::EResize => CursorIcon::EResize,
+            Cursor::NResize => CursorIcon::NResize,
+            Cursor::NeResize => CursorIcon::NeResize,
+            Cursor::NwResize => CursorIcon::NwResize,
+            Cursor::SResize => CursorIcon::SResize,
+            Cursor::SeResize => CursorIcon::SeResize,
+            Cursor::SwResize => CursorIcon::SwResize,
+            Cursor::WResize => CursorIcon::WResize,
+            Cursor::EwResize => CursorIcon::EwResize,
+            Cursor::NsResize => CursorIcon::NsResize,
+            Cursor::NeswResize => CursorIcon::NeswResize,
+            Cursor::NwseResize => CursorIcon::NwseResize,
+            Cursor::ColResize => CursorIcon::ColResize,
+            Cursor::RowResize => CursorIcon::RowResize,
+            Cursor::AllScroll => CursorIcon::AllScroll,
+            Cursor::ZoomIn => CursorIcon::ZoomIn,
+            Cursor::ZoomOut => CursorIcon::ZoomOut,
+        })
+    }
+
+    /// Switches to `next`, updating the window's cursor if it's any different. Returns whether
+    /// it changed.
+    pub fn update(&mut self, next: MouseCursor, window: &Window) -> bool {
+        if *self == next {
+            return false;
+        }
+
+        match next.icon {
+            Some(icon) => {
+                window.set_cursor(icon);
+                window.set_cursor_visible(true);
+            }
+            None => window.set_cursor_visible(false),
+        }
+
+        *self = next;
+        true
+    }
+}
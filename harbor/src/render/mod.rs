@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::rc::Rc;
 use std::sync::Arc;
 
 use winit::application::ApplicationHandler;
@@ -7,19 +8,22 @@ use winit::event_loop::ActiveEventLoop;
 use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::window::{Window, WindowId};
 
-use wgpu::{self, Device};
-
 use crate::css::r#box::Box;
 use crate::css::colors::UsedColor;
 use crate::css::layout::Layout;
 use crate::font::otf_dtypes::GLYPH_ID;
+use crate::font::tables::colr::ColorLayer;
 use crate::font::tables::glyf::Point;
 use crate::font::ttf::TableDirectory;
 use crate::html5::dom::Document;
+use crate::render::cursor::MouseCursor;
+use crate::render::shaping::{ShapedRun, ShapingCache};
 use crate::render::state::WindowState;
-use crate::render::text::{GlyphInstance, GlyphMesh, GlyphVertex};
+use crate::render::text::{BatchedGlyphVertex, GlyphBatch, GlyphInstance, GlyphMesh, GlyphVertex};
 
+pub mod cursor;
 pub mod shapes;
+pub mod shaping;
 pub mod state;
 pub mod text;
 
@@ -66,6 +70,30 @@ pub fn fill_descriptor() -> wgpu::VertexBufferLayout<'static> {
     }
 }
 
+pub fn glyph_descriptor() -> wgpu::VertexBufferLayout<'static> {
+    wgpu::VertexBufferLayout {
+        array_stride: (std::mem::size_of::<BatchedGlyphVertex>() as wgpu::BufferAddress),
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &[
+            wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 0,
+                format: wgpu::VertexFormat::Float32x2,
+            },
+            wgpu::VertexAttribute {
+                offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                shader_location: 1,
+                format: wgpu::VertexFormat::Float32x2,
+            },
+            wgpu::VertexAttribute {
+                offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                shader_location: 2,
+                format: wgpu::VertexFormat::Float32x4,
+            },
+        ],
+    }
+}
+
 #[derive(Hash, Eq, PartialEq, Clone)]
 pub struct RendererIdentifier {
     pub font_family: String,
@@ -80,97 +108,133 @@ pub struct TextRenderer {
 
     pub font: TableDirectory,
 
-    /// Key: (glyph_id, font size)
+    /// Key: (glyph_id, bits of the font size)
     pub glyph_cache: HashMap<(GLYPH_ID, u32), GlyphMesh>,
+
+    pub shaping_cache: ShapingCache,
 }
 
 impl TextRenderer {
-    pub fn get_from_char(
-        &mut self,
-        ch: char,
-        font_size: u32,
-        device: &Device,
-        queue: &wgpu::Queue,
-    ) -> Option<GlyphMesh> {
-        let glyph_id = self.font.cmap_lookup(ch as u32);
-
-        if let Some(gid) = glyph_id {
-            if let Some(glyph) = self.glyph_cache.get(&(gid, font_size)) {
-                return Some(glyph.clone());
-            } else {
-                let mut points: Vec<Point> = Vec::new();
-                self.font.make_glyph_points(gid, 5.0, &mut points);
-
-                if points.len() == 0 {
-                    return None;
-                }
+    pub fn new(font: TableDirectory, weight: u16, italic: bool) -> Self {
+        Self {
+            _associated_weight: weight,
+            _associated_italic: italic,
+            font,
+            glyph_cache: HashMap::new(),
+            shaping_cache: ShapingCache::new(),
+        }
+    }
 
-                let mut min_x = f32::INFINITY;
-                let mut min_y = f32::INFINITY;
-                let mut max_x = f32::NEG_INFINITY;
-                let mut max_y = f32::NEG_INFINITY;
+    /// Switches to drawing with `font`, dropping everything made with the old one.
+    pub fn reload_font(&mut self, font: TableDirectory) {
+        self.font = font;
 
-                for p in &points {
-                    min_x = min_x.min(p.x);
-                    min_y = min_y.min(p.y);
-                    max_x = max_x.max(p.x);
-                    max_y = max_y.max(p.y);
-                }
+        self.glyph_cache.clear();
+        self.shaping_cache.invalidate();
+    }
 
-                let scale = font_size as f32 / self.font.units_per_em() as f32;
-
-                let glyph_verts = points
-                    .iter()
-                    .map(|p| GlyphVertex {
-                        position: [(p.x - min_x) * scale, (p.y) * scale],
-                    })
-                    .collect::<Vec<GlyphVertex>>();
-
-                let glyph_mesh = GlyphMesh {
-                    outline_vertex_buffer: device.create_buffer(&wgpu::BufferDescriptor {
-                        label: Some("Glyph Vertex Buffer"),
-                        size: (glyph_verts.len() * std::mem::size_of::<GlyphVertex>()) as u64,
-                        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                        mapped_at_creation: false,
-                    }),
-                    outline_vertex_count: glyph_verts.len() as u32,
-                    // TODO: THIS
-                    fill_vertex_buffer: device.create_buffer(&wgpu::BufferDescriptor {
-                        label: Some("Glyph Fill Vertex Buffer"),
-                        size: 0,
-                        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                        mapped_at_creation: false,
-                    }),
-                    fill_vertex_count: 0,
-                    advance_width: self.font.advance_width(gid).unwrap_or_else(|| {
-                        self.font
-                            .advance_width(self.font.last_glyph_index().unwrap())
-                            .unwrap_or(0)
-                    }) as f32
-                        * scale,
-                    instance_buffer: device.create_buffer(&wgpu::BufferDescriptor {
-                        label: Some("Glyph Instance Buffer"),
-                        size: 10_000 * std::mem::size_of::<GlyphInstance>() as u64,
-                        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                        mapped_at_creation: false,
-                    }),
-                    instance_count: 0,
-                };
-
-                queue.write_buffer(
-                    &glyph_mesh.outline_vertex_buffer,
-                    0,
-                    bytemuck::cast_slice(&glyph_verts),
-                );
-
-                self.glyph_cache
-                    .insert((gid, font_size), glyph_mesh.clone());
-
-                Some(glyph_mesh)
+    /// The glyphs of `text` and where they go along the line, shaped once for every font size.
+    pub fn shape(&mut self, text: &str) -> Rc<ShapedRun> {
+        self.shaping_cache.shape(&self.font, text)
+    }
+
+    pub fn get_from_char(&mut self, ch: char, font_size: f32) -> Option<&GlyphMesh> {
+        // Characters without a glyph are drawn as the missing glyph
+        let gid = self.font.resolve_glyph(ch as u32);
+
+        self.get_from_glyph(gid, font_size)
+    }
+
+    pub fn get_from_glyph(&mut self, gid: GLYPH_ID, font_size: f32) -> Option<&GlyphMesh> {
+        // Fractional sizes are kept apart, rather than sharing the mesh of the size they round to
+        let key = (gid, font_size.to_bits());
+
+        if !self.glyph_cache.contains_key(&key) {
+            let mut points: Vec<Point> = Vec::new();
+            self.font.make_glyph_points(gid, 5.0, &mut points);
+
+            if points.len() == 0 {
+                return None;
             }
-        } else {
-            None
+
+            let mut min_x = f32::INFINITY;
+
+            for p in &points {
+                min_x = min_x.min(p.x);
+            }
+
+            let scale = font_size / self.font.units_per_em() as f32;
+
+            // The outline starts its left side bearing to the right of the pen
+            let left_edge = self.font.glyph_left_edge(gid, 0.0, scale);
+
+            let outline_vertices = points
+                .iter()
+                .map(|p| GlyphVertex {
+                    position: [(p.x - min_x) * scale + left_edge, (p.y) * scale],
+                })
+                .collect::<Vec<GlyphVertex>>();
+
+            let glyph_mesh = GlyphMesh {
+                outline_vertices,
+                advance_width: self.font.advance_width(gid).unwrap_or(0) as f32 * scale,
+            };
+
+            self.glyph_cache.insert(key, glyph_mesh);
         }
+
+        self.glyph_cache.get(&key)
+    }
+
+    /// Adds the glyphs of each line to `batch`, the first line with its pen at `origin` and
+    /// each line after it `line_height` further down, returning the (start x, end x,
+    /// baseline y) of every line.
+    pub fn batch_lines(
+        &mut self,
+        lines: &[String],
+        origin: (f32, f32),
+        font_size: f32,
+        line_height: f32,
+        color: UsedColor,
+        batch: &mut GlyphBatch,
+    ) -> Vec<(f32, f32, f32)> {
+        let (line_x, first_baseline) = origin;
+        let mut line_extents = Vec::with_capacity(lines.len());
+
+        for (i, line) in lines.iter().enumerate() {
+            let pen_y = first_baseline + i as f32 * line_height;
+
+            let run = self.shape(line);
+
+            for (glyph_id, x) in run.scaled(font_size) {
+                // Color glyphs are a stack of other glyphs, each with a color of its own
+                let layers = self.font.color_layers(glyph_id, 0).unwrap_or_else(|| {
+                    vec![ColorLayer {
+                        glyph_id,
+                        color: None,
+                    }]
+                });
+
+                for layer in layers {
+                    // Glyphs without an outline, like spaces, only move the pen
+                    let Some(glyph_mesh) = self.get_from_glyph(layer.glyph_id, font_size) else {
+                        continue;
+                    };
+
+                    batch.push(
+                        glyph_mesh,
+                        GlyphInstance {
+                            offset: [line_x + x, pen_y],
+                            color: layer.color.unwrap_or(color),
+                        },
+                    );
+                }
+            }
+
+            line_extents.push((line_x, line_x + run.scaled_advance_width(font_size), pen_y));
+        }
+
+        line_extents
     }
 }
 
@@ -191,9 +255,14 @@ pub struct App {
 
 impl ApplicationHandler<WindowState> for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        let title = match self.document.document_title() {
+            title if title.is_empty() => String::from("Harbor Browser"),
+            title => title,
+        };
+
         #[allow(unused_mut)]
         let mut window_attributes = Window::default_attributes()
-            .with_title("Harbor Browser")
+            .with_title(title)
             // TODO: Change this to not have any decorations
             .with_decorations(true);
 
@@ -245,6 +314,10 @@ impl ApplicationHandler<WindowState> for App {
                         }
                     }
 
+                    state
+                        .mouse_cursor
+                        .update(MouseCursor::for_hovered(&elems), &state.window);
+
                     state.prev_hovered_elements = elems;
                 }
             }
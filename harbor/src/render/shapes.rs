@@ -1,4 +1,7 @@
-use crate::{css::colors::UsedColor, render::ColoredVertex};
+use crate::{
+    css::{r#box::Box, colors::UsedColor},
+    render::ColoredVertex,
+};
 
 /// Gives vertices with 3D positions for a rectangle starting at (0,0) to be built with a triangle
 /// list
@@ -67,6 +70,60 @@ pub fn rectangle_at(
     ]
 }
 
+/// A rectangle given in pixels from the top left of the screen, converted to ndc
+pub fn pixel_rectangle(
+    (x, y, width, height): (f64, f64, f64, f64),
+    color: UsedColor,
+    screen_width: f32,
+    screen_height: f32,
+) -> Vec<ColoredVertex> {
+    rectangle_at(
+        (x as f32 / screen_width) * 2.0 - 1.0,
+        1.0 - (y as f32 / screen_height) * 2.0,
+        (width as f32 / screen_width) * 2.0,
+        (height as f32 / screen_height) * 2.0,
+        color,
+    )
+}
+
+/// Triangle list vertices for a box's background and then its borders, where `position` is
+/// the top left of the content of the box's parent.
+///
+/// https://www.w3.org/TR/CSS2/zindex.html#painting-order
+pub fn box_decoration_vertices(
+    layout_box: &Box,
+    position: (f64, f64),
+    screen_width: f32,
+    screen_height: f32,
+) -> Vec<ColoredVertex> {
+    let moved = |(x, y, width, height): (f64, f64, f64, f64)| {
+        (x + position.0, y + position.1, width, height)
+    };
+
+    let mut vertices = Vec::new();
+
+    if let Some(rect) = layout_box.background_rect() {
+        let color = layout_box.style().unwrap().background.color().used();
+        vertices.extend(pixel_rectangle(
+            moved(rect),
+            color,
+            screen_width,
+            screen_height,
+        ));
+    }
+
+    for (rect, color) in layout_box.border_rects() {
+        vertices.extend(pixel_rectangle(
+            moved(rect),
+            color,
+            screen_width,
+            screen_height,
+        ));
+    }
+
+    vertices
+}
+
 pub fn circle_at(
     center_x: f32,
     center_y: f32,
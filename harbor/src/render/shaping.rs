@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::font::otf_dtypes::GLYPH_ID;
+use crate::font::ttf::TableDirectory;
+
+/// A glyph in a shaped run, placed in font units from the start of the run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapedGlyph {
+    pub glyph_id: GLYPH_ID,
+
+    /// How far the glyph's origin is from the start of the run
+    pub x: f32,
+
+    pub advance_width: f32,
+}
+
+/// A piece of text mapped to glyphs and laid out along a line, in font units so that the same
+/// run can be drawn at any font size.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShapedRun {
+    pub glyphs: Vec<ShapedGlyph>,
+
+    /// How far the pen moves over the whole run
+    pub advance_width: f32,
+
+    units_per_em: f32,
+}
+
+impl ShapedRun {
+    pub fn shape(font: &TableDirectory, text: &str) -> Self {
+        let mut glyphs = Vec::new();
+        let mut pen_x = 0.0;
+
+        for glyph_id in font.glyph_indices(text) {
+            let advance_width = font
+                .advance_width(glyph_id)
+                .or_else(|| font.rawdog_advance_width(glyph_id))
+                .unwrap_or(0) as f32;
+
+            glyphs.push(ShapedGlyph {
+                glyph_id,
+                x: pen_x,
+                advance_width,
+            });
+
+            pen_x += advance_width;
+        }
+
+        Self {
+            glyphs,
+            advance_width: pen_x,
+            units_per_em: font.units_per_em() as f32,
+        }
+    }
+
+    /// Pixels per font unit at `font_size`.
+    pub fn scale(&self, font_size: f32) -> f32 {
+        font_size / self.units_per_em
+    }
+
+    /// The glyphs of the run and how far each one is from its start, in pixels at `font_size`.
+    pub fn scaled(&self, font_size: f32) -> impl Iterator<Item = (GLYPH_ID, f32)> + '_ {
+        let scale = self.scale(font_size);
+
+        self.glyphs
+            .iter()
+            .map(move |glyph| (glyph.glyph_id, glyph.x * scale))
+    }
+
+    /// The width of the whole run in pixels at `font_size`.
+    pub fn scaled_advance_width(&self, font_size: f32) -> f32 {
+        self.advance_width * self.scale(font_size)
+    }
+}
+
+/// How many runs a `ShapingCache` keeps before it forgets the least recently used one.
+pub const DEFAULT_SHAPING_CACHE_CAPACITY: usize = 4096;
+
+/// Shaped runs of a single font, kept by their text. Runs don't depend on the font size, so
+/// the same run is drawn at every size. Only the most recently used runs are kept, so pages
+/// with a lot of distinct text don't grow the cache forever.
+#[derive(Debug, Clone)]
+pub struct ShapingCache {
+    /// Each run with the `clock` reading from when it was last asked for
+    runs: HashMap<String, (Rc<ShapedRun>, u64)>,
+    capacity: usize,
+    clock: u64,
+}
+
+impl Default for ShapingCache {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_SHAPING_CACHE_CAPACITY)
+    }
+}
+
+impl ShapingCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A cache that keeps at most `capacity` runs, and at least one.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            runs: HashMap::new(),
+            capacity: capacity.max(1),
+            clock: 0,
+        }
+    }
+
+    /// The run for `text`, shaping it with `font` the first time it's asked for.
+    pub fn shape(&mut self, font: &TableDirectory, text: &str) -> Rc<ShapedRun> {
+        self.clock += 1;
+
+        if let Some((run, last_used)) = self.runs.get_mut(text) {
+            *last_used = self.clock;
+            return run.clone();
+        }
+
+        if self.runs.len() >= self.capacity {
+            self._evict_least_recently_used();
+        }
+
+        let run = Rc::new(ShapedRun::shape(font, text));
+        self.runs
+            .insert(text.to_string(), (run.clone(), self.clock));
+
+        run
+    }
+
+    fn _evict_least_recently_used(&mut self) {
+        let oldest = self
+            .runs
+            .iter()
+            .min_by_key(|(_, (_, last_used))| *last_used)
+            .map(|(text, _)| text.clone());
+
+        if let Some(text) = oldest {
+            self.runs.remove(&text);
+        }
+    }
+
+    /// Forgets every run, which must be done whenever the font they were shaped with changes.
+    pub fn invalidate(&mut self) {
+        self.runs.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.runs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+}
@@ -1,4 +1,4 @@
-use std::{cell::RefCell, collections::HashMap, ops::Deref, rc::Rc, sync::Arc};
+use std::{cell::RefCell, ops::Deref, rc::Rc, sync::Arc};
 
 use wgpu::util::DeviceExt;
 use winit::window::Window;
@@ -12,12 +12,17 @@ use crate::{
     globals::DEFAULT_FONT_FAMILY,
     html5::dom::{Document, Element, NodeKind},
     render::{
-        Globals, RendererIdentifier, WindowOptions, fill_descriptor,
-        shapes::{circle_at, rectangle_at},
-        text::{GlyphInstance, GlyphVertex},
+        Globals, RendererIdentifier, WindowOptions,
+        cursor::MouseCursor,
+        fill_descriptor, glyph_descriptor,
+        shapes::{box_decoration_vertices, circle_at, rectangle_at},
+        text::{BatchedGlyphVertex, GlyphBatch},
     },
 };
 
+/// Bytes of glyph vertices the window starts out with room for
+const INITIAL_GLYPH_BUFFER_SIZE: wgpu::BufferAddress = 1 << 16;
+
 /// WindowState
 /// Holds all data about the WGPU state, along with the window
 pub struct WindowState {
@@ -44,9 +49,15 @@ pub struct WindowState {
     pub document: Document,
 
     pub prev_hovered_elements: Vec<Rc<RefCell<Element>>>,
+    pub mouse_cursor: MouseCursor,
 
     pub globals_buffer: wgpu::Buffer,
     pub globals_bind_group: wgpu::BindGroup,
+
+    /// The glyphs of the frame being drawn, which go out together whenever something is
+    /// painted over them
+    pub glyph_batch: GlyphBatch,
+    pub glyph_vertex_buffer: wgpu::Buffer,
 }
 
 impl WindowState {
@@ -59,83 +70,10 @@ impl WindowState {
     ) {
         match layout_box._box_type {
             BoxType::Block => {
-                render_pass.set_pipeline(&self.fill_render_pipeline);
-                let bg_color = layout_box.style().unwrap().background.color().used();
-
-                if bg_color[3] > 0.0 {
-                    let window_size = self.window.inner_size();
-
-                    // println!("Box: {:#?}", layout_box);
-
-                    let pixel_x =
-                        (layout_box.position().0 + position.0 + layout_box.margin().left()) as f32;
-                    let pixel_y =
-                        (layout_box.position().1 + position.1 + layout_box.margin().top()) as f32;
-
-                    let x_pos = (pixel_x / window_size.width as f32) * 2.0 - 1.0;
-                    let y_pos = 1.0 - (pixel_y / window_size.height as f32) * 2.0;
-
-                    let pixel_w = layout_box.content_edges().horizontal() as f32;
-                    let pixel_h = layout_box.content_edges().vertical() as f32;
-
-                    let width = (pixel_w / window_size.width as f32) * 2.0;
-                    let height = (pixel_h / window_size.height as f32) * 2.0;
-
-                    let verts = rectangle_at(x_pos, y_pos, width, height, bg_color);
-
-                    // println!("verts: {:#?}", verts);
-
-                    let bg_vertex_buffer =
-                        self.device
-                            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                                label: Some("Background Vertex Buffer"),
-                                contents: bytemuck::cast_slice(&verts),
-                                usage: wgpu::BufferUsages::VERTEX,
-                            });
-
-                    render_pass.set_vertex_buffer(0, bg_vertex_buffer.slice(..));
-                    render_pass.draw(0..verts.len() as u32, 0..1);
-                }
+                self.render_box_decorations(&layout_box, position, render_pass);
             }
             BoxType::Inline => {
-                let bg_color = layout_box
-                    .style()
-                    .map(|s| s.background.color().used())
-                    .unwrap_or([0.0, 0.0, 0.0, 0.0]);
-                if bg_color[3] > 0.0 {
-                    render_pass.set_pipeline(&self.fill_render_pipeline);
-
-                    let window_size = self.window.inner_size();
-
-                    let pixel_x =
-                        (layout_box.position().0 + position.0 + layout_box.margin().left()) as f32;
-                    let pixel_y =
-                        (layout_box.position().1 + position.1 + layout_box.margin().top()) as f32;
-
-                    let x_pos = (pixel_x / window_size.width as f32) * 2.0 - 1.0;
-                    let y_pos = 1.0 - (pixel_y / window_size.height as f32) * 2.0;
-
-                    let pixel_w = layout_box.content_edges().horizontal() as f32;
-                    let pixel_h = layout_box.content_edges().vertical() as f32;
-
-                    let width = (pixel_w / window_size.width as f32) * 2.0;
-                    let height = (pixel_h / window_size.height as f32) * 2.0;
-
-                    let verts = rectangle_at(x_pos, y_pos, width, height, bg_color);
-
-                    let bg_vertex_buffer =
-                        self.device
-                            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                                label: Some("Inline Background Vertex Buffer"),
-                                contents: bytemuck::cast_slice(&verts),
-                                usage: wgpu::BufferUsages::VERTEX,
-                            });
-
-                    render_pass.set_vertex_buffer(0, bg_vertex_buffer.slice(..));
-                    render_pass.draw(0..verts.len() as u32, 0..1);
-                }
-
-                render_pass.set_pipeline(&self.line_render_pipeline);
+                self.render_box_decorations(&layout_box, position, render_pass);
 
                 let adj_position = (
                     layout_box.position().0 as f64 + position.0,
@@ -202,84 +140,38 @@ impl WindowState {
                                     // panic!("No suitable font renderer found");
                                 });
 
-                            let mut glyph_instances: HashMap<char, Vec<GlyphInstance>> =
-                                HashMap::new();
-
-                            let mut pen_x = adj_position.0 as f32;
-                            let pen_y = adj_position.1 as f32
-                                + renderer.font.ascent().unwrap() as f32
-                                    * (style.font.resolved_font_size().unwrap_or(16.0) as f32
-                                        / renderer.font.units_per_em() as f32);
-
                             let font_size = style.font.resolved_font_size().unwrap_or(16.0) as f32;
-
-                            for ch in text_content.chars() {
-                                let glyph_mesh = renderer.get_from_char(
-                                    ch,
-                                    font_size as u32,
-                                    &self.device,
-                                    &self.queue,
-                                );
-
-                                if let Some(glyph) = glyph_mesh {
-                                    glyph_instances.entry(ch).or_default().push(GlyphInstance {
-                                        offset: [pen_x, pen_y],
-                                        color: style.color.used(),
-                                    });
-
-                                    pen_x += glyph.advance_width;
-                                } else {
-                                    pen_x += renderer
-                                        .font
-                                        .advance_width(
-                                            renderer.font.cmap_lookup(ch as u32).unwrap_or_else(
-                                                || {
-                                                    renderer
-                                                        .font
-                                                        .advance_width(
-                                                            renderer
-                                                                .font
-                                                                .last_glyph_index()
-                                                                .unwrap(),
-                                                        )
-                                                        .unwrap_or(0)
-                                                },
-                                            ),
-                                        )
-                                        .unwrap_or(0)
-                                        as f32
-                                        * (font_size / renderer.font.units_per_em() as f32);
-                                }
-                            }
-
-                            for (ch, instances) in glyph_instances {
-                                let mut glyph = renderer
-                                    .get_from_char(ch, font_size as u32, &self.device, &self.queue)
-                                    .unwrap();
-
-                                self.queue.write_buffer(
-                                    &glyph.instance_buffer,
-                                    0,
-                                    bytemuck::cast_slice(&instances),
-                                );
-
-                                glyph.instance_count = instances.len() as u32;
-
-                                render_pass
-                                    .set_vertex_buffer(0, glyph.outline_vertex_buffer.slice(..));
-                                render_pass.set_vertex_buffer(1, glyph.instance_buffer.slice(..));
-
-                                render_pass.draw(
-                                    0..glyph.outline_vertex_count,
-                                    0..glyph.instance_count as u32,
-                                );
-                            }
+                            let line_height =
+                                style.font.resolved_line_height().unwrap_or(19.2) as f32;
+                            let first_line_y = adj_position.1 as f32
+                                + renderer.font.ascent().unwrap() as f32
+                                    * (font_size / renderer.font.units_per_em() as f32);
+
+                            // Glyphs are drawn along with those of the runs either side, unless
+                            // something is painted in between
+                            let line_extents = renderer.batch_lines(
+                                layout_box.text_lines(),
+                                (adj_position.0 as f32, first_line_y),
+                                font_size,
+                                line_height,
+                                style.color.used(),
+                                &mut self.glyph_batch,
+                            );
+
+                            self.render_text_decorations(
+                                &line_extents,
+                                font_size,
+                                parents,
+                                render_pass,
+                            );
                         }
                         _ => {}
                     }
                 }
             }
             BoxType::Marker => {
+                self.flush_glyphs(render_pass);
+
                 // use circle render pipeline
                 render_pass.set_pipeline(&self.circle_render_pipeline);
 
@@ -335,6 +227,183 @@ impl WindowState {
         }
 
         parents.pop();
+
+        // Outlines are drawn over the box's contents
+        self.render_outline(&layout_box, position, render_pass);
+    }
+
+    /// Draws the background and borders of a box, which go under its contents.
+    fn render_box_decorations(
+        &mut self,
+        layout_box: &Box,
+        position: (f64, f64),
+        render_pass: &mut wgpu::RenderPass,
+    ) {
+        let window_size = self.window.inner_size();
+
+        let verts = box_decoration_vertices(
+            layout_box,
+            position,
+            window_size.width as f32,
+            window_size.height as f32,
+        );
+
+        if verts.is_empty() {
+            return;
+        }
+
+        self.flush_glyphs(render_pass);
+
+        let decoration_vertex_buffer =
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Background Vertex Buffer"),
+                    contents: bytemuck::cast_slice(&verts),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+
+        render_pass.set_pipeline(&self.fill_render_pipeline);
+        render_pass.set_vertex_buffer(0, decoration_vertex_buffer.slice(..));
+        render_pass.draw(0..verts.len() as u32, 0..1);
+    }
+
+    /// Draws the lines of every `text-decoration` among the text's ancestors across each of its
+    /// lines, which are given as (start x, end x, baseline y).
+    ///
+    /// https://drafts.csswg.org/css-text-decor/#line-decoration
+    fn render_text_decorations(
+        &mut self,
+        line_extents: &[(f32, f32, f32)],
+        font_size: f32,
+        parents: &[Box],
+        render_pass: &mut wgpu::RenderPass,
+    ) {
+        let window_size = self.window.inner_size();
+        let thickness = (font_size / 16.0).max(1.0);
+
+        let mut verts = Vec::new();
+
+        for style in parents.iter().filter_map(|parent| parent.style()) {
+            let line = style.text_decoration.line;
+            let color = style.text_decoration.color.unwrap_or(style.color).used();
+
+            // Offsets from the baseline
+            let offsets = [
+                (line.underline, font_size * 0.1),
+                (line.overline, -font_size * 0.9),
+                (line.line_through, -font_size * 0.3),
+            ];
+
+            for (start_x, end_x, baseline_y) in line_extents {
+                for (_, offset) in offsets.iter().filter(|(drawn, _)| *drawn) {
+                    let x_pos = (start_x / window_size.width as f32) * 2.0 - 1.0;
+                    let y_pos = 1.0 - ((baseline_y + offset) / window_size.height as f32) * 2.0;
+
+                    let width = ((end_x - start_x) / window_size.width as f32) * 2.0;
+                    let height = (thickness / window_size.height as f32) * 2.0;
+
+                    verts.extend(rectangle_at(x_pos, y_pos, width, height, color));
+                }
+            }
+        }
+
+        if verts.is_empty() {
+            return;
+        }
+
+        self.flush_glyphs(render_pass);
+
+        let decoration_vertex_buffer =
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Text Decoration Vertex Buffer"),
+                    contents: bytemuck::cast_slice(&verts),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+
+        render_pass.set_pipeline(&self.fill_render_pipeline);
+        render_pass.set_vertex_buffer(0, decoration_vertex_buffer.slice(..));
+        render_pass.draw(0..verts.len() as u32, 0..1);
+    }
+
+    fn render_outline(
+        &mut self,
+        layout_box: &Box,
+        position: (f64, f64),
+        render_pass: &mut wgpu::RenderPass,
+    ) {
+        let rects = layout_box.outline_rects();
+        if rects.is_empty() {
+            return;
+        }
+
+        let style = layout_box.style().unwrap();
+        let color = style.outline.used_color(&style.color).used();
+        if color[3] <= 0.0 {
+            return;
+        }
+
+        let window_size = self.window.inner_size();
+
+        let verts = rects
+            .iter()
+            .flat_map(|(x, y, w, h)| {
+                let x_pos = ((x + position.0) as f32 / window_size.width as f32) * 2.0 - 1.0;
+                let y_pos = 1.0 - ((y + position.1) as f32 / window_size.height as f32) * 2.0;
+
+                let width = (*w as f32 / window_size.width as f32) * 2.0;
+                let height = (*h as f32 / window_size.height as f32) * 2.0;
+
+                rectangle_at(x_pos, y_pos, width, height, color)
+            })
+            .collect::<Vec<_>>();
+
+        self.flush_glyphs(render_pass);
+
+        let outline_vertex_buffer =
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Outline Vertex Buffer"),
+                    contents: bytemuck::cast_slice(&verts),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+
+        render_pass.set_pipeline(&self.fill_render_pipeline);
+        render_pass.set_vertex_buffer(0, outline_vertex_buffer.slice(..));
+        render_pass.draw(0..verts.len() as u32, 0..1);
+    }
+
+    /// Draws the glyphs batched since the last flush, which must be done before anything is
+    /// painted over them. Each flush writes its own part of the vertex buffer, which grows when
+    /// the frame has more glyphs than it fits.
+    fn flush_glyphs(&mut self, render_pass: &mut wgpu::RenderPass) {
+        let Some(draw_range) = self.glyph_batch.flush() else {
+            return;
+        };
+
+        let stride = std::mem::size_of::<BatchedGlyphVertex>() as wgpu::BufferAddress;
+        let start = draw_range.start as wgpu::BufferAddress * stride;
+        let end = draw_range.end as wgpu::BufferAddress * stride;
+
+        if end > self.glyph_vertex_buffer.size() {
+            self.glyph_vertex_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Glyph Vertex Buffer"),
+                size: end.next_power_of_two(),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+        }
+
+        let vertices = &self.glyph_batch.vertices()[draw_range.start as usize..];
+        self.queue.write_buffer(
+            &self.glyph_vertex_buffer,
+            start,
+            bytemuck::cast_slice(vertices),
+        );
+
+        render_pass.set_pipeline(&self.line_render_pipeline);
+        render_pass.set_vertex_buffer(0, self.glyph_vertex_buffer.slice(..));
+        render_pass.draw(draw_range, 0..1);
     }
 
     pub fn render(&mut self) {
@@ -386,7 +455,10 @@ impl WindowState {
 
             let root_box = self.layout.root_box.as_ref().unwrap().borrow().clone();
 
+            self.glyph_batch.clear();
             self.render_box(root_box, (0.0, 0.0), &mut vec![], &mut _render_pass);
+
+            self.flush_glyphs(&mut _render_pass);
         }
 
         self.queue.submit(std::iter::once(encoder.finish()));
@@ -502,33 +574,7 @@ impl WindowState {
             vertex: wgpu::VertexState {
                 module: &shader,
                 entry_point: Some("glyph_vs_main"),
-                buffers: &[
-                    wgpu::VertexBufferLayout {
-                        array_stride: std::mem::size_of::<GlyphVertex>() as wgpu::BufferAddress,
-                        step_mode: wgpu::VertexStepMode::Vertex,
-                        attributes: &[wgpu::VertexAttribute {
-                            offset: 0,
-                            shader_location: 0,
-                            format: wgpu::VertexFormat::Float32x2,
-                        }],
-                    },
-                    wgpu::VertexBufferLayout {
-                        array_stride: std::mem::size_of::<GlyphInstance>() as wgpu::BufferAddress,
-                        step_mode: wgpu::VertexStepMode::Instance,
-                        attributes: &[
-                            wgpu::VertexAttribute {
-                                offset: 0,
-                                shader_location: 1,
-                                format: wgpu::VertexFormat::Float32x2,
-                            },
-                            wgpu::VertexAttribute {
-                                offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
-                                shader_location: 2,
-                                format: wgpu::VertexFormat::Float32x4,
-                            },
-                        ],
-                    },
-                ],
+                buffers: &[glyph_descriptor()],
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
             },
             fragment: Some(wgpu::FragmentState {
@@ -678,6 +724,13 @@ impl WindowState {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        let glyph_vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Glyph Vertex Buffer"),
+            size: INITIAL_GLYPH_BUFFER_SIZE,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
         let globals_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Globals Bind Group"),
             layout: &globals_bind_group_layout,
@@ -703,8 +756,11 @@ impl WindowState {
             window_options,
             document,
             prev_hovered_elements: vec![],
+            mouse_cursor: MouseCursor::default(),
             globals_buffer,
             globals_bind_group,
+            glyph_batch: GlyphBatch::new(),
+            glyph_vertex_buffer,
         }
     }
 
@@ -78,18 +78,14 @@ impl Segment {
     }
 }
 
+/// A glyph's outline at one font size, kept on the CPU until it's batched with the rest of
+/// the frame's text.
 #[derive(Clone)]
 pub struct GlyphMesh {
-    pub outline_vertex_buffer: wgpu::Buffer,
-    pub outline_vertex_count: u32,
-
-    pub fill_vertex_buffer: wgpu::Buffer,
-    pub fill_vertex_count: u32,
+    /// Pairs of points, one pair for each line of the outline, in pixels from the pen
+    pub outline_vertices: Vec<GlyphVertex>,
 
     pub advance_width: f32,
-
-    pub instance_buffer: wgpu::Buffer,
-    pub instance_count: u32,
 }
 
 #[repr(C)]
@@ -104,3 +100,68 @@ pub struct GlyphInstance {
 pub struct GlyphVertex {
     pub position: [f32; 2],
 }
+
+/// A vertex of a glyph outline along with where its glyph is drawn and in what color, so that
+/// every glyph can share one vertex buffer.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, Debug)]
+pub struct BatchedGlyphVertex {
+    pub position: [f32; 2],
+    pub offset: [f32; 2],
+    pub color: UsedColor,
+}
+
+/// The glyphs of a frame, gathered so that text that's painted one run after another goes out
+/// in one draw instead of a draw for every glyph of every run. The batch is flushed whenever
+/// something else is painted, so that it still goes over any text painted before it.
+#[derive(Debug, Default, Clone)]
+pub struct GlyphBatch {
+    vertices: Vec<BatchedGlyphVertex>,
+
+    /// How many of the vertices have already been drawn
+    flushed: usize,
+}
+
+impl GlyphBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a glyph with its pen at `instance.offset`. Glyphs are drawn in the order they're
+    /// added, so later ones go on top.
+    pub fn push(&mut self, mesh: &GlyphMesh, instance: GlyphInstance) {
+        self.vertices.extend(
+            mesh.outline_vertices
+                .iter()
+                .map(|vertex| BatchedGlyphVertex {
+                    position: vertex.position,
+                    offset: instance.offset,
+                    color: instance.color,
+                }),
+        );
+    }
+
+    /// Every vertex added this frame, drawn or not.
+    pub fn vertices(&self) -> &[BatchedGlyphVertex] {
+        &self.vertices
+    }
+
+    /// The vertices added since the last flush, which are drawn together, or `None` if there
+    /// haven't been any.
+    pub fn flush(&mut self) -> Option<std::ops::Range<u32>> {
+        if self.flushed == self.vertices.len() {
+            return None;
+        }
+
+        let pending = self.flushed as u32..self.vertices.len() as u32;
+        self.flushed = self.vertices.len();
+
+        Some(pending)
+    }
+
+    /// Empties the batch for the next frame, keeping its memory.
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.flushed = 0;
+    }
+}
@@ -0,0 +1,59 @@
+use harbor::{
+    css::{colors::Color, parser::parse_css_declaration_block, properties::CSSParseable},
+    infra::InputStream,
+};
+
+/// Parses `value` as the value of a `color` declaration.
+fn parse_color(value: &str) -> Option<Color> {
+    let declaration = parse_css_declaration_block(format!("color: {value};")).remove(0);
+
+    Color::from_cv(&mut InputStream::new(&declaration.value))
+}
+
+#[test]
+fn test_transparent() {
+    assert_eq!(parse_color("transparent").unwrap().used(), [0.0; 4]);
+    assert_eq!(parse_color("Transparent").unwrap().used(), [0.0; 4]);
+}
+
+#[test]
+fn test_named_colors() {
+    assert_eq!(
+        parse_color("rebeccapurple").unwrap().used(),
+        [102.0 / 255.0, 51.0 / 255.0, 153.0 / 255.0, 1.0]
+    );
+    assert_eq!(parse_color("RED").unwrap().used(), [1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(
+        parse_color("lightgoldenrodyellow").unwrap().used(),
+        [250.0 / 255.0, 250.0 / 255.0, 210.0 / 255.0, 1.0]
+    );
+    assert_eq!(parse_color("white").unwrap().used(), [1.0; 4]);
+    assert!(parse_color("notacolor").is_none());
+}
+
+#[test]
+fn test_system_colors_ignore_case() {
+    assert_eq!(
+        parse_color("canvastext").unwrap().used(),
+        parse_color("CanvasText").unwrap().used()
+    );
+    assert_eq!(parse_color("Canvas").unwrap().used(), [1.0; 4]);
+}
+
+#[test]
+fn test_hex_colors() {
+    assert_eq!(parse_color("#f00").unwrap().used(), [1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(
+        parse_color("#f008").unwrap().used(),
+        [1.0, 0.0, 0.0, 136.0 / 255.0]
+    );
+    assert_eq!(parse_color("#ff0000").unwrap().used(), [1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(
+        parse_color("#ff000080").unwrap().used(),
+        [1.0, 0.0, 0.0, 128.0 / 255.0]
+    );
+
+    assert!(parse_color("#ff00").is_some());
+    assert!(parse_color("#ff000").is_none());
+    assert!(parse_color("#ggg").is_none());
+}
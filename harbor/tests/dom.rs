@@ -0,0 +1,640 @@
+use std::{cell::RefCell, ops::Deref, rc::Rc};
+
+use harbor::{
+    css::{colors::Color, parser::parse_stylesheet, tokenize::tokenize},
+    html5::dom::{Document, DocumentPosition, Element, IElement, Node, NodeKind, Origin},
+    infra::{InputStream, Serializable},
+};
+
+fn append(parent: &Rc<RefCell<Element>>, child: NodeKind) {
+    Node::append_child(parent.borrow().node(), Rc::new(RefCell::new(child)));
+}
+
+fn element_node(document: &Rc<RefCell<Document>>, name: &str) -> Rc<RefCell<NodeKind>> {
+    Rc::new(RefCell::new(NodeKind::Element(Document::create_element(
+        document, name,
+    ))))
+}
+
+fn name_of(node: &Rc<RefCell<NodeKind>>) -> String {
+    match node.borrow().deref() {
+        NodeKind::Element(element) => element.borrow().local_name.clone(),
+        _ => panic!("Expected an element"),
+    }
+}
+
+/// Checks the child list of `parent` along with every first/last child, sibling and parent link.
+fn assert_children(parent: &Rc<RefCell<Node>>, expected: &[&str]) {
+    let parent_borrow = parent.borrow();
+    let children = parent_borrow.child_nodes();
+
+    assert_eq!(children.len(), expected.len());
+    assert_eq!(
+        parent_borrow.first_child().map(name_of).as_deref(),
+        expected.first().copied()
+    );
+    assert_eq!(
+        parent_borrow.last_child().map(name_of).as_deref(),
+        expected.last().copied()
+    );
+
+    for (i, child) in children.iter().enumerate() {
+        assert_eq!(name_of(child), expected[i]);
+
+        let child_node = child.borrow().node();
+        let child_node = child_node.borrow();
+
+        let child_parent = child_node.parent_node().and_then(|p| p.upgrade()).unwrap();
+        assert!(Rc::ptr_eq(&child_parent, parent));
+
+        let previous = child_node.previous_sibling().map(|n| name_of(&n));
+        let next = child_node.next_sibling().map(|n| name_of(&n));
+
+        assert_eq!(previous.as_deref(), i.checked_sub(1).map(|p| expected[p]));
+        assert_eq!(next.as_deref(), expected.get(i + 1).copied());
+    }
+}
+
+#[test]
+fn test_programmatic_dom_building() {
+    let document = Document::new(Origin::Opaque);
+
+    let div = Document::create_element(&document, "div");
+    Element::push_attr_raw_rc(&div, "class", "greeting \"quoted\"");
+
+    let p = Document::create_element(&document, "p");
+    append(
+        &p,
+        NodeKind::Text(Document::create_text_node(&document, "Hello & <bye>")),
+    );
+
+    let br = Document::create_element(&document, "br");
+
+    append(&div, NodeKind::Element(Rc::clone(&p)));
+    append(&div, NodeKind::Element(Rc::clone(&br)));
+    append(
+        &div,
+        NodeKind::Comment(Document::create_comment(&document, " note ")),
+    );
+
+    assert_eq!(
+        div.borrow().outer_html(),
+        "<div class=\"greeting &quot;quoted&quot;\"><p>Hello &amp; &lt;bye&gt;</p><br><!-- note --></div>"
+    );
+    assert_eq!(p.borrow().inner_html(), "Hello &amp; &lt;bye&gt;");
+
+    for element in [&div, &p, &br] {
+        let owner = element.borrow().node().borrow().node_document.clone();
+        assert!(owner.is_some_and(|doc| Rc::ptr_eq(&doc.upgrade().unwrap(), &document)));
+    }
+
+    assert_ne!(div.borrow().id, p.borrow().id);
+    assert_ne!(p.borrow().id, br.borrow().id);
+}
+
+#[test]
+fn test_insert_before_first_child() {
+    let document = Document::new(Origin::Opaque);
+    let parent = Document::create_element(&document, "div");
+    let parent_node = Rc::clone(parent.borrow().node());
+
+    let a = element_node(&document, "a");
+    let b = element_node(&document, "b");
+    Node::append_child(&parent_node, Rc::clone(&a));
+    Node::append_child(&parent_node, Rc::clone(&b));
+
+    let first = element_node(&document, "first");
+    assert!(Node::insert_before(&parent_node, Rc::clone(&first), Some(&a)).is_some());
+
+    assert_children(&parent_node, &["first", "a", "b"]);
+
+    let stranger = element_node(&document, "stranger");
+    assert!(
+        Node::insert_before(&parent_node, element_node(&document, "x"), Some(&stranger)).is_none()
+    );
+    assert_children(&parent_node, &["first", "a", "b"]);
+}
+
+#[test]
+fn test_remove_middle_child() {
+    let document = Document::new(Origin::Opaque);
+    let parent = Document::create_element(&document, "div");
+    let parent_node = Rc::clone(parent.borrow().node());
+
+    for name in ["a", "b", "c"] {
+        Node::append_child(&parent_node, element_node(&document, name));
+    }
+
+    let middle = Rc::clone(parent_node.borrow().nth_child(1).unwrap());
+    let middle_kind = middle.borrow().clone();
+    let removed = parent_node.borrow_mut().remove_child(&middle_kind).unwrap();
+
+    assert!(Rc::ptr_eq(&removed, &middle));
+    assert!(removed.borrow().node().borrow().parent_node().is_none());
+    assert!(
+        removed
+            .borrow()
+            .node()
+            .borrow()
+            .previous_sibling()
+            .is_none()
+    );
+    assert!(removed.borrow().node().borrow().next_sibling().is_none());
+
+    assert_children(&parent_node, &["a", "c"]);
+}
+
+#[test]
+fn test_replace_child() {
+    let document = Document::new(Origin::Opaque);
+    let parent = Document::create_element(&document, "div");
+    let parent_node = Rc::clone(parent.borrow().node());
+
+    for name in ["a", "b", "c"] {
+        Node::append_child(&parent_node, element_node(&document, name));
+    }
+
+    let old = Rc::clone(parent_node.borrow().nth_child(1).unwrap());
+    let replacement = element_node(&document, "new");
+
+    let replaced = Node::replace_child(&parent_node, Rc::clone(&replacement), &old).unwrap();
+
+    assert!(Rc::ptr_eq(&replaced, &old));
+    assert!(old.borrow().node().borrow().parent_node().is_none());
+
+    assert_children(&parent_node, &["a", "new", "c"]);
+
+    // Moving an existing child replaces the target and leaves its old position.
+    let a = Rc::clone(parent_node.borrow().nth_child(0).unwrap());
+    let c = Rc::clone(parent_node.borrow().nth_child(2).unwrap());
+    Node::replace_child(&parent_node, a, &c).unwrap();
+
+    assert_children(&parent_node, &["new", "a"]);
+}
+
+#[test]
+fn test_append_child_sibling_chains() {
+    let document = Document::new(Origin::Opaque);
+    let parent = Document::create_element(&document, "div");
+    let parent_node = Rc::clone(parent.borrow().node());
+
+    for name in ["a", "b", "c"] {
+        Node::append_child(&parent_node, element_node(&document, name));
+    }
+
+    let mut forward = vec![];
+    let mut current = parent_node.borrow().first_child().cloned();
+    while let Some(node) = current {
+        forward.push(name_of(&node));
+        current = node.borrow().node().borrow().next_sibling();
+    }
+
+    let mut backward = vec![];
+    let mut current = parent_node.borrow().last_child().cloned();
+    while let Some(node) = current {
+        backward.push(name_of(&node));
+        current = node.borrow().node().borrow().previous_sibling();
+    }
+
+    assert_eq!(forward, ["a", "b", "c"]);
+    assert_eq!(backward, ["c", "b", "a"]);
+
+    // Appending an existing child moves it to the end instead of duplicating it.
+    let a = Rc::clone(parent_node.borrow().first_child().unwrap());
+    Node::append_child(&parent_node, a);
+    assert_children(&parent_node, &["b", "c", "a"]);
+
+    let popped = parent_node.borrow_mut().pop_child(None).unwrap();
+    assert!(popped.borrow().node().borrow().parent_node().is_none());
+    assert_children(&parent_node, &["b", "c"]);
+}
+
+#[test]
+fn test_children_skips_text_nodes() {
+    let document = Document::new(Origin::Opaque);
+    let parent = Document::create_element(&document, "div");
+
+    append(
+        &parent,
+        NodeKind::Text(Document::create_text_node(&document, "leading")),
+    );
+    let first = Document::create_element(&document, "span");
+    append(&parent, NodeKind::Element(Rc::clone(&first)));
+    append(
+        &parent,
+        NodeKind::Comment(Document::create_comment(&document, "between")),
+    );
+    let second = Document::create_element(&document, "em");
+    append(&parent, NodeKind::Element(Rc::clone(&second)));
+    append(
+        &parent,
+        NodeKind::Text(Document::create_text_node(&document, "trailing")),
+    );
+
+    let parent = parent.borrow();
+
+    assert_eq!(parent.child_nodes().len(), 5);
+
+    let children = parent.children();
+    assert_eq!(children.len(), 2);
+    assert!(Rc::ptr_eq(&children[0], &first));
+    assert!(Rc::ptr_eq(&children[1], &second));
+
+    assert!(Rc::ptr_eq(&parent.first_element_child().unwrap(), &first));
+    assert!(Rc::ptr_eq(&parent.last_element_child().unwrap(), &second));
+
+    assert!(Rc::ptr_eq(
+        &first.borrow().next_element_sibling().unwrap(),
+        &second
+    ));
+    assert!(Rc::ptr_eq(
+        &second.borrow().previous_element_sibling().unwrap(),
+        &first
+    ));
+    assert!(first.borrow().previous_element_sibling().is_none());
+    assert!(second.borrow().next_element_sibling().is_none());
+}
+
+#[test]
+fn test_class_list_toggle() {
+    let document = Document::new(Origin::Opaque);
+
+    let css = ".red { color: red; }".chars().collect::<Vec<char>>();
+    let stylesheet = parse_stylesheet(
+        &mut InputStream::new(&tokenize(&mut InputStream::new(&css))),
+        Rc::downgrade(&document),
+        None,
+    );
+    document.borrow_mut().push_stylesheet(stylesheet);
+
+    let element = Document::create_element(&document, "div");
+    Element::set_attribute(&element, "class", "  big\tbig   bold ");
+
+    let class_list = Element::class_list(&element);
+    assert_eq!(class_list.length(), 2);
+    assert!(class_list.contains("big"));
+    assert!(!class_list.contains("red"));
+
+    assert!(class_list.toggle("red", None));
+    assert_eq!(
+        element.borrow().get_attribute("class"),
+        Some("big bold red")
+    );
+    assert_eq!(
+        element.borrow().style().color,
+        Color::Named("red".to_string())
+    );
+
+    assert!(!class_list.toggle("red", None));
+    assert_eq!(element.borrow().get_attribute("class"), Some("big bold"));
+    assert_eq!(element.borrow().style().color, Color::default());
+
+    class_list.add("bold");
+    class_list.remove("big");
+    assert_eq!(element.borrow().get_attribute("class"), Some("bold"));
+
+    assert!(class_list.toggle("bold", Some(true)));
+    assert!(!class_list.toggle("italic", Some(false)));
+    assert_eq!(class_list.value(), "bold");
+}
+
+#[test]
+fn test_parse_html() {
+    let document = harbor::parse_html(
+        "<!DOCTYPE html><html><head><title>List</title></head>\
+        <body><ul><li>One</li><li>Two</li></ul></body></html>",
+    );
+
+    let document = document.borrow();
+    assert!(document.doctype().is_some());
+
+    let items = document.get_elements_by_tag_name("li");
+    assert_eq!(items.length(), 2);
+    assert!(items.iter().all(|item| name_of(item) == "li"));
+    assert_eq!(document.get_elements_by_tag_name("ul").length(), 1);
+}
+
+#[test]
+fn test_parse_fragment_uses_context_element() {
+    let document = Document::new(Origin::Opaque);
+
+    let in_row = harbor::parse_fragment("<td>x</td>", &Document::create_element(&document, "tr"));
+    assert_children(in_row.node(), &["td"]);
+    let first = Rc::clone(in_row.node().borrow().first_child().unwrap());
+    match first.borrow().deref() {
+        NodeKind::Element(cell) => {
+            assert_eq!(cell.borrow().node().borrow().child_nodes().length(), 1)
+        }
+        _ => unreachable!(),
+    }
+
+    let in_body =
+        harbor::parse_fragment("<td>x</td>", &Document::create_element(&document, "body"));
+    assert_eq!(in_body.node().borrow().child_nodes().length(), 1);
+    let first = Rc::clone(in_body.node().borrow().first_child().unwrap());
+    match first.borrow().deref() {
+        NodeKind::Text(text) => assert_eq!(text.borrow().data(), "x"),
+        _ => panic!("Expected the td tags to be ignored outside of a table"),
+    }
+}
+
+#[test]
+fn test_set_inner_html_replaces_children() {
+    let document = harbor::parse_html("<html><body><div><p>Old</p></div></body></html>");
+    let div = match document.borrow().get_elements_by_tag_name("div").item(0) {
+        Some(node) => match node.borrow().deref() {
+            NodeKind::Element(div) => Rc::clone(div),
+            _ => unreachable!(),
+        },
+        None => panic!("Expected a div"),
+    };
+
+    Element::set_inner_html(&div, "<span>One</span>Two<br>");
+
+    let children = div.borrow().child_nodes();
+    assert_eq!(children.length(), 3);
+    assert_eq!(name_of(children.item(0).unwrap()), "span");
+    assert_eq!(name_of(children.item(2).unwrap()), "br");
+    assert_eq!(div.borrow().inner_html(), "<span>One</span>Two<br>");
+    assert_eq!(document.borrow().get_elements_by_tag_name("p").length(), 0);
+}
+
+#[test]
+fn test_template_contents() {
+    let html = "<html><head><template><tr><td>One</td></tr><tr><td>Two</td></tr></template>\
+        </head><body></body></html>";
+
+    let document = harbor::parse_html(html);
+    let template = match document
+        .borrow()
+        .get_elements_by_tag_name("template")
+        .item(0)
+    {
+        Some(node) => match node.borrow().deref() {
+            NodeKind::Element(template) => Rc::clone(template),
+            _ => unreachable!(),
+        },
+        None => panic!("Expected a template"),
+    };
+
+    assert!(!template.borrow().node().borrow().has_child_nodes());
+    assert_eq!(document.borrow().get_elements_by_tag_name("td").length(), 0);
+
+    let contents = template.borrow().template_contents().unwrap().clone();
+    assert_children(contents.node(), &["tr", "tr"]);
+    assert_eq!(
+        template.borrow().inner_html(),
+        "<tr><td>One</td></tr><tr><td>Two</td></tr>"
+    );
+    assert_eq!(document.borrow().serialize(), html);
+
+    Element::set_inner_html(&template, "<td>Three</td>");
+    assert!(!template.borrow().node().borrow().has_child_nodes());
+    assert_children(contents.node(), &["td"]);
+}
+
+#[test]
+fn test_serialize_keeps_comments_and_doctype() {
+    let html = "<!DOCTYPE html><html><head><!-- in head --></head>\
+        <body><!-- in body --><p>x<!-- in p --></p></body></html>";
+
+    let document = harbor::parse_html(html);
+    assert_eq!(document.borrow().serialize(), html);
+
+    let reparsed = harbor::parse_html(&document.borrow().serialize());
+    assert_eq!(reparsed.borrow().serialize(), html);
+}
+
+#[test]
+fn test_is_equal_node() {
+    let document = Document::new(Origin::Opaque);
+
+    let build = |class: &str| {
+        let div = Document::create_element(&document, "div");
+        Element::push_attr_raw_rc(&div, "id", "main");
+        Element::push_attr_raw_rc(&div, "class", class);
+
+        let p = Document::create_element(&document, "p");
+        append(
+            &p,
+            NodeKind::Text(Document::create_text_node(&document, "Hello")),
+        );
+        append(&div, NodeKind::Element(p));
+
+        NodeKind::Element(div)
+    };
+
+    assert!(build("a").is_equal_node(&build("a")));
+    assert!(!build("a").is_equal_node(&build("b")));
+
+    let reordered = Document::create_element(&document, "div");
+    Element::push_attr_raw_rc(&reordered, "class", "a");
+    Element::push_attr_raw_rc(&reordered, "id", "main");
+    let p = Document::create_element(&document, "p");
+    append(
+        &p,
+        NodeKind::Text(Document::create_text_node(&document, "Hello")),
+    );
+    append(&reordered, NodeKind::Element(p));
+    assert!(build("a").is_equal_node(&NodeKind::Element(reordered)));
+}
+
+#[test]
+fn test_compare_document_position() {
+    let document = Document::new(Origin::Opaque);
+
+    let div = Document::create_element(&document, "div");
+    let first = Document::create_element(&document, "p");
+    let second = Document::create_element(&document, "p");
+    append(&div, NodeKind::Element(Rc::clone(&first)));
+    append(&div, NodeKind::Element(Rc::clone(&second)));
+
+    let div_node = Rc::clone(div.borrow().node());
+    let first_node = Rc::clone(first.borrow().node());
+    let second_node = Rc::clone(second.borrow().node());
+
+    assert_eq!(
+        Node::compare_document_position(&first_node, &div_node),
+        DocumentPosition::Contains as u16 | DocumentPosition::Preceding as u16
+    );
+    assert_eq!(
+        Node::compare_document_position(&div_node, &first_node),
+        DocumentPosition::ContainedBy as u16 | DocumentPosition::Following as u16
+    );
+    assert_eq!(
+        Node::compare_document_position(&first_node, &second_node),
+        DocumentPosition::Following as u16
+    );
+    assert_eq!(
+        Node::compare_document_position(&second_node, &first_node),
+        DocumentPosition::Preceding as u16
+    );
+    assert_eq!(Node::compare_document_position(&div_node, &div_node), 0);
+
+    let detached = Document::create_element(&document, "span");
+    let position = Node::compare_document_position(&div_node, detached.borrow().node());
+    assert_ne!(position & DocumentPosition::Disconnected as u16, 0);
+}
+
+#[test]
+fn test_contains() {
+    let document = Document::new(Origin::Opaque);
+
+    let div = Document::create_element(&document, "div");
+    let p = Document::create_element(&document, "p");
+    let sibling = Document::create_element(&document, "p");
+    let em = Document::create_element(&document, "em");
+    append(&p, NodeKind::Element(Rc::clone(&em)));
+    append(&div, NodeKind::Element(Rc::clone(&p)));
+    append(&div, NodeKind::Element(Rc::clone(&sibling)));
+
+    let div_node = Rc::clone(div.borrow().node());
+    let p_node = Rc::clone(p.borrow().node());
+
+    assert!(Node::contains(&div_node, em.borrow().node()));
+    assert!(!Node::contains(&p_node, sibling.borrow().node()));
+    assert!(!Node::contains(&p_node, &div_node));
+    assert!(Node::contains(&p_node, &p_node));
+
+    let detached = Document::create_element(&document, "span");
+    assert!(!Node::contains(&div_node, detached.borrow().node()));
+}
+
+#[test]
+fn test_get_root_node() {
+    let document = harbor::parse_html("<html><body><div><p><em>deep</em></p></div></body></html>");
+
+    let em = document.borrow().get_elements_by_tag_name("em");
+    let em_node = em.item(0).unwrap().borrow().node();
+    let document_node = Rc::clone(&document.borrow()._node);
+
+    assert!(Rc::ptr_eq(
+        &Node::get_root_node(&em_node, false),
+        &document_node
+    ));
+    assert!(Rc::ptr_eq(
+        &Node::get_root_node(&em_node, true),
+        &document_node
+    ));
+    assert!(Rc::ptr_eq(
+        &Node::get_root_node(&document_node, false),
+        &document_node
+    ));
+}
+
+#[test]
+fn test_base_href_sets_the_base_uri() {
+    let document = harbor::parse_html(
+        "<html><head><base href=\"https://cdn.example/\"><base href=\"https://other.example/\">\
+        </head><body><p>x</p></body></html>",
+    );
+
+    assert_eq!(
+        document.borrow().document_base_url().serialize(),
+        "https://cdn.example/"
+    );
+
+    let p = document.borrow().get_elements_by_tag_name("p");
+    assert_eq!(
+        p.item(0).unwrap().borrow().node().borrow().base_uri(),
+        "https://cdn.example/"
+    );
+
+    let without_base = harbor::parse_html("<html><body></body></html>");
+    assert_eq!(
+        without_base.borrow().document_base_url(),
+        *without_base.borrow().url()
+    );
+}
+
+#[test]
+fn test_links_resolve_against_base_href() {
+    let document = harbor::parse_html(
+        "<html><head><base href=\"https://cdn.example/assets/\"></head>\
+        <body><a href=\"x\">x</a><a>no href</a></body></html>",
+    );
+    document.borrow_mut().set_url(
+        harbor::http::url::URL::pure_parse(String::from("https://origin.example/page")).unwrap(),
+    );
+
+    let links = document.borrow().get_elements_by_tag_name("a");
+    let link_url = |index: usize| match links.item(index).unwrap().borrow().deref() {
+        NodeKind::Element(element) => element.borrow().hyperlink_url(),
+        _ => unreachable!(),
+    };
+
+    assert_eq!(
+        link_url(0).unwrap().serialize(),
+        "https://cdn.example/assets/x"
+    );
+    assert!(link_url(1).is_none());
+}
+
+#[test]
+fn test_rel_tokens() {
+    let document = Document::new(Origin::Opaque);
+
+    let link = Document::create_element(&document, "link");
+    assert!(link.borrow().rel_tokens().is_empty());
+
+    Element::set_attribute(&link, "rel", " Stylesheet\talternate stylesheet ");
+    assert_eq!(link.borrow().rel_tokens(), ["stylesheet", "alternate"]);
+}
+
+#[test]
+fn test_document_title() {
+    let document = harbor::parse_html(
+        "<html><head><title>\n  Hello\n  there </title><title>Second</title></head>\
+        <body></body></html>",
+    );
+    assert_eq!(document.borrow().document_title(), "Hello there");
+
+    let untitled = harbor::parse_html("<html><head></head><body></body></html>");
+    assert_eq!(untitled.borrow().document_title(), "");
+}
+
+#[test]
+fn test_document_fragment() {
+    let document = Document::new(Origin::Opaque);
+
+    let fragment = Document::create_document_fragment(&document);
+    Node::append_child(fragment.node(), element_node(&document, "p"));
+    Node::append_child(fragment.node(), element_node(&document, "span"));
+    assert_eq!(fragment.serialize(), "<p></p><span></span>");
+
+    let div = Document::create_element(&document, "div");
+    append(&div, NodeKind::DocumentFragment(fragment.clone()));
+
+    assert_children(div.borrow().node(), &["p", "span"]);
+    assert_eq!(div.borrow().inner_html(), "<p></p><span></span>");
+    assert!(!fragment.node().borrow().has_child_nodes());
+}
+
+#[test]
+fn test_dataset() {
+    let document = harbor::parse_html(
+        "<html><body><div data-foo-bar=\"1\" data-x=\"2\" title=\"t\"></div></body></html>",
+    );
+    let div = match document.borrow().get_elements_by_tag_name("div").item(0) {
+        Some(node) => match node.borrow().deref() {
+            NodeKind::Element(div) => Rc::clone(div),
+            _ => unreachable!(),
+        },
+        None => panic!("Expected a div"),
+    };
+
+    let dataset = Element::dataset(&div);
+    assert_eq!(dataset.names(), ["fooBar", "x"]);
+    assert_eq!(dataset.get("fooBar").as_deref(), Some("1"));
+    assert_eq!(dataset.get("title"), None);
+
+    dataset.set("fooBar", "changed").unwrap();
+    dataset.set("userId", "42").unwrap();
+    assert!(dataset.set("user-id", "42").is_err());
+
+    assert_eq!(div.borrow().get_attribute("data-foo-bar"), Some("changed"));
+    assert_eq!(div.borrow().get_attribute("data-user-id"), Some("42"));
+
+    dataset.remove("x");
+    assert_eq!(dataset.names(), ["fooBar", "userId"]);
+}
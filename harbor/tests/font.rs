@@ -0,0 +1,536 @@
+use std::rc::Rc;
+
+use harbor::font::{
+    self,
+    tables::{
+        ParseContext, TableTrait,
+        cmap::CMAPTable,
+        colr::ColorLayer,
+        glyf::{GlyphComponent, GlyphDataType},
+        loca::LocaTable,
+    },
+    ttf::{TableDirectory, TableRecordData},
+    woff::{self, WoffError},
+};
+use harbor::render::shaping::ShapingCache;
+
+fn tahoma() -> TableDirectory {
+    font::parse_ttf(include_bytes!("../../res/fonts/Tahoma.ttf"))
+}
+
+fn hmtx(font: &TableDirectory) -> &harbor::font::tables::hmtx::HMtxTable {
+    match font.get_table_record(b"hmtx").unwrap().data() {
+        TableRecordData::HMtx(hmtx) => hmtx,
+        _ => unreachable!(),
+    }
+}
+
+fn glyph_bounds(font: &TableDirectory, glyph_index: u16) -> (i16, i16) {
+    match font.get_table_record(b"glyf").unwrap().data() {
+        TableRecordData::Glyf(glyf) => {
+            let header = &glyf.glyphs[glyph_index as usize].header;
+            (header.x_min, header.x_max)
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_side_bearings() {
+    let font = tahoma();
+    let a = font.glyph_index('A' as u32).unwrap();
+
+    let metric = &hmtx(&font).h_metrics[a as usize];
+    let (x_min, x_max) = glyph_bounds(&font, a);
+
+    assert_eq!(font.left_side_bearing(a), Some(metric.lsb));
+    assert_eq!(
+        font.right_side_bearing(a),
+        Some(metric.advance_width as i32 - (metric.lsb as i32 + x_max as i32 - x_min as i32))
+    );
+
+    // The outline starts the left side bearing to the right of the glyph's origin
+    assert_eq!(
+        font.glyph_left_edge(a, 100.0, 0.5),
+        100.0 + metric.lsb as f32 * 0.5
+    );
+
+    // A bounding box wider than an int16 can hold still gives the right bearing
+    let mut wide = tahoma();
+    match &mut wide
+        .table_records
+        .iter_mut()
+        .find(|record| &record.table_tag == b"glyf")
+        .unwrap()
+        ._data
+    {
+        TableRecordData::Glyf(glyf) => {
+            let header = &mut glyf.glyphs[a as usize].header;
+            (header.x_min, header.x_max) = (-30000, 30000);
+        }
+        _ => unreachable!(),
+    }
+    assert_eq!(
+        wide.right_side_bearing(a),
+        Some(metric.advance_width as i32 - (metric.lsb as i32 + 60000))
+    );
+}
+
+#[test]
+fn test_glyphs_past_the_long_metrics_share_the_last_advance() {
+    // A monospaced font only needs the one advance width
+    let fonts = &harbor::globals::FONTS["Courier Prime"];
+    let font = fonts.get_regular_font().unwrap();
+    let hmtx = hmtx(font);
+
+    let last_long = hmtx.h_metrics.len() as u16 - 1;
+    let advance = hmtx.h_metrics.last().unwrap().advance_width;
+
+    let m = font.glyph_index('m' as u32).unwrap();
+    assert!(m > last_long);
+
+    assert_eq!(font.advance_width(m), Some(advance));
+    assert_eq!(
+        font.left_side_bearing(m),
+        Some(hmtx.left_side_bearings[(m - last_long - 1) as usize])
+    );
+}
+
+#[test]
+fn test_batched_glyph_lookup_matches_single_lookups() {
+    let font = tahoma();
+    let text = "The quick brown fox jumps over the lazy dog! 0123456789 {}[]~";
+
+    let batched = font.glyph_indices(text);
+    let single = text
+        .chars()
+        .map(|ch| font.glyph_index(ch as u32).unwrap_or(0))
+        .collect::<Vec<_>>();
+
+    assert_eq!(batched, single);
+    assert!(batched.iter().all(|&glyph_index| glyph_index != 0));
+
+    // Characters without a glyph map to the missing glyph
+    assert_eq!(
+        font.glyph_indices("a\u{10FFFF}"),
+        vec![font.glyph_index('a' as u32).unwrap(), 0]
+    );
+}
+
+#[test]
+fn test_segmented_coverage_cmap() {
+    // Garamond maps characters outside the Basic Multilingual Plane with a format 12 subtable
+    let font = &harbor::globals::FONTS["Garamond"].table_directories[0];
+
+    // U+1F44C OK HAND SIGN and U+1F44D THUMBS UP SIGN are one group
+    assert_eq!(font.glyph_index(0x1F44C), Some(2566));
+    assert_eq!(font.glyph_index(0x1F44D), Some(2567));
+
+    // U+1F1E6 to U+1F1FF, the regional indicators, are another
+    assert_eq!(font.glyph_index(0x1F1E6), Some(2879));
+    assert_eq!(font.glyph_index(0x1F1FF), Some(2879 + 25));
+
+    assert_eq!(font.glyph_index(0x1F44E), None);
+    assert!(font.glyph_index('A' as u32).is_some());
+
+    // A group whose glyph IDs run past 32 bits maps those characters to no glyph
+    let mut data = vec![0, 0, 0, 1, 0, 3, 0, 10, 0, 0, 0, 12];
+    data.extend([0, 12, 0, 0, 0, 0, 0, 40, 0, 0, 0, 0, 0, 0, 0, 2]);
+    for (start, end, start_glyph_id) in [(0x10u32, 0x20u32, u32::MAX - 4), (0x41, 0x5A, 36)] {
+        data.extend([start, end, start_glyph_id].map(u32::to_be_bytes).concat());
+    }
+
+    let cmap = CMAPTable::parse(&data, None);
+    assert_eq!(cmap.char_to_glyph_index(0x18), None);
+    assert_eq!(cmap.char_to_glyph_index(0x42), Some(37));
+}
+
+fn loca(index_to_loc_format: i16, num_glyphs: u16, offsets: &[u32]) -> LocaTable {
+    let data = offsets
+        .iter()
+        .flat_map(|&offset| match index_to_loc_format {
+            0 => ((offset / 2) as u16).to_be_bytes().to_vec(),
+            _ => offset.to_be_bytes().to_vec(),
+        })
+        .collect::<Vec<u8>>();
+
+    LocaTable::parse(
+        &data,
+        Some(ParseContext::Loca((index_to_loc_format, num_glyphs))),
+    )
+}
+
+#[test]
+fn test_short_and_long_loca_offsets() {
+    let offsets = [0, 20, 20, 64];
+
+    for index_to_loc_format in [0, 1] {
+        let loca = loca(index_to_loc_format, 3, &offsets);
+
+        assert_eq!(loca.glyph_offset(1), Some(20));
+        assert_eq!(loca.glyph_offset(3), Some(64));
+        assert_eq!(loca.offsets(), offsets);
+
+        // The second glyph has no outline
+        assert_eq!(loca.glyph_range(0), Some((0, 20)));
+        assert_eq!(loca.glyph_range(1), Some((20, 20)));
+        assert_eq!(loca.glyph_range(2), Some((20, 64)));
+
+        assert_eq!(loca.glyph_offset(4), None);
+        assert_eq!(loca.glyph_range(3), None);
+        assert_eq!(loca.glyph_range(u16::MAX), None);
+    }
+
+    // A table that's cut short only has the offsets that fit
+    let truncated = loca(1, 10, &offsets);
+    assert_eq!(truncated.offsets(), offsets);
+    assert_eq!(truncated.glyph_range(5), None);
+}
+
+#[test]
+fn test_composite_glyphs_are_assembled() {
+    let font = tahoma();
+    let n_tilde = font.glyph_index('ñ' as u32).unwrap();
+
+    let components = match font.get_table_record(b"glyf").unwrap().data() {
+        TableRecordData::Glyf(glyf) => match &glyf.glyphs[n_tilde as usize].data {
+            GlyphDataType::Composite(composite) => composite.components.clone(),
+            GlyphDataType::Simple(_) => panic!("expected 'ñ' to be a composite glyph"),
+        },
+        _ => unreachable!(),
+    };
+    assert_eq!(components.len(), 2);
+
+    let base = font.glyph_contours(components[0].glyph_index);
+    let tilde = font.glyph_contours(components[1].glyph_index);
+
+    let contours = font.glyph_contours(n_tilde);
+    assert_eq!(contours.len(), base.len() + tilde.len());
+
+    // The tilde is moved into place above the base glyph
+    let (dx, dy) = (components[1].arg1 as f32, components[1].arg2 as f32);
+    let placed = &contours[base.len()].points[0];
+    assert_eq!(placed.x, tilde[0].points[0].x + dx);
+    assert_eq!(placed.y, tilde[0].points[0].y + dy);
+
+    let mut segments = Vec::new();
+    font.make_glyph_segments(n_tilde, 1.0, &mut segments);
+    assert!(!segments.is_empty());
+}
+
+/// Replaces the components of a composite glyph.
+fn set_components(font: &mut TableDirectory, glyph_index: u16, components: Vec<GlyphComponent>) {
+    let glyf = font
+        .table_records
+        .iter_mut()
+        .find(|record| &record.table_tag == b"glyf")
+        .unwrap();
+
+    match &mut glyf._data {
+        TableRecordData::Glyf(glyf) => match &mut glyf.glyphs[glyph_index as usize].data {
+            GlyphDataType::Composite(composite) => composite.components = components,
+            GlyphDataType::Simple(_) => panic!("expected a composite glyph"),
+        },
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_hostile_composite_glyphs() {
+    let mut font = tahoma();
+    let n_tilde = font.glyph_index('ñ' as u32).unwrap();
+    let n = font.glyph_index('n' as u32).unwrap();
+
+    let component = |glyph_index| GlyphComponent {
+        glyph_index,
+        ..Default::default()
+    };
+
+    // A glyph made of itself has nothing to draw, rather than never finishing
+    set_components(&mut font, n_tilde, vec![component(n_tilde); 8]);
+    assert!(font.glyph_contours(n_tilde).is_empty());
+
+    let mut segments = Vec::new();
+    font.make_glyph_segments(n_tilde, 1.0, &mut segments);
+    assert!(segments.is_empty());
+
+    // Only so many components are drawn, however many times a glyph repeats another
+    set_components(&mut font, n_tilde, vec![component(n); 4096]);
+    assert_eq!(
+        font.glyph_contours(n_tilde).len(),
+        1024 * font.glyph_contours(n).len()
+    );
+}
+
+#[test]
+fn test_unmapped_characters_use_the_missing_glyph() {
+    let font = tahoma();
+    let unmapped = '\u{E123}';
+
+    assert_eq!(font.notdef_glyph(), 0);
+    assert_eq!(font.glyph_index(unmapped as u32), None);
+    assert_eq!(font.resolve_glyph(unmapped as u32), font.notdef_glyph());
+    assert_eq!(
+        font.glyph_indices("a\u{E123}"),
+        [font.resolve_glyph('a' as u32), 0]
+    );
+
+    // The missing glyph is a box, which has an outline and takes up space
+    assert!(!font.glyph_contours(font.notdef_glyph()).is_empty());
+    assert!(font.advance_width(font.notdef_glyph()).unwrap() > 0);
+}
+
+#[test]
+fn test_descent_from_os2_win_metrics() {
+    let data = include_bytes!("../../res/fonts/Tahoma.ttf");
+    let os2 = (0..u16::from_be_bytes([data[4], data[5]]) as usize)
+        .map(|i| &data[12 + i * 16..28 + i * 16])
+        .find(|record| &record[..4] == b"OS/2")
+        .map(|record| u32::from_be_bytes(record[8..12].try_into().unwrap()) as usize)
+        .unwrap();
+
+    // Tahoma has a version 1 OS/2 table, whose descent is the unsigned usWinDescent. It's only
+    // used over hhea's when USE_TYPO_METRICS is set.
+    let mut patched = data.to_vec();
+    patched[os2 + 63] |= 0x80;
+    patched[os2 + 76..os2 + 78].copy_from_slice(&0x9000u16.to_be_bytes());
+
+    assert!(tahoma().descent().unwrap() > 0);
+    assert_eq!(font::parse_ttf(&patched).descent(), Some(0x9000));
+}
+
+#[test]
+fn test_shaped_runs_are_reused_across_font_sizes() {
+    let font = tahoma();
+    let mut cache = ShapingCache::new();
+
+    let run = cache.shape(&font, "Harbor");
+    let again = cache.shape(&font, "Harbor");
+    assert!(Rc::ptr_eq(&run, &again));
+    assert_eq!(cache.len(), 1);
+
+    // Fractional sizes scale the same run, rather than each needing their own
+    let small = run.scaled(12.5).collect::<Vec<_>>();
+    let large = run.scaled(12.75).collect::<Vec<_>>();
+    assert_eq!(cache.len(), 1);
+    assert_eq!(small.len(), "Harbor".len());
+
+    for ((small_glyph, small_x), (large_glyph, large_x)) in small.iter().zip(&large) {
+        assert_eq!(small_glyph, large_glyph);
+        assert!((small_x * 12.75 / 12.5 - large_x).abs() < 1e-3);
+    }
+
+    let h = font.glyph_index('H' as u32).unwrap();
+    assert_eq!(small[0], (h, 0.0));
+    assert_eq!(
+        small[1].1,
+        font.advance_width(h).unwrap() as f32 * 12.5 / font.units_per_em() as f32
+    );
+
+    cache.invalidate();
+    assert!(cache.is_empty());
+    assert!(!Rc::ptr_eq(&run, &cache.shape(&font, "Harbor")));
+}
+
+#[test]
+fn test_shaping_cache_forgets_the_least_recently_used_run() {
+    let font = tahoma();
+    let mut cache = ShapingCache::with_capacity(2);
+
+    let a = cache.shape(&font, "a");
+    let b = cache.shape(&font, "b");
+    cache.shape(&font, "a");
+    cache.shape(&font, "c");
+
+    assert_eq!(cache.len(), 2);
+    assert!(Rc::ptr_eq(&a, &cache.shape(&font, "a")));
+    assert!(!Rc::ptr_eq(&b, &cache.shape(&font, "b")));
+}
+
+fn outline(font: &TableDirectory, ch: char) -> Vec<(f32, f32, u32)> {
+    let glyph_index = font.glyph_index(ch as u32).unwrap();
+
+    font.glyph_contours(glyph_index)
+        .iter()
+        .flat_map(|contour| contour.points.iter())
+        .map(|point| (point.x, point.y, point.on_curve))
+        .collect()
+}
+
+#[test]
+fn test_woff_fonts_match_their_source() {
+    let data = include_bytes!("../../res/fonts/Tahoma.woff");
+    assert!(woff::is_woff(data));
+    assert!(!woff::is_woff(include_bytes!("../../res/fonts/Tahoma.ttf")));
+
+    let ttf = tahoma();
+    let woff = font::parse_woff(data).unwrap();
+
+    assert_eq!(woff.num_tables, ttf.num_tables);
+    assert_eq!(woff.units_per_em(), ttf.units_per_em());
+
+    // 'ñ' is a composite glyph
+    for ch in ['a', 'H', 'g', '&', 'ñ'] {
+        assert_eq!(woff.glyph_index(ch as u32), ttf.glyph_index(ch as u32));
+        assert_eq!(outline(&woff, ch), outline(&ttf, ch));
+    }
+}
+
+#[test]
+fn test_malformed_woff_fonts() {
+    let data = include_bytes!("../../res/fonts/Tahoma.woff");
+
+    let mut woff2 = data.to_vec();
+    woff2[..4].copy_from_slice(b"wOF2");
+    assert_eq!(woff::decode_woff(&woff2), Err(WoffError::UnsupportedWoff2));
+    assert_eq!(
+        woff::decode_woff(include_bytes!("../../res/fonts/Tahoma.ttf")),
+        Err(WoffError::InvalidSignature)
+    );
+
+    assert_eq!(
+        woff::decode_woff(&data[..100]),
+        Err(WoffError::UnexpectedEnd)
+    );
+
+    // The first table's checksum is off by one
+    let mut corrupted = data.to_vec();
+    corrupted[44 + 19] ^= 1;
+    let tag = corrupted[44..48].try_into().unwrap();
+    assert_eq!(
+        woff::decode_woff(&corrupted),
+        Err(WoffError::ChecksumMismatch(tag))
+    );
+
+    // A font's table directory can't count this many tables
+    let mut crowded = data[..44].to_vec();
+    crowded[12..14].copy_from_slice(&4096u16.to_be_bytes());
+    crowded.resize(44 + 4096 * 20, 0);
+    assert_eq!(woff::decode_woff(&crowded), Err(WoffError::TooManyTables));
+}
+
+/// Copies a font with `tables` added to it.
+fn with_tables(data: &[u8], tables: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let num_tables = u16::from_be_bytes([data[4], data[5]]) as usize;
+
+    let mut records = (0..num_tables)
+        .map(|i| {
+            let record = &data[12 + i * 16..28 + i * 16];
+            let offset = u32::from_be_bytes(record[8..12].try_into().unwrap()) as usize;
+            let length = u32::from_be_bytes(record[12..16].try_into().unwrap()) as usize;
+
+            (record[..4].to_vec(), data[offset..offset + length].to_vec())
+        })
+        .collect::<Vec<_>>();
+    records.extend(
+        tables
+            .iter()
+            .map(|(tag, table)| (tag.to_vec(), table.clone())),
+    );
+
+    let mut font = data[..4].to_vec();
+    font.extend((records.len() as u16).to_be_bytes());
+    font.extend([0; 6]);
+
+    let mut offset = 12 + records.len() * 16;
+    for (tag, table) in &records {
+        font.extend(tag);
+        font.extend([0; 4]);
+        font.extend((offset as u32).to_be_bytes());
+        font.extend((table.len() as u32).to_be_bytes());
+        offset += table.len().next_multiple_of(4);
+    }
+
+    for (_, table) in &records {
+        font.extend(table);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+
+    font
+}
+
+#[test]
+fn test_color_glyph_layers() {
+    let plain = tahoma();
+    let glyph = |ch: char| plain.glyph_index(ch as u32).unwrap();
+    let (base, outer, inner, dot) = (glyph('O'), glyph('O'), glyph('o'), glyph('.'));
+
+    // 'O' is drawn as an 'O', an 'o' inside it and a '.' in the text color
+    let mut colr = Vec::new();
+    colr.extend([0, 0, 0, 1, 0, 0, 0, 14, 0, 0, 0, 20, 0, 3]);
+    colr.extend([base.to_be_bytes(), 0u16.to_be_bytes(), 3u16.to_be_bytes()].concat());
+    for (layer, palette_index) in [(outer, 0), (inner, 1), (dot, 0xFFFF)] {
+        colr.extend(layer.to_be_bytes());
+        colr.extend(u16::to_be_bytes(palette_index));
+    }
+
+    // Two palettes of two colors, stored as BGRA
+    let mut cpal = vec![0, 0, 0, 2, 0, 2, 0, 4, 0, 0, 0, 16, 0, 0, 0, 2];
+    cpal.extend([0, 0, 255, 255, 255, 0, 0, 255]);
+    cpal.extend([0, 255, 0, 255, 0, 0, 0, 0]);
+
+    let font = font::parse_ttf(&with_tables(
+        include_bytes!("../../res/fonts/Tahoma.ttf"),
+        &[(b"COLR", colr), (b"CPAL", cpal)],
+    ));
+
+    let red = [1.0, 0.0, 0.0, 1.0];
+    let blue = [0.0, 0.0, 1.0, 1.0];
+    let green = [0.0, 1.0, 0.0, 1.0];
+
+    let layers = |palette: u16| {
+        font.color_layers(base, palette)
+            .unwrap()
+            .into_iter()
+            .map(|layer| (layer.glyph_id, layer.color))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        layers(0),
+        [(outer, Some(red)), (inner, Some(blue)), (dot, None)]
+    );
+    assert_eq!(
+        layers(1),
+        [
+            (outer, Some(green)),
+            (inner, Some([0.0, 0.0, 0.0, 0.0])),
+            (dot, None)
+        ]
+    );
+
+    // Glyphs without layers, and fonts without color glyphs, are drawn as plain outlines
+    assert_eq!(font.color_layers(inner, 0), None);
+    assert_eq!(plain.color_layers(base, 0), None);
+
+    // Every layer has an outline of its own to draw
+    for (glyph_id, _) in layers(0) {
+        assert!(!font.glyph_contours(glyph_id).is_empty());
+    }
+
+    // Truncated tables are kept raw, leaving plain outlines or layers in the text color
+    let truncated = font::parse_ttf(&with_tables(
+        include_bytes!("../../res/fonts/Tahoma.ttf"),
+        &[(b"COLR", vec![0; 13]), (b"CPAL", vec![0; 11])],
+    ));
+    assert!(matches!(
+        truncated.get_table_record(b"COLR").unwrap().data(),
+        TableRecordData::Raw(_)
+    ));
+    assert_eq!(truncated.color_layers(base, 0), None);
+
+    let mut colr = vec![0, 0, 0, 1, 0, 0, 0, 14, 0, 0, 0, 20, 0, 1];
+    colr.extend([base.to_be_bytes(), 0u16.to_be_bytes(), 1u16.to_be_bytes()].concat());
+    colr.extend([outer.to_be_bytes(), 0u16.to_be_bytes()].concat());
+    let uncolored = font::parse_ttf(&with_tables(
+        include_bytes!("../../res/fonts/Tahoma.ttf"),
+        &[(b"COLR", colr), (b"CPAL", vec![0; 11])],
+    ));
+    assert_eq!(
+        uncolored.color_layers(base, 0),
+        Some(vec![ColorLayer {
+            glyph_id: outer,
+            color: None
+        }])
+    );
+}
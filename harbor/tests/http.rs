@@ -0,0 +1,726 @@
+use std::{
+    cell::Cell,
+    io::{self, Read, Write},
+    net::TcpListener,
+    rc::Rc,
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use harbor::{
+    http::{self, ConnectionStream, cache::CacheControl, date::parse_http_date, url::URL},
+    infra::Serializable,
+};
+
+/// Answers every request on a local port with what `respond` makes of it, passing the raw
+/// request on.
+fn serve(respond: fn(&str) -> String) -> (u16, Receiver<String>) {
+    serve_connections(respond, false)
+}
+
+/// Like `serve`, but leaves every connection open after answering, the way a server that keeps
+/// connections alive does.
+fn serve_kept_alive(respond: fn(&str) -> String) -> (u16, Receiver<String>) {
+    serve_connections(respond, true)
+}
+
+fn serve_connections(respond: fn(&str) -> String, keep_alive: bool) -> (u16, Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut open = vec![];
+
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+
+            let mut request = Vec::new();
+            let mut buffer = [0; 512];
+            while !request.ends_with(b"\r\n\r\n") {
+                let bytes_read = stream.read(&mut buffer).unwrap();
+                if bytes_read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..bytes_read]);
+            }
+
+            let request = String::from_utf8(request).unwrap();
+            stream.write_all(respond(&request).as_bytes()).unwrap();
+            sender.send(request).unwrap();
+
+            if keep_alive {
+                open.push(stream);
+            }
+        }
+    });
+
+    (port, receiver)
+}
+
+/// Answers every request with an empty 200.
+fn capture_requests() -> (u16, Receiver<String>) {
+    serve(|_| String::from("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"))
+}
+
+/// The values of every header named `name` in a raw request.
+fn header_values(request: &str, name: &str) -> Vec<String> {
+    request
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim().to_string())
+        .collect()
+}
+
+#[test]
+fn test_default_headers_are_sent() {
+    let (port, requests) = capture_requests();
+    let url = URL::pure_parse(format!("http://127.0.0.1:{}/", port)).unwrap();
+
+    let mut client = http::Client::new(http::Protocol::HTTP1_1, true);
+    client
+        .with_user_agent(String::from("HarborTest/1.0"))
+        .with_default_header(String::from("Accept-Language"), String::from("en-GB"));
+
+    assert!(client.fetch(&url).is_some());
+
+    let request = requests.recv().unwrap();
+    assert_eq!(header_values(&request, "User-Agent"), ["HarborTest/1.0"]);
+    assert_eq!(header_values(&request, "Accept-Language"), ["en-GB"]);
+
+    // Headers set on the request itself win over the defaults
+    assert!(client.connect_to_url(url.serialize()).is_ok());
+    let response = client.send_request(http::Request {
+        method: String::from("GET"),
+        request_target: String::from("/"),
+        protocol: http::Protocol::HTTP1_1,
+        headers: vec![
+            http::Header::new(String::from("Host"), format!("127.0.0.1:{}", port)),
+            http::Header::new(String::from("accept-language"), String::from("fr")),
+        ],
+        body: None,
+    });
+    assert!(response.is_some());
+
+    let request = requests.recv().unwrap();
+    assert_eq!(header_values(&request, "User-Agent"), ["HarborTest/1.0"]);
+    assert_eq!(header_values(&request, "Accept-Language"), ["fr"]);
+}
+
+#[test]
+fn test_navigation_negotiates_content() {
+    let (port, requests) = capture_requests();
+    let url = URL::pure_parse(format!("http://127.0.0.1:{}/", port)).unwrap();
+
+    let mut client = http::Client::new(http::Protocol::HTTP1_1, true);
+
+    assert!(client.navigate(&url).is_some());
+
+    let request = requests.recv().unwrap();
+    assert_eq!(
+        header_values(&request, "Accept"),
+        [http::DEFAULT_NAVIGATION_ACCEPT]
+    );
+    assert_eq!(
+        header_values(&request, "Accept-Language"),
+        [http::DEFAULT_ACCEPT_LANGUAGE]
+    );
+
+    // Subresources accept anything
+    assert!(client.fetch(&url).is_some());
+    assert_eq!(header_values(&requests.recv().unwrap(), "Accept"), ["*/*"]);
+
+    client
+        .with_accept(String::from("text/html"))
+        .with_accept_language(String::from("de"));
+
+    assert!(client.navigate(&url).is_some());
+
+    let request = requests.recv().unwrap();
+    assert_eq!(header_values(&request, "Accept"), ["text/html"]);
+    assert_eq!(header_values(&request, "Accept-Language"), ["de"]);
+}
+
+#[test]
+fn test_unreachable_hosts_fail_to_load() {
+    let mut client = http::Client::new(http::Protocol::HTTP1_1, true);
+
+    // Nothing listens on port 1, and the .invalid TLD never resolves
+    let refused = URL::pure_parse(String::from("http://127.0.0.1:1/x.css")).unwrap();
+    assert!(client.fetch(&refused).is_none());
+    assert!(client.navigate(&refused).is_none());
+    assert!(client.history().is_empty());
+
+    let unresolved = URL::pure_parse(String::from("http://harbor.invalid/x.css")).unwrap();
+    assert!(client.fetch(&unresolved).is_none());
+}
+
+#[test]
+fn test_fresh_response_is_served_from_cache() {
+    let (port, requests) = serve(|_| {
+        String::from(
+            "HTTP/1.1 200 OK\r\nCache-Control: max-age=3600\r\nContent-Length: 6\r\n\r\ncached",
+        )
+    });
+    let url = URL::pure_parse(format!("http://127.0.0.1:{}/style.css", port)).unwrap();
+
+    let mut client = http::Client::new(http::Protocol::HTTP1_1, true);
+
+    let first = client.fetch(&url).unwrap();
+    assert_eq!(first.body.as_deref(), Some("cached"));
+    requests.recv().unwrap();
+
+    let second = client.fetch(&url).unwrap();
+    assert_eq!(second.body.as_deref(), Some("cached"));
+    assert!(
+        requests.recv_timeout(Duration::from_millis(200)).is_err(),
+        "A fresh response shouldn't be requested again"
+    );
+}
+
+#[test]
+fn test_stale_response_is_revalidated() {
+    let (port, requests) = serve(|request| {
+        if header_values(request, "If-None-Match") == ["\"v1\""] {
+            String::from("HTTP/1.1 304 Not Modified\r\nContent-Length: 0\r\n\r\n")
+        } else {
+            String::from(
+                "HTTP/1.1 200 OK\r\nCache-Control: no-cache\r\nETag: \"v1\"\r\nLast-Modified: Wed, 21 Oct 2015 07:28:00 GMT\r\nContent-Length: 4\r\n\r\nbody",
+            )
+        }
+    });
+    let url = URL::pure_parse(format!("http://127.0.0.1:{}/page", port)).unwrap();
+
+    let mut client = http::Client::new(http::Protocol::HTTP1_1, true);
+
+    assert_eq!(client.fetch(&url).unwrap().body.as_deref(), Some("body"));
+    requests.recv().unwrap();
+
+    let revalidated = client.fetch(&url).unwrap();
+    assert_eq!(revalidated.status_code, Some(200));
+    assert_eq!(revalidated.body.as_deref(), Some("body"));
+
+    let request = requests.recv().unwrap();
+    assert_eq!(header_values(&request, "If-None-Match"), ["\"v1\""]);
+    assert_eq!(
+        header_values(&request, "If-Modified-Since"),
+        ["Wed, 21 Oct 2015 07:28:00 GMT"]
+    );
+}
+
+#[test]
+fn test_not_modified_without_a_body_on_a_kept_alive_connection() {
+    let (port, requests) = serve_kept_alive(|request| {
+        if header_values(request, "If-None-Match") == ["\"v1\""] {
+            String::from(
+                "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nCache-Control: max-age=3600\r\n\r\n",
+            )
+        } else {
+            String::from(
+                "HTTP/1.1 200 OK\r\nCache-Control: no-cache\r\nETag: \"v1\"\r\nContent-Length: 4\r\n\r\nbody",
+            )
+        }
+    });
+    let url = URL::pure_parse(format!("http://127.0.0.1:{}/page", port)).unwrap();
+
+    let mut client = http::Client::new(http::Protocol::HTTP1_1, true);
+
+    assert_eq!(client.fetch(&url).unwrap().body.as_deref(), Some("body"));
+    requests.recv().unwrap();
+
+    // The 304 ends with its head, even though the server never closes the connection
+    let revalidated = client.fetch(&url).unwrap();
+    assert_eq!(revalidated.body.as_deref(), Some("body"));
+    assert_eq!(
+        revalidated.get_header_value(String::from("Cache-Control")),
+        Some(String::from("max-age=3600"))
+    );
+    requests.recv().unwrap();
+
+    // The max-age from the 304 makes the stored response fresh again
+    assert_eq!(client.fetch(&url).unwrap().body.as_deref(), Some("body"));
+    assert!(
+        requests.recv_timeout(Duration::from_millis(200)).is_err(),
+        "A freshened response shouldn't be requested again"
+    );
+}
+
+#[test]
+fn test_parse_cache_control() {
+    let cache_control = CacheControl::parse("max-age=3600, no-cache");
+
+    assert_eq!(cache_control.max_age, Some(3600));
+    assert!(cache_control.no_cache);
+    assert!(!cache_control.no_store);
+    assert!(!cache_control.must_revalidate);
+
+    let cache_control = CacheControl::parse("Private, MUST-REVALIDATE, max-age=\"60\", no-store");
+
+    assert_eq!(
+        cache_control,
+        CacheControl {
+            max_age: Some(60),
+            no_cache: false,
+            no_store: true,
+            private: true,
+            public: false,
+            must_revalidate: true,
+        }
+    );
+
+    assert_eq!(CacheControl::parse("max-age=soon").max_age, None);
+}
+
+#[test]
+fn test_status_code_classification() {
+    let ok = http::StatusCode(200);
+    assert!(ok.is_success());
+    assert!(!ok.is_redirect() && !ok.is_client_error() && !ok.is_server_error());
+    assert_eq!(ok.reason_phrase(), Some("OK"));
+
+    let moved = http::StatusCode(301);
+    assert!(moved.is_redirect());
+    assert!(!moved.is_success() && !moved.is_client_error() && !moved.is_server_error());
+    assert_eq!(moved.reason_phrase(), Some("Moved Permanently"));
+
+    let not_found = http::StatusCode(404);
+    assert!(not_found.is_client_error());
+    assert!(!not_found.is_success() && !not_found.is_redirect() && !not_found.is_server_error());
+    assert_eq!(not_found.reason_phrase(), Some("Not Found"));
+
+    let unavailable = http::StatusCode(503);
+    assert!(unavailable.is_server_error());
+    assert!(!unavailable.is_success() && !unavailable.is_redirect());
+    assert!(!unavailable.is_client_error());
+    assert_eq!(unavailable.reason_phrase(), Some("Service Unavailable"));
+
+    // Not Modified has no Location to follow
+    assert!(!http::StatusCode::NOT_MODIFIED.is_redirect());
+    assert_eq!(http::StatusCode(299).reason_phrase(), None);
+}
+
+#[test]
+fn test_redirects_are_followed() {
+    let (port, requests) = serve(|request| {
+        let target = request.split(' ').nth(1).unwrap();
+        match target {
+            "/start" => String::from(
+                "HTTP/1.1 301 Moved Permanently\r\nLocation: /moved/here\r\nContent-Length: 0\r\n\r\n",
+            ),
+            // Relative to /moved/here
+            "/moved/here" => {
+                String::from("HTTP/1.1 302 Found\r\nLocation: there\r\nContent-Length: 0\r\n\r\n")
+            }
+            "/moved/there" => String::from("HTTP/1.1 200 OK\r\nContent-Length: 7\r\n\r\narrived"),
+            _ => String::from(
+                "HTTP/1.1 307 Temporary Redirect\r\nLocation: /loop\r\nContent-Length: 0\r\n\r\n",
+            ),
+        }
+    });
+    let url = |path: &str| URL::pure_parse(format!("http://127.0.0.1:{}{}", port, path)).unwrap();
+
+    let mut client = http::Client::new(http::Protocol::HTTP1_1, true);
+
+    let response = client.fetch(&url("/start")).unwrap();
+    assert_eq!(response.status_code, Some(200));
+    assert_eq!(response.body.as_deref(), Some("arrived"));
+
+    let targets = (0..3)
+        .map(|_| {
+            requests
+                .recv()
+                .unwrap()
+                .split(' ')
+                .nth(1)
+                .unwrap()
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(targets, ["/start", "/moved/here", "/moved/there"]);
+
+    // A loop is noticed once it comes back around, and the redirect is returned as is
+    let response = client.fetch(&url("/loop")).unwrap();
+    assert_eq!(response.status_code, Some(307));
+
+    client.with_max_redirects(1);
+    let response = client.fetch(&url("/start")).unwrap();
+    assert_eq!(response.status_code, Some(302));
+
+    // Navigating ends up at, and remembers, the URL the last redirect led to
+    client.with_max_redirects(http::DEFAULT_MAX_REDIRECTS);
+    let (response, final_url) = client.navigate(&url("/start")).unwrap();
+    assert_eq!(response.body.as_deref(), Some("arrived"));
+    assert_eq!(final_url, url("/moved/there"));
+    assert_eq!(client.history(), [url("/moved/there")]);
+}
+
+#[test]
+fn test_method_after_redirect() {
+    assert_eq!(http::StatusCode(301).method_after_redirect("POST"), "GET");
+    assert_eq!(http::StatusCode(302).method_after_redirect("POST"), "GET");
+    assert_eq!(http::StatusCode(302).method_after_redirect("PUT"), "PUT");
+    assert_eq!(http::StatusCode(303).method_after_redirect("POST"), "GET");
+    assert_eq!(http::StatusCode(303).method_after_redirect("HEAD"), "HEAD");
+    assert_eq!(http::StatusCode(307).method_after_redirect("POST"), "POST");
+    assert_eq!(
+        http::StatusCode(308).method_after_redirect("DELETE"),
+        "DELETE"
+    );
+}
+
+/// Needs network access, so it only runs with `--features network-tests`.
+#[cfg(feature = "network-tests")]
+#[test]
+fn test_https_end_to_end() {
+    let url = URL::pure_parse(String::from("https://example.com/")).unwrap();
+
+    let mut client = http::Client::new(http::Protocol::HTTP1_1, true);
+    let response = client.fetch(&url).unwrap();
+
+    assert_eq!(response.status_code, Some(200));
+    assert!(response.body.unwrap().contains("<html"));
+}
+
+/// A connection that's reset the first `failures` times a request is written to it, and then
+/// answers with a 200.
+struct FlakyStream {
+    failures: u32,
+    attempts: Rc<Cell<u32>>,
+    response: Vec<u8>,
+}
+
+impl FlakyStream {
+    fn new(failures: u32) -> (Self, Rc<Cell<u32>>) {
+        let attempts = Rc::new(Cell::new(0));
+        let stream = Self {
+            failures,
+            attempts: Rc::clone(&attempts),
+            response: b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok".to_vec(),
+        };
+
+        (stream, attempts)
+    }
+}
+
+impl FlakyStream {
+    /// A connection that never fails, answering with `response`.
+    fn answering(response: Vec<u8>) -> Self {
+        Self {
+            failures: 0,
+            attempts: Rc::new(Cell::new(0)),
+            response,
+        }
+    }
+}
+
+impl ConnectionStream for FlakyStream {
+    fn cs_read(&mut self, buffer: &mut [u8]) -> Result<usize, io::Error> {
+        let length = self.response.len().min(buffer.len());
+        buffer[..length].copy_from_slice(&self.response[..length]);
+        self.response.drain(..length);
+
+        Ok(length)
+    }
+
+    fn cs_write(&mut self, data: &[u8]) -> Result<usize, io::Error> {
+        self.attempts.set(self.attempts.get() + 1);
+
+        if self.failures > 0 {
+            self.failures -= 1;
+            return Err(io::Error::from(io::ErrorKind::ConnectionReset));
+        }
+
+        Ok(data.len())
+    }
+}
+
+fn request(method: &str) -> http::Request {
+    http::Request {
+        method: String::from(method),
+        request_target: String::from("/"),
+        protocol: http::Protocol::HTTP1_1,
+        headers: vec![http::Header::new(
+            String::from("Host"),
+            String::from("example.com"),
+        )],
+        body: None,
+    }
+}
+
+#[test]
+fn test_transient_errors_are_retried() {
+    let retry_policy = http::RetryPolicy {
+        max_retries: 3,
+        initial_backoff: Duration::from_millis(1),
+        retry_all_methods: false,
+    };
+
+    let (stream, attempts) = FlakyStream::new(2);
+    let mut client = http::Client::new(http::Protocol::HTTP1_1, true);
+    client
+        .with_retry_policy(retry_policy.clone())
+        .with_connection(Box::new(stream));
+
+    let response = client.send_request(request("GET")).unwrap();
+    assert_eq!(response.status(), Some(http::StatusCode::OK));
+    assert_eq!(response.body.as_deref(), Some("ok"));
+    assert_eq!(attempts.get(), 3);
+
+    // Methods that aren't idempotent are only sent once
+    let (stream, attempts) = FlakyStream::new(2);
+    client.with_connection(Box::new(stream));
+
+    assert!(client.send_request(request("POST")).is_none());
+    assert_eq!(attempts.get(), 1);
+
+    // Giving up once the retries run out
+    let (stream, attempts) = FlakyStream::new(5);
+    client.with_connection(Box::new(stream));
+
+    assert!(client.send_request(request("GET")).is_none());
+    assert_eq!(attempts.get(), 4);
+}
+
+#[test]
+fn test_requests_go_through_proxy() {
+    let (port, requests) = serve(|request| {
+        if request.starts_with("CONNECT ") {
+            String::from("HTTP/1.1 200 Connection established\r\n\r\n")
+        } else {
+            String::from("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+        }
+    });
+
+    let mut client = http::Client::new(http::Protocol::HTTP1_1, true);
+    client.with_proxy(URL::pure_parse(format!("http://127.0.0.1:{}/", port)).unwrap());
+
+    // The mock proxy doesn't speak TLS past the tunnel, so the request itself goes nowhere
+    let secure = URL::pure_parse(String::from("https://example.com/secret")).unwrap();
+    assert!(client.fetch(&secure).is_none());
+
+    let request = requests.recv().unwrap();
+    assert_eq!(
+        request.lines().next(),
+        Some("CONNECT example.com:443 HTTP/1.1")
+    );
+    assert_eq!(header_values(&request, "Host"), ["example.com:443"]);
+
+    let plain = URL::pure_parse(String::from("http://example.com/page")).unwrap();
+    assert!(client.fetch(&plain).is_some());
+
+    let request = requests.recv().unwrap();
+    assert_eq!(
+        request.lines().next(),
+        Some("GET http://example.com/page HTTP/1.1")
+    );
+    assert_eq!(header_values(&request, "Host"), ["example.com"]);
+}
+
+#[test]
+fn test_parse_http_date_formats() {
+    // Sun, 06 Nov 1994 08:49:37 GMT
+    let expected = UNIX_EPOCH + Duration::from_secs(784_111_777);
+
+    assert_eq!(
+        parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+        Some(expected)
+    );
+    assert_eq!(
+        parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"),
+        Some(expected)
+    );
+    assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), Some(expected));
+
+    assert_eq!(
+        parse_http_date("Thu, 29 Feb 2024 23:59:59 GMT"),
+        Some(UNIX_EPOCH + Duration::from_secs(1_709_251_199))
+    );
+
+    assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 PST"), None);
+    assert_eq!(parse_http_date("Thu, 29 Feb 2023 00:00:00 GMT"), None);
+    assert_eq!(parse_http_date("Sun, 06 Nov 1994 24:00:00 GMT"), None);
+    assert_eq!(parse_http_date("yesterday"), None);
+
+    // Years past 9999 are rejected, rather than counted out day by day
+    assert_eq!(
+        parse_http_date("Thu, 31 Dec 9999 23:59:59 GMT"),
+        Some(UNIX_EPOCH + Duration::from_secs(253_402_300_799))
+    );
+    assert_eq!(parse_http_date("Thu, 01 Jan 99999999999 00:00:00 GMT"), None);
+    assert_eq!(parse_http_date("Thu Jan  1 00:00:00 10000"), None);
+}
+
+#[test]
+fn test_expires_keeps_response_fresh() {
+    let (port, requests) = serve(|_| {
+        String::from(
+            "HTTP/1.1 200 OK\r\nDate: Sun, 06 Nov 1994 08:49:37 GMT\r\nExpires: Sun, 06 Nov 1994 09:49:37 GMT\r\nContent-Length: 2\r\n\r\nok",
+        )
+    });
+    let url = URL::pure_parse(format!("http://127.0.0.1:{}/", port)).unwrap();
+
+    let mut client = http::Client::new(http::Protocol::HTTP1_1, true);
+
+    assert!(client.fetch(&url).is_some());
+    requests.recv().unwrap();
+
+    assert!(client.fetch(&url).is_some());
+    assert!(
+        requests.recv_timeout(Duration::from_millis(200)).is_err(),
+        "A response within its Expires shouldn't be requested again"
+    );
+}
+
+/// Sends a request over a connection that answers with a 200 with `headers` and `body`.
+fn receive_body(headers: &str, body: &[u8]) -> Option<http::Response> {
+    let mut response = format!(
+        "HTTP/1.1 200 OK\r\n{headers}Content-Length: {}\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    response.extend_from_slice(body);
+
+    let mut client = http::Client::new(http::Protocol::HTTP1_1, true);
+    client.with_connection(Box::new(FlakyStream::answering(response)));
+    client.send_request(request("GET"))
+}
+
+#[test]
+fn test_compressed_bodies_are_decoded() {
+    let html = include_str!("fixtures/page.html");
+    let gzipped = include_bytes!("fixtures/page.html.gz");
+
+    let response = receive_body("Content-Encoding: gzip\r\n", gzipped).unwrap();
+    assert_eq!(response.body.as_deref(), Some(html));
+
+    // zlib, as the deflate coding should be, and the raw DEFLATE some servers send instead
+    let zlib = [
+        120, 156, 75, 73, 77, 203, 73, 44, 73, 77, 81, 72, 202, 79, 169, 4, 0, 35, 81, 5, 8,
+    ];
+    let response = receive_body("Content-Encoding: deflate\r\n", &zlib).unwrap();
+    assert_eq!(response.body.as_deref(), Some("deflated body"));
+
+    let response = receive_body("Content-Encoding: deflate\r\n", &zlib[2..17]).unwrap();
+    assert_eq!(response.body.as_deref(), Some("deflated body"));
+
+    // Unknown codings are passed through untouched
+    let response = receive_body("Content-Encoding: br\r\n", b"as is").unwrap();
+    assert_eq!(response.body.as_deref(), Some("as is"));
+
+    // A truncated stream is an error rather than a panic
+    assert!(receive_body("Content-Encoding: gzip\r\n", &gzipped[..100]).is_none());
+    assert_eq!(
+        http::content_coding::decode_gzip(&gzipped[..100], usize::MAX),
+        Err(http::content_coding::DecodeError::UnexpectedEnd)
+    );
+
+    // Bodies stop decompressing once they're over the limit
+    assert_eq!(
+        http::content_coding::decode_gzip(gzipped, html.len() - 1),
+        Err(http::content_coding::DecodeError::TooLarge)
+    );
+    assert_eq!(
+        http::content_coding::decode_gzip(gzipped, html.len()).as_deref(),
+        Ok(html.as_bytes())
+    );
+}
+
+#[test]
+fn test_head_split_across_reads() {
+    let gzipped = include_bytes!("fixtures/page.html.gz");
+
+    // Pad the head so that the blank line ending it straddles the end of the first read
+    let head = |padding: usize| {
+        format!(
+            "HTTP/1.1 200 OK\r\nX-Padding: {}\r\nContent-Encoding: gzip\r\n\
+            Content-Length: {}\r\n\r\n",
+            "a".repeat(padding),
+            gzipped.len()
+        )
+    };
+    let padding = http::CHUNK_LENGTH + 1 - head(0).len();
+
+    let mut response = head(padding).into_bytes();
+    assert_eq!(&response[http::CHUNK_LENGTH - 3..][..4], b"\r\n\r\n");
+    response.extend_from_slice(gzipped);
+
+    let mut client = http::Client::new(http::Protocol::HTTP1_1, true);
+    client.with_connection(Box::new(FlakyStream::answering(response)));
+
+    let response = client.send_request(request("GET")).unwrap();
+    assert_eq!(
+        response.body.as_deref(),
+        Some(include_str!("fixtures/page.html"))
+    );
+}
+
+#[test]
+fn test_accept_encoding_is_sent() {
+    let (port, requests) = capture_requests();
+    let url = URL::pure_parse(format!("http://127.0.0.1:{}/", port)).unwrap();
+
+    let mut client = http::Client::new(http::Protocol::HTTP1_1, true);
+    assert!(client.fetch(&url).is_some());
+    assert_eq!(
+        header_values(&requests.recv().unwrap(), "Accept-Encoding"),
+        [http::DEFAULT_ACCEPT_ENCODING]
+    );
+
+    // Turning it off asks for bodies as they are
+    client.with_accept_encoding(String::new());
+    assert!(client.navigate(&url).is_some());
+    assert!(header_values(&requests.recv().unwrap(), "Accept-Encoding").is_empty());
+}
+
+#[test]
+fn test_decode_chunked_bodies() {
+    use http::chunked::{ChunkedError, decode_chunked};
+
+    assert_eq!(
+        decode_chunked(b"4\r\nWiki\r\n6\r\npedia \r\nE\r\nin \r\n\r\nchunks.\r\n0\r\n\r\n")
+            .unwrap(),
+        b"Wikipedia in \r\n\r\nchunks."
+    );
+    assert_eq!(decode_chunked(b"0\r\n\r\n").unwrap(), b"");
+
+    // Chunk extensions and trailer fields are discarded
+    let body = [
+        b"1a;foo=bar\r\n".as_slice(),
+        b"abcdefghijklmnopqrstuvwxyz\r\n",
+        b"0;last\r\nExpires: never\r\n\r\n",
+    ]
+    .concat();
+    assert_eq!(
+        decode_chunked(&body).unwrap(),
+        b"abcdefghijklmnopqrstuvwxyz"
+    );
+
+    assert_eq!(decode_chunked(b"5\r\nabc"), Err(ChunkedError::Incomplete));
+    assert_eq!(
+        decode_chunked(b"zz\r\nabc\r\n0\r\n\r\n"),
+        Err(ChunkedError::InvalidChunkSize)
+    );
+    assert_eq!(
+        decode_chunked(b"2\r\nabc\r\n0\r\n\r\n"),
+        Err(ChunkedError::MissingLineBreak)
+    );
+}
+
+#[test]
+fn test_chunked_responses_are_decoded() {
+    // There is no Content-Length, so the last chunk is what ends the body
+    let (port, _requests) = serve(|_| {
+        String::from(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            6\r\n<p>Hel\r\n5;x=y\r\nlo</p\r\n1\r\n>\r\n0\r\n\r\n",
+        )
+    });
+    let url = URL::pure_parse(format!("http://127.0.0.1:{}/", port)).unwrap();
+
+    let mut client = http::Client::new(http::Protocol::HTTP1_1, true);
+    let response = client.fetch(&url).unwrap();
+    assert_eq!(response.body.as_deref(), Some("<p>Hello</p>"));
+}
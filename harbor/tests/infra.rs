@@ -0,0 +1,81 @@
+use harbor::{
+    css::{
+        colors::Color,
+        parser::parse_css_declaration_block,
+        properties::{CSSParseable, Margin},
+        tokenize::{CSSToken, tokenize},
+    },
+    html5::parse::{Parser, Token},
+    infra::{InputStream, is_noncharacter},
+};
+
+#[test]
+fn test_lookahead_does_not_consume() {
+    let chars = "abcd".chars().collect::<Vec<char>>();
+    let mut stream = InputStream::new(&chars);
+
+    assert_eq!(stream.lookahead(2), ['a', 'b']);
+    assert_eq!(stream.lookahead(2), ['a', 'b']);
+    assert_eq!(stream.consume(), Some('a'));
+
+    assert_eq!(stream.lookahead(3), ['b', 'c', 'd']);
+    assert_eq!(stream.lookahead(10), ['b', 'c', 'd']);
+    assert_eq!(stream.consume(), Some('b'));
+
+    stream.reconsume();
+    assert_eq!(stream.lookahead(2), ['b', 'c']);
+    assert_eq!(stream.consume(), Some('b'));
+
+    assert_eq!(stream.consume(), Some('c'));
+    assert_eq!(stream.consume(), Some('d'));
+    assert_eq!(stream.lookahead(1), [] as [char; 0]);
+    assert_eq!(stream.consume(), None);
+    assert_eq!(stream.lookahead(1), [] as [char; 0]);
+}
+
+#[test]
+fn test_dimension_at_end_of_input() {
+    let chars = "6px".chars().collect::<Vec<char>>();
+    let tokens = tokenize(&mut InputStream::new(&chars));
+
+    assert!(matches!(
+        &tokens[0],
+        CSSToken::Dimension(dimension) if dimension.value == 6.0 && dimension.unit == "px"
+    ));
+}
+
+#[test]
+fn test_is_noncharacter() {
+    assert!(is_noncharacter(0xFDD0));
+    assert!(is_noncharacter(0xFDEF));
+    assert!(is_noncharacter(0xFFFE));
+    assert!(is_noncharacter(0x1FFFF));
+    assert!(is_noncharacter(0x10FFFF));
+
+    assert!(!is_noncharacter(0x41));
+    assert!(!is_noncharacter(0xFDCF));
+    assert!(!is_noncharacter(0xFDF0));
+    assert!(!is_noncharacter(0x1FFFD));
+}
+
+#[test]
+fn test_input_stream_drives_html_and_css() {
+    let chars = "<p class=\"note\">Hi</p>".chars().collect::<Vec<char>>();
+    let mut stream = InputStream::new(&chars);
+    let mut parser = Parser::new(&mut stream);
+    parser.parse();
+
+    assert!(parser.emitted_tokens.iter().any(|token| matches!(
+        token,
+        Token::StartTag(tag) if tag.name == "p" && tag.attributes == [("class".to_string(), "note".to_string())]
+    )));
+
+    let declarations = parse_css_declaration_block(String::from("color: red; margin: 1px 2px"));
+    assert_eq!(declarations.len(), 2);
+
+    let color = Color::from_cv(&mut InputStream::new(&declarations[0].value));
+    assert_eq!(color, Some(Color::Named(String::from("red"))));
+
+    let margin = Margin::from_cv(&mut InputStream::new(&declarations[1].value));
+    assert!(margin.is_some());
+}
@@ -0,0 +1,665 @@
+use std::ops::Deref;
+
+use harbor::{
+    css::{
+        r#box::{Box, break_lines, collapse_white_space},
+        layout::Layout,
+    },
+    html5::{self, dom::NodeKind},
+    infra,
+    render::{cursor::MouseCursor, shapes::box_decoration_vertices, text::GlyphBatch},
+};
+use winit::window::CursorIcon;
+
+/// Parses `html_content`, then builds and lays out its box tree in an 800x600 viewport.
+fn lay_out(html_content: &str) -> Layout {
+    let chars = html_content.chars().collect::<Vec<char>>();
+    let mut stream = infra::InputStream::new(&chars);
+    let mut parser = html5::parse::Parser::new(&mut stream);
+
+    parser.parse();
+
+    let mut layout = Layout::new(parser.document.document().clone(), (800.0, 600.0));
+    layout.make_tree();
+    layout.layout();
+
+    layout
+}
+
+#[test]
+fn test_debug_layout_tree() {
+    let layout = lay_out(
+        "<!DOCTYPE html><html><head><style>html, body { display: block } \
+        body { margin: 10px }</style></head><body></body></html>",
+    );
+
+    assert_eq!(
+        layout.root_box.unwrap().borrow().debug_layout_tree(),
+        "Block <html> at (0, 0) size 20x20\n  Block <body> at (0, 0) size 0x10\n"
+    );
+}
+
+#[test]
+fn test_flow_root_contains_child_margins() {
+    let styles = "html, body, div { display: block } .outer { margin-top: 20px } \
+        .inner { margin-top: 30px }";
+
+    // A plain block's first child shares its top margin, so the pair only takes the larger one
+    let collapsed = lay_out(&format!(
+        "<!DOCTYPE html><html><head><style>{styles}</style></head>\
+        <body><div class=outer><div class=inner></div></div></body></html>"
+    ));
+    assert_eq!(
+        collapsed.root_box.unwrap().borrow().debug_layout_tree(),
+        "Block <html> at (0, 0) size 0x30\n  \
+        Block <body> at (0, 0) size 0x30\n    \
+        Block <div> at (0, 0) size 0x30\n      \
+        Block <div> at (0, 0) size 0x30\n"
+    );
+
+    // A flow-root keeps its child's margin inside it
+    let contained = lay_out(&format!(
+        "<!DOCTYPE html><html><head><style>{styles} .outer {{ display: flow-root }}</style>\
+        </head><body><div class=outer><div class=inner></div></div></body></html>"
+    ));
+    assert_eq!(
+        contained.root_box.unwrap().borrow().debug_layout_tree(),
+        "Block <html> at (0, 0) size 0x50\n  \
+        Block <body> at (0, 0) size 0x50\n    \
+        Block <div> at (0, 0) size 0x50\n      \
+        Block <div> at (0, 20) size 0x30\n"
+    );
+}
+
+#[test]
+fn test_negative_margins_collapse_with_their_parent() {
+    let tree = |outer: &str, inner: &str| {
+        lay_out(&format!(
+            "<!DOCTYPE html><html><head><style>html, body, div {{ display: block }} \
+            .outer {{ margin-top: {outer} }} .inner {{ margin-top: {inner} }}</style></head>\
+            <body><div class=outer><div class=inner></div></div></body></html>"
+        ))
+        .root_box
+        .unwrap()
+        .borrow()
+        .debug_layout_tree()
+    };
+
+    // A negative margin is subtracted from the positive one it collapses with
+    assert_eq!(
+        tree("20px", "-10px"),
+        "Block <html> at (0, 0) size 0x10\n  \
+        Block <body> at (0, 0) size 0x10\n    \
+        Block <div> at (0, 0) size 0x10\n      \
+        Block <div> at (0, 20) size 0x-10\n"
+    );
+
+    // Of two negative margins, only the most negative one is kept
+    assert_eq!(
+        tree("-20px", "-10px"),
+        "Block <html> at (0, 0) size 0x-20\n  \
+        Block <body> at (0, 0) size 0x-20\n    \
+        Block <div> at (0, 0) size 0x-20\n      \
+        Block <div> at (0, -10) size 0x-10\n"
+    );
+}
+
+#[test]
+fn test_em_margins_use_the_element_font_size() {
+    let layout = lay_out(
+        "<!DOCTYPE html><html><head><style>html, body, div { display: block } \
+        div { font-size: 20px; margin: 1em }</style></head><body><div></div></body></html>",
+    );
+
+    let root = layout.root_box.unwrap();
+    let body = root.borrow().children[0].clone();
+    let div = body.borrow().children[0].clone();
+    let margin = *div.borrow().margin();
+
+    assert_eq!(
+        (margin.0, margin.1, margin.2, margin.3),
+        (20.0, 20.0, 20.0, 20.0)
+    );
+}
+
+#[test]
+fn test_viewport_meta_sets_the_initial_containing_block() {
+    let lay_out_with = |meta: &str| {
+        lay_out(&format!(
+            "<!DOCTYPE html><html><head>{meta}<style>html, body {{ display: block }} \
+            body {{ margin: 0; width: 50% }}</style></head><body></body></html>"
+        ))
+    };
+    let body_width = |layout: Layout| {
+        let root = layout.root_box.unwrap();
+        let body = root.borrow().children[0].clone();
+        body.borrow()._content_width
+    };
+
+    let layout = lay_out_with("");
+    assert_eq!(layout.viewport_size(), (800.0, 600.0));
+    assert_eq!(body_width(layout), 400.0);
+
+    let layout = lay_out_with("<meta name=viewport content=\"width=device-width\">");
+    assert_eq!(layout.viewport_size(), (800.0, 600.0));
+    assert_eq!(body_width(layout), 400.0);
+
+    // A missing height keeps the window's aspect ratio
+    let layout = lay_out_with("<meta name=viewport content=\"width=400\">");
+    assert_eq!(layout.viewport_size(), (400.0, 300.0));
+    assert_eq!(body_width(layout), 200.0);
+
+    let layout = lay_out_with("<meta name=viewport content=\"initial-scale=2\">");
+    assert_eq!(layout.viewport_size(), (400.0, 300.0));
+    assert_eq!(body_width(layout), 200.0);
+}
+
+#[test]
+fn test_auto_margins_center_fixed_width_blocks() {
+    let layout = lay_out(
+        "<!DOCTYPE html><html><head><style>html, body, div { display: block } \
+        .outer { width: 600px } .inner { width: 200px; margin: 0 auto }</style></head>\
+        <body><div class=outer><div class=inner></div></div></body></html>",
+    );
+
+    let root = layout.root_box.unwrap();
+    let body = root.borrow().children[0].clone();
+    let outer = body.borrow().children[0].clone();
+    let inner = outer.borrow().children[0].clone();
+    let inner = inner.borrow();
+
+    assert_eq!(inner.position().0 + inner.margin().left(), 200.0);
+    assert_eq!(inner.margin().right(), 200.0);
+}
+
+#[test]
+fn test_outlines_do_not_affect_layout() {
+    let styles = "html, body, div { display: block } html { font-family: Arial } \
+        div { width: 100px; margin: 10px }";
+
+    let plain = lay_out(&format!(
+        "<!DOCTYPE html><html><head><style>{styles}</style></head>\
+        <body><div>a</div><div>b</div></body></html>"
+    ));
+    let outlined = lay_out(&format!(
+        "<!DOCTYPE html><html><head><style>{styles} \
+        div {{ outline: 4px solid red; outline-offset: 2px }}</style></head>\
+        <body><div>a</div><div>b</div></body></html>"
+    ));
+
+    let plain_root = plain.root_box.unwrap();
+    let outlined_root = outlined.root_box.unwrap();
+    assert_eq!(
+        plain_root.borrow().debug_layout_tree(),
+        outlined_root.borrow().debug_layout_tree()
+    );
+    assert!(
+        plain_root.borrow().children[0].borrow().children[0]
+            .borrow()
+            .outline_rects()
+            .is_empty()
+    );
+
+    let body = outlined_root.borrow().children[0].clone();
+    let div = body.borrow().children[1].clone();
+    let div = div.borrow();
+
+    let x = div.position().0 + div.margin().left();
+    let y = div.position().1 + div.margin().top();
+    let width = div.border_edges().horizontal();
+    let height = div.border_edges().vertical();
+
+    // Top, bottom, left and right, each 4px thick and 2px out from the border box
+    assert_eq!(
+        div.outline_rects(),
+        [
+            (x - 6.0, y - 6.0, width + 12.0, 4.0),
+            (x - 6.0, y + height + 2.0, width + 12.0, 4.0),
+            (x - 6.0, y - 2.0, 4.0, height + 4.0),
+            (x + width + 2.0, y - 2.0, 4.0, height + 4.0),
+        ]
+    );
+}
+
+#[test]
+fn test_hovering_a_link_shows_a_pointer() {
+    let layout = lay_out(
+        "<!DOCTYPE html><html><head><style>html, body, a, div { display: block } \
+        html { font-family: Arial }</style></head>\
+        <body><a href=/next><span>link</span></a><div>text</div></body></html>",
+    );
+    let root = layout.root_box.unwrap();
+    let body = root.borrow().children[0].clone();
+
+    let cursor_over = |index: usize| {
+        let target = body.borrow().children[index].clone();
+        let (x, y) = target.borrow().position();
+        let hovered = Box::get_hovered_elems(&root, x + 1.0, y + 1.0, 0.0, 0.0);
+
+        MouseCursor::for_hovered(&hovered).icon
+    };
+
+    assert_eq!(cursor_over(0), Some(CursorIcon::Pointer));
+    assert_eq!(cursor_over(1), Some(CursorIcon::Default));
+}
+
+#[test]
+fn test_authored_cursors_map_to_os_cursors() {
+    let layout = lay_out(
+        "<!DOCTYPE html><html><head><style>html, body, div { display: block } \
+        html { font-family: Arial } .button { cursor: pointer } .field { cursor: text }\
+        </style></head><body><div class=button>press</div><div class=field>type</div>\
+        </body></html>",
+    );
+    let root = layout.root_box.unwrap();
+    let body = root.borrow().children[0].clone();
+
+    let cursor_over = |index: usize| {
+        let target = body.borrow().children[index].clone();
+        let (x, y) = target.borrow().position();
+        let hovered = Box::get_hovered_elems(&root, x + 1.0, y + 1.0, 0.0, 0.0);
+
+        MouseCursor::for_hovered(&hovered).icon
+    };
+
+    assert_eq!(cursor_over(0), Some(CursorIcon::Pointer));
+    assert_eq!(cursor_over(1), Some(CursorIcon::Text));
+}
+
+#[test]
+fn test_text_flows_beside_floats() {
+    let styles = "html, body, div { display: block } html { font-family: Arial } \
+        .left { float: left; width: 100px } .right { float: right; width: 100px }";
+
+    let layout = lay_out(&format!(
+        "<!DOCTYPE html><html><head><style>{styles}</style></head>\
+        <body><div class=left>A</div>Beside</body></html>"
+    ));
+    let root = layout.root_box.unwrap();
+    let body = root.borrow().children[0].clone();
+
+    let float = body.borrow().children[0].clone();
+    assert_eq!(float.borrow().position(), (0.0, 0.0));
+    assert_eq!(float.borrow()._content_width, 100.0);
+
+    // The text is on the same line as the float, just to its right
+    let text = body.borrow().children[1].clone();
+    assert_eq!(text.borrow().position(), (100.0, 0.0));
+
+    let layout = lay_out(&format!(
+        "<!DOCTYPE html><html><head><style>{styles}</style></head>\
+        <body><div class=right>A</div>Beside</body></html>"
+    ));
+    let root = layout.root_box.unwrap();
+    let body = root.borrow().children[0].clone();
+
+    assert_eq!(body.borrow().children[0].borrow().position(), (700.0, 0.0));
+    assert_eq!(body.borrow().children[1].borrow().position(), (0.0, 0.0));
+
+    // Lines beside a right float wrap before they reach it
+    let layout = lay_out(&format!(
+        "<!DOCTYPE html><html><head><style>{styles} body {{ width: 300px }}</style></head>\
+        <body><div class=right>A</div>Some words that are far too long to fit on one line \
+        beside the float</body></html>"
+    ));
+    let root = layout.root_box.unwrap();
+    let body = root.borrow().children[0].clone();
+
+    assert_eq!(body.borrow().children[0].borrow().position(), (200.0, 0.0));
+
+    let text = body.borrow().children[1].clone();
+    assert!(text.borrow().text_lines().len() > 1);
+    assert!(text.borrow()._content_width <= 200.0);
+}
+
+/// Lays out a small and a big span on one line, with `align` as the small span's
+/// `vertical-align`, and returns the (y, height, baseline) of each along with the line height.
+fn align_spans(align: &str) -> ((f64, f64, f64), (f64, f64, f64), f64) {
+    let layout = lay_out(&format!(
+        "<!DOCTYPE html><html><head><style>html, body {{ display: block }} \
+        html {{ font-family: Arial }} .small {{ vertical-align: {align} }} \
+        .big {{ font-size: 48px }}</style></head>\
+        <body><span class=small>small</span><span class=big>Big</span></body></html>"
+    ));
+    let root = layout.root_box.unwrap();
+    let body = root.borrow().children[0].clone();
+
+    let metrics = |i: usize| {
+        let span = body.borrow().children[i].clone();
+        let span = span.borrow();
+        (
+            span.position().1,
+            span.content_edges().bottom(),
+            span.baseline(),
+        )
+    };
+
+    let line_height = body.borrow().content_edges().bottom();
+    (metrics(0), metrics(1), line_height)
+}
+
+#[test]
+fn test_vertical_align() {
+    let ((small_y, _, small_baseline), (big_y, big_height, big_baseline), line_height) =
+        align_spans("baseline");
+    assert!(small_y > big_y);
+    assert_eq!(small_y + small_baseline, big_y + big_baseline);
+    assert_eq!(line_height, big_height);
+
+    let ((small_y, _, _), _, _) = align_spans("top");
+    assert_eq!(small_y, 0.0);
+
+    let ((small_y, small_height, _), _, line_height) = align_spans("bottom");
+    assert_eq!(small_y + small_height, line_height);
+
+    let ((small_y, small_height, _), _, line_height) = align_spans("middle");
+    assert_eq!(small_y + small_height / 2.0, line_height / 2.0);
+}
+
+#[test]
+fn test_mixed_font_sizes_share_a_baseline() {
+    let layout = lay_out(
+        "<!DOCTYPE html><html><head><style>html, body { display: block } \
+        html { font-family: Arial } .small { font-size: 12px } .big { font-size: 24px }\
+        </style></head><body><span class=small>small<span class=big>Big</span></span></body>\
+        </html>",
+    );
+    let root = layout.root_box.unwrap();
+    let body = root.borrow().children[0].clone();
+    let outer = body.borrow().children[0].clone();
+
+    let small = outer.borrow().children[0].clone();
+    let big = outer.borrow().children[1].clone();
+    let (small, big) = (small.borrow(), big.borrow());
+
+    assert!(small.position().1 > big.position().1);
+    assert_eq!(
+        small.position().1 + small.baseline(),
+        big.position().1 + big.baseline()
+    );
+
+    // The outer span's baseline is the shared one
+    assert_eq!(outer.borrow().baseline(), big.position().1 + big.baseline());
+}
+
+#[test]
+fn test_collapse_white_space_keeps_nbsp() {
+    assert_eq!(collapse_white_space("a \n\t b", false, false), "a b");
+    assert_eq!(
+        collapse_white_space("a\u{a0}\u{a0}b", false, false),
+        "a\u{a0}\u{a0}b"
+    );
+    assert_eq!(collapse_white_space("  a  ", true, false), "a ");
+    assert_eq!(
+        collapse_white_space("\u{a0}a\u{a0} ", true, true),
+        "\u{a0}a\u{a0}"
+    );
+}
+
+#[test]
+fn test_nbsp_is_laid_out_as_a_glyph() {
+    let layout = lay_out(
+        "<!DOCTYPE html><html><head><style>html, body { display: block } \
+        html { font-family: Arial }</style></head><body>a&nbsp;&nbsp;b</body></html>",
+    );
+    let root = layout.root_box.unwrap();
+    let body = root.borrow().children[0].clone();
+    let text = body.borrow().children[0].clone();
+
+    let data = match text
+        .borrow()
+        .associated_node
+        .as_ref()
+        .unwrap()
+        .borrow()
+        .deref()
+    {
+        NodeKind::Text(text) => text.borrow().data().to_string(),
+        _ => unreachable!(),
+    };
+    assert_eq!(data, "a\u{a0}\u{a0}b");
+
+    // Both spaces take up room, on a single line
+    let single = lay_out(
+        "<!DOCTYPE html><html><head><style>html, body { display: block } \
+        html { font-family: Arial }</style></head><body>a&nbsp;b</body></html>",
+    );
+    let single_root = single.root_box.unwrap();
+    let single_body = single_root.borrow().children[0].clone();
+
+    assert!(text.borrow()._content_width > single_body.borrow()._content_width);
+    assert_eq!(
+        body.borrow().content_edges().bottom(),
+        single_body.borrow().content_edges().bottom()
+    );
+}
+
+#[test]
+fn test_break_lines_at_spaces_and_soft_hyphens() {
+    // Every character is 10 wide
+    let lines = |text: &str, max_width: f64| break_lines(text, Some(max_width), |_| 10.0);
+
+    assert_eq!(lines("abc def", 100.0), [(String::from("abc def"), 70.0)]);
+    assert_eq!(
+        lines("abc def", 50.0),
+        [(String::from("abc"), 30.0), (String::from("def"), 30.0)]
+    );
+
+    // Soft hyphens are invisible unless the line breaks at them
+    assert_eq!(lines("ab\u{ad}cd", 100.0), [(String::from("abcd"), 40.0)]);
+    assert_eq!(
+        lines("ab\u{ad}cd", 30.0),
+        [(String::from("ab-"), 30.0), (String::from("cd"), 20.0)]
+    );
+
+    // A word with nowhere to break overflows
+    assert_eq!(lines("abcdef", 30.0), [(String::from("abcdef"), 60.0)]);
+}
+
+#[test]
+fn test_soft_hyphen_breaks_in_narrow_container() {
+    let layout = lay_out(
+        "<!DOCTYPE html><html><head><style>html, body, div { display: block } \
+        html { font-family: Arial } div { width: 120px }</style></head>\
+        <body><div>Supercalifragilistic&shy;expialidocious</div></body></html>",
+    );
+    let root = layout.root_box.unwrap();
+    let body = root.borrow().children[0].clone();
+    let div = body.borrow().children[0].clone();
+    let text = div.borrow().children[0].clone();
+
+    assert_eq!(
+        text.borrow().text_lines(),
+        ["Supercalifragilistic-", "expialidocious"]
+    );
+}
+
+#[test]
+fn test_percent_and_rem_lengths_resolve_during_layout() {
+    let layout = lay_out(
+        "<!DOCTYPE html><html><head><style>html { font-size: 10px } \
+        html, body, div { display: block } body { margin: 0 } \
+        .outer { width: 400px } .inner { width: 50%; margin-left: 2rem; font-size: 20px }\
+        </style></head><body><div class=outer><div class=inner></div></div></body></html>",
+    );
+
+    let root = layout.root_box.unwrap();
+    let body = root.borrow().children[0].clone();
+    let outer = body.borrow().children[0].clone();
+    let inner = outer.borrow().children[0].clone();
+    let inner = inner.borrow();
+
+    assert_eq!(inner._content_width, 200.0);
+    assert_eq!(inner.margin().left(), 20.0);
+}
+
+#[test]
+fn test_content_sized_widths_fall_back_to_auto() {
+    let tree_with = |width: &str| {
+        let layout = lay_out(&format!(
+            "<!DOCTYPE html><html><head><style>html, body, div {{ display: block }} \
+            .outer {{ width: 400px }} .inner {{ width: {width}; margin: 0 auto; padding: 4px }}\
+            </style></head><body><div class=outer><div class=inner></div></div></body></html>"
+        ));
+
+        layout.root_box.unwrap().borrow().debug_layout_tree()
+    };
+
+    let auto = tree_with("auto");
+    for width in ["max-content", "min-content", "fit-content", "stretch"] {
+        assert_eq!(tree_with(width), auto);
+    }
+}
+
+#[test]
+fn test_padding_and_border_offset_children() {
+    let layout = lay_out(
+        "<!DOCTYPE html><html><head><style>html, body, div { display: block } \
+        body { margin: 0 } .outer { padding: 10px 20px; border: 5px solid black } \
+        </style></head><body><div class=outer><div></div></div></body></html>",
+    );
+
+    let root = layout.root_box.unwrap();
+    let body = root.borrow().children[0].clone();
+    let outer = body.borrow().children[0].clone();
+    let outer = outer.borrow();
+
+    let padding = *outer.padding();
+    assert_eq!(
+        (padding.0, padding.1, padding.2, padding.3),
+        (10.0, 20.0, 10.0, 20.0)
+    );
+    assert_eq!(outer.border().horizontal(), 10.0);
+
+    let inner = outer.children[0].borrow();
+    assert_eq!(inner.position(), (25.0, 15.0));
+}
+
+#[test]
+fn test_backgrounds_and_borders_are_painted() {
+    let layout = lay_out(
+        "<!DOCTYPE html><html><head><style>html, body, div { display: block } \
+        body { margin: 0 } div { width: 100px; padding-top: 50px; background-color: red } \
+        .bordered { border: 10px solid blue }</style></head>\
+        <body><div></div><div class=bordered></div></body></html>",
+    );
+
+    let root = layout.root_box.unwrap();
+    let body = root.borrow().children[0].clone();
+    let red = [1.0, 0.0, 0.0, 1.0];
+
+    // There's no `height` yet, so the divs are 50px tall from their padding, which the
+    // background covers too
+
+    let div = body.borrow().children[0].clone();
+    let vertices = box_decoration_vertices(&div.borrow(), (0.0, 0.0), 800.0, 600.0);
+
+    // Two triangles from the top left corner, 100px across and 50px down an 800x600 screen
+    let (left, top) = (-1.0, 1.0);
+    let (right, bottom) = (-1.0 + 2.0 * 100.0 / 800.0, 1.0 - 2.0 * 50.0 / 600.0);
+    assert_eq!(
+        vertices.iter().map(|v| v.position).collect::<Vec<_>>(),
+        [
+            [left, top],
+            [right, top],
+            [right, bottom],
+            [left, top],
+            [right, bottom],
+            [left, bottom],
+        ]
+    );
+    assert!(vertices.iter().all(|v| v.color == red));
+
+    // The background fills the border box, and each side of the border goes over it
+    let bordered = body.borrow().children[1].clone();
+    let bordered = bordered.borrow();
+    let (x, y) = (bordered.position().0, bordered.position().1);
+
+    assert_eq!(bordered.background_rect(), Some((x, y, 120.0, 70.0)));
+    assert_eq!(y, 50.0);
+
+    let blue = [0.0, 0.0, 1.0, 1.0];
+    assert_eq!(
+        bordered.border_rects(),
+        [
+            ((x, y, 120.0, 10.0), blue),
+            ((x, y + 60.0, 120.0, 10.0), blue),
+            ((x, y + 10.0, 10.0, 50.0), blue),
+            ((x + 110.0, y + 10.0, 10.0, 50.0), blue),
+        ]
+    );
+    assert_eq!(
+        box_decoration_vertices(&bordered, (0.0, 0.0), 800.0, 600.0).len(),
+        6 * 5
+    );
+}
+
+#[test]
+fn test_glyphs_are_batched_between_flushes() {
+    let layout = lay_out(
+        "<!DOCTYPE html><html><head><style>html, body, div { display: block } \
+        html { font-family: Arial } div { width: 200px }</style></head>\
+        <body><div>The quick brown fox jumps over the lazy dog while the five boxing \
+        wizards jump quickly and a wise old owl lived in an oak</div></body></html>",
+    );
+    let root = layout.root_box.clone().unwrap();
+    let body = root.borrow().children[0].clone();
+    let div = body.borrow().children[0].clone();
+    let text = div.borrow().children[0].clone();
+    let text = text.borrow();
+
+    let lines = text.text_lines();
+    assert!(lines.len() > 1);
+
+    let mut renderer = layout.get_renderer(String::from("Arial")).cloned().unwrap();
+    let mut batch = GlyphBatch::new();
+
+    let extents = renderer.batch_lines(
+        lines,
+        (0.0, 16.0),
+        16.0,
+        19.2,
+        [0.0, 0.0, 0.0, 1.0],
+        &mut batch,
+    );
+    assert_eq!(extents.len(), lines.len());
+
+    // Every visible character of every line is batched, each at its own pen position
+    let mut offsets = batch
+        .vertices()
+        .iter()
+        .map(|vertex| vertex.offset)
+        .collect::<Vec<_>>();
+    offsets.dedup();
+    assert_eq!(
+        offsets.len(),
+        lines
+            .iter()
+            .flat_map(|line| line.chars())
+            .filter(|ch| !ch.is_whitespace())
+            .count()
+    );
+
+    // With nothing painted in between, the whole paragraph goes out in a single draw
+    let paragraph = batch.vertices().len() as u32;
+    assert_eq!(batch.flush(), Some(0..paragraph));
+    assert_eq!(batch.flush(), None);
+
+    // Text after a flush is drawn on its own, from where the last draw left off
+    renderer.batch_lines(
+        &[String::from("More")],
+        (0.0, 200.0),
+        16.0,
+        19.2,
+        [0.0, 0.0, 0.0, 1.0],
+        &mut batch,
+    );
+    assert_eq!(
+        batch.flush(),
+        Some(paragraph..batch.vertices().len() as u32)
+    );
+
+    batch.clear();
+    assert!(batch.vertices().is_empty());
+    assert_eq!(batch.flush(), None);
+}
@@ -0,0 +1,88 @@
+use harbor::{
+    html5::meta::{
+        RefreshDirective, ViewportConfig, ViewportLength, refresh_directive, viewport_config,
+    },
+    http::url::URL,
+    infra::Serializable,
+};
+
+#[test]
+fn test_parse_viewport_meta() {
+    let config = ViewportConfig::parse("width=device-width, initial-scale=1");
+
+    assert_eq!(config.width, Some(ViewportLength::DeviceWidth));
+    assert_eq!(config.initial_scale, Some(1.0));
+    assert_eq!(config.height, None);
+    assert!(config.user_scalable);
+    assert_eq!(config.layout_size((400.0, 800.0)), (400.0, 800.0));
+
+    let config = ViewportConfig::parse(" width = 980 ; user-scalable=no,maximum-scale=2 bogus");
+    assert_eq!(config.width, Some(ViewportLength::Px(980.0)));
+    assert_eq!(config.maximum_scale, Some(2.0));
+    assert!(!config.user_scalable);
+    assert_eq!(config.layout_size((400.0, 800.0)), (980.0, 1960.0));
+
+    let config = ViewportConfig::parse("initial-scale=2");
+    assert_eq!(config.layout_size((400.0, 800.0)), (200.0, 400.0));
+}
+
+#[test]
+fn test_viewport_config_from_document() {
+    let document = harbor::parse_html(
+        "<html><head><meta charset=\"utf-8\">\
+        <meta name=\"Viewport\" content=\"width=device-width, initial-scale=1.5\">\
+        </head><body></body></html>",
+    );
+
+    let config = viewport_config(&document).unwrap();
+    assert_eq!(config.width, Some(ViewportLength::DeviceWidth));
+    assert_eq!(config.initial_scale, Some(1.5));
+
+    let without_meta = harbor::parse_html("<html><head></head><body></body></html>");
+    assert_eq!(viewport_config(&without_meta), None);
+}
+
+#[test]
+fn test_parse_refresh_directive() {
+    let base = URL::pure_parse(String::from("https://example.com/dir/page.html")).unwrap();
+
+    let refresh = RefreshDirective::parse("5;url=next.html", &base).unwrap();
+    assert_eq!(refresh.delay, 5);
+    assert_eq!(
+        refresh.url.unwrap().serialize(),
+        "https://example.com/dir/next.html"
+    );
+
+    let refresh = RefreshDirective::parse(" 0 , URL = 'https://other.example/' ", &base).unwrap();
+    assert_eq!(refresh.delay, 0);
+    assert_eq!(refresh.url.unwrap().serialize(), "https://other.example/");
+
+    let reload = RefreshDirective::parse("30", &base).unwrap();
+    assert_eq!(reload.delay, 30);
+    assert_eq!(reload.url, None);
+
+    // A delay too long to count doesn't turn into an immediate refresh
+    let never = RefreshDirective::parse("99999999999999999999999", &base).unwrap();
+    assert_eq!(never.delay, u64::MAX);
+    assert_eq!(RefreshDirective::parse(".5", &base).unwrap().delay, 0);
+
+    assert_eq!(RefreshDirective::parse("soon", &base), None);
+}
+
+#[test]
+fn test_refresh_directive_from_document() {
+    let document = harbor::parse_html(
+        "<html><head><meta http-equiv=\"Refresh\" content=\"2; url=/moved\"></head>\
+        <body></body></html>",
+    );
+    document
+        .borrow_mut()
+        .set_url(URL::pure_parse(String::from("https://example.com/old/page")).unwrap());
+
+    let refresh = refresh_directive(&document).unwrap();
+    assert_eq!(refresh.delay, 2);
+    assert_eq!(
+        refresh.url.unwrap().serialize(),
+        "https://example.com/moved"
+    );
+}
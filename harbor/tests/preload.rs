@@ -0,0 +1,279 @@
+use std::{
+    cell::RefCell,
+    io::{Read, Write},
+    net::TcpListener,
+    rc::Rc,
+    thread,
+};
+
+use harbor::{
+    css::colors::Color,
+    html5::{
+        self,
+        dom::{Document, Element, NodeKind},
+        preload::{SubresourceKind, collect_subresources, favicon_url, load_stylesheets},
+    },
+    http::{self, url::URL},
+    infra::{self, Serializable},
+};
+
+/// Serves each `(path, body)` pair as a stylesheet on a local port, answering anything else with
+/// a 404.
+fn serve_stylesheets(routes: &'static [(&'static str, &'static str)]) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+
+            let mut request = Vec::new();
+            let mut buffer = [0; 512];
+            while !request.ends_with(b"\r\n\r\n") {
+                let bytes_read = stream.read(&mut buffer).unwrap();
+                if bytes_read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..bytes_read]);
+            }
+
+            let request = String::from_utf8(request).unwrap();
+            let path = request.split(' ').nth(1).unwrap_or_default();
+
+            match routes.iter().find(|(route, _)| *route == path) {
+                Some((_, body)) => write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/css\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                ),
+                None => write!(
+                    stream,
+                    "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n"
+                ),
+            }
+            .unwrap();
+        }
+    });
+
+    port
+}
+
+/// Parses `html_content` as if it were served from `url`, and loads its stylesheets.
+fn load_document(html_content: &str, url: String) -> Rc<RefCell<Document>> {
+    let chars = html_content.chars().collect::<Vec<char>>();
+    let mut stream = infra::InputStream::new(&chars);
+    let mut parser = html5::parse::Parser::new(&mut stream);
+
+    parser.parse();
+
+    let document = Rc::clone(parser.document.document());
+    document.borrow_mut().set_url(URL::pure_parse(url).unwrap());
+
+    let mut client = http::Client::new(http::Protocol::HTTP1_1, true);
+    load_stylesheets(&document, &mut client);
+
+    document
+}
+
+/// Computes the styles of the document and returns its elements named `name`, in tree order.
+fn styled_elements(document: &Rc<RefCell<Document>>, name: &str) -> Vec<Rc<RefCell<Element>>> {
+    let html = match document.borrow()._node.borrow().child_nodes().item(1) {
+        Some(node) => match node.borrow().clone() {
+            NodeKind::Element(element) => element,
+            _ => panic!("Expected the html element"),
+        },
+        None => panic!("Expected the html element"),
+    };
+    html.borrow_mut().compute_element_styles(None);
+
+    let mut found = vec![];
+    find_elements(&html, name, &mut found);
+
+    found
+}
+
+fn find_elements(
+    element: &Rc<RefCell<Element>>,
+    name: &str,
+    found: &mut Vec<Rc<RefCell<Element>>>,
+) {
+    for child in element.borrow().children() {
+        if child.borrow().local_name == name {
+            found.push(Rc::clone(&child));
+        }
+        find_elements(&child, name, found);
+    }
+}
+
+#[test]
+fn test_collects_resolved_subresources() {
+    let html_content = r#"<!DOCTYPE html>
+<html>
+<head>
+    <link rel="stylesheet" href="css/site.css">
+    <link rel="icon" href="favicon.ico">
+    <style>@charset "utf-8"; @import url("print.css"); @layer base; @import "/theme.css";
+        p { color: red; } @import "late.css";</style>
+    <script src="https://cdn.example.org/lib.js"></script>
+</head>
+<body>
+    <img src="../images/logo.png">
+    <img alt="no source">
+</body>
+</html>"#;
+
+    let chars = html_content.chars().collect::<Vec<char>>();
+    let mut stream = infra::InputStream::new(&chars);
+    let mut parser = html5::parse::Parser::new(&mut stream);
+
+    parser.parse();
+
+    let document = parser.document.document();
+    document
+        .borrow_mut()
+        .set_url(URL::pure_parse("https://example.com/dir/page.html".to_string()).unwrap());
+
+    let subresources = collect_subresources(document)
+        .into_iter()
+        .map(|subresource| (subresource.kind, subresource.url.serialize()))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        subresources,
+        vec![
+            (
+                SubresourceKind::Stylesheet,
+                "https://example.com/dir/css/site.css".to_string()
+            ),
+            (
+                SubresourceKind::Import,
+                "https://example.com/dir/print.css".to_string()
+            ),
+            (
+                SubresourceKind::Import,
+                "https://example.com/theme.css".to_string()
+            ),
+            (
+                SubresourceKind::Script,
+                "https://cdn.example.org/lib.js".to_string()
+            ),
+            (
+                SubresourceKind::Image,
+                "https://example.com/images/logo.png".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_applies_external_stylesheet() {
+    let port = serve_stylesheets(&[(
+        "/css/site.css",
+        "p { color: red; } .inline { color: green; }",
+    )]);
+
+    let document = load_document(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <link rel="stylesheet" href="css/site.css">
+    <link rel="stylesheet" href="http://127.0.0.1:1/unreachable.css">
+    <style>.inline { color: blue; }</style>
+</head>
+<body>
+    <p>External</p>
+    <p class="inline">Inline</p>
+</body>
+</html>"#,
+        format!("http://127.0.0.1:{}/index.html", port),
+    );
+
+    // The sheet on a host that can't be reached is skipped
+    assert_eq!(document.borrow().style_sheets().length(), 2);
+
+    let paragraphs = styled_elements(&document, "p");
+
+    assert_eq!(paragraphs.len(), 2);
+    assert_eq!(
+        paragraphs[0].borrow().style().color,
+        Color::Named("red".to_string())
+    );
+    // The <style> element comes later in the cascade than the external sheet
+    assert_eq!(
+        paragraphs[1].borrow().style().color,
+        Color::Named("blue".to_string())
+    );
+}
+
+#[test]
+fn test_imported_rules_apply() {
+    let port = serve_stylesheets(&[
+        (
+            "/css/a.css",
+            "@import url(\"nested/b.css\"); p { color: red; }",
+        ),
+        (
+            "/css/nested/b.css",
+            "@import \"../a.css\"; p { color: green; } em { color: green; }",
+        ),
+    ]);
+
+    let document = load_document(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <link rel="stylesheet" href="css/a.css">
+</head>
+<body>
+    <p>Importing sheet</p>
+    <em>Imported sheet</em>
+</body>
+</html>"#,
+        format!("http://127.0.0.1:{}/index.html", port),
+    );
+
+    // The imported sheet is inlined rather than added alongside, and the cycle back to a.css is cut
+    assert_eq!(document.borrow().style_sheets().length(), 1);
+
+    // Rules of the importing sheet come after the imported ones
+    let paragraph = &styled_elements(&document, "p")[0];
+    assert_eq!(
+        paragraph.borrow().style().color,
+        Color::Named("red".to_string())
+    );
+
+    let emphasis = &styled_elements(&document, "em")[0];
+    assert_eq!(
+        emphasis.borrow().style().color,
+        Color::Named("green".to_string())
+    );
+}
+
+#[test]
+fn test_favicon_url() {
+    let page = URL::pure_parse("https://example.com/dir/page.html".to_string()).unwrap();
+
+    let document = harbor::parse_html(
+        r#"<html><head>
+    <link rel="icon" href="small.png" sizes="16x16">
+    <link rel="shortcut icon" href="large.png" sizes="32x32 64x64">
+    <link rel="icon" href="other.png" sizes="64x64">
+    <link rel="stylesheet" href="site.css">
+</head><body></body></html>"#,
+    );
+    document.borrow_mut().set_url(page.clone());
+
+    assert_eq!(
+        favicon_url(&document).unwrap().serialize(),
+        "https://example.com/dir/large.png"
+    );
+
+    let without_icon = harbor::parse_html("<html><head></head><body></body></html>");
+    without_icon.borrow_mut().set_url(page);
+
+    assert_eq!(
+        favicon_url(&without_icon).unwrap().serialize(),
+        "https://example.com/favicon.ico"
+    );
+}
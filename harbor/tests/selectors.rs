@@ -0,0 +1,200 @@
+use std::{cell::RefCell, ops::Deref, rc::Rc};
+
+use harbor::{
+    css::{
+        colors::Color,
+        selectors::{MatchesElement, parse_tokens_as_selector_list},
+        tokenize::tokenize,
+    },
+    html5::dom::{Document, Element, NodeKind},
+    infra::InputStream,
+};
+
+/// The `index`th element named `name` in `document`.
+fn nth_element(document: &Rc<RefCell<Document>>, name: &str, index: usize) -> Rc<RefCell<Element>> {
+    let elements = document.borrow().get_elements_by_tag_name(name);
+
+    match elements.item(index).unwrap().borrow().deref() {
+        NodeKind::Element(element) => Rc::clone(element),
+        _ => unreachable!(),
+    }
+}
+
+fn matches(raw_selector: &str, element: &Rc<RefCell<Element>>) -> bool {
+    let tokens = tokenize(&mut InputStream::new(
+        &raw_selector.chars().collect::<Vec<char>>()[..],
+    ));
+    let selector = parse_tokens_as_selector_list(tokens).unwrap();
+    let ancestors = Element::ancestors(element);
+
+    selector[0].matches(&element.borrow(), Some(&ancestors))
+}
+
+#[test]
+fn test_compound_selectors_check_every_subclass() {
+    let document =
+        harbor::parse_html("<html><body><p class=\"a b\" id=\"main\">x</p></body></html>");
+    let p = nth_element(&document, "p", 0);
+
+    assert!(matches("p.a.b", &p));
+    assert!(matches("p#main.b", &p));
+    assert!(!matches("p.c", &p));
+    assert!(!matches("div.a", &p));
+}
+
+#[test]
+fn test_checked_and_disabled() {
+    let document = harbor::parse_html(
+        "<html><body>\
+        <input type=checkbox checked><input type=checkbox><input type=text checked>\
+        <button disabled>a</button><button>b</button>\
+        <fieldset disabled><input></fieldset>\
+        </body></html>",
+    );
+
+    assert!(matches(
+        "input:checked",
+        &nth_element(&document, "input", 0)
+    ));
+    assert!(!matches(
+        "input:checked",
+        &nth_element(&document, "input", 1)
+    ));
+    assert!(!matches(
+        "input:checked",
+        &nth_element(&document, "input", 2)
+    ));
+
+    assert!(matches(":disabled", &nth_element(&document, "button", 0)));
+    assert!(!matches(":disabled", &nth_element(&document, "button", 1)));
+    assert!(matches(":enabled", &nth_element(&document, "button", 1)));
+    assert!(matches(
+        "input:disabled",
+        &nth_element(&document, "input", 3)
+    ));
+    assert!(!matches(":disabled", &nth_element(&document, "body", 0)));
+}
+
+#[test]
+fn test_of_type_pseudo_classes() {
+    let document = harbor::parse_html(
+        "<html><body><div><h1>t</h1><p>a</p><span>s</span><p>b</p></div>\
+        <ul><li>1</li><li>2</li><li>3</li><li>4</li><li>5</li></ul></body></html>",
+    );
+
+    let first_p = nth_element(&document, "p", 0);
+    let last_p = nth_element(&document, "p", 1);
+    assert!(matches("p:first-of-type", &first_p));
+    assert!(!matches("p:first-of-type", &last_p));
+    assert!(matches("p:last-of-type", &last_p));
+    assert!(matches(
+        "span:only-of-type",
+        &nth_element(&document, "span", 0)
+    ));
+    assert!(!matches("p:only-of-type", &first_p));
+
+    let li = |index| nth_element(&document, "li", index);
+    assert!(matches("li:nth-of-type(2)", &li(1)));
+    assert!(!matches("li:nth-of-type(2)", &li(2)));
+    assert!(matches("li:nth-of-type(odd)", &li(4)));
+    assert!(matches("li:nth-of-type(2n+1)", &li(2)));
+    assert!(!matches("li:nth-of-type(2n+1)", &li(3)));
+    assert!(matches("li:nth-of-type(2n-1)", &li(0)));
+    assert!(matches("li:nth-of-type(-n+2)", &li(1)));
+    assert!(!matches("li:nth-of-type(-n+2)", &li(2)));
+    assert!(matches("li:nth-last-of-type(1)", &li(4)));
+}
+
+#[test]
+fn test_of_type_rules_apply_while_computing_styles() {
+    let document = harbor::parse_html(
+        "<html><head><style>p:first-of-type { color: red }</style></head>\
+        <body><h1>t</h1><p>a</p><p>b</p></body></html>",
+    );
+
+    let html = nth_element(&document, "html", 0);
+    html.borrow_mut().compute_element_styles(None);
+
+    assert_eq!(
+        nth_element(&document, "p", 0).borrow().style().color,
+        Color::Named("red".to_string())
+    );
+    assert_eq!(
+        nth_element(&document, "p", 1).borrow().style().color,
+        Color::default()
+    );
+}
+
+#[test]
+fn test_unchanged_elements_reuse_their_cascade() {
+    let document = harbor::parse_html(
+        "<html><head><style>.hot { color: red }</style></head>\
+        <body><p>a</p><p>b</p></body></html>",
+    );
+
+    let html = nth_element(&document, "html", 0);
+    let first = nth_element(&document, "p", 0);
+    let second = nth_element(&document, "p", 1);
+
+    html.borrow_mut().compute_element_styles(None);
+    let generations = (
+        first.borrow().style_generation(),
+        second.borrow().style_generation(),
+    );
+
+    html.borrow_mut().compute_element_styles(None);
+    assert_eq!(first.borrow().style_generation(), generations.0);
+    assert_eq!(second.borrow().style_generation(), generations.1);
+
+    Element::set_attribute(&first, "class", "hot");
+    html.borrow_mut().compute_element_styles(None);
+
+    assert_eq!(first.borrow().style_generation(), generations.0 + 1);
+    assert_eq!(second.borrow().style_generation(), generations.1);
+    assert_eq!(
+        first.borrow().style().color,
+        Color::Named("red".to_string())
+    );
+}
+
+#[test]
+fn test_not_pseudo_class() {
+    let document = harbor::parse_html(
+        "<html><body><a href=\"/home\">home</a>\
+        <a class=\"external nav\" href=\"https://elsewhere.example\">away</a>\
+        <button disabled>x</button><button>y</button></body></html>",
+    );
+
+    let internal = nth_element(&document, "a", 0);
+    let external = nth_element(&document, "a", 1);
+    assert!(matches("a:not(.external)", &internal));
+    assert!(!matches("a:not(.external)", &external));
+    assert!(matches("a:not(.active)", &external));
+    assert!(!matches("a:not([href^=\"https:\"], .nav)", &external));
+
+    assert!(!matches(
+        "button:not([disabled])",
+        &nth_element(&document, "button", 0)
+    ));
+    assert!(matches(
+        "button:not([disabled])",
+        &nth_element(&document, "button", 1)
+    ));
+}
+
+#[test]
+fn test_attribute_selectors() {
+    let document = harbor::parse_html(
+        "<html><body><p lang=\"en-US\" title=\"Hello World\" data-x=\"\">x</p></body></html>",
+    );
+    let p = nth_element(&document, "p", 0);
+
+    assert!(matches("p[data-x]", &p));
+    assert!(!matches("p[data-y]", &p));
+    assert!(matches("[lang|=en]", &p));
+    assert!(matches("[title~=World]", &p));
+    assert!(!matches("[title~=world]", &p));
+    assert!(matches("[title~=world i]", &p));
+    assert!(matches("[title^=Hell][title$=rld][title*=\"o W\"]", &p));
+    assert!(!matches("[data-x^=\"\"]", &p));
+}
@@ -0,0 +1,504 @@
+use std::{cell::RefCell, ops::Deref, rc::Rc};
+
+use harbor::{
+    css::{
+        r#box::handle_declaration,
+        colors::Color,
+        cssom::ComputedStyle,
+        parser::{parse_css_declaration_block, parse_stylesheet},
+        properties::{
+            BackgroundSize, CSSParseable, Cursor, Display, Float, FontStyle, Image, Length,
+            LengthContext, LineHeight, Origin, OutlineStyle, Position, RepeatStyle, VerticalAlign,
+            WidthValue,
+        },
+        registry,
+        tokenize::tokenize,
+    },
+    html5::dom::{Document, Element, NodeKind},
+    http::url::URL,
+    infra::InputStream,
+};
+
+/// The styles of the first element named `name` once the whole document has been styled.
+fn style_of(document: &Rc<RefCell<Document>>, name: &str) -> ComputedStyle {
+    let element_of = |name: &str| -> Rc<RefCell<Element>> {
+        let elements = document.borrow().get_elements_by_tag_name(name);
+
+        match elements.item(0).unwrap().borrow().deref() {
+            NodeKind::Element(element) => Rc::clone(element),
+            _ => unreachable!(),
+        }
+    };
+
+    element_of("html").borrow_mut().compute_element_styles(None);
+
+    element_of(name).borrow().style().clone()
+}
+
+#[test]
+fn test_initial_values() {
+    let style = ComputedStyle::default();
+
+    assert_eq!(style.color, Color::Named("black".to_string()));
+    assert_eq!(style.background.color().used()[3], 0.0);
+    assert_eq!(style.display, Display::Inline);
+    assert!(matches!(style.position, Position::Static));
+    assert_eq!(style.float, Float::None);
+    assert_eq!(style.vertical_align, VerticalAlign::Baseline);
+    assert!(matches!(style.width, WidthValue::Auto));
+    assert_eq!(
+        style
+            .margin
+            .to_edges(&LengthContext {
+                container_width: 800.0,
+                ..Default::default()
+            })
+            .horizontal(),
+        0.0
+    );
+
+    let mut font = style.font.clone();
+    assert_eq!(font.resolve_font_size(&vec![]), Some(16.0));
+}
+
+#[test]
+fn test_css_wide_keywords() {
+    let document = harbor::parse_html(
+        "<html><head><style>\
+        body { color: red; display: block; font-size: 20px; background-image: url(\"a.png\") }\
+        p { color: blue; display: initial; background-image: url(\"b.png\"); \
+        background-image: initial }\
+        .inherit { color: inherit; font-size: 2em; font-size: inherit; \
+        background-image: inherit }\
+        .unset { color: unset; display: unset }\
+        </style></head><body><p>a</p><em class=inherit>b</em><div class=unset>c</div>\
+        </body></html>",
+    );
+
+    let p = style_of(&document, "p");
+    assert_eq!(p.color, Color::Named("blue".to_string()));
+    assert_eq!(p.display, Display::Inline);
+    assert!(
+        p.background
+            .layers
+            .iter()
+            .all(|layer| matches!(layer.image, Image::None))
+    );
+
+    let em = style_of(&document, "em");
+    assert_eq!(em.color, Color::Named("red".to_string()));
+    assert_eq!(em.font.resolved_font_size(), Some(20.0));
+    assert!(matches!(
+        em.background.layers.last().unwrap().image,
+        Image::FromUrl(ref url) if url == "a.png"
+    ));
+
+    let div = style_of(&document, "div");
+    assert_eq!(div.color, Color::Named("red".to_string()));
+    assert_eq!(div.display, Display::Inline);
+}
+
+#[test]
+fn test_later_declarations_win_between_shorthands_and_longhands() {
+    let margin_of = |css: &str| {
+        let document = harbor::parse_html(&format!(
+            "<html><head><style>{css}</style></head><body><p>a</p></body></html>"
+        ));
+        let edges = style_of(&document, "p").margin.to_edges(&LengthContext {
+            container_width: 800.0,
+            ..Default::default()
+        });
+
+        (edges.0, edges.1, edges.2, edges.3)
+    };
+
+    assert_eq!(
+        margin_of("p { margin: 10px; margin-top: 20px }"),
+        (20.0, 10.0, 10.0, 10.0)
+    );
+    assert_eq!(
+        margin_of("p { margin-top: 20px; margin: 10px }"),
+        (10.0, 10.0, 10.0, 10.0)
+    );
+
+    assert_eq!(
+        margin_of("p { margin: 10px } p { margin-left: 5px }"),
+        (10.0, 10.0, 10.0, 5.0)
+    );
+    assert_eq!(
+        margin_of("p { margin-left: 5px } p { margin: 10px }"),
+        (10.0, 10.0, 10.0, 10.0)
+    );
+}
+
+#[test]
+fn test_every_registered_property_applies() {
+    let mut names = registry::property_names().collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "background",
+            "background-color",
+            "background-image",
+            "background-origin",
+            "background-position",
+            "background-repeat",
+            "background-size",
+            "border",
+            "border-bottom",
+            "border-left",
+            "border-right",
+            "border-style",
+            "border-top",
+            "border-width",
+            "color",
+            "cursor",
+            "display",
+            "float",
+            "font",
+            "font-family",
+            "font-size",
+            "font-style",
+            "font-weight",
+            "line-height",
+            "margin",
+            "margin-bottom",
+            "margin-left",
+            "margin-right",
+            "margin-top",
+            "outline",
+            "outline-color",
+            "outline-offset",
+            "outline-style",
+            "outline-width",
+            "padding",
+            "padding-bottom",
+            "padding-left",
+            "padding-right",
+            "padding-top",
+            "position",
+            "text-decoration",
+            "text-decoration-color",
+            "text-decoration-line",
+            "vertical-align",
+            "width",
+        ]
+    );
+
+    let apply = |css: &str| {
+        let mut style = ComputedStyle::default();
+        for declaration in parse_css_declaration_block(css.to_string()) {
+            assert!(handle_declaration(&declaration, &mut style, None));
+        }
+        style
+    };
+
+    let style = apply(
+        "width: 50px; margin: 1px 2px; margin-top: 3px; margin-right: 4px; \
+        margin-bottom: 5px; margin-left: 6px; color: red; display: block; float: left; \
+        position: absolute; vertical-align: middle",
+    );
+    assert_eq!(style.color, Color::Named("red".to_string()));
+    assert_eq!(style.display, Display::Block);
+    assert_eq!(style.float, Float::Left);
+    assert!(matches!(style.position, Position::Absolute));
+    assert_eq!(style.vertical_align, VerticalAlign::Middle);
+    assert!(matches!(style.width, WidthValue::Length(Length::Px(50.0))));
+
+    let margin = style.margin.to_edges(&LengthContext {
+        container_width: 800.0,
+        ..Default::default()
+    });
+    assert_eq!(
+        (margin.0, margin.1, margin.2, margin.3),
+        (3.0, 4.0, 5.0, 6.0)
+    );
+
+    let style = apply("font: 12px serif; color: red");
+    assert_eq!(style.font.family().entries[0].value(), "serif");
+    assert_eq!(style.font.resolved_font_size(), Some(12.0));
+
+    let style = apply(
+        "font-family: monospace; font-size: 20px; font-weight: bold; font-style: italic; \
+        line-height: 1.5",
+    );
+    assert_eq!(style.font.family().entries[0].value(), "monospace");
+    assert_eq!(style.font.resolved_font_size(), Some(20.0));
+    assert_eq!(style.font.resolved_font_weight(), Some(700));
+    assert!(matches!(style.font.style(), FontStyle::Italic));
+    assert!(matches!(style.font.line_height(), LineHeight::Number(1.5)));
+
+    let style = apply(
+        "background: blue; background-color: green; background-image: url(\"a.png\"); \
+        background-repeat: no-repeat; background-position: 0 0; \
+        background-origin: content-box",
+    );
+    let layer = style.background.layers.last().unwrap();
+    assert_eq!(layer.color, Color::Named("green".to_string()));
+    assert!(matches!(layer.image, Image::FromUrl(ref url) if url == "a.png"));
+    assert!(matches!(layer.repeat_style, RepeatStyle::NoRepeat));
+    assert!(matches!(layer.origin, Origin::ContentBox));
+
+    let context = LengthContext::default();
+    let style = apply("outline: thin dashed; outline-width: thick; outline-color: blue");
+    assert_eq!(style.outline.style, OutlineStyle::Dashed);
+    assert_eq!(style.outline.resolved_width(&context), 5.0);
+    assert_eq!(style.outline.color, Some(Color::Named("blue".to_string())));
+
+    let style = apply("outline-style: solid; outline-offset: 1em; outline-color: currentColor");
+    assert_eq!(style.outline.resolved_offset(&context), 16.0);
+    assert_eq!(style.outline.resolved_width(&context), 3.0);
+
+    // Absolute units are converted, and line widths don't take percentages
+    let style = apply("outline: 3pt solid; outline-offset: -0.5in; outline-width: 10%");
+    assert_eq!(style.outline.resolved_width(&context), 4.0);
+    assert_eq!(style.outline.resolved_offset(&context), -48.0);
+    assert_eq!(style.outline.color, None);
+
+    let style = apply("text-decoration: underline dotted red; text-decoration-line: overline");
+    assert!(style.text_decoration.line.overline && !style.text_decoration.line.underline);
+    assert_eq!(
+        style.text_decoration.color,
+        Some(Color::Named("red".to_string()))
+    );
+
+    let style = apply("text-decoration-line: underline line-through; text-decoration-color: blue");
+    assert!(style.text_decoration.line.underline && style.text_decoration.line.line_through);
+    assert_eq!(
+        style.text_decoration.color,
+        Some(Color::Named("blue".to_string()))
+    );
+}
+
+#[test]
+fn test_unknown_properties_are_reported() {
+    let mut style = ComputedStyle::default();
+
+    for declaration in parse_css_declaration_block("colour: red; color: red".to_string()) {
+        let supported = handle_declaration(&declaration, &mut style, None);
+        assert_eq!(supported, declaration.property_name == "color");
+    }
+
+    assert_eq!(style.color, Color::Named("red".to_string()));
+}
+
+#[test]
+fn test_background_size() {
+    let style = style_of(
+        &harbor::parse_html(
+            "<html><head><style>p { background-size: cover }</style></head>\
+            <body><p>a</p></body></html>",
+        ),
+        "p",
+    );
+    let size = &style.background.layers[0].size;
+    assert!(matches!(size, BackgroundSize::Cover));
+
+    // A 100x50 image covering a 300x300 box is scaled up until its height fits
+    assert_eq!(
+        size.draw_size((100.0, 50.0), (300.0, 300.0)),
+        (600.0, 300.0)
+    );
+    assert_eq!(
+        BackgroundSize::Contain.draw_size((100.0, 50.0), (300.0, 300.0)),
+        (300.0, 150.0)
+    );
+
+    let sizes = |css: &str| {
+        let declaration = parse_css_declaration_block(format!("background-size: {css};")).remove(0);
+        let mut style = ComputedStyle::default();
+        handle_declaration(&declaration, &mut style, None);
+
+        style
+            .background
+            .layers
+            .iter()
+            .map(|layer| layer.size.draw_size((100.0, 50.0), (300.0, 300.0)))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(sizes("50px"), [(50.0, 25.0)]);
+    assert_eq!(sizes("auto 100px"), [(200.0, 100.0)]);
+    assert_eq!(sizes("50% 10px, auto"), [(150.0, 10.0), (100.0, 50.0)]);
+    assert_eq!(sizes("contain, cover"), [(300.0, 150.0), (600.0, 300.0)]);
+}
+
+#[test]
+fn test_cursor() {
+    let document = harbor::parse_html(
+        "<html><head><style>a { cursor: pointer } p { cursor: url(\"hand.cur\"), text } \
+        em { cursor: none }</style></head><body><a>link <b>bold</b></a><p>text</p><em>hidden</em>\
+        </body></html>",
+    );
+
+    assert_eq!(style_of(&document, "a").cursor, Cursor::Pointer);
+    assert_eq!(style_of(&document, "b").cursor, Cursor::Pointer);
+    assert_eq!(style_of(&document, "p").cursor, Cursor::Text);
+    assert_eq!(style_of(&document, "em").cursor, Cursor::None);
+    assert_eq!(style_of(&document, "body").cursor, Cursor::Auto);
+}
+
+#[test]
+fn test_link_colors_follow_the_history() {
+    let document = harbor::parse_html(
+        "<html><head></head><body><a href=/seen>seen</a><p><a href=/new>new</a></p>\
+        <em><a>placeholder</a></em></body></html>",
+    );
+    document
+        .borrow_mut()
+        .set_url(URL::pure_parse("https://example.com/".to_string()).unwrap());
+    document
+        .borrow_mut()
+        .mark_visited(&URL::pure_parse("https://example.com/seen".to_string()).unwrap());
+
+    let ua = include_str!("../../res/css/ua.css")
+        .chars()
+        .collect::<Vec<char>>();
+    let stylesheet = parse_stylesheet(
+        &mut InputStream::new(&tokenize(&mut InputStream::new(&ua))),
+        Rc::downgrade(&document),
+        None,
+    );
+    document.borrow_mut().insert_stylesheet(0, stylesheet);
+
+    // Styles the whole document
+    style_of(&document, "html");
+
+    let links = document.borrow().get_elements_by_tag_name("a");
+    let style_at = |index: usize| match links.item(index).unwrap().borrow().deref() {
+        NodeKind::Element(element) => element.borrow().style().clone(),
+        _ => unreachable!(),
+    };
+
+    let visited = style_at(0);
+    assert_eq!(visited.color, Color::SystemNamed("VisitedText".to_string()));
+    assert!(visited.text_decoration.line.underline);
+
+    let unvisited = style_at(1);
+    assert_eq!(unvisited.color, Color::SystemNamed("LinkText".to_string()));
+    assert!(unvisited.text_decoration.line.underline);
+    assert_eq!(unvisited.cursor, Cursor::Pointer);
+
+    // Without an href it isn't a link at all
+    let placeholder = style_at(2);
+    assert_eq!(placeholder.color, Color::default());
+    assert!(!placeholder.text_decoration.line.underline);
+}
+
+#[test]
+fn test_visited_links_resolve_against_base_href() {
+    let document = harbor::parse_html(
+        "<html><head><base href=\"https://cdn.example/assets/\"></head>\
+        <body><a href=seen>seen</a></body></html>",
+    );
+    document
+        .borrow_mut()
+        .set_url(URL::pure_parse("https://example.com/".to_string()).unwrap());
+    document
+        .borrow_mut()
+        .mark_visited(&URL::pure_parse("https://cdn.example/assets/seen".to_string()).unwrap());
+
+    let ua = include_str!("../../res/css/ua.css")
+        .chars()
+        .collect::<Vec<char>>();
+    let stylesheet = parse_stylesheet(
+        &mut InputStream::new(&tokenize(&mut InputStream::new(&ua))),
+        Rc::downgrade(&document),
+        None,
+    );
+    document.borrow_mut().insert_stylesheet(0, stylesheet);
+
+    assert_eq!(
+        style_of(&document, "a").color,
+        Color::SystemNamed("VisitedText".to_string())
+    );
+}
+
+#[test]
+fn test_lengths_resolve_against_their_context() {
+    let context = LengthContext {
+        container_width: 600.0,
+        font_size: 20.0,
+        root_font_size: 10.0,
+    };
+
+    assert_eq!(Length::Px(12.0).resolve(&context), 12.0);
+    assert_eq!(Length::Percent(25.0).resolve(&context), 150.0);
+    assert_eq!(Length::Em(1.5).resolve(&context), 30.0);
+    assert_eq!(Length::Rem(2.0).resolve(&context), 20.0);
+
+    let parse = |css: &str| {
+        let declaration = &parse_css_declaration_block(format!("width: {css}"))[0];
+        Length::from_cv(&mut InputStream::new(&declaration.value))
+    };
+    assert_eq!(parse("50%"), Some(Length::Percent(50.0)));
+    assert_eq!(parse("2em"), Some(Length::Em(2.0)));
+    assert_eq!(parse("1rem"), Some(Length::Rem(1.0)));
+    assert_eq!(parse("1in"), Some(Length::Px(96.0)));
+    assert_eq!(parse("0"), Some(Length::Px(0.0)));
+    assert_eq!(parse("3vw"), None);
+}
+
+#[test]
+fn test_padding_and_border_declarations() {
+    let apply = |css: &str| {
+        let mut style = ComputedStyle::default();
+        for declaration in parse_css_declaration_block(css.to_string()) {
+            assert!(handle_declaration(&declaration, &mut style, None));
+        }
+        style
+    };
+    let context = LengthContext {
+        container_width: 800.0,
+        ..Default::default()
+    };
+
+    let padding = apply("padding: 10px 20px").padding.to_edges(&context);
+    assert_eq!(
+        (padding.0, padding.1, padding.2, padding.3),
+        (10.0, 20.0, 10.0, 20.0)
+    );
+
+    let padding = apply("padding: 1px 2px 3px; padding-left: 10%")
+        .padding
+        .to_edges(&context);
+    assert_eq!(
+        (padding.0, padding.1, padding.2, padding.3),
+        (1.0, 2.0, 3.0, 80.0)
+    );
+
+    // Negative padding is invalid, so the declaration is dropped
+    let padding = apply("padding: -5px").padding.to_edges(&context);
+    assert!(padding.is_none());
+
+    // One invalid value drops the whole declaration, not just the sides after it
+    let padding = apply("padding: 10px -5px").padding.to_edges(&context);
+    assert!(padding.is_none());
+
+    let padding = apply("padding-top: 4px; padding-top: 8px solid")
+        .padding
+        .to_edges(&context);
+    assert_eq!(
+        (padding.0, padding.1, padding.2, padding.3),
+        (4.0, 0.0, 0.0, 0.0)
+    );
+
+    let border = apply("border: 2px solid red").border.to_edges(&context);
+    assert_eq!(
+        (border.0, border.1, border.2, border.3),
+        (2.0, 2.0, 2.0, 2.0)
+    );
+
+    // Without a style there is no border, whatever its width
+    let border = apply("border-width: 4px").border.to_edges(&context);
+    assert!(border.is_none());
+
+    let border = apply("border-style: solid; border-width: 1px 2px 3px 4px; border-left: none")
+        .border
+        .to_edges(&context);
+    assert_eq!(
+        (border.0, border.1, border.2, border.3),
+        (1.0, 2.0, 3.0, 0.0)
+    );
+}
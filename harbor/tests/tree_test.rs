@@ -1,7 +1,10 @@
+use std::cell::RefCell;
 use std::ops::Deref;
+use std::rc::Rc;
 
 use harbor::html5;
-use harbor::infra;
+use harbor::html5::parse::ScriptMode;
+use harbor::infra::{self, Serializable};
 
 mod common;
 
@@ -181,3 +184,593 @@ fn test_css002() {
         },
     );
 }
+
+fn noscript_children(scripting: bool) -> (Vec<String>, String) {
+    let html_content =
+        "<!DOCTYPE html><html><head></head><body><noscript><p>x</p></noscript></body></html>";
+
+    let chars = html_content.chars().collect::<Vec<char>>();
+    let mut stream = infra::InputStream::new(&chars);
+    let mut parser = html5::parse::Parser::new(&mut stream).with_scripting(scripting);
+
+    parser.parse();
+
+    let noscript = parser
+        .document
+        .get_elements_by_tag_name("noscript")
+        .pop()
+        .expect("Document should contain a noscript element");
+    let noscript = noscript.borrow();
+
+    let children = noscript
+        .children()
+        .iter()
+        .map(|child| child.borrow().local_name.clone())
+        .collect();
+
+    let text = noscript
+        .child_nodes()
+        .iter()
+        .filter_map(|child| match child.borrow().deref() {
+            html5::dom::NodeKind::Text(text) => Some(text.borrow().data().to_string()),
+            _ => None,
+        })
+        .collect();
+
+    (children, text)
+}
+
+#[test]
+fn test_noscript_without_scripting() {
+    let (children, text) = noscript_children(false);
+
+    assert_eq!(children, vec!["p".to_string()]);
+    assert!(text.is_empty());
+}
+
+#[test]
+fn test_noscript_with_scripting() {
+    let (children, text) = noscript_children(true);
+
+    assert!(children.is_empty());
+    assert_eq!(text, "<p>x</p>");
+}
+
+#[test]
+fn test_script_is_closed_and_parsing_continues() {
+    let html_content = r#"<!DOCTYPE html>
+<html>
+<head>
+    <script>var x=1;</script>
+</head>
+<body>
+    <script>if (a < b && c > d) { x = "</p>"; }</script>
+    <p>after</p>
+</body>
+</html>"#;
+
+    let chars = html_content.chars().collect::<Vec<char>>();
+    let mut stream = infra::InputStream::new(&chars);
+    let mut parser = html5::parse::Parser::new(&mut stream);
+
+    parser.parse();
+
+    assert_eq!(
+        parser
+            .scripts()
+            .iter()
+            .map(|script| script.source.clone())
+            .collect::<Vec<String>>(),
+        vec![
+            "var x=1;".to_string(),
+            r#"if (a < b && c > d) { x = "</p>"; }"#.to_string()
+        ]
+    );
+
+    let scripts = parser.document.get_elements_by_tag_name("script");
+    assert_eq!(scripts.len(), 2);
+    assert_eq!(scripts[0].borrow().inner_html(), "var x=1;");
+    assert_eq!(
+        scripts[1].borrow().inner_html(),
+        r#"if (a < b && c > d) { x = "</p>"; }"#
+    );
+
+    common::verify_element_structure(
+        parser.document.document().borrow().deref(),
+        common::ElementStructure {
+            tag_name: "html".to_string(),
+            attributes: vec![],
+            children: vec![
+                common::ElementStructure {
+                    tag_name: "head".to_string(),
+                    attributes: vec![],
+                    children: vec![common::ElementStructure {
+                        tag_name: "script".to_string(),
+                        attributes: vec![],
+                        children: vec![],
+                    }],
+                },
+                common::ElementStructure {
+                    tag_name: "body".to_string(),
+                    attributes: vec![],
+                    children: vec![
+                        common::ElementStructure {
+                            tag_name: "script".to_string(),
+                            attributes: vec![],
+                            children: vec![],
+                        },
+                        common::ElementStructure {
+                            tag_name: "p".to_string(),
+                            attributes: vec![],
+                            children: vec![],
+                        },
+                    ],
+                },
+            ],
+        },
+    );
+}
+
+struct MockScriptRunner {
+    received: Rc<RefCell<Vec<(String, String)>>>,
+}
+
+impl html5::parse::ScriptRunner for MockScriptRunner {
+    fn run(&mut self, src: &str, element: &html5::dom::Element) {
+        self.received
+            .borrow_mut()
+            .push((src.to_string(), element.local_name.clone()));
+    }
+}
+
+#[test]
+fn test_script_runner_receives_source() {
+    let html_content = "<!DOCTYPE html><html><head><script>first()</script></head><body><script>second()</script></body></html>";
+
+    let received = Rc::new(RefCell::new(vec![]));
+
+    let chars = html_content.chars().collect::<Vec<char>>();
+    let mut stream = infra::InputStream::new(&chars);
+    let mut parser =
+        html5::parse::Parser::new(&mut stream).with_script_runner(Box::new(MockScriptRunner {
+            received: Rc::clone(&received),
+        }));
+
+    parser.parse();
+
+    assert_eq!(
+        *received.borrow(),
+        vec![
+            ("first()".to_string(), "script".to_string()),
+            ("second()".to_string(), "script".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_script_modes_are_bucketed() {
+    let html_content = r#"<!DOCTYPE html>
+<html>
+<head>
+    <script src="blocking.js"></script>
+    <script defer src="first-deferred.js"></script>
+    <script async src="async.js"></script>
+    <script defer>inline()</script>
+    <script type="module">module()</script>
+    <script type="text/template"><p>not a script</p></script>
+</head>
+<body>
+    <script defer src="second-deferred.js"></script>
+</body>
+</html>"#;
+
+    let chars = html_content.chars().collect::<Vec<char>>();
+    let mut stream = infra::InputStream::new(&chars);
+    let mut parser = html5::parse::Parser::new(&mut stream);
+
+    parser.parse();
+
+    let describe = |mode: ScriptMode| {
+        parser
+            .scripts_with_mode(mode)
+            .iter()
+            .map(|script| script.src.clone().unwrap_or(script.source.clone()))
+            .collect::<Vec<String>>()
+    };
+
+    assert_eq!(parser.scripts().len(), 6);
+    assert_eq!(describe(ScriptMode::ParserBlocking), vec!["blocking.js"]);
+    assert_eq!(
+        describe(ScriptMode::Defer),
+        vec!["first-deferred.js", "module()", "second-deferred.js"]
+    );
+    assert_eq!(describe(ScriptMode::Async), vec!["async.js"]);
+    assert_eq!(describe(ScriptMode::Inline), vec!["inline()"]);
+}
+
+fn parsed_body(body: &str) -> String {
+    let document = harbor::parse_html(&format!("<!DOCTYPE html><html><head></head><body>{body}"));
+    let html = document.borrow().serialize();
+
+    html.split_once("<body>")
+        .and_then(|(_, rest)| rest.split_once("</body>"))
+        .map(|(body, _)| body.to_string())
+        .expect("Document should have a body")
+}
+
+#[test]
+fn test_adoption_agency_misnested_formatting_elements() {
+    assert_eq!(parsed_body("<b>1<i>2</b>3</i>"), "<b>1<i>2</i></b><i>3</i>");
+}
+
+#[test]
+fn test_adoption_agency_formatting_element_around_block() {
+    assert_eq!(parsed_body("<b>1<p>2</b>3</p>"), "<b>1</b><p><b>2</b>3</p>");
+}
+
+#[test]
+fn test_adoption_agency_nested_anchors() {
+    assert_eq!(
+        parsed_body("<a href=\"x\">1<p>2<a href=\"y\">3</a></p>"),
+        "<a href=\"x\">1</a><p><a href=\"x\">2</a><a href=\"y\">3</a></p>"
+    );
+}
+
+#[test]
+fn test_adoption_agency_unmatched_end_tag_is_ignored() {
+    assert_eq!(parsed_body("<p>1</b>2</p>"), "<p>12</p>");
+}
+
+fn first_element(html: &str, tag_name: &str) -> Rc<RefCell<html5::dom::Element>> {
+    let document = harbor::parse_html(html);
+    let document = document.borrow();
+    let node = document
+        .get_elements_by_tag_name(tag_name)
+        .item(0)
+        .cloned()
+        .expect("Document should contain the element");
+
+    match node.borrow().deref() {
+        html5::dom::NodeKind::Element(element) => Rc::clone(element),
+        _ => unreachable!(),
+    }
+}
+
+fn text_of(element: &Rc<RefCell<html5::dom::Element>>) -> String {
+    element
+        .borrow()
+        .child_nodes()
+        .iter()
+        .filter_map(|child| match child.borrow().deref() {
+            html5::dom::NodeKind::Text(text) => Some(text.borrow().data().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_named_character_references_in_data() {
+    let p = first_element(
+        "<p>&amp;&nbsp;&copy; &notin; &notit; &amp &ampx &unknown; &</p>",
+        "p",
+    );
+
+    assert_eq!(
+        text_of(&p),
+        "&\u{a0}\u{a9} \u{2209} \u{ac}it; & &x &unknown; &"
+    );
+}
+
+#[test]
+fn test_named_character_references_in_attributes() {
+    let a = first_element(
+        "<a title=\"&amp;&copy;\" href=\"?a=1&copy=2&amp;b\" rel=\"x &not y\" id=&lt>",
+        "a",
+    );
+    let a = a.borrow();
+
+    assert_eq!(a.get_attribute("title"), Some("&\u{a9}"));
+    assert_eq!(a.get_attribute("href"), Some("?a=1&copy=2&b"));
+    assert_eq!(a.get_attribute("rel"), Some("x \u{ac} y"));
+    assert_eq!(a.get_attribute("id"), Some("<"));
+}
+
+#[test]
+fn test_tag_split_across_fed_input() {
+    let mut stream = infra::InputStream::streaming();
+    let mut parser = html5::parse::Parser::new(&mut stream);
+
+    parser.feed("<!DOCTYPE html><html><body><di");
+    assert!(!parser.emitted_tokens.iter().any(|token| matches!(
+        token,
+        html5::parse::Token::StartTag(tag) if tag.name.starts_with("di")
+    )));
+
+    parser.feed("v class=\"greeting\">Hi</div>");
+    parser.close_input();
+
+    let divs = parser
+        .emitted_tokens
+        .iter()
+        .filter_map(|token| match token {
+            html5::parse::Token::StartTag(tag) if tag.name.starts_with("di") => Some(tag),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(divs.len(), 1);
+    assert_eq!(divs[0].name, "div");
+    assert_eq!(
+        divs[0].attributes,
+        vec![("class".to_string(), "greeting".to_string())]
+    );
+    assert!(
+        parser
+            .emitted_tokens
+            .iter()
+            .any(|token| matches!(token, html5::parse::Token::EOF))
+    );
+    assert_eq!(parser.document.get_elements_by_tag_name("div").len(), 1);
+}
+
+#[test]
+fn test_finishing_pops_every_open_element() {
+    let mut stream = infra::InputStream::streaming();
+    let mut parser = html5::parse::Parser::new(&mut stream);
+
+    parser.feed("<!DOCTYPE html><div><p>unclosed");
+    assert!(!parser.open_elements().is_empty());
+
+    parser.finish();
+
+    assert!(parser.open_elements().is_empty());
+    assert!(matches!(
+        parser.emitted_tokens.last(),
+        Some(html5::parse::Token::EOF)
+    ));
+    assert_eq!(
+        parser
+            .emitted_tokens
+            .iter()
+            .filter(|token| matches!(token, html5::parse::Token::EOF))
+            .count(),
+        1
+    );
+    assert_eq!(parser.document.get_elements_by_tag_name("p").len(), 1);
+
+    parser.finish();
+    assert!(parser.open_elements().is_empty());
+}
+
+#[test]
+fn test_parse_errors_are_counted() {
+    let parse = |html: &str| {
+        let chars = html.chars().collect::<Vec<char>>();
+        let mut stream = infra::InputStream::new(&chars);
+        let mut parser = html5::parse::Parser::new(&mut stream);
+        parser.parse();
+
+        (parser.parse_errors(), parser.is_well_formed())
+    };
+
+    assert_eq!(
+        parse(
+            "<!DOCTYPE html><html><head><title>t</title></head>\
+            <body><p class=\"a\">hi</p></body></html>"
+        ),
+        (0, true)
+    );
+
+    // A missing DOCTYPE, a stray end tag and a null character, which both the tokenizer and the
+    // tree builder report
+    let (errors, well_formed) = parse("<p>a</b>\u{0}</p>");
+    assert_eq!(errors, 4);
+    assert!(!well_formed);
+}
+
+#[test]
+fn test_table_gets_an_implied_tbody() {
+    assert_eq!(
+        parsed_body("<table><tr><td>x</td></tr></table>"),
+        "<table><tbody><tr><td>x</td></tr></tbody></table>"
+    );
+    assert_eq!(
+        parsed_body("<table><td>1<td>2</table>after"),
+        "<table><tbody><tr><td>1</td><td>2</td></tr></tbody></table>after"
+    );
+}
+
+#[test]
+fn test_table_captions_and_column_groups() {
+    assert_eq!(
+        parsed_body("<table><caption>c</caption><col><thead><tr><th>h</table>"),
+        "<table><caption>c</caption><colgroup><col></colgroup>\
+        <thead><tr><th>h</th></tr></thead></table>"
+    );
+}
+
+#[test]
+fn test_misplaced_table_content_is_foster_parented() {
+    assert_eq!(
+        parsed_body("<table> a<b>bold</b><tr> <td>x</td></tr></table>"),
+        " a<b>bold</b><table><tbody><tr> <td>x</td></tr></tbody></table>"
+    );
+}
+
+fn character_tokens(parser: &html5::parse::Parser) -> String {
+    parser
+        .emitted_tokens
+        .iter()
+        .filter_map(|token| match token {
+            html5::parse::Token::Character(ch) => Some(*ch),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_newlines_are_normalized() {
+    let chars = "<p>a\r\nb\rc\r\r\nd</p>".chars().collect::<Vec<char>>();
+    let mut stream = infra::InputStream::new(&chars);
+    let mut parser = html5::parse::Parser::new(&mut stream);
+
+    parser.parse();
+
+    assert_eq!(character_tokens(&parser), "a\nb\nc\n\nd");
+}
+
+#[test]
+fn test_newlines_are_normalized_across_fed_input() {
+    let mut stream = infra::InputStream::streaming();
+    let mut parser = html5::parse::Parser::new(&mut stream);
+
+    parser.feed("<p>a\r");
+    parser.feed("\nb\r");
+    parser.feed("c\r");
+    parser.close_input();
+
+    assert_eq!(character_tokens(&parser), "a\nb\nc\n");
+}
+
+#[test]
+fn test_numeric_character_references() {
+    let p = first_element(
+        "<p>&#65;&#x41;&#X61;&#0;&#xD800;&#x110000;&#128512;&#128;&#x10FFFF;&#99999999999;&#65x</p>",
+        "p",
+    );
+
+    assert_eq!(
+        text_of(&p),
+        "AAa\u{fffd}\u{fffd}\u{fffd}\u{1f600}\u{20ac}\u{10ffff}\u{fffd}Ax"
+    );
+}
+
+#[test]
+fn test_list_items_close_open_list_items() {
+    assert_eq!(
+        parsed_body("<ul><li>a<li>b</ul>"),
+        "<ul><li>a</li><li>b</li></ul>"
+    );
+    assert_eq!(
+        parsed_body("<ol><li><div>a<li>b</ol>"),
+        "<ol><li><div>a</div></li><li>b</li></ol>"
+    );
+    assert_eq!(
+        parsed_body("<ul><li><ul><li>a</ul>b</ul>"),
+        "<ul><li><ul><li>a</li></ul>b</li></ul>"
+    );
+}
+
+#[test]
+fn test_definition_list_items_close_each_other() {
+    assert_eq!(
+        parsed_body("<dl><dt>a<dd>b<dt>c<dd>d</dl>"),
+        "<dl><dt>a</dt><dd>b</dd><dt>c</dt><dd>d</dd></dl>"
+    );
+}
+
+#[test]
+fn test_formatting_is_reconstructed_after_blocks() {
+    assert_eq!(parsed_body("<b>1<p>2</p>3</b>"), "<b>1<p>2</p>3</b>");
+    assert_eq!(
+        parsed_body("<p><b>1</p>2<input>3"),
+        "<p><b>1</b></p><b>2<input>3</b>"
+    );
+    assert_eq!(
+        parsed_body("<p><i>1</p><select><option>a<option>b</select>"),
+        "<p><i>1</i></p><i><select><option>a</option><option>b</option></select></i>"
+    );
+    assert_eq!(
+        parsed_body("<p><b>1</p>2</br>3"),
+        "<p><b>1</b></p><b>2<br>3</b>"
+    );
+}
+
+#[test]
+fn test_end_tags_close_the_elements_above_them() {
+    assert_eq!(
+        parsed_body("<div><span>a</div>b"),
+        "<div><span>a</span></div>b"
+    );
+    assert_eq!(
+        parsed_body("<foo><span><bar>a</foo>b"),
+        "<foo><span><bar>a</bar></span></foo>b"
+    );
+
+    // A special element in the way means the end tag is ignored
+    assert_eq!(
+        parsed_body("<foo><div>a</foo>b</div>c"),
+        "<foo><div>ab</div>c</foo>"
+    );
+}
+
+#[test]
+fn test_content_after_the_body() {
+    let document = harbor::parse_html(
+        "<!DOCTYPE html><html><head></head><body>a</body><!--x--> b</html><!--y-->\n",
+    );
+
+    assert_eq!(
+        document.borrow().serialize(),
+        "<!DOCTYPE html><html><head></head><body>a b\n</body><!--x--></html><!--y-->"
+    );
+
+    // A stray start tag goes back into the body
+    assert_eq!(parsed_body("a</body></html><p>b"), "a<p>b</p>");
+}
+
+#[test]
+fn test_comments_before_html_go_in_the_document() {
+    let document = harbor::parse_html(
+        "<!--first--><!DOCTYPE html><!--second--><html><!--third--><head></head><body></body></html>",
+    );
+
+    assert_eq!(
+        document.borrow().serialize(),
+        "<!--first--><!DOCTYPE html><!--second--><html><!--third--><head></head><body></body></html>"
+    );
+
+    let document = document.borrow();
+    let children = document
+        ._node
+        .borrow()
+        .child_nodes()
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    assert!(matches!(
+        &*children[0].borrow(),
+        html5::dom::NodeKind::Comment(_)
+    ));
+    assert!(matches!(
+        &*children[2].borrow(),
+        html5::dom::NodeKind::Comment(_)
+    ));
+    assert!(matches!(
+        &*children[3].borrow(),
+        html5::dom::NodeKind::Element(_)
+    ));
+}
+
+#[test]
+fn test_framesets() {
+    let document =
+        harbor::parse_html("<!DOCTYPE html><html><head></head><frameset><frame></frameset></html>");
+    assert_eq!(
+        document.borrow().serialize(),
+        "<!DOCTYPE html><html><head></head><frameset><frame></frameset></html>"
+    );
+
+    // Nested framesets, with stray text and tags dropped and noframes kept as raw text
+    let document = harbor::parse_html(
+        "<html><head></head><frameset cols=\"50%,50%\"><frame src=a>text<div>\
+        <frameset><frame src=b></frameset></frameset><noframes><p>none</p></noframes>\
+        <!--after--></html><!--end-->",
+    );
+    assert_eq!(
+        document.borrow().serialize(),
+        "<html><head></head><frameset cols=\"50%,50%\"><frame src=\"a\">\
+        <frameset><frame src=\"b\"></frameset></frameset><noframes><p>none</p></noframes>\
+        <!--after--></html><!--end-->"
+    );
+}
@@ -0,0 +1,197 @@
+use std::net::SocketAddr;
+
+use harbor::{
+    http::{
+        Client, Protocol,
+        dns::DnsResolver,
+        url::{HostParseError, ParseURLError, URL},
+    },
+    infra::Serializable,
+};
+
+fn url(input: &str) -> URL {
+    URL::pure_parse(input.to_string()).unwrap()
+}
+
+fn parse_error(input: &str) -> ParseURLError {
+    URL::pure_parse(input.to_string()).unwrap_err()
+}
+
+#[test]
+fn test_origin_uses_effective_port() {
+    assert_eq!(
+        url("http://a.com/").origin(),
+        Some((String::from("http"), String::from("a.com"), 80))
+    );
+    assert_eq!(
+        url("https://a.com:8443/").origin(),
+        Some((String::from("https"), String::from("a.com"), 8443))
+    );
+    assert_eq!(url("file:///etc/hosts").origin(), None);
+}
+
+#[test]
+fn test_same_origin() {
+    assert!(url("http://a.com").is_same_origin(&url("http://a.com:80")));
+    assert!(url("http://a.com/one").is_same_origin(&url("http://a.com/two")));
+
+    assert!(!url("http://a.com").is_same_origin(&url("https://a.com")));
+    assert!(!url("http://a.com").is_same_origin(&url("http://a.com:8080")));
+    assert!(!url("http://a.com").is_same_origin(&url("http://b.a.com")));
+    assert!(!url("file:///a").is_same_origin(&url("file:///a")));
+}
+
+#[test]
+fn test_malformed_urls_return_errors() {
+    assert!(matches!(
+        parse_error("example.com"),
+        ParseURLError::MissingSchemeNonRelativeURL
+    ));
+    assert!(matches!(
+        parse_error("http://a.com:8o"),
+        ParseURLError::PortInvalid
+    ));
+    assert!(matches!(
+        parse_error("http://a.com:99999"),
+        ParseURLError::PortOutOfRange
+    ));
+    assert!(matches!(parse_error("http://"), ParseURLError::HostMissing));
+    assert!(matches!(
+        parse_error("http://:80"),
+        ParseURLError::HostMissing
+    ));
+    assert!(matches!(
+        parse_error("http://[::1"),
+        ParseURLError::HostParseError(HostParseError::IPv6UnclosedValidation)
+    ));
+    assert!(matches!(
+        parse_error("http://a%20b.com/"),
+        ParseURLError::HostParseError(HostParseError::DomainInvalidCodePoint)
+    ));
+}
+
+#[test]
+fn test_url_errors_describe_the_problem() {
+    assert_eq!(
+        parse_error("example.com").to_string(),
+        "URL has no scheme and no base URL to resolve it against"
+    );
+    assert!(matches!(
+        parse_error("http://h:notaport/"),
+        ParseURLError::PortInvalid
+    ));
+    assert_eq!(
+        parse_error("http://h:notaport/").to_string(),
+        "port is not a number"
+    );
+
+    let mut client = Client::new(Protocol::HTTP1_1, false);
+    let unsupported = client.connect_to_url(String::from("ftp://x")).unwrap_err();
+    assert!(matches!(&unsupported, ParseURLError::SchemeUnsupported(scheme) if scheme == "ftp"));
+    assert_eq!(unsupported.to_string(), "unsupported scheme 'ftp'");
+}
+
+#[test]
+fn test_ipv6_host_keeps_last_piece() {
+    assert_eq!(
+        url("http://[2001:db8::1]:8080/").host.unwrap().serialize(),
+        "[2001:db8::1]"
+    );
+}
+
+#[test]
+fn test_connect_to_malformed_url_is_an_error() {
+    let mut client = Client::new(Protocol::HTTP1_1, false);
+
+    assert!(matches!(
+        client.connect_to_url(String::from("http://a.com:8o/")),
+        Err(ParseURLError::PortInvalid)
+    ));
+    assert!(matches!(
+        client.connect_to_url(String::from("ftp://127.0.0.1/")),
+        Err(ParseURLError::SchemeUnsupported(scheme)) if scheme == "ftp"
+    ));
+}
+
+#[test]
+fn test_serialize_round_trips() {
+    for input in [
+        "http://a.com/",
+        "https://a.com:8443/path/to/page",
+        "http://127.0.0.1:8080/",
+        "http://[::1]/",
+        "file:///etc/hosts",
+    ] {
+        assert_eq!(url(input).serialize(), input);
+    }
+
+    // Default ports are dropped and hosts are lowercased
+    assert_eq!(url("HTTP://A.com:80").serialize(), "http://a.com/");
+}
+
+#[test]
+fn test_query_and_fragment() {
+    let parsed = url("http://h/a?b=c#d");
+
+    assert_eq!(parsed.scheme, "http");
+    assert_eq!(parsed.host.as_ref().unwrap().serialize(), "h");
+    assert_eq!(parsed.path.serialize(), "/a");
+    assert_eq!(parsed.query.as_deref(), Some("b=c"));
+    assert_eq!(parsed.fragment.as_deref(), Some("d"));
+    assert_eq!(parsed.serialize(), "http://h/a?b=c#d");
+
+    let query_only = url("http://h/a?b=c");
+    assert_eq!(query_only.query.as_deref(), Some("b=c"));
+    assert_eq!(query_only.fragment, None);
+    assert_eq!(query_only.serialize(), "http://h/a?b=c");
+
+    // Empty ones are kept apart from missing ones, and spaces are percent-encoded
+    let empty = url("http://h/?#");
+    assert_eq!(empty.query.as_deref(), Some(""));
+    assert_eq!(empty.fragment.as_deref(), Some(""));
+    assert_eq!(empty.serialize(), "http://h/?#");
+    assert_eq!(
+        url("http://h/?a b#c d").serialize(),
+        "http://h/?a%20b#c%20d"
+    );
+
+    // Relative references replace the query and fragment of their base
+    let base = url("http://h/dir/page?old#top");
+    let relative = |input: &str| URL::parse(input.to_string(), Some(base.clone()), None).unwrap();
+    assert_eq!(relative("other?x=1").serialize(), "http://h/dir/other?x=1");
+    assert_eq!(relative("?y=2").serialize(), "http://h/dir/page?y=2");
+    assert_eq!(
+        relative("#bottom").serialize(),
+        "http://h/dir/page?old#bottom"
+    );
+}
+
+#[test]
+fn test_effective_port() {
+    assert_eq!(url("http://a.com/").effective_port(), Some(80));
+    assert_eq!(url("https://a.com/").effective_port(), Some(443));
+    assert_eq!(url("http://a.com:8080/").effective_port(), Some(8080));
+    assert_eq!(url("file:///etc/hosts").effective_port(), None);
+}
+
+#[test]
+fn test_resolve_parsed_url() {
+    let mut resolver = DnsResolver::new();
+
+    assert_eq!(
+        resolver.resolve_url(&url("http://127.0.0.1:8080/")),
+        Some(SocketAddr::from(([127, 0, 0, 1], 8080)))
+    );
+    assert_eq!(resolver.resolve_url(&url("file:///etc/hosts")), None);
+}
+
+#[test]
+fn test_idn_hosts_are_punycode_encoded() {
+    let idn = url("http://bücher.example/");
+
+    assert_eq!(idn.serialize(), "http://xn--bcher-kva.example/");
+    assert_eq!(idn.host.as_ref().unwrap().to_unicode(), "bücher.example");
+
+    // Hosts that are already ASCII display unchanged
+    assert_eq!(url("http://a.com/").host.unwrap().to_unicode(), "a.com");
+}